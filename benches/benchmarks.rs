@@ -124,6 +124,76 @@ fn bench_directory_processing(c: &mut Criterion) {
     group.finish();
 }
 
+fn bench_large_directory(c: &mut Criterion) {
+    let mut group = c.benchmark_group("large_directory");
+    group.sample_size(10);
+    
+    // A single flat directory with many entries stresses entry sorting
+    let temp_dir = TempDir::new().unwrap();
+    for i in 0..50_000 {
+        fs::write(temp_dir.path().join(format!("entry_{:05}", (i * 7919) % 50_000)), b"").unwrap();
+    }
+    
+    let computer = SwhidComputer::new();
+    
+    group.bench_function("process_directory_50k_entries", |b| {
+        b.iter(|| computer.compute_directory_swhid(black_box(temp_dir.path())))
+    });
+    
+    // The sorting step alone: listed names sorted, then entries sorted again,
+    // as before, against a single sort by cached tree keys
+    let names: Vec<_> = fs::read_dir(temp_dir.path()).unwrap().map(|entry| entry.unwrap().file_name()).collect();
+    group.bench_function("sort_twice_50k_entries", |b| {
+        b.iter(|| {
+            let mut listed = black_box(&names).clone();
+            listed.sort_by_key(|name| name.clone());
+            let mut entries: Vec<Vec<u8>> = listed.iter().map(|name| name.as_encoded_bytes().to_vec()).collect();
+            entries.sort();
+            entries
+        })
+    });
+    group.bench_function("sort_once_50k_entries", |b| {
+        b.iter(|| {
+            let mut entries: Vec<Vec<u8>> = black_box(&names).iter().map(|name| name.as_encoded_bytes().to_vec()).collect();
+            entries.sort_by_cached_key(|name| name.clone());
+            entries
+        })
+    });
+    
+    group.finish();
+}
+
+fn bench_parallel_traversal(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parallel_traversal");
+    group.sample_size(10);
+    
+    // A source-checkout-like tree: many directories of small and medium files
+    let temp_dir = TempDir::new().unwrap();
+    for d in 0..50 {
+        let sub = temp_dir.path().join(format!("module_{:02}", d));
+        fs::create_dir(&sub).unwrap();
+        for f in 0..100 {
+            let size = 256 + (d * 100 + f) % 16 * 1024;
+            fs::write(sub.join(format!("source_{:03}.rs", f)), vec![b'a' + (f % 26) as u8; size]).unwrap();
+        }
+    }
+    
+    let computer = SwhidComputer::new();
+    group.bench_function("sequential_5k_files", |b| {
+        b.iter(|| computer.compute_directory_swhid(black_box(temp_dir.path())))
+    });
+    
+    #[cfg(feature = "parallel")]
+    {
+        let computer = SwhidComputer::new().with_parallelism(0);
+        group.bench_function("parallel_5k_files", |b| {
+            b.iter(|| computer.compute_directory_swhid(black_box(temp_dir.path())))
+        });
+    }
+    
+    group.finish();
+}
+
 fn bench_symlink_handling(c: &mut Criterion) {
     let mut group = c.benchmark_group("symlink_handling");
     
@@ -183,6 +253,8 @@ criterion_group!(
     bench_swhid_parsing,
     bench_swhid_computation,
     bench_directory_processing,
+    bench_large_directory,
+    bench_parallel_traversal,
     bench_symlink_handling,
     bench_verification
 );
@@ -0,0 +1,205 @@
+//! Client for the Software Heritage archive web API
+//!
+//! Only the endpoints that take locally computed SWHIDs are covered, such as
+//! [`ArchiveClient::known`], which tells which objects are already archived.
+
+use std::collections::HashMap;
+
+use serde_json::Value;
+
+use crate::error::SwhidError;
+use crate::swhid::Swhid;
+
+/// Base URL of the Software Heritage web API
+pub const API_URL: &str = "https://archive.softwareheritage.org/api/1/";
+
+/// Largest number of SWHIDs the `/known/` endpoint accepts in one request
+pub const KNOWN_BATCH_SIZE: usize = 1000;
+
+/// Client for the Software Heritage web API
+#[derive(Debug, Clone)]
+pub struct ArchiveClient {
+    agent: ureq::Agent,
+    base_url: String,
+    token: Option<String>,
+    batch_size: usize,
+}
+
+impl Default for ArchiveClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ArchiveClient {
+    /// Create a client for the public archive, without authentication
+    pub fn new() -> Self {
+        Self {
+            agent: ureq::AgentBuilder::new()
+                .user_agent(concat!("swhid-rs/", env!("CARGO_PKG_VERSION")))
+                .build(),
+            base_url: API_URL.to_string(),
+            token: None,
+            batch_size: KNOWN_BATCH_SIZE,
+        }
+    }
+
+    /// Send requests to another deployment of the API, e.g. a mirror
+    ///
+    /// `base_url` is the URL endpoints are relative to, such as [`API_URL`].
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into();
+        if !self.base_url.ends_with('/') {
+            self.base_url.push('/');
+        }
+        self
+    }
+
+    /// Authenticate with a bearer token, which raises the rate limits
+    pub fn with_token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    /// Set how many SWHIDs are sent per request, at most [`KNOWN_BATCH_SIZE`]
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.clamp(1, KNOWN_BATCH_SIZE);
+        self
+    }
+
+    /// Check which objects the archive holds
+    ///
+    /// Returns one flag per SWHID, in order. Each distinct SWHID is sent
+    /// once, in batches of the configured size. Fails with `SwhidError::Api`
+    /// if a request fails, e.g. when rate limited, or if the reply leaves out
+    /// a SWHID.
+    pub fn known(&self, swhids: &[Swhid]) -> Result<Vec<bool>, SwhidError> {
+        let mut unique: Vec<&Swhid> = Vec::new();
+        let mut seen = HashMap::new();
+        for swhid in swhids {
+            seen.entry(swhid).or_insert_with(|| {
+                unique.push(swhid);
+                false
+            });
+        }
+
+        for batch in unique.chunks(self.batch_size) {
+            let body = Value::Array(batch.iter().map(|swhid| Value::String(swhid.to_string())).collect());
+            let reply = self.post("known/", &body)?;
+            for swhid in batch {
+                let known = reply
+                    .get(swhid.to_string())
+                    .and_then(|status| status.get("known"))
+                    .and_then(Value::as_bool)
+                    .ok_or_else(|| SwhidError::Api(format!("no known status for {}", swhid)))?;
+                seen.insert(swhid, known);
+            }
+        }
+        Ok(swhids.iter().map(|swhid| seen[swhid]).collect())
+    }
+
+    /// POST a JSON body to an endpoint, returning the JSON reply
+    fn post(&self, endpoint: &str, body: &Value) -> Result<Value, SwhidError> {
+        let url = format!("{}{}", self.base_url, endpoint);
+        let mut request = self.agent.post(&url).set("Content-Type", "application/json");
+        if let Some(token) = &self.token {
+            request = request.set("Authorization", &format!("Bearer {}", token));
+        }
+        let response = request.send_string(&body.to_string()).map_err(|err| match err {
+            ureq::Error::Status(code, response) => {
+                SwhidError::Api(format!("{} returned {} {}", url, code, response.status_text()))
+            }
+            // Transport errors name the URL already
+            ureq::Error::Transport(transport) => SwhidError::Api(transport.to_string()),
+        })?;
+        let reply = response.into_string()?;
+        serde_json::from_str(&reply).map_err(|e| SwhidError::Api(format!("{} returned invalid JSON: {}", url, e)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::swhid::ObjectType;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::sync::mpsc;
+
+    /// Serve `/known/` on a local port, answering that SWHIDs whose hash
+    /// starts with `00` are known; request bodies are sent to the channel
+    fn serve_known(requests: usize) -> (String, mpsc::Receiver<(String, Option<String>)>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/api/1", listener.local_addr().unwrap());
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || {
+            for stream in listener.incoming().take(requests) {
+                let mut stream = BufReader::new(stream.unwrap());
+                let (mut length, mut authorization) = (0, None);
+                loop {
+                    let mut line = String::new();
+                    stream.read_line(&mut line).unwrap();
+                    let line = line.trim_end();
+                    if line.is_empty() {
+                        break;
+                    }
+                    let (name, value) = line.split_once(": ").unwrap_or((line, ""));
+                    match name.to_ascii_lowercase().as_str() {
+                        "content-length" => length = value.parse().unwrap(),
+                        "authorization" => authorization = Some(value.to_string()),
+                        _ => {}
+                    }
+                }
+                let mut body = vec![0; length];
+                stream.read_exact(&mut body).unwrap();
+                let body = String::from_utf8(body).unwrap();
+
+                let swhids: Vec<String> = serde_json::from_str(&body).unwrap();
+                let reply: serde_json::Map<String, Value> = swhids
+                    .iter()
+                    .map(|swhid| (swhid.clone(), serde_json::json!({ "known": swhid[10..].starts_with("00") })))
+                    .collect();
+                let reply = Value::Object(reply).to_string();
+                write!(
+                    stream.get_mut(),
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    reply.len(),
+                    reply
+                )
+                .unwrap();
+                sender.send((body, authorization)).unwrap();
+            }
+        });
+        (url, receiver)
+    }
+
+    #[test]
+    fn test_known_in_batches() {
+        let (url, requests) = serve_known(2);
+        let known = Swhid::new(ObjectType::Content, [0; 20]);
+        let unknown = Swhid::new(ObjectType::Directory, [1; 20]);
+        let other = Swhid::new(ObjectType::Content, [2; 20]);
+
+        let client = ArchiveClient::new().with_base_url(url).with_token("secret").with_batch_size(2);
+        let swhids = [known.clone(), unknown.clone(), known.clone(), other.clone()];
+        assert_eq!(client.known(&swhids).unwrap(), vec![true, false, true, false]);
+
+        // Duplicates are only sent once
+        let (body, authorization) = requests.recv().unwrap();
+        assert_eq!(body, format!(r#"["{}","{}"]"#, known, unknown));
+        assert_eq!(authorization.as_deref(), Some("Bearer secret"));
+        assert_eq!(requests.recv().unwrap().0, format!(r#"["{}"]"#, other));
+
+        assert!(client.known(&[]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_known_unreachable() {
+        // Nothing listens on a port just released
+        let url = format!("http://{}/", TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap());
+        let client = ArchiveClient::new().with_base_url(url);
+        assert!(matches!(
+            client.known(&[Swhid::new(ObjectType::Content, [0; 20])]),
+            Err(SwhidError::Api(_))
+        ));
+    }
+}
@@ -0,0 +1,551 @@
+//! Directory SWHIDs of tar and zip archives, computed without extracting them
+
+use std::collections::BTreeMap;
+#[cfg(feature = "tar")]
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, Read, Seek};
+use std::path::Path;
+#[cfg(feature = "tar")]
+use tar::{Archive, EntryType as TarEntryType};
+use crate::computer::{SpecialFilePolicy, SwhidComputer};
+use crate::content::Content;
+use crate::directory::{relative_components, DirectoryEntry, EntryType, Permissions, TreeNode};
+use crate::error::SwhidError;
+use crate::swhid::Swhid;
+
+/// Size of a tar header block
+#[cfg(feature = "tar")]
+const TAR_BLOCK_LEN: usize = 512;
+
+/// An archive member, hashed but not yet placed in a tree
+enum Member {
+    Directory,
+    Leaf(EntryType, Permissions, [u8; 20]),
+}
+
+impl SwhidComputer {
+    /// Compute the directory SWHID of the tree an archive file extracts to
+    ///
+    /// The format is detected from the first bytes of the file, whatever its
+    /// name: tar archives, plain or gzip-compressed, are read as by
+    /// [`SwhidComputer::compute_tar_swhid`] with the `tar` feature, and zip
+    /// archives as by [`SwhidComputer::compute_zip_swhid`] with the `zip`
+    /// feature. Tar archives are recognized by their `ustar` magic, so only
+    /// POSIX and GNU tar archives are read. Other files, including other
+    /// compressions, fail with `SwhidError::UnsupportedOperation`.
+    pub fn compute_archive_swhid<P: AsRef<Path>>(&self, path: P) -> Result<Swhid, SwhidError> {
+        let path = path.as_ref();
+        self.archive_swhid(path).map_err(|err| err.at_path(path))
+    }
+
+    fn archive_swhid(&self, path: &Path) -> Result<Swhid, SwhidError> {
+        let mut file = File::open(path)?;
+        let mut magic = Vec::with_capacity(6);
+        (&mut file).take(6).read_to_end(&mut magic)?;
+        file.rewind()?;
+        let unsupported = |reason: &str| Err(SwhidError::UnsupportedOperation(reason.to_string()));
+
+        match magic.as_slice() {
+            #[cfg(feature = "tar")]
+            [0x1f, 0x8b, ..] => self.tar_file_swhid(flate2::read::MultiGzDecoder::new(BufReader::new(file))),
+            #[cfg(not(feature = "tar"))]
+            [0x1f, 0x8b, ..] => unsupported("tar archives need the `tar` feature"),
+            #[cfg(feature = "zip")]
+            [b'P', b'K', 3, 4, ..] | [b'P', b'K', 5, 6, ..] => self.compute_zip_swhid(BufReader::new(file)),
+            #[cfg(not(feature = "zip"))]
+            [b'P', b'K', 3, 4, ..] | [b'P', b'K', 5, 6, ..] => unsupported("zip archives need the `zip` feature"),
+            [b'B', b'Z', b'h', ..] | [0xfd, b'7', b'z', b'X', b'Z', 0] | [0x28, 0xb5, 0x2f, 0xfd, ..] => {
+                unsupported("bzip2, xz and zstd compressed archives are not supported")
+            }
+            #[cfg(feature = "tar")]
+            _ => self.tar_file_swhid(BufReader::new(file)),
+            #[cfg(not(feature = "tar"))]
+            _ => unsupported("unsupported archive format"),
+        }
+    }
+
+    /// Compute the SWHID of a tar stream, checking first that it is one
+    ///
+    /// The first header block must carry the `ustar` magic, or be the
+    /// all-zero block ending an empty archive.
+    #[cfg(feature = "tar")]
+    fn tar_file_swhid<R: Read>(&self, mut reader: R) -> Result<Swhid, SwhidError> {
+        let mut header = Vec::with_capacity(TAR_BLOCK_LEN);
+        (&mut reader).take(TAR_BLOCK_LEN as u64).read_to_end(&mut header)?;
+        let is_tar = header.len() == TAR_BLOCK_LEN
+            && (&header[257..262] == b"ustar" || header.iter().all(|&b| b == 0));
+        if !is_tar {
+            return Err(SwhidError::UnsupportedOperation(
+                "unsupported archive format: not a tar, gzip or zip file".to_string(),
+            ));
+        }
+        self.compute_tar_swhid(std::io::Cursor::new(header).chain(reader))
+    }
+
+    /// Compute the directory SWHID of the tree a tar archive extracts to
+    ///
+    /// Members are read in archive order and hashed as they stream by, so the
+    /// archive is never extracted nor held in memory. The executable bit and
+    /// symlinks are taken from the member headers, and hardlinks are hashed as
+    /// copies of the regular file they link to. As on extraction, a member
+    /// overrides an earlier one at the same path.
+    ///
+    /// If every member lives under a single top-level directory, as in most
+    /// source release tarballs, the SWHID is that directory's, i.e. the one
+    /// of the extracted release. Otherwise it is the SWHID of the directory
+    /// the archive is extracted into. Exclude patterns and the other
+    /// traversal options apply relative to that root, as they would on disk.
+    ///
+    /// Devices and FIFOs are handled as set by
+    /// [`SwhidComputer::with_special_file_policy`]. Fails with
+    /// `SwhidError::InvalidFilePath` for members that escape the archive
+    /// root, and hardlinks to files not earlier in the archive. Materializing
+    /// symlinks is not supported, since their targets may come later in the
+    /// archive.
+    #[cfg(feature = "tar")]
+    pub fn compute_tar_swhid<R: Read>(&self, reader: R) -> Result<Swhid, SwhidError> {
+        if self.materialize_symlinks {
+            return Err(SwhidError::UnsupportedOperation(
+                "symlinks in tar archives cannot be materialized".to_string(),
+            ));
+        }
+
+        let mut members = BTreeMap::new();
+        // Regular files by path, for later hardlinks to refer to
+        let mut files: HashMap<Vec<Vec<u8>>, (Permissions, [u8; 20])> = HashMap::new();
+
+        let mut archive = Archive::new(reader);
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            let path = entry.path()?.into_owned();
+            let invalid = |reason: &str| SwhidError::InvalidFilePath(path.clone(), reason.to_string());
+            let components = relative_components(&path)
+                .ok_or_else(|| invalid("tar member escapes the archive root"))?;
+
+            let member = match entry.header().entry_type() {
+                TarEntryType::Directory => Member::Directory,
+                TarEntryType::Regular | TarEntryType::Continuous | TarEntryType::GNUSparse => {
+                    let permissions = Permissions::from_mode(entry.header().mode()? & 0o7777);
+                    let size = entry.size();
+                    let hash = self.reader_hash(&path, &mut entry, size)?;
+                    files.insert(components.clone(), (permissions, hash));
+                    Member::Leaf(EntryType::File, permissions, hash)
+                }
+                TarEntryType::Link => {
+                    let target = entry
+                        .link_name()?
+                        .ok_or_else(|| invalid("hardlink without a target"))?;
+                    let (permissions, hash) = relative_components(&target)
+                        .and_then(|target| files.get(&target).copied())
+                        .ok_or_else(|| invalid("hardlink to a file not earlier in the archive"))?;
+                    files.insert(components.clone(), (permissions, hash));
+                    Member::Leaf(EntryType::File, permissions, hash)
+                }
+                TarEntryType::Symlink => {
+                    let target = entry
+                        .link_name_bytes()
+                        .ok_or_else(|| invalid("symlink without a target"))?;
+                    let hash = *Content::from_data_with(target.into_owned(), self.hash_algorithm).sha1_git();
+                    Member::Leaf(EntryType::Symlink, Permissions::Symlink, hash)
+                }
+                TarEntryType::Char | TarEntryType::Block | TarEntryType::Fifo => {
+                    match self.special_member(&path)? {
+                        Some(member) => member,
+                        None => continue,
+                    }
+                }
+                // Archive-wide metadata, not a member
+                TarEntryType::XGlobalHeader | TarEntryType::XHeader => continue,
+                other => return Err(invalid(&format!("unsupported tar entry type {:?}", other))),
+            };
+
+            if components.is_empty() {
+                match member {
+                    // The archive root itself, e.g. `./`
+                    Member::Directory => continue,
+                    Member::Leaf(..) => return Err(invalid("tar member has an empty path")),
+                }
+            }
+            members.insert(components, member);
+        }
+        self.members_swhid(&members)
+    }
+
+    /// Compute the directory SWHID of the tree a zip archive extracts to
+    ///
+    /// Members are hashed as they are decompressed, and placed in a tree as
+    /// by [`SwhidComputer::compute_tar_swhid`], a single top-level directory
+    /// included. The executable bit and symlinks are taken from the Unix mode
+    /// members carry when created on Unix; other members are regular,
+    /// non-executable files. Fails with `SwhidError::InvalidFormat` if the
+    /// archive cannot be read.
+    #[cfg(feature = "zip")]
+    pub fn compute_zip_swhid<R: Read + Seek>(&self, reader: R) -> Result<Swhid, SwhidError> {
+        if self.materialize_symlinks {
+            return Err(SwhidError::UnsupportedOperation(
+                "symlinks in zip archives cannot be materialized".to_string(),
+            ));
+        }
+
+        let mut members = BTreeMap::new();
+        let mut archive = zip::ZipArchive::new(reader).map_err(zip_error)?;
+        for index in 0..archive.len() {
+            let mut file = archive.by_index(index).map_err(zip_error)?;
+            let path = std::path::PathBuf::from(file.name());
+            let invalid = |reason: &str| SwhidError::InvalidFilePath(path.clone(), reason.to_string());
+            let components = relative_components(&path)
+                .ok_or_else(|| invalid("zip member escapes the archive root"))?;
+
+            let mode = file.unix_mode();
+            let member = match mode.map(|mode| mode & 0o170000) {
+                _ if file.is_dir() => Member::Directory,
+                Some(0o120000) => {
+                    let mut target = Vec::new();
+                    file.read_to_end(&mut target)?;
+                    let hash = *Content::from_data_with(target, self.hash_algorithm).sha1_git();
+                    Member::Leaf(EntryType::Symlink, Permissions::Symlink, hash)
+                }
+                Some(0o010000 | 0o020000 | 0o060000 | 0o140000) => match self.special_member(&path)? {
+                    Some(member) => member,
+                    None => continue,
+                },
+                _ => {
+                    let permissions = mode.map_or(Permissions::File, |mode| Permissions::from_mode(mode & 0o7777));
+                    let size = file.size();
+                    let hash = self.reader_hash(&path, &mut file, size)?;
+                    Member::Leaf(EntryType::File, permissions, hash)
+                }
+            };
+
+            if components.is_empty() {
+                match member {
+                    Member::Directory => continue,
+                    Member::Leaf(..) => return Err(invalid("zip member has an empty path")),
+                }
+            }
+            members.insert(components, member);
+        }
+        self.members_swhid(&members)
+    }
+
+    /// Hash a device or FIFO as set by [`SwhidComputer::with_special_file_policy`],
+    /// or `None` to skip it
+    fn special_member(&self, path: &Path) -> Result<Option<Member>, SwhidError> {
+        match self.special_file_policy {
+            SpecialFilePolicy::Skip => Ok(None),
+            SpecialFilePolicy::Error => Err(SwhidError::InvalidFilePath(
+                path.to_path_buf(),
+                "special files such as devices and FIFOs cannot be hashed".to_string(),
+            )),
+            SpecialFilePolicy::HashAsEmpty => {
+                let hash = *Content::from_data_with(Vec::new(), self.hash_algorithm).sha1_git();
+                Ok(Some(Member::Leaf(EntryType::File, Permissions::File, hash)))
+            }
+        }
+    }
+
+    /// Compute the SWHID of the tree archive members extract to
+    fn members_swhid(&self, members: &BTreeMap<Vec<Vec<u8>>, Member>) -> Result<Swhid, SwhidError> {
+        let prefix = single_top_level_directory(members);
+        let mut tree = TreeNode::new();
+        for (components, member) in members {
+            let components = &components[prefix..];
+            if components.is_empty() || self.is_excluded(components) {
+                continue;
+            }
+            let names: Vec<Vec<u8>> = components
+                .iter()
+                .map(|name| self.entry_name(name.clone()))
+                .collect();
+            match member {
+                Member::Directory => {
+                    tree.directory(&names)?;
+                }
+                Member::Leaf(entry_type, permissions, hash) => {
+                    let (name, parents) = names.split_last().expect("components are not empty");
+                    tree.insert(parents, DirectoryEntry::new(name.clone(), *entry_type, *permissions, *hash))?;
+                }
+            }
+        }
+
+        Ok(tree.into_directory(self.hash_algorithm)?.swhid_with(self.hash_algorithm))
+    }
+
+    /// Check whether a member, or any directory above it, is left out
+    fn is_excluded(&self, components: &[Vec<u8>]) -> bool {
+        (0..components.len()).any(|depth| {
+            let relative_path = components[..=depth]
+                .iter()
+                .map(|name| String::from_utf8_lossy(name))
+                .collect::<Vec<_>>()
+                .join("/");
+            self.is_skipped(&components[depth], &relative_path)
+        })
+    }
+}
+
+/// Error reading a zip archive
+#[cfg(feature = "zip")]
+fn zip_error(err: zip::result::ZipError) -> SwhidError {
+    match err {
+        zip::result::ZipError::Io(err) => SwhidError::Io(err),
+        other => SwhidError::InvalidFormat(format!("zip archive: {}", other)),
+    }
+}
+
+/// Number of leading components to strip: 1 if all members are under a
+/// single top-level directory, 0 otherwise
+fn single_top_level_directory(members: &BTreeMap<Vec<Vec<u8>>, Member>) -> usize {
+    let Some(top) = members.keys().next().map(|components| &components[0]) else {
+        return 0;
+    };
+    let single = members.iter().all(|(components, member)| {
+        components[0] == *top && !(components.len() == 1 && matches!(member, Member::Leaf(..)))
+    });
+    usize::from(single)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    #[cfg(feature = "tar")]
+    use tar::{Builder, Header};
+    use tempfile::TempDir;
+
+    /// A small tree with a nested directory and an excludable file
+    fn sample_tree() -> TempDir {
+        let temp_dir = TempDir::new().unwrap();
+        fs::create_dir_all(temp_dir.path().join("src").join("empty")).unwrap();
+        fs::write(temp_dir.path().join("README"), b"readme\n").unwrap();
+        fs::write(temp_dir.path().join("src").join("lib.rs"), b"pub fn f() {}\n").unwrap();
+        fs::write(temp_dir.path().join("scratch.tmp"), b"scratch\n").unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::{symlink, PermissionsExt};
+            let script = temp_dir.path().join("build.sh");
+            fs::write(&script, b"#!/bin/sh\n").unwrap();
+            fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+            symlink("src/lib.rs", temp_dir.path().join("lib.rs")).unwrap();
+        }
+        temp_dir
+    }
+
+    #[cfg(feature = "tar")]
+    fn tar_of(dir: &Path, prefix: &str) -> Vec<u8> {
+        let mut builder = Builder::new(Vec::new());
+        builder.follow_symlinks(false);
+        builder.append_dir_all(prefix, dir).unwrap();
+        builder.into_inner().unwrap()
+    }
+
+    #[cfg(feature = "tar")]
+    #[test]
+    fn test_tar_swhid_matches_disk() {
+        let tree = sample_tree();
+        for computer in [
+            SwhidComputer::new(),
+            SwhidComputer::new().with_exclude_patterns(&["*.tmp".to_string(), "src/empty".to_string()]),
+        ] {
+            let expected = computer.compute_directory_swhid(tree.path()).unwrap();
+
+            // Files at the archive root
+            let archive = tar_of(tree.path(), ".");
+            assert_eq!(computer.compute_tar_swhid(archive.as_slice()).unwrap(), expected);
+
+            // A single top-level directory is the extracted release
+            let archive = tar_of(tree.path(), "release-1.0");
+            assert_eq!(computer.compute_tar_swhid(archive.as_slice()).unwrap(), expected);
+        }
+    }
+
+    #[cfg(feature = "tar")]
+    #[test]
+    fn test_tar_swhid_root_file_keeps_top_level() {
+        let tree = sample_tree();
+        let mut builder = Builder::new(Vec::new());
+        builder.follow_symlinks(false);
+        builder.append_dir_all("release-1.0", tree.path()).unwrap();
+        builder.append_path_with_name(tree.path().join("README"), "NOTICE").unwrap();
+        let archive = builder.into_inner().unwrap();
+
+        let extracted = TempDir::new().unwrap();
+        tar::Archive::new(archive.as_slice()).unpack(extracted.path()).unwrap();
+        let computer = SwhidComputer::new();
+        assert_eq!(
+            computer.compute_tar_swhid(archive.as_slice()).unwrap(),
+            computer.compute_directory_swhid(extracted.path()).unwrap()
+        );
+    }
+
+    #[cfg(feature = "tar")]
+    #[test]
+    fn test_tar_swhid_hardlink_is_regular_file() {
+        let mut builder = Builder::new(Vec::new());
+        let mut header = Header::new_gnu();
+        header.set_size(6);
+        header.set_mode(0o755);
+        header.set_entry_type(TarEntryType::Regular);
+        builder.append_data(&mut header, "pkg/tool", &b"tool\n\n"[..]).unwrap();
+
+        let mut link = Header::new_gnu();
+        link.set_size(0);
+        link.set_entry_type(TarEntryType::Link);
+        builder.append_link(&mut link, "pkg/tool-link", "pkg/tool").unwrap();
+        let archive = builder.into_inner().unwrap();
+
+        let cnt = Content::from_data(b"tool\n\n".to_vec()).swhid();
+        let mut expected = crate::directory::Directory::new();
+        expected.add_file(b"tool".to_vec(), &cnt, true).unwrap();
+        expected.add_file(b"tool-link".to_vec(), &cnt, true).unwrap();
+        assert_eq!(
+            SwhidComputer::new().compute_tar_swhid(archive.as_slice()).unwrap(),
+            expected.swhid()
+        );
+
+        // A hardlink must refer to a file seen earlier
+        let mut builder = Builder::new(Vec::new());
+        let mut link = Header::new_gnu();
+        link.set_size(0);
+        link.set_entry_type(TarEntryType::Link);
+        builder.append_link(&mut link, "pkg/tool-link", "pkg/tool").unwrap();
+        let archive = builder.into_inner().unwrap();
+        assert!(matches!(
+            SwhidComputer::new().compute_tar_swhid(archive.as_slice()),
+            Err(SwhidError::InvalidFilePath(path, _)) if path == Path::new("pkg/tool-link")
+        ));
+    }
+
+    #[cfg(feature = "tar")]
+    #[test]
+    fn test_tar_swhid_special_file_policy() {
+        let mut builder = Builder::new(Vec::new());
+        let mut header = Header::new_gnu();
+        header.set_size(7);
+        header.set_mode(0o644);
+        header.set_entry_type(TarEntryType::Regular);
+        builder.append_data(&mut header, "README", &b"readme\n"[..]).unwrap();
+        let mut fifo = Header::new_gnu();
+        fifo.set_size(0);
+        fifo.set_mode(0o644);
+        fifo.set_entry_type(TarEntryType::Fifo);
+        builder.append_data(&mut fifo, "pipe", &b""[..]).unwrap();
+        let archive = builder.into_inner().unwrap();
+
+        let readme = Content::from_data(b"readme\n".to_vec()).swhid();
+        let mut skipped = crate::directory::Directory::new();
+        skipped.add_file(b"README".to_vec(), &readme, false).unwrap();
+        let mut empty = skipped.clone();
+        empty.add_file(b"pipe".to_vec(), &Content::from_data(Vec::new()).swhid(), false).unwrap();
+
+        assert!(matches!(
+            SwhidComputer::new().compute_tar_swhid(archive.as_slice()),
+            Err(SwhidError::InvalidFilePath(path, _)) if path == Path::new("pipe")
+        ));
+        let computer = SwhidComputer::new().with_special_file_policy(SpecialFilePolicy::Skip);
+        assert_eq!(computer.compute_tar_swhid(archive.as_slice()).unwrap(), skipped.swhid());
+        let computer = SwhidComputer::new().with_special_file_policy(SpecialFilePolicy::HashAsEmpty);
+        assert_eq!(computer.compute_tar_swhid(archive.as_slice()).unwrap(), empty.swhid());
+    }
+
+    /// Zip `dir` under `prefix`, recording Unix modes and symlinks
+    #[cfg(feature = "zip")]
+    fn zip_of(dir: &Path, prefix: &str) -> Vec<u8> {
+        fn add(writer: &mut zip::ZipWriter<std::io::Cursor<Vec<u8>>>, path: &Path, name: &str) {
+            let options = zip::write::SimpleFileOptions::default();
+            let mut entries: Vec<_> = fs::read_dir(path).unwrap().map(|entry| entry.unwrap().path()).collect();
+            entries.sort();
+            for entry in entries {
+                let child = format!("{}{}", name, entry.file_name().unwrap().to_str().unwrap());
+                let metadata = fs::symlink_metadata(&entry).unwrap();
+                if metadata.is_dir() {
+                    writer.add_directory(child.as_str(), options).unwrap();
+                    add(writer, &entry, &format!("{}/", child));
+                } else if metadata.file_type().is_symlink() {
+                    let target = fs::read_link(&entry).unwrap();
+                    writer.add_symlink(child.as_str(), target.to_str().unwrap(), options).unwrap();
+                } else {
+                    #[cfg(unix)]
+                    let options = {
+                        use std::os::unix::fs::PermissionsExt;
+                        options.unix_permissions(metadata.permissions().mode())
+                    };
+                    writer.start_file(child.as_str(), options).unwrap();
+                    std::io::Write::write_all(writer, &fs::read(&entry).unwrap()).unwrap();
+                }
+            }
+        }
+
+        let mut writer = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        add(&mut writer, dir, prefix);
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[cfg(feature = "zip")]
+    #[test]
+    fn test_zip_swhid_matches_disk() {
+        let tree = sample_tree();
+        let computer = SwhidComputer::new();
+        let expected = computer.compute_directory_swhid(tree.path()).unwrap();
+
+        let archive = zip_of(tree.path(), "");
+        assert_eq!(computer.compute_zip_swhid(std::io::Cursor::new(archive)).unwrap(), expected);
+        let archive = zip_of(tree.path(), "release-1.0/");
+        assert_eq!(computer.compute_zip_swhid(std::io::Cursor::new(archive)).unwrap(), expected);
+
+        assert!(matches!(
+            computer.compute_zip_swhid(std::io::Cursor::new(b"PK\x03\x04 not a zip".to_vec())),
+            Err(SwhidError::InvalidFormat(_))
+        ));
+    }
+
+    #[cfg(all(feature = "tar", feature = "zip"))]
+    #[test]
+    fn test_archive_swhid_detects_format() {
+        let tree = sample_tree();
+        let computer = SwhidComputer::new();
+        let expected = computer.compute_directory_swhid(tree.path()).unwrap();
+        let archives = TempDir::new().unwrap();
+
+        let tar = tar_of(tree.path(), "release-1.0");
+        let mut gzip = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        std::io::Write::write_all(&mut gzip, &tar).unwrap();
+        // Names do not matter, only contents
+        for (name, data) in [
+            ("release.tar", tar.clone()),
+            ("release.tar.gz", gzip.finish().unwrap()),
+            ("release.zip", zip_of(tree.path(), "release-1.0/")),
+            ("release", tar),
+        ] {
+            let path = archives.path().join(name);
+            fs::write(&path, data).unwrap();
+            assert_eq!(computer.compute_archive_swhid(&path).unwrap(), expected, "{}", name);
+        }
+
+        // Only tar archives with the ustar magic are read, even compressed
+        let mut text = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        std::io::Write::write_all(&mut text, b"not a tarball\n").unwrap();
+        for (name, data) in [
+            ("release.tar.xz", b"\xfd7zXZ\x00\x00".to_vec()),
+            ("empty.tar", Vec::new()),
+            ("README", b"not a tarball\n".repeat(100)),
+            ("README.gz", text.finish().unwrap()),
+        ] {
+            let path = archives.path().join(name);
+            fs::write(&path, data).unwrap();
+            assert!(
+                matches!(computer.compute_archive_swhid(&path), Err(SwhidError::UnsupportedOperation(_))),
+                "{}",
+                name
+            );
+        }
+        let empty = archives.path().join("empty-archive.tar");
+        fs::write(&empty, tar::Builder::new(Vec::new()).into_inner().unwrap()).unwrap();
+        assert_eq!(computer.compute_archive_swhid(&empty).unwrap(), crate::Directory::new().swhid());
+        assert!(matches!(
+            computer.compute_archive_swhid(archives.path().join("missing.tar")),
+            Err(SwhidError::PathIo(..))
+        ));
+    }
+}
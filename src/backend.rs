@@ -0,0 +1,488 @@
+//! Access to Git repositories, independent of any Git library
+//!
+//! Revisions, releases and snapshots only need a few operations from a
+//! repository: reading objects, listing references and resolving names.
+//! [`GitBackend`] abstracts these, so that any repository access layer can
+//! be used to compute their SWHIDs.
+
+use std::collections::{BTreeMap, HashSet};
+use std::path::Path;
+use crate::directory::{parse_tree_object, relative_components, Directory, EntryType};
+use crate::error::SwhidError;
+use crate::hash::{hash_git_object, parse_object_id};
+use crate::release::Release;
+use crate::revision::Revision;
+use crate::snapshot::{Snapshot, SnapshotTarget};
+use crate::swhid::{ObjectType, Swhid};
+
+/// What a Git reference points to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GitReference {
+    /// An object, by id
+    Direct([u8; 20]),
+    /// Another reference, by full name, e.g. `HEAD` pointing to `refs/heads/main`
+    Symbolic(Vec<u8>),
+}
+
+/// Read access to a Git repository
+///
+/// Object types are given as the type of SWHID the object has: blobs are
+/// [`ObjectType::Content`], trees [`ObjectType::Directory`], commits
+/// [`ObjectType::Revision`] and tags [`ObjectType::Release`].
+pub trait GitBackend {
+    /// Read the type and body of an object, without the Git object header
+    fn read_object(&self, id: &[u8; 20]) -> Result<(ObjectType, Vec<u8>), SwhidError>;
+
+    /// List every reference by full name, including `HEAD`
+    fn references(&self) -> Result<Vec<(Vec<u8>, GitReference)>, SwhidError>;
+
+    /// Look up the type of an object
+    ///
+    /// By default this reads the whole object; backends that can read just
+    /// the header should override it.
+    fn object_type(&self, id: &[u8; 20]) -> Result<ObjectType, SwhidError> {
+        Ok(self.read_object(id)?.0)
+    }
+
+    /// Resolve a name to an object id, without peeling tags
+    ///
+    /// By default, `name` is either a 40-digit object id, or a reference
+    /// looked up as Git does: as given, then under `refs/`, `refs/tags/`,
+    /// `refs/heads/` and `refs/remotes/`. Symbolic references are followed.
+    /// Backends wrapping a Git library may support more, e.g. `HEAD~2`.
+    fn resolve(&self, name: &str) -> Result<[u8; 20], SwhidError> {
+        if let Some(id) = parse_object_id(name.as_bytes()) {
+            return Ok(id);
+        }
+        let references = self.references()?;
+        let lookup = |full_name: &[u8]| {
+            references
+                .iter()
+                .find(|(reference, _)| reference == full_name)
+                .map(|(_, target)| target)
+        };
+        let mut target = ["", "refs/", "refs/tags/", "refs/heads/", "refs/remotes/"]
+            .iter()
+            .find_map(|prefix| lookup(format!("{}{}", prefix, name).as_bytes()))
+            .ok_or_else(|| SwhidError::Git(format!("Unknown revision or reference: {}", name)))?;
+        // Bounded, in case symbolic references form a cycle
+        for _ in 0..references.len() {
+            match target {
+                GitReference::Direct(id) => return Ok(*id),
+                GitReference::Symbolic(other) => {
+                    target = lookup(other).ok_or_else(|| {
+                        SwhidError::Git(format!("Reference {} points to nothing", String::from_utf8_lossy(other)))
+                    })?;
+                }
+            }
+        }
+        Err(SwhidError::Git(format!("Symbolic references of {} form a cycle", name)))
+    }
+
+    /// Read a commit, checking that it hashes to `id`
+    fn read_revision(&self, id: &[u8; 20]) -> Result<Revision, SwhidError> {
+        let revision = match self.read_object(id)? {
+            (ObjectType::Revision, data) => Revision::from_git_object(data)?,
+            _ => return Err(SwhidError::InvalidInput(format!("Object {} is not a commit", hex::encode(id)))),
+        };
+        check_id(revision.swhid().v1_hash()?, id)?;
+        Ok(revision)
+    }
+
+    /// Read an annotated tag, checking that it hashes to `id`
+    fn read_release(&self, id: &[u8; 20]) -> Result<Release, SwhidError> {
+        let release = match self.read_object(id)? {
+            (ObjectType::Release, data) => Release::from_git_object(data)?,
+            _ => return Err(SwhidError::InvalidInput(format!("Object {} is not a tag", hex::encode(id)))),
+        };
+        check_id(release.swhid().v1_hash()?, id)?;
+        Ok(release)
+    }
+}
+
+impl Revision {
+    /// Read the commit `name` resolves to in `backend`
+    ///
+    /// `name` is resolved by [`GitBackend::resolve`], and tags are peeled to
+    /// the commit they point to. Objects are hashed by this crate rather than
+    /// trusted: fails with `SwhidError::InvalidHash` if one does not hash to
+    /// its id, and with `SwhidError::InvalidInput` if `name` does not lead to
+    /// a commit.
+    pub fn from_backend<B: GitBackend + ?Sized>(backend: &B, name: &str) -> Result<Self, SwhidError> {
+        let mut id = backend.resolve(name)?;
+        loop {
+            match backend.object_type(&id)? {
+                ObjectType::Revision => return backend.read_revision(&id),
+                ObjectType::Release => id = *backend.read_release(&id)?.target().v1_hash()?,
+                _ => return Err(SwhidError::InvalidInput(format!("{} does not name a commit", name))),
+            }
+        }
+    }
+}
+
+impl Release {
+    /// Read the annotated tag `name` resolves to in `backend`
+    ///
+    /// Lightweight tags are plain references to a commit, with no release
+    /// object, and fail with `SwhidError::InvalidInput`. As for revisions,
+    /// the tag object is checked to hash to its id.
+    pub fn from_backend<B: GitBackend + ?Sized>(backend: &B, name: &str) -> Result<Self, SwhidError> {
+        let id = backend.resolve(name)?;
+        if backend.object_type(&id)? != ObjectType::Release {
+            return Err(SwhidError::InvalidInput(format!("{} is not an annotated tag", name)));
+        }
+        backend.read_release(&id)
+    }
+}
+
+impl Directory {
+    /// Compute the SWHID of the directory at `path` in a tree of `backend`
+    ///
+    /// `name` is resolved by [`GitBackend::resolve`] to a tree, or to a tag
+    /// or commit, which is peeled to its tree. `path` is relative to the root
+    /// of that tree; an empty path gives the root itself. The directory is
+    /// identified by the entry of its parent, so only the objects on the way
+    /// to it are read, and checked to hash to their id. Fails with
+    /// `SwhidError::InvalidFilePath` if `path` does not name a directory.
+    pub fn from_backend<B: GitBackend + ?Sized>(backend: &B, name: &str, path: &Path) -> Result<Swhid, SwhidError> {
+        let invalid_path = |reason: &str| SwhidError::InvalidFilePath(path.to_path_buf(), reason.to_string());
+        let components = relative_components(path).ok_or_else(|| invalid_path("path must be relative"))?;
+
+        let mut id = backend.resolve(name)?;
+        loop {
+            match backend.object_type(&id)? {
+                ObjectType::Directory => break,
+                ObjectType::Revision => id = *backend.read_revision(&id)?.directory().v1_hash()?,
+                ObjectType::Release => id = *backend.read_release(&id)?.target().v1_hash()?,
+                _ => return Err(SwhidError::InvalidInput(format!("{} does not name a tree", name))),
+            }
+        }
+        for component in components {
+            let data = match backend.read_object(&id)? {
+                (ObjectType::Directory, data) => data,
+                _ => return Err(SwhidError::InvalidInput(format!("Object {} is not a tree", hex::encode(id)))),
+            };
+            check_id(&hash_git_object("tree", &data), &id)?;
+            let entry = parse_tree_object(&data)?
+                .into_iter()
+                .find(|entry| entry.name == component)
+                .ok_or_else(|| invalid_path(&format!("no such entry in {}", name)))?;
+            if entry.entry_type != EntryType::Directory {
+                return Err(invalid_path(&format!("not a directory in {}", name)));
+            }
+            id = entry.target;
+        }
+        Ok(Swhid::new(ObjectType::Directory, id))
+    }
+}
+
+impl Snapshot {
+    /// Build the snapshot of every reference of `backend`
+    ///
+    /// Each reference is a branch named as in Git, e.g. `refs/heads/main` or
+    /// `refs/tags/v1.0`, and so is `HEAD`. Symbolic references, usually just
+    /// `HEAD`, are aliases to the branch they name; others point to their
+    /// object, with its type looked up in the repository. Annotated tags thus
+    /// point to releases, and other branches usually to revisions.
+    pub fn from_backend<B: GitBackend + ?Sized>(backend: &B) -> Result<Self, SwhidError> {
+        let mut snapshot = Snapshot::new();
+        for (name, reference) in backend.references()? {
+            let target = match reference {
+                GitReference::Symbolic(branch) => SnapshotTarget::Alias(branch),
+                GitReference::Direct(id) => SnapshotTarget::Object(Swhid::new(backend.object_type(&id)?, id)),
+            };
+            snapshot.add_branch(name, target)?;
+        }
+        Ok(snapshot)
+    }
+
+    /// Build the snapshot of the references advertised by a remote repository
+    ///
+    /// This needs no clone: `refs` are the references as a server advertises
+    /// them, e.g. as listed by `git ls-remote`. As for
+    /// [`Snapshot::from_backend`], symbolic references are aliases. An entry
+    /// named `<name>^{}` gives the object an annotated tag `<name>` peels to:
+    /// this marks `<name>` as a release, and is not a branch itself. Objects
+    /// are not available to look up the other types, so every other
+    /// reference is taken to point to a revision, as branches and lightweight
+    /// tags do.
+    pub fn from_advertised_refs<I>(refs: I) -> Result<Self, SwhidError>
+    where
+        I: IntoIterator<Item = (Vec<u8>, GitReference)>,
+    {
+        let mut references = BTreeMap::new();
+        let mut tags = HashSet::new();
+        for (name, reference) in refs {
+            if let Some(tag) = name.strip_suffix(b"^{}") {
+                tags.insert(tag.to_vec());
+                continue;
+            }
+            // With `--symref`, `HEAD` is listed both as an alias and as its object
+            match (references.get(&name), &reference) {
+                (Some(GitReference::Symbolic(_)), GitReference::Direct(_)) => {}
+                (Some(GitReference::Direct(_)), GitReference::Symbolic(_)) | (None, _) => {
+                    references.insert(name, reference);
+                }
+                (Some(_), _) => {
+                    return Err(SwhidError::DuplicateEntry(String::from_utf8_lossy(&name).into_owned()))
+                }
+            }
+        }
+
+        let mut snapshot = Snapshot::new();
+        for (name, reference) in references {
+            let target = match reference {
+                GitReference::Symbolic(branch) => SnapshotTarget::Alias(branch),
+                GitReference::Direct(id) if tags.contains(&name) => {
+                    SnapshotTarget::Object(Swhid::new(ObjectType::Release, id))
+                }
+                GitReference::Direct(id) => SnapshotTarget::Object(Swhid::new(ObjectType::Revision, id)),
+            };
+            snapshot.add_branch(name, target)?;
+        }
+        Ok(snapshot)
+    }
+
+    /// Build the snapshot of a remote repository from `git ls-remote` output
+    ///
+    /// Each line is an object id, or `ref: <name>` with `--symref`, then a
+    /// tab and the reference name. See [`Snapshot::from_advertised_refs`]:
+    /// without `--symref`, `HEAD` is a revision rather than an alias. Fails
+    /// with `SwhidError::InvalidInput` on a malformed line.
+    pub fn from_ls_remote(output: &[u8]) -> Result<Self, SwhidError> {
+        let mut refs = Vec::new();
+        for line in output.split(|&b| b == b'\n') {
+            let line = line.strip_suffix(b"\r").unwrap_or(line);
+            if line.is_empty() {
+                continue;
+            }
+            let invalid = || {
+                SwhidError::InvalidInput(format!("Invalid ls-remote line: {}", String::from_utf8_lossy(line)))
+            };
+            let tab = line.iter().position(|&b| b == b'\t').ok_or_else(invalid)?;
+            let (target, name) = (&line[..tab], &line[tab + 1..]);
+            let reference = match target.strip_prefix(b"ref: ") {
+                Some(branch) => GitReference::Symbolic(branch.to_vec()),
+                None => GitReference::Direct(parse_object_id(target).ok_or_else(invalid)?),
+            };
+            refs.push((name.to_vec(), reference));
+        }
+        Self::from_advertised_refs(refs)
+    }
+}
+
+/// Check that an object hashed by this crate matches its Git object id
+fn check_id(hash: &[u8; 20], id: &[u8; 20]) -> Result<(), SwhidError> {
+    if hash != id {
+        return Err(SwhidError::InvalidHash(format!(
+            "Git object {} does not hash to its id",
+            hex::encode(id)
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::hash::hash_git_object;
+    use std::collections::HashMap;
+
+    const COMMIT: &[u8] = b"tree 37d98dc6f78d31f91c15676c00d504e425b0e9a7\n\
+parent a9483e1e52cd91ea9974c409aee7080d6c58f316\n\
+author Jane Doe <jane@example.org> 1700000000 +0100\n\
+committer Jane Doe <jane@example.org> 1700000000 +0100\n\
+\n\
+Second\n\
+\n\
+With body\n";
+
+    const TAG: &[u8] = b"object 1e048ab552fec7fed3c552773ce146bcb5e57254\n\
+type commit\n\
+tag v1.0\n\
+tagger Jane Doe <jane@example.org> 1700000000 +0100\n\
+\n\
+Release 1.0\n";
+
+    /// A repository held in memory, as a downstream backend might be
+    #[derive(Default)]
+    pub(crate) struct MemoryBackend {
+        pub(crate) objects: HashMap<[u8; 20], (ObjectType, Vec<u8>)>,
+        references: Vec<(Vec<u8>, GitReference)>,
+    }
+
+    impl MemoryBackend {
+        pub(crate) fn add_object(&mut self, object_type: ObjectType, git_type: &str, data: &[u8]) -> [u8; 20] {
+            let id = hash_git_object(git_type, data);
+            self.objects.insert(id, (object_type, data.to_vec()));
+            id
+        }
+
+        pub(crate) fn add_reference(&mut self, name: &str, target: GitReference) {
+            self.references.push((name.as_bytes().to_vec(), target));
+        }
+    }
+
+    impl GitBackend for MemoryBackend {
+        fn read_object(&self, id: &[u8; 20]) -> Result<(ObjectType, Vec<u8>), SwhidError> {
+            self.objects
+                .get(id)
+                .cloned()
+                .ok_or_else(|| SwhidError::Git(format!("Missing object {}", hex::encode(id))))
+        }
+
+        fn references(&self) -> Result<Vec<(Vec<u8>, GitReference)>, SwhidError> {
+            Ok(self.references.clone())
+        }
+    }
+
+    fn sample_backend() -> (MemoryBackend, [u8; 20], [u8; 20]) {
+        let mut backend = MemoryBackend::default();
+        let commit = backend.add_object(ObjectType::Revision, "commit", COMMIT);
+        let tag = backend.add_object(ObjectType::Release, "tag", TAG);
+        backend.add_reference("HEAD", GitReference::Symbolic(b"refs/heads/main".to_vec()));
+        backend.add_reference("refs/heads/main", GitReference::Direct(commit));
+        backend.add_reference("refs/tags/v1.0", GitReference::Direct(tag));
+        (backend, commit, tag)
+    }
+
+    #[test]
+    fn test_revision_and_release_from_backend() {
+        let (backend, commit, tag) = sample_backend();
+        for name in ["HEAD", "main", "refs/heads/main", "v1.0", &hex::encode(commit), &hex::encode(tag)] {
+            assert_eq!(
+                Revision::from_backend(&backend, name).unwrap().swhid(),
+                Swhid::new(ObjectType::Revision, commit),
+                "{}",
+                name
+            );
+        }
+
+        let release = Release::from_backend(&backend, "v1.0").unwrap();
+        assert_eq!(release.swhid(), Swhid::new(ObjectType::Release, tag));
+        assert!(matches!(
+            Release::from_backend(&backend, "main"),
+            Err(SwhidError::InvalidInput(_))
+        ));
+        assert!(matches!(
+            Revision::from_backend(&backend, "no-such-branch"),
+            Err(SwhidError::Git(_))
+        ));
+    }
+
+    #[test]
+    fn test_snapshot_from_backend() {
+        let (backend, commit, tag) = sample_backend();
+        let mut expected = Snapshot::new();
+        expected.add_branch(b"HEAD".to_vec(), SnapshotTarget::Alias(b"refs/heads/main".to_vec())).unwrap();
+        expected
+            .add_branch(b"refs/heads/main".to_vec(), SnapshotTarget::Object(Swhid::new(ObjectType::Revision, commit)))
+            .unwrap();
+        expected
+            .add_branch(b"refs/tags/v1.0".to_vec(), SnapshotTarget::Object(Swhid::new(ObjectType::Release, tag)))
+            .unwrap();
+        assert_eq!(Snapshot::from_backend(&backend).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_snapshot_from_ls_remote() {
+        let (backend, commit, tag) = sample_backend();
+        let output = format!(
+            "ref: refs/heads/main\tHEAD\n\
+             {commit}\tHEAD\n\
+             {commit}\trefs/heads/main\n\
+             {tag}\trefs/tags/v1.0\n\
+             {commit}\trefs/tags/v1.0^{{}}\n",
+            commit = hex::encode(commit),
+            tag = hex::encode(tag)
+        );
+        let snapshot = Snapshot::from_ls_remote(output.as_bytes()).unwrap();
+        assert_eq!(snapshot, Snapshot::from_backend(&backend).unwrap());
+
+        // Without `--symref`, HEAD is only known by its object
+        let plain = output.replace("ref: refs/heads/main\tHEAD\n", "");
+        assert_eq!(
+            Snapshot::from_ls_remote(plain.as_bytes()).unwrap().branches()[&b"HEAD"[..]],
+            SnapshotTarget::Object(Swhid::new(ObjectType::Revision, commit))
+        );
+
+        for invalid in ["HEAD\n", "1e048ab5\tHEAD\n"] {
+            assert!(matches!(
+                Snapshot::from_ls_remote(invalid.as_bytes()),
+                Err(SwhidError::InvalidInput(_))
+            ));
+        }
+        let duplicate = format!("{0}\tHEAD\n{0}\tHEAD\n", hex::encode(commit));
+        assert!(matches!(
+            Snapshot::from_ls_remote(duplicate.as_bytes()),
+            Err(SwhidError::DuplicateEntry(_))
+        ));
+    }
+
+    #[test]
+    fn test_directory_from_backend() {
+        let mut backend = MemoryBackend::default();
+        let main = backend.add_object(ObjectType::Content, "blob", b"fn main() {}\n");
+        let mut src = b"100644 main.rs\0".to_vec();
+        src.extend_from_slice(&main);
+        let src = backend.add_object(ObjectType::Directory, "tree", &src);
+        let mut root = b"100644 README\0".to_vec();
+        root.extend_from_slice(&main);
+        root.extend_from_slice(b"40000 src\0");
+        root.extend_from_slice(&src);
+        let root = backend.add_object(ObjectType::Directory, "tree", &root);
+        let commit = format!(
+            "tree {}\nauthor Jane Doe <jane@example.org> 1700000000 +0000\n\
+             committer Jane Doe <jane@example.org> 1700000000 +0000\n\nFirst\n",
+            hex::encode(root)
+        );
+        let commit = backend.add_object(ObjectType::Revision, "commit", commit.as_bytes());
+        let tag = format!("object {}\ntype commit\ntag v1.0\n\nRelease\n", hex::encode(commit));
+        let tag = backend.add_object(ObjectType::Release, "tag", tag.as_bytes());
+        backend.add_reference("refs/heads/main", GitReference::Direct(commit));
+        backend.add_reference("refs/tags/v1.0", GitReference::Direct(tag));
+
+        for name in ["main", "v1.0", &hex::encode(root)] {
+            for (path, expected) in [("", root), ("src", src), ("./src/", src)] {
+                assert_eq!(
+                    Directory::from_backend(&backend, name, Path::new(path)).unwrap(),
+                    Swhid::new(ObjectType::Directory, expected)
+                );
+            }
+        }
+        for path in ["README", "docs", "src/main.rs/x", "../src", "/src"] {
+            assert!(matches!(
+                Directory::from_backend(&backend, "main", Path::new(path)),
+                Err(SwhidError::InvalidFilePath(..))
+            ));
+        }
+        assert!(matches!(
+            Directory::from_backend(&backend, &hex::encode(main), Path::new("")),
+            Err(SwhidError::InvalidInput(_))
+        ));
+
+        backend.objects.insert(root, (ObjectType::Directory, b"tampered".to_vec()));
+        assert!(matches!(
+            Directory::from_backend(&backend, "main", Path::new("src")),
+            Err(SwhidError::InvalidHash(_))
+        ));
+    }
+
+    #[test]
+    fn test_backend_objects_are_verified() {
+        let (mut backend, commit, _) = sample_backend();
+        let mut tampered = COMMIT.to_vec();
+        tampered.extend_from_slice(b"Tampered\n");
+        backend.objects.insert(commit, (ObjectType::Revision, tampered));
+        assert!(matches!(
+            Revision::from_backend(&backend, "HEAD"),
+            Err(SwhidError::InvalidHash(_))
+        ));
+
+        // Symbolic references that loop never resolve
+        let mut looping = MemoryBackend::default();
+        looping.add_reference("HEAD", GitReference::Symbolic(b"refs/heads/a".to_vec()));
+        looping.add_reference("refs/heads/a", GitReference::Symbolic(b"HEAD".to_vec()));
+        assert!(matches!(looping.resolve("HEAD"), Err(SwhidError::Git(_))));
+    }
+}
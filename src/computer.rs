@@ -1,14 +1,233 @@
-use std::path::Path;
-use crate::swhid::Swhid;
+use std::fs::{self, File, Metadata};
+use std::io::{BufRead, BufReader, Read};
+use std::path::{Component, Path, PathBuf};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::Arc;
+use crate::swhid::{Swhid, ObjectType, QualifiedSwhid};
+use crate::hash::V1HashAlgorithm;
 use crate::error::SwhidError;
-use crate::content::Content;
-use crate::directory::Directory;
+use crate::content::{Content, LfsPointer};
+use crate::directory::{name_bytes, name_path, relative_components, Directory, EntryType, OpenFileLimit, TreeNode, WalkEntry};
+
+/// Unicode normalization forms applicable to file names
+#[cfg(feature = "unicode")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NormalizationForm {
+    /// Canonical composition, as typically found on Linux
+    Nfc,
+    /// Canonical decomposition, as stored by macOS file systems
+    Nfd,
+}
+
+/// Names of version control metadata entries skipped by [`SwhidComputer::with_skip_vcs`]
+pub const VCS_NAMES: &[&str] = &[".bzr", ".git", ".hg", ".svn", "CVS", "_darcs"];
+
+/// Caller-supplied lookup of the real content behind a Git LFS pointer
+pub type LfsResolver = Arc<dyn Fn(&LfsPointer) -> Result<Vec<u8>, SwhidError> + Send + Sync>;
+
+/// A path whose SWHID differs from the one recorded in a manifest
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ManifestMismatch {
+    pub path: PathBuf,
+    pub expected: Swhid,
+    pub actual: Swhid,
+}
+
+/// Outcome of [`SwhidComputer::verify_tree_manifest`]
+///
+/// All paths are relative to the verified root and sorted.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ManifestReport {
+    /// Paths whose SWHID matches the manifest
+    pub matches: Vec<PathBuf>,
+    /// Paths whose SWHID differs from the manifest
+    pub mismatches: Vec<ManifestMismatch>,
+    /// Manifest paths that do not exist in the tree
+    pub missing: Vec<PathBuf>,
+    /// Tree paths that are not listed in the manifest
+    pub extra: Vec<PathBuf>,
+}
+
+impl ManifestReport {
+    /// Whether the tree matches the manifest exactly
+    pub fn is_ok(&self) -> bool {
+        self.mismatches.is_empty() && self.missing.is_empty() && self.extra.is_empty()
+    }
+}
+
+/// Outcome of [`SwhidComputer::diff_trees`]
+///
+/// All paths are relative to the compared roots and sorted. A directory only
+/// one side has is listed alone, not with its contents.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TreeDiff {
+    /// Directory SWHID of the old tree
+    pub old: Swhid,
+    /// Directory SWHID of the new tree
+    pub new: Swhid,
+    /// Paths only the new tree has
+    pub added: Vec<PathBuf>,
+    /// Paths only the old tree has
+    pub removed: Vec<PathBuf>,
+    /// Paths both trees have, with a different SWHID, kind or mode
+    ///
+    /// Directories are not listed for changes below them.
+    pub modified: Vec<PathBuf>,
+}
+
+impl TreeDiff {
+    /// Whether both trees have the same SWHID
+    pub fn is_empty(&self) -> bool {
+        self.old == self.new
+    }
+}
+
+/// Outcome of [`SwhidComputer::verify_swhid_detailed`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verification {
+    /// The computed SWHID is the expected one
+    Match,
+    /// The path is a different kind of object than expected
+    ObjectTypeMismatch { expected: Swhid, actual: Swhid },
+    /// The path is the expected kind of object, with a different hash
+    HashMismatch { expected: Swhid, actual: Swhid },
+}
+
+impl Verification {
+    /// Compare a computed SWHID to the expected one
+    fn new(expected: Swhid, actual: Swhid) -> Self {
+        if expected == actual {
+            Verification::Match
+        } else if expected.object_type() != actual.object_type() {
+            Verification::ObjectTypeMismatch { expected, actual }
+        } else {
+            Verification::HashMismatch { expected, actual }
+        }
+    }
+
+    /// Whether the computed SWHID is the expected one
+    pub fn is_match(&self) -> bool {
+        matches!(self, Verification::Match)
+    }
+}
+
+impl std::fmt::Display for Verification {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Verification::Match => write!(f, "SWHID matches"),
+            Verification::ObjectTypeMismatch { expected, actual } => write!(
+                f,
+                "object type differs: expected {}, got {}",
+                expected.object_type(),
+                actual.object_type()
+            ),
+            Verification::HashMismatch { expected, actual } => {
+                write!(f, "hash differs: expected {}, got {}", expected, actual)
+            }
+        }
+    }
+}
+
+/// Outcome of checking one qualifier in [`SwhidComputer::verify_qualified_swhid`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QualifierCheck {
+    /// The qualifier holds for the local files
+    Passed,
+    /// The qualifier does not hold, for the reason given
+    Failed(String),
+    /// The qualifier cannot be checked from local files
+    Unchecked,
+}
+
+/// Outcome of [`SwhidComputer::verify_qualified_swhid`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QualifiedVerification {
+    /// Outcome for the core SWHID, of the entry the `path` qualifier locates if any
+    pub core: Verification,
+    /// Outcome for each qualifier set, in canonical order
+    pub qualifiers: Vec<(&'static str, QualifierCheck)>,
+}
+
+impl QualifiedVerification {
+    /// Whether the core SWHID matches and no qualifier failed
+    pub fn is_match(&self) -> bool {
+        self.core.is_match() && !self.qualifiers.iter().any(|(_, check)| matches!(check, QualifierCheck::Failed(_)))
+    }
+
+    /// Get the outcome for a qualifier, if it is set
+    pub fn qualifier(&self, key: &str) -> Option<&QualifierCheck> {
+        self.qualifiers.iter().find(|(k, _)| *k == key).map(|(_, check)| check)
+    }
+}
+
+/// Caller-supplied transformation of directory entry names
+pub type NameTransform = Arc<dyn Fn(&[u8]) -> Vec<u8> + Send + Sync>;
+
+/// Files bucketed by content SWHID, as built by [`SwhidComputer::group_by_content`]
+pub type ContentGroups = HashMap<Swhid, Vec<PathBuf>>;
+
+/// Caller-supplied warning for a file that changed while it was hashed
+pub type FileChangedHandler = Arc<dyn Fn(&Path) + Send + Sync>;
+
+/// What to do with devices, FIFOs and sockets found in a directory
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SpecialFilePolicy {
+    /// Leave the entry out of its directory
+    Skip,
+    /// Fail with `SwhidError::InvalidFilePath`
+    #[default]
+    Error,
+    /// Hash the entry as an empty, non-executable regular file
+    HashAsEmpty,
+}
+
+/// What to do with symlinks whose target does not exist
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BrokenSymlinkPolicy {
+    /// Hash the symlink as its target path, as the specification does
+    #[default]
+    Hash,
+    /// Leave the entry out of its directory
+    Skip,
+    /// Fail with `SwhidError::InvalidFilePath`
+    Error,
+}
+
+/// Where the SWHID of a directory inside a Git working tree comes from
+#[cfg(feature = "git")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum GitTreePolicy {
+    /// Hash the files on disk, as for any other directory
+    #[default]
+    Recompute,
+    /// Take the id of the tree Git recorded for the directory in `HEAD`
+    Trust,
+}
 
 /// Minimal SWHID computer for core functionality
 #[derive(Clone, Default)]
 pub struct SwhidComputer {
     pub follow_symlinks: bool,
     pub exclude_patterns: Vec<String>,
+    pub record_mtime: bool,
+    pub ignore_shebang: bool,
+    pub skip_vcs: bool,
+    pub materialize_symlinks: bool,
+    pub max_concurrency: Option<usize>,
+    pub lfs_resolver: Option<LfsResolver>,
+    pub name_transform: Option<NameTransform>,
+    pub on_file_changed: Option<FileChangedHandler>,
+    pub strict_consistency: bool,
+    pub special_file_policy: SpecialFilePolicy,
+    pub broken_symlink_policy: BrokenSymlinkPolicy,
+    pub submodules: bool,
+    pub hash_algorithm: V1HashAlgorithm,
+    #[cfg(feature = "unicode")]
+    pub unicode_normalization: Option<NormalizationForm>,
+    #[cfg(feature = "parallel")]
+    pub parallelism: Option<usize>,
+    #[cfg(feature = "git")]
+    pub git_tree_policy: GitTreePolicy,
 }
 
 impl SwhidComputer {
@@ -18,33 +237,726 @@ impl SwhidComputer {
     }
 
     /// Set whether to follow symlinks
+    ///
+    /// This is non-standard within directories: the specification hashes a
+    /// symlink as its target path, so the resulting SWHIDs will not match the
+    /// archive. A symlink given directly to [`SwhidComputer::compute_swhid`]
+    /// is resolved to its target. Within directories, a symlink to a file is
+    /// hashed as that file, with its executable bit, and a symlink to a
+    /// directory as that directory; dangling symlinks stay symlinks. Symlink
+    /// cycles fail with `SwhidError::InvalidFilePath`. Takes precedence over
+    /// [`SwhidComputer::with_materialize_symlinks`].
     pub fn with_follow_symlinks(mut self, follow_symlinks: bool) -> Self {
         self.follow_symlinks = follow_symlinks;
         self
     }
 
     /// Set exclude patterns
+    ///
+    /// Patterns use shell-style wildcards (`*`, `?`, `[...]`) as in Python's
+    /// `fnmatch`. A pattern without `/` is matched against entry names, e.g.
+    /// `*.tmp`; one with a `/` is matched against the path relative to the
+    /// traversal root, e.g. `build/*`.
     pub fn with_exclude_patterns(mut self, exclude_patterns: &[String]) -> Self {
         self.exclude_patterns = exclude_patterns.to_vec();
         self
     }
 
+    /// Set whether to skip version control metadata in directories
+    ///
+    /// Entries named after the metadata of common version control systems
+    /// ([`VCS_NAMES`]) are left out of the tree, whatever their type. This
+    /// covers `.git` files as well as directories: Git worktrees and
+    /// submodules have a `.git` file pointing to the actual repository.
+    pub fn with_skip_vcs(mut self, skip_vcs: bool) -> Self {
+        self.skip_vcs = skip_vcs;
+        self
+    }
+
+    /// Add the exclude patterns of a `swh scanner` configuration file
+    ///
+    /// Only the `exclude` list of the `scanner` section is honored, given
+    /// either as a block sequence or as an inline `[...]` list; a top-level
+    /// `exclude` list is accepted too. Every other setting (server, templates,
+    /// global and VCS pattern toggles) is ignored. Patterns are appended to
+    /// the ones already configured.
+    pub fn with_scanner_config<P: AsRef<Path>>(mut self, path: P) -> Result<Self, SwhidError> {
+        let config = std::fs::read_to_string(path)?;
+        self.exclude_patterns.extend(parse_scanner_excludes(&config)?);
+        Ok(self)
+    }
+
+    /// Hash the real content of Git LFS pointer files instead of the pointers
+    ///
+    /// Without a resolver, pointer files are hashed as they are on disk, which
+    /// is what a Git checkout without LFS contains. With a resolver, any file
+    /// that parses as an LFS pointer is replaced by the bytes `resolver`
+    /// returns for it, which must match the pointer's oid and size.
+    pub fn with_resolve_lfs<F>(mut self, resolver: F) -> Self
+    where
+        F: Fn(&LfsPointer) -> Result<Vec<u8>, SwhidError> + Send + Sync + 'static,
+    {
+        self.lfs_resolver = Some(Arc::new(resolver));
+        self
+    }
+
+    /// Set whether to hash content without its leading `#!` line
+    ///
+    /// This is non-standard: the resulting SWHIDs identify a modified content
+    /// and will not match the archive. It lets scripts that differ only in
+    /// their interpreter line compare equal. Only content starting with `#!`
+    /// is affected; the line is dropped along with its newline. Applies to
+    /// files and to [`SwhidComputer::compute_content_swhid`], but not to
+    /// [`SwhidComputer::compute_content_reader_sized`].
+    pub fn with_ignore_shebang(mut self, ignore_shebang: bool) -> Self {
+        self.ignore_shebang = ignore_shebang;
+        self
+    }
+
+    /// Set whether symlinks in directories are replaced by their target file
+    ///
+    /// This is non-standard: the specification hashes a symlink as its target
+    /// path, so the resulting SWHIDs will not match the archive. It mimics
+    /// exports such as `git archive` setups that store a copy of the target
+    /// file in place of the link. The entry keeps its name but takes the
+    /// target's content and executable bit. Dangling symlinks and symlinks to
+    /// anything but a regular file fail with `SwhidError::InvalidFilePath`.
+    pub fn with_materialize_symlinks(mut self, materialize_symlinks: bool) -> Self {
+        self.materialize_symlinks = materialize_symlinks;
+        self
+    }
+
+    /// Set how devices, FIFOs and sockets in directories are handled
+    ///
+    /// They have no content to hash, and are never read as regular files.
+    /// By default they fail with `SwhidError::InvalidFilePath`.
+    pub fn with_special_file_policy(mut self, policy: SpecialFilePolicy) -> Self {
+        self.special_file_policy = policy;
+        self
+    }
+
+    /// Set how symlinks in directories whose target does not exist are handled
+    ///
+    /// By default they are hashed like any other symlink, as their target
+    /// path. Materialized symlinks must resolve regardless of this policy.
+    pub fn with_broken_symlink_policy(mut self, policy: BrokenSymlinkPolicy) -> Self {
+        self.broken_symlink_policy = policy;
+        self
+    }
+
+    /// Set whether nested Git checkouts in directories are hashed as submodules
+    ///
+    /// Git records a submodule as a gitlink entry (mode `160000`) pointing to
+    /// the commit checked out in it, rather than as its files. With this set,
+    /// any subdirectory holding a `.git` directory or file becomes such an
+    /// entry, pointing to the commit its `HEAD` resolves to, so a checkout
+    /// with submodules hashes as the tree Git would commit. Resolving `HEAD`
+    /// only reads references, so no Git library is needed. The traversal root
+    /// itself is always read as a directory.
+    pub fn with_submodules(mut self, submodules: bool) -> Self {
+        self.submodules = submodules;
+        self
+    }
+
+    /// Set the algorithm contents and directories are hashed with
+    ///
+    /// The default, [`V1HashAlgorithm::Sha1Checked`], is what the specification
+    /// requires. [`V1HashAlgorithm::Sha1`] is faster and gives the same SWHIDs
+    /// except for files crafted for a collision attack, so it only suits
+    /// trusted trees.
+    pub fn with_hash_algorithm(mut self, hash_algorithm: V1HashAlgorithm) -> Self {
+        self.hash_algorithm = hash_algorithm;
+        self
+    }
+
+    /// Cap the number of files open or being hashed at the same time
+    ///
+    /// Meant for parallel traversal of huge trees, where unbounded parallelism
+    /// can exhaust file descriptors: worker threads set with
+    /// `with_parallelism` wait for one of the `max_concurrency` slots before
+    /// listing a directory or hashing a file. Sequential traversal only ever
+    /// has one file open, so it always satisfies the cap. A cap of 0 is
+    /// treated as 1. The computed SWHIDs never depend on this setting.
+    pub fn with_max_concurrency(mut self, max_concurrency: usize) -> Self {
+        self.max_concurrency = Some(max_concurrency.max(1));
+        self
+    }
+
+    /// Hash directories with `threads` worker threads
+    ///
+    /// Subdirectories and files are hashed concurrently; the resulting SWHIDs
+    /// are identical to those of sequential traversal. 0 uses one thread per
+    /// CPU. Only [`SwhidComputer::compute_directory_swhid`] (and
+    /// [`SwhidComputer::compute_swhid`] on directories) and the batch
+    /// [`SwhidComputer::compute_file_swhids`] run in parallel; walks and
+    /// best-effort traversal report entries in order, so they stay sequential.
+    #[cfg(feature = "parallel")]
+    pub fn with_parallelism(mut self, threads: usize) -> Self {
+        self.parallelism = Some(threads);
+        self
+    }
+
+    /// Build the thread pool set by [`SwhidComputer::with_parallelism`], if any
+    #[cfg(feature = "parallel")]
+    pub(crate) fn thread_pool(&self) -> Result<Option<rayon::ThreadPool>, SwhidError> {
+        let Some(threads) = self.parallelism else {
+            return Ok(None);
+        };
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .map(Some)
+            .map_err(|e| SwhidError::Io(std::io::Error::other(e)))
+    }
+
+    /// Set whether walks record each entry's modification time
+    ///
+    /// Modification times are reported alongside entries by
+    /// [`SwhidComputer::walk`] and never affect the computed SWHIDs.
+    pub fn with_record_mtime(mut self, record_mtime: bool) -> Self {
+        self.record_mtime = record_mtime;
+        self
+    }
+
+    /// Warn about files that change while they are being hashed
+    ///
+    /// Each file's size and modification time are checked again once it has
+    /// been read; if they differ, or the file is gone, `handler` is called
+    /// with its path and the scan goes on. The SWHID is then computed from
+    /// whatever bytes were read, and may not reflect a consistent snapshot of
+    /// a live tree. Changes that keep both size and modification time (within
+    /// the file system's timestamp resolution) go unnoticed.
+    pub fn with_on_file_changed<F>(mut self, handler: F) -> Self
+    where
+        F: Fn(&Path) + Send + Sync + 'static,
+    {
+        self.on_file_changed = Some(Arc::new(handler));
+        self
+    }
+
+    /// Set whether a file changing while it is being hashed is an error
+    ///
+    /// Performs the same check as [`SwhidComputer::with_on_file_changed`],
+    /// but fails with `SwhidError::InvalidFilePath` instead of warning.
+    pub fn with_strict_consistency(mut self, strict_consistency: bool) -> Self {
+        self.strict_consistency = strict_consistency;
+        self
+    }
+
+    /// Normalize file names to the given Unicode form before hashing
+    ///
+    /// This is non-standard: the Software Heritage archive hashes names as raw
+    /// bytes, so SWHIDs computed with normalization enabled may not match the
+    /// archive. It is meant to make checkouts of the same tree on macOS (NFD)
+    /// and Linux (NFC) produce the same identifier. Names that are not valid
+    /// UTF-8 are left untouched.
+    #[cfg(feature = "unicode")]
+    pub fn with_unicode_normalization(mut self, form: NormalizationForm) -> Self {
+        self.unicode_normalization = Some(form);
+        self
+    }
+
+    /// Choose whether directories inside Git working trees are read from Git
+    ///
+    /// With [`GitTreePolicy::Trust`], [`SwhidComputer::compute_directory_swhid`]
+    /// looks up the repository holding the directory, and returns the tree
+    /// recorded for it in the commit checked out, without reading any file.
+    /// This is much faster on large trees, but trusts both Git's hashes and
+    /// the checkout: uncommitted changes and untracked files are not seen, and
+    /// the other options of the computer do not apply. Directories outside a
+    /// working tree, or not recorded in `HEAD`, are hashed from disk.
+    #[cfg(feature = "git")]
+    pub fn with_git_tree_policy(mut self, policy: GitTreePolicy) -> Self {
+        self.git_tree_policy = policy;
+        self
+    }
+
+    /// Transform every directory entry name before hashing
+    ///
+    /// This is non-standard: the resulting SWHIDs identify a tree other than
+    /// the one on disk and will not match the archive. It supports custom
+    /// identity schemes, e.g. lowercasing names for a case-insensitive
+    /// identity. The transform runs after Unicode normalization, if any, and
+    /// must be deterministic. Entries whose transformed names collide are
+    /// reported as `SwhidError::DuplicateEntry`.
+    pub fn with_name_transform<F>(mut self, transform: F) -> Self
+    where
+        F: Fn(&[u8]) -> Vec<u8> + Send + Sync + 'static,
+    {
+        self.name_transform = Some(Arc::new(transform));
+        self
+    }
+
+    /// Check whether a directory entry is left out, by its on-disk name
+    /// and its `/`-separated path from the traversal root
+    pub(crate) fn is_skipped(&self, name: &[u8], relative_path: &str) -> bool {
+        if self.skip_vcs && VCS_NAMES.iter().any(|vcs| vcs.as_bytes() == name) {
+            return true;
+        }
+        Directory::should_exclude(name, relative_path, &self.exclude_patterns)
+    }
+
+    /// Apply the configured name transformations to a directory entry name
+    pub(crate) fn entry_name(&self, name: Vec<u8>) -> Vec<u8> {
+        let name = self.normalize_name(name);
+        match &self.name_transform {
+            Some(transform) => transform(&name),
+            None => name,
+        }
+    }
+
+    /// Apply the configured Unicode normalization to a directory entry name
+    fn normalize_name(&self, name: Vec<u8>) -> Vec<u8> {
+        #[cfg(feature = "unicode")]
+        if let Some(form) = self.unicode_normalization {
+            use unicode_normalization::UnicodeNormalization;
+
+            if let Ok(name_str) = std::str::from_utf8(&name) {
+                let normalized: String = match form {
+                    NormalizationForm::Nfc => name_str.nfc().collect(),
+                    NormalizationForm::Nfd => name_str.nfd().collect(),
+                };
+                return normalized.into_bytes();
+            }
+        }
+        name
+    }
+
     /// Compute SWHID for content bytes
     pub fn compute_content_swhid(&self, content: &[u8]) -> Result<Swhid, SwhidError> {
-        let content_obj = Content::from_data(content.to_vec());
+        let content_obj = Content::from_data_with(self.content_data(content).to_vec(), self.hash_algorithm);
         Ok(content_obj.swhid())
     }
 
+    /// Compute the draft v2 content SWHID of `content`, transformed as for
+    /// [`SwhidComputer::compute_content_swhid`]
+    #[cfg(feature = "v2")]
+    pub fn compute_content_swhid_v2(&self, content: &[u8]) -> Swhid {
+        let content_obj = Content::from_data(self.content_data(content).to_vec());
+        content_obj.swhid_v2().expect("content data is retained")
+    }
+
+    /// Compute the draft v2 content SWHID of a file
+    ///
+    /// The content options apply as for [`SwhidComputer::compute_file_swhid`],
+    /// but the file is read into memory. Directories and other object types
+    /// have no v2 SWHIDs yet, as tree entries hold SHA-1 ids.
+    #[cfg(feature = "v2")]
+    pub fn compute_file_swhid_v2<P: AsRef<Path>>(&self, path: P) -> Result<Swhid, SwhidError> {
+        let path = path.as_ref();
+        let data = fs::read(path).map_err(|e| SwhidError::from(e).at_path(path))?;
+        let content = self.data_content(path, data)?;
+        Ok(content.swhid_v2().expect("content data is retained"))
+    }
+
+    /// Apply the configured content transformations to raw content bytes
+    fn content_data<'a>(&self, data: &'a [u8]) -> &'a [u8] {
+        if self.ignore_shebang && data.starts_with(b"#!") {
+            return match data.iter().position(|&b| b == b'\n') {
+                Some(end) => &data[end + 1..],
+                None => &[],
+            };
+        }
+        data
+    }
+
+    /// Compute content SWHID for data read from `reader`, of known `length`
+    ///
+    /// The data is streamed into the hasher rather than buffered. Fails with
+    /// `SwhidError::InvalidInput` if the reader yields a different number of
+    /// bytes than `length`.
+    pub fn compute_content_reader_sized<R: Read>(
+        &self,
+        reader: R,
+        length: u64,
+    ) -> Result<Swhid, SwhidError> {
+        let hash = self.hash_algorithm.hash_git_object_reader("blob", reader, length)?;
+        Ok(Swhid::new(ObjectType::Content, hash))
+    }
+
     /// Compute SWHID for a file
+    ///
+    /// The file is streamed through the hasher rather than held in memory,
+    /// except for files that may be LFS pointers when a resolver is set.
     pub fn compute_file_swhid<P: AsRef<Path>>(&self, path: P) -> Result<Swhid, SwhidError> {
-        let content = Content::from_file(path)?;
-        Ok(content.swhid())
+        let hash = self.file_hash(path.as_ref())?;
+        Ok(Swhid::new(ObjectType::Content, hash))
+    }
+
+    /// Compute the SWHIDs of many files, as by [`SwhidComputer::compute_file_swhid`]
+    ///
+    /// Each path is returned with its own result, in input order, so one
+    /// unreadable file does not abort the batch. With
+    /// [`SwhidComputer::with_parallelism`], files are hashed concurrently.
+    pub fn compute_file_swhids<I: IntoIterator<Item = PathBuf>>(
+        &self,
+        paths: I,
+    ) -> Vec<(PathBuf, Result<Swhid, SwhidError>)> {
+        let open_files = self.max_concurrency.map(OpenFileLimit::new);
+        let hash = |path: PathBuf| {
+            let _permit = open_files.as_ref().map(OpenFileLimit::acquire);
+            let result = self.compute_file_swhid(&path);
+            (path, result)
+        };
+        #[cfg(feature = "parallel")]
+        // A pool that cannot be built falls back to hashing sequentially
+        if let Ok(Some(pool)) = self.thread_pool() {
+            use rayon::prelude::*;
+            let paths: Vec<PathBuf> = paths.into_iter().collect();
+            return pool.install(|| paths.into_par_iter().map(hash).collect());
+        }
+        paths.into_iter().map(hash).collect()
+    }
+
+    /// Group files by their content SWHID, e.g. to find duplicates
+    ///
+    /// Each path is hashed as by [`SwhidComputer::compute_file_swhid`]; files
+    /// with identical content end up in the same bucket, in the order they
+    /// were given. Files that cannot be hashed are left out of the groups and
+    /// reported alongside their path instead.
+    pub fn group_by_content(
+        &self,
+        paths: &[PathBuf],
+    ) -> (ContentGroups, Vec<(PathBuf, SwhidError)>) {
+        let mut groups = ContentGroups::new();
+        let mut errors = Vec::new();
+        for (path, result) in self.compute_file_swhids(paths.iter().cloned()) {
+            match result {
+                Ok(swhid) => groups.entry(swhid).or_default().push(path),
+                Err(err) => errors.push((path, err)),
+            }
+        }
+        (groups, errors)
+    }
+
+    /// Hash a regular file, applying the content options, without retaining it
+    pub(crate) fn file_hash(&self, path: &Path) -> Result<[u8; 20], SwhidError> {
+        let file = File::open(path)?;
+        let metadata = file.metadata()?;
+        let hash = self.reader_hash(path, file, metadata.len())?;
+        self.check_unchanged(path, &metadata)?;
+        Ok(hash)
+    }
+
+    /// Hash `length` bytes of a file's data from `reader`, applying the
+    /// content options; `path` names the file in errors
+    pub(crate) fn reader_hash<R: Read>(&self, path: &Path, reader: R, mut length: u64) -> Result<[u8; 20], SwhidError> {
+        // LFS pointers are tiny, and resolving one needs its bytes
+        if self.lfs_resolver.is_some() && length < LfsPointer::MAX_SIZE as u64 {
+            let mut data = Vec::with_capacity(length as usize);
+            reader.take(length).read_to_end(&mut data)?;
+            if data.len() as u64 != length {
+                return Err(SwhidError::InvalidInput(format!(
+                    "Reader yielded {} bytes, expected {}", data.len(), length
+                )));
+            }
+            return Ok(*self.data_content(path, data)?.sha1_git());
+        }
+
+        let mut reader = BufReader::new(reader);
+        if self.ignore_shebang && reader.fill_buf()?.starts_with(b"#!") {
+            let mut shebang = Vec::new();
+            reader.read_until(b'\n', &mut shebang)?;
+            length -= shebang.len() as u64;
+        }
+        self.hash_algorithm.hash_git_object_reader("blob", reader, length)
+    }
+
+    /// Report a file whose size or modification time no longer match `before`
+    fn check_unchanged(&self, path: &Path, before: &Metadata) -> Result<(), SwhidError> {
+        if self.on_file_changed.is_none() && !self.strict_consistency {
+            return Ok(());
+        }
+        let changed = match fs::metadata(path) {
+            Ok(after) => after.len() != before.len() || after.modified().ok() != before.modified().ok(),
+            Err(_) => true,
+        };
+        if !changed {
+            return Ok(());
+        }
+        if self.strict_consistency {
+            return Err(SwhidError::InvalidFilePath(
+                path.to_path_buf(),
+                "file changed while being read".to_string(),
+            ));
+        }
+        if let Some(handler) = &self.on_file_changed {
+            handler(path);
+        }
+        Ok(())
+    }
+
+    /// Apply the content options to a file's data; `path` names it in errors
+    fn data_content(&self, path: &Path, file_data: Vec<u8>) -> Result<Content, SwhidError> {
+        if let Some(resolver) = &self.lfs_resolver {
+            if let Some(pointer) = LfsPointer::parse(&file_data) {
+                let data = resolver(&pointer)?;
+                if !pointer.matches(&data) {
+                    return Err(SwhidError::InvalidHash(format!(
+                        "Resolved LFS content does not match oid sha256:{} in {}",
+                        pointer.oid,
+                        path.display()
+                    )));
+                }
+                return Ok(Content::from_data_with(self.content_data(&data).to_vec(), self.hash_algorithm));
+            }
+        }
+
+        if self.ignore_shebang && file_data.starts_with(b"#!") {
+            return Ok(Content::from_data_with(self.content_data(&file_data).to_vec(), self.hash_algorithm));
+        }
+        Ok(Content::from_data_with(file_data, self.hash_algorithm))
     }
 
     /// Compute SWHID for a directory
     pub fn compute_directory_swhid<P: AsRef<Path>>(&self, path: P) -> Result<Swhid, SwhidError> {
-        let mut dir = Directory::from_disk(path, &self.exclude_patterns)?;
-        Ok(dir.swhid())
+        #[cfg(feature = "git")]
+        if self.git_tree_policy == GitTreePolicy::Trust {
+            if let Some(swhid) = crate::git::recorded_directory_swhid(path.as_ref())? {
+                return Ok(swhid);
+            }
+        }
+        let mut dir = Directory::read_disk(path.as_ref(), self)?;
+        Ok(dir.swhid_with(self.hash_algorithm))
+    }
+
+    /// Compute SWHID for a directory, collecting per-entry errors
+    ///
+    /// Entries that cannot be read are treated as absent and reported alongside
+    /// their path. When any error is returned, the SWHID is a non-standard
+    /// partial result that identifies only the readable part of the tree.
+    pub fn compute_directory_best_effort<P: AsRef<Path>>(
+        &self,
+        path: P,
+    ) -> (Swhid, Vec<(PathBuf, SwhidError)>) {
+        let mut errors = Vec::new();
+        let mut dir = Directory::read_disk_best_effort(path.as_ref(), self, &mut errors);
+        (dir.swhid_with(self.hash_algorithm), errors)
+    }
+
+    /// Walk a directory, reporting the SWHID of every object in the tree
+    ///
+    /// `visitor` is called for each file, symlink and subdirectory as soon as
+    /// its SWHID is computed, so results can be consumed while the walk is
+    /// still running. Children are visited before their parent, and the root
+    /// directory is visited last. Returns the SWHID of the root.
+    pub fn walk<P, F>(&self, path: P, mut visitor: F) -> Result<Swhid, SwhidError>
+    where
+        P: AsRef<Path>,
+        F: FnMut(WalkEntry),
+    {
+        let mut dir = Directory::walk_disk(path.as_ref(), self, &mut visitor)?;
+        Ok(dir.swhid_with(self.hash_algorithm))
+    }
+
+    /// Compute the SWHID of every object of a directory tree
+    ///
+    /// Maps each file, symlink and subdirectory, by its path relative to
+    /// `root`, to its SWHID; the root itself is under the empty path. The
+    /// result is in the format [`SwhidComputer::verify_tree_manifest`] takes.
+    /// Use [`SwhidComputer::walk`] to consume entries as they are computed.
+    pub fn compute_swhid_tree<P: AsRef<Path>>(&self, root: P) -> Result<BTreeMap<PathBuf, Swhid>, SwhidError> {
+        let root = root.as_ref();
+        let mut tree = BTreeMap::new();
+        self.walk(root, |entry| {
+            let path = entry.path.strip_prefix(root).unwrap_or(&entry.path).to_path_buf();
+            tree.insert(path, entry.swhid);
+        })?;
+        Ok(tree)
+    }
+
+    /// Compute a qualified SWHID for every file of a directory tree
+    ///
+    /// Each file gets its content SWHID, anchored to the directory SWHID of
+    /// `root` and with its path from `root` as `path`, e.g. `/src/main.rs`,
+    /// as recorded in the tree: names are transformed as configured. This is
+    /// the per-file provenance SBOM tools list. Symlinks and directories are
+    /// left out. Results are sorted by path.
+    pub fn compute_qualified_file_swhids<P: AsRef<Path>>(&self, root: P) -> Result<Vec<QualifiedSwhid>, SwhidError> {
+        let root = root.as_ref();
+        let mut files = Vec::new();
+        let anchor = self.walk(root, |entry| {
+            if entry.entry_type != EntryType::File {
+                return;
+            }
+            let relative = entry.path.strip_prefix(root).unwrap_or(&entry.path);
+            let mut path = Vec::new();
+            for name in relative_components(relative).unwrap_or_default() {
+                path.push(b'/');
+                path.extend_from_slice(&self.entry_name(name));
+            }
+            files.push((path, entry.swhid));
+        })?;
+
+        files.sort_by(|a, b| a.0.cmp(&b.0));
+        files
+            .into_iter()
+            .map(|(path, swhid)| Ok(QualifiedSwhid::new(swhid).with_anchor(anchor.clone())?.with_path(path)))
+            .collect()
+    }
+
+    /// Verify every object of a directory tree against a manifest of SWHIDs
+    ///
+    /// `manifest` maps paths relative to `root` to their expected SWHID, e.g.
+    /// as listed by the archive for the corresponding directory. Each file,
+    /// symlink and subdirectory of the local tree is checked. The root itself
+    /// is only checked if the manifest has an entry for the empty path, and
+    /// is never reported as extra.
+    pub fn verify_tree_manifest<P: AsRef<Path>>(
+        &self,
+        root: P,
+        manifest: &BTreeMap<PathBuf, Swhid>,
+    ) -> Result<ManifestReport, SwhidError> {
+        let root = root.as_ref();
+        let mut report = ManifestReport::default();
+        let mut seen = BTreeSet::new();
+
+        self.walk(root, |entry| {
+            let path = entry.path.strip_prefix(root).unwrap_or(&entry.path).to_path_buf();
+            match manifest.get(&path) {
+                Some(expected) if *expected == entry.swhid => report.matches.push(path.clone()),
+                Some(expected) => report.mismatches.push(ManifestMismatch {
+                    path: path.clone(),
+                    expected: expected.clone(),
+                    actual: entry.swhid,
+                }),
+                None if path.as_os_str().is_empty() => {}
+                None => report.extra.push(path.clone()),
+            }
+            seen.insert(path);
+        })?;
+
+        report.missing = manifest
+            .keys()
+            .filter(|path| !seen.contains(*path))
+            .cloned()
+            .collect();
+        report.matches.sort();
+        report.mismatches.sort_by(|a, b| a.path.cmp(&b.path));
+        report.extra.sort();
+        Ok(report)
+    }
+
+    /// Compare two directory trees object by object
+    ///
+    /// Both trees are walked with the same settings, and each path is
+    /// compared by SWHID and by the kind and mode it has in its parent tree,
+    /// which together make up the directory SWHIDs. This explains why the
+    /// SWHIDs of `old` and `new` differ.
+    pub fn diff_trees<P: AsRef<Path>, Q: AsRef<Path>>(&self, old: P, new: Q) -> Result<TreeDiff, SwhidError> {
+        let (old_tree, old_root) = self.tree_entries(old.as_ref())?;
+        let (new_tree, new_root) = self.tree_entries(new.as_ref())?;
+
+        let mut diff = TreeDiff {
+            old: old_root,
+            new: new_root,
+            added: Vec::new(),
+            removed: Vec::new(),
+            modified: Vec::new(),
+        };
+        // Contents of an added or removed directory are not listed
+        let topmost = |other: &BTreeMap<PathBuf, WalkEntry>, path: &Path| {
+            path.parent()
+                .is_none_or(|parent| parent.as_os_str().is_empty() || other.contains_key(parent))
+        };
+        for (path, old_entry) in &old_tree {
+            match new_tree.get(path) {
+                None if topmost(&new_tree, path) => diff.removed.push(path.clone()),
+                None => {}
+                Some(new_entry)
+                    if old_entry.swhid == new_entry.swhid && old_entry.permissions == new_entry.permissions => {}
+                // Changes below a directory are listed themselves
+                Some(new_entry)
+                    if old_entry.entry_type == EntryType::Directory && new_entry.entry_type == EntryType::Directory => {}
+                Some(_) => diff.modified.push(path.clone()),
+            }
+        }
+        for path in new_tree.keys() {
+            if !old_tree.contains_key(path) && topmost(&old_tree, path) {
+                diff.added.push(path.clone());
+            }
+        }
+        Ok(diff)
+    }
+
+    /// Walk a tree, mapping the path of each object below `root` to its entry
+    fn tree_entries(&self, root: &Path) -> Result<(BTreeMap<PathBuf, WalkEntry>, Swhid), SwhidError> {
+        let mut tree = BTreeMap::new();
+        let swhid = self.walk(root, |entry| {
+            let path = entry.path.strip_prefix(root).unwrap_or(&entry.path).to_path_buf();
+            if !path.as_os_str().is_empty() {
+                tree.insert(path, entry);
+            }
+        })?;
+        Ok((tree, swhid))
+    }
+
+    /// Compute the SWHID of a directory restricted to an allowlist of paths
+    ///
+    /// A synthetic tree is built from just `include_paths` (relative to `root`),
+    /// creating intermediate directories as needed; everything else on disk is
+    /// ignored. Included directories are traversed in full. The result is a
+    /// filtered identity: it only matches the SWHID of `root` itself when the
+    /// allowlist covers the whole tree.
+    pub fn compute_directory_of<P: AsRef<Path>>(
+        &self,
+        root: P,
+        include_paths: &[PathBuf],
+    ) -> Result<Swhid, SwhidError> {
+        let root = root.as_ref();
+        let mut tree = TreeNode::new();
+
+        for include_path in include_paths {
+            let Some(names) = relative_components(include_path) else {
+                return Err(SwhidError::InvalidFilePath(
+                    include_path.to_path_buf(),
+                    "included path must be relative to the root".to_string(),
+                ));
+            };
+            let mut names: Vec<Vec<u8>> = names.into_iter().map(|name| self.entry_name(name)).collect();
+            let Some(name) = names.pop() else {
+                return Err(SwhidError::InvalidFilePath(
+                    include_path.to_path_buf(),
+                    "included path must name an entry below the root".to_string(),
+                ));
+            };
+
+            if let Some(entry) = Directory::read_disk_entry(root, &root.join(include_path), name, self)? {
+                tree.insert(&names, entry)?;
+            }
+        }
+
+        Ok(tree.into_directory(self.hash_algorithm)?.swhid_with(self.hash_algorithm))
+    }
+
+    /// Compute the directory SWHID of a subdirectory of `root`
+    ///
+    /// Only `root/relative_subdir` is scanned; the result equals the target
+    /// recorded for that subdirectory in `root`'s tree.
+    pub fn compute_subtree_swhid<P: AsRef<Path>, Q: AsRef<Path>>(
+        &self,
+        root: P,
+        relative_subdir: Q,
+    ) -> Result<Swhid, SwhidError> {
+        let relative_subdir = relative_subdir.as_ref();
+        let escapes_root = relative_subdir
+            .components()
+            .any(|c| !matches!(c, Component::Normal(_) | Component::CurDir));
+        if escapes_root {
+            return Err(SwhidError::InvalidFilePath(
+                relative_subdir.to_path_buf(),
+                "subtree path must be relative to the root".to_string(),
+            ));
+        }
+
+        let path = root.as_ref().join(relative_subdir);
+        if !path.is_dir() {
+            return Err(SwhidError::InvalidFilePath(
+                path,
+                "subtree path is not a directory".to_string(),
+            ));
+        }
+        self.compute_directory_swhid(path)
     }
 
     /// Auto-detect object type and compute SWHID
@@ -64,8 +976,7 @@ impl SwhidComputer {
             } else {
                 // Hash the symlink target as content
                 let target = std::fs::read_link(path)?;
-                let target_bytes = target.to_string_lossy().as_bytes().to_vec();
-                let content = Content::from_data(target_bytes);
+                let content = Content::from_data_with(name_bytes(target.as_os_str()), self.hash_algorithm);
                 Ok(content.swhid())
             }
         } else if path.is_file() {
@@ -78,15 +989,231 @@ impl SwhidComputer {
     }
 
     /// Verify that a SWHID matches the computed SWHID for a path
+    ///
+    /// `expected_swhid` may be qualified, as copied from the archive; only
+    /// its core identifier is compared.
     pub fn verify_swhid<P: AsRef<Path>>(&self, path: P, expected_swhid: &str) -> Result<bool, SwhidError> {
-        // Parse the expected SWHID
-        let expected = Swhid::from_string(expected_swhid)?;
-        
-        // Compute the actual SWHID
+        Ok(self.verify_swhid_detailed(path, expected_swhid)?.is_match())
+    }
+
+    /// Verify a SWHID as [`SwhidComputer::verify_swhid`], reporting what differs
+    pub fn verify_swhid_detailed<P: AsRef<Path>>(
+        &self,
+        path: P,
+        expected_swhid: &str,
+    ) -> Result<Verification, SwhidError> {
+        let expected = expected_swhid.parse::<QualifiedSwhid>()?.core().clone();
         let actual = self.compute_swhid(path)?;
-        
-        Ok(expected == actual)
+        Ok(Verification::new(expected, actual))
+    }
+
+    /// Verify the object a qualified SWHID locates with its `path` qualifier
+    ///
+    /// `root` is a local checkout of the anchor, and `path` leads from it to
+    /// an entry, whose SWHID is computed and compared to the core SWHID of
+    /// `qualified`. A directory anchor is checked against the SWHID of
+    /// `root`; other anchors cannot be checked from disk and are reported as
+    /// [`QualifierCheck::Unchecked`], as are all qualifiers but `path`. The
+    /// path is resolved as in the archive: a symlink it ends on is hashed as
+    /// a symlink, and one it passes through is not followed. Fails with
+    /// `SwhidError::InvalidQualifier` without a `path` qualifier, and with
+    /// `SwhidError::InvalidFilePath` if the path climbs out of `root` or
+    /// leads nowhere.
+    pub fn verify_path_qualifier<P: AsRef<Path>>(
+        &self,
+        root: P,
+        qualified: &QualifiedSwhid,
+    ) -> Result<QualifiedVerification, SwhidError> {
+        let root = root.as_ref();
+        let path = qualified
+            .path()
+            .ok_or_else(|| SwhidError::InvalidQualifier("No path qualifier to resolve".to_string()))?;
+        let target = locate_path_qualifier(root, path)?;
+
+        let mut anchor_check = QualifierCheck::Unchecked;
+        if let Some(anchor) = qualified.anchor().filter(|anchor| anchor.object_type() == ObjectType::Directory) {
+            let actual = self.compute_directory_swhid(root)?;
+            anchor_check = if actual == *anchor {
+                QualifierCheck::Passed
+            } else {
+                QualifierCheck::Failed(format!("{} hashes to {}", root.display(), actual))
+            };
+        }
+        // The archive never follows symlinks, so the entry is not followed either
+        let actual = if fs::symlink_metadata(&target)?.file_type().is_symlink() {
+            let link = fs::read_link(&target)?;
+            Content::from_data_with(name_bytes(link.as_os_str()), self.hash_algorithm).swhid()
+        } else {
+            self.compute_swhid(&target)?
+        };
+
+        let qualifiers = qualified
+            .qualifiers()
+            .into_iter()
+            .map(|(key, _)| {
+                let check = match key {
+                    "anchor" => anchor_check.clone(),
+                    "path" => QualifierCheck::Passed,
+                    _ => QualifierCheck::Unchecked,
+                };
+                (key, check)
+            })
+            .collect();
+        Ok(QualifiedVerification {
+            core: Verification::new(qualified.core().clone(), actual),
+            qualifiers,
+        })
+    }
+
+    /// Verify a qualified SWHID against local files, checking every qualifier it can
+    ///
+    /// Without a `path` qualifier, `path` is the object itself. With one,
+    /// `path` is a checkout of the anchor and the qualifier locates the
+    /// object, as for [`SwhidComputer::verify_path_qualifier`]. A `lines`
+    /// range is checked to be within the file, once its content matches.
+    /// `origin`, `visit` and `bytes`, and anchors other than directories, are
+    /// left unchecked. Fails with `SwhidError::InvalidFilePath` if the `path`
+    /// qualifier climbs out of `path` or leads nowhere.
+    pub fn verify_qualified_swhid<P: AsRef<Path>>(
+        &self,
+        path: P,
+        expected_swhid: &str,
+    ) -> Result<QualifiedVerification, SwhidError> {
+        let path = path.as_ref();
+        let qualified = expected_swhid.parse::<QualifiedSwhid>()?;
+        let (mut outcome, target) = match qualified.path() {
+            Some(qualifier) => {
+                let target = locate_path_qualifier(path, qualifier)?;
+                (self.verify_path_qualifier(path, &qualified)?, target)
+            }
+            None => {
+                let core = Verification::new(qualified.core().clone(), self.compute_swhid(path)?);
+                let qualifiers = qualified
+                    .qualifiers()
+                    .into_iter()
+                    .map(|(key, _)| (key, QualifierCheck::Unchecked))
+                    .collect();
+                (QualifiedVerification { core, qualifiers }, path.to_path_buf())
+            }
+        };
+
+        if outcome.core.is_match() && fs::symlink_metadata(&target)?.is_file() {
+            let data = fs::read(&target).map_err(|e| SwhidError::from(e).at_path(&target))?;
+            let lines_check = match qualified.extract_lines(&data) {
+                Ok(_) => QualifierCheck::Passed,
+                Err(SwhidError::InvalidQualifierValue(reason)) => QualifierCheck::Failed(reason),
+                // The file was hashed after a transformation, such as LFS
+                // pointer resolution, so its lines are not those cited
+                Err(_) => QualifierCheck::Unchecked,
+            };
+            if let Some((_, check)) = outcome.qualifiers.iter_mut().find(|(key, _)| *key == "lines") {
+                *check = lines_check;
+            }
+        }
+        Ok(outcome)
+    }
+}
+
+/// Locate the entry a `path` qualifier names below a checkout of its anchor
+///
+/// Every name but the last must be a directory, not a symlink to one, so the
+/// entry cannot lie outside `root`.
+fn locate_path_qualifier(root: &Path, path: &[u8]) -> Result<PathBuf, SwhidError> {
+    let mut target = root.to_path_buf();
+    let mut names = path.split(|&b| b == b'/').filter(|name| !name.is_empty() && *name != b".").peekable();
+    while let Some(name) = names.next() {
+        if name == b".." {
+            return Err(SwhidError::InvalidFilePath(
+                name_path(path),
+                "path qualifier must not climb out of the anchor".to_string(),
+            ));
+        }
+        target.push(name_path(name));
+        let file_type = match fs::symlink_metadata(&target) {
+            Ok(metadata) => metadata.file_type(),
+            Err(_) => return Err(SwhidError::InvalidFilePath(target, "path qualifier leads nowhere".to_string())),
+        };
+        if names.peek().is_some() && !file_type.is_dir() {
+            return Err(SwhidError::InvalidFilePath(
+                target,
+                "path qualifier passes through an entry that is not a directory".to_string(),
+            ));
+        }
+    }
+    Ok(target)
+}
+
+/// Extract the exclude patterns from a `swh scanner` YAML configuration
+///
+/// This understands just enough YAML to read the `scanner.exclude` list.
+fn parse_scanner_excludes(config: &str) -> Result<Vec<String>, SwhidError> {
+    let mut patterns = Vec::new();
+    let mut in_scanner = false;
+    let mut scanner_indent = None;
+    // Indentation of the `exclude` key while reading its block sequence
+    let mut list_indent = None;
+
+    for line in config.lines() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        let indent = line.len() - line.trim_start().len();
+
+        if let Some(key_indent) = list_indent {
+            if indent >= key_indent {
+                if let Some(item) = trimmed.strip_prefix('-') {
+                    patterns.push(unquote_yaml(item.trim()).to_string());
+                    continue;
+                }
+            }
+            list_indent = None;
+        }
+
+        if indent == 0 {
+            in_scanner = trimmed == "scanner:";
+            scanner_indent = None;
+            if in_scanner {
+                continue;
+            }
+        } else if in_scanner && scanner_indent.is_none() {
+            scanner_indent = Some(indent);
+        }
+
+        let is_exclude_key = indent == 0 || (in_scanner && scanner_indent == Some(indent));
+        let value = match trimmed.strip_prefix("exclude:") {
+            Some(value) if is_exclude_key => value.trim(),
+            _ => continue,
+        };
+
+        if value.is_empty() {
+            list_indent = Some(indent);
+        } else if let Some(items) = value.strip_prefix('[').and_then(|v| v.strip_suffix(']')) {
+            patterns.extend(
+                items
+                    .split(',')
+                    .map(|item| unquote_yaml(item.trim()))
+                    .filter(|item| !item.is_empty())
+                    .map(str::to_string),
+            );
+        } else {
+            return Err(SwhidError::InvalidInput(format!(
+                "Scanner config exclude must be a list, got: {}", value
+            )));
+        }
+    }
+
+    Ok(patterns)
+}
+
+/// Strip matching single or double quotes around a YAML scalar
+fn unquote_yaml(value: &str) -> &str {
+    for quote in ['"', '\''] {
+        if let Some(inner) = value.strip_prefix(quote).and_then(|v| v.strip_suffix(quote)) {
+            return inner;
+        }
     }
+    value
 }
 
 #[cfg(test)]
@@ -94,19 +1221,18 @@ mod tests {
     use super::*;
     use tempfile::TempDir;
     use std::fs;
-    use crate::swhid::ObjectType;
 
     #[test]
     fn test_swhid_computer_new() {
         let computer = SwhidComputer::new();
-        assert_eq!(computer.follow_symlinks, false);
+        assert!(!computer.follow_symlinks);
         assert!(computer.exclude_patterns.is_empty());
     }
 
     #[test]
     fn test_swhid_computer_with_follow_symlinks() {
         let computer = SwhidComputer::new().with_follow_symlinks(true);
-        assert_eq!(computer.follow_symlinks, true);
+        assert!(computer.follow_symlinks);
     }
 
     #[test]
@@ -123,7 +1249,27 @@ mod tests {
         let swhid = computer.compute_content_swhid(content).unwrap();
         
         assert_eq!(swhid.object_type(), ObjectType::Content);
-        assert_eq!(swhid.hash().len(), 20);
+        assert_eq!(swhid.digest().len(), 20);
+    }
+
+    #[test]
+    fn test_swhid_computer_compute_content_reader_sized() {
+        let computer = SwhidComputer::new();
+        let content = b"streamed content";
+        let expected = computer.compute_content_swhid(content).unwrap();
+
+        let swhid = computer
+            .compute_content_reader_sized(&content[..], content.len() as u64)
+            .unwrap();
+        assert_eq!(swhid, expected);
+
+        // Too few bytes
+        let result = computer.compute_content_reader_sized(&content[..], 100);
+        assert!(matches!(result, Err(SwhidError::InvalidInput(_))));
+
+        // Too many bytes
+        let result = computer.compute_content_reader_sized(&content[..], 4);
+        assert!(matches!(result, Err(SwhidError::InvalidInput(_))));
     }
 
     #[test]
@@ -136,33 +1282,857 @@ mod tests {
         let swhid = computer.compute_file_swhid(&file_path).unwrap();
         
         assert_eq!(swhid.object_type(), ObjectType::Content);
-        assert_eq!(swhid.hash().len(), 20);
+        assert_eq!(swhid.digest().len(), 20);
     }
 
     #[test]
-    fn test_swhid_computer_compute_directory_swhid() {
+    fn test_swhid_computer_group_by_content() {
         let temp_dir = TempDir::new().unwrap();
-        let file_path = temp_dir.path().join("test.txt");
-        fs::write(&file_path, b"test content").unwrap();
+        let a = temp_dir.path().join("a.txt");
+        let b = temp_dir.path().join("b.txt");
+        let c = temp_dir.path().join("c.txt");
+        let missing = temp_dir.path().join("missing.txt");
+        fs::write(&a, b"same").unwrap();
+        fs::write(&b, b"different").unwrap();
+        fs::write(&c, b"same").unwrap();
 
         let computer = SwhidComputer::new();
-        let swhid = computer.compute_directory_swhid(temp_dir.path()).unwrap();
-        
-        assert_eq!(swhid.object_type(), ObjectType::Directory);
-        assert_eq!(swhid.hash().len(), 20);
+        let paths = vec![a.clone(), b.clone(), missing.clone(), c.clone()];
+        let (groups, errors) = computer.group_by_content(&paths);
+
+        assert_eq!(groups.len(), 2);
+        let same = computer.compute_content_swhid(b"same").unwrap();
+        let different = computer.compute_content_swhid(b"different").unwrap();
+        assert_eq!(groups[&same], vec![a, c]);
+        assert_eq!(groups[&different], vec![b]);
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].0, missing);
+        assert!(matches!(errors[0].1, SwhidError::Io(_)));
     }
 
     #[test]
-    fn test_swhid_computer_auto_detect_file() {
+    fn test_swhid_computer_compute_swhid_tree() {
         let temp_dir = TempDir::new().unwrap();
-        let file_path = temp_dir.path().join("test.txt");
-        fs::write(&file_path, b"test content").unwrap();
+        fs::create_dir_all(temp_dir.path().join("src").join("util")).unwrap();
+        fs::write(temp_dir.path().join("README"), b"readme\n").unwrap();
+        fs::write(temp_dir.path().join("src").join("lib.rs"), b"mod util;\n").unwrap();
+        fs::write(temp_dir.path().join("src").join("util").join("mod.rs"), b"\n").unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink("README", temp_dir.path().join("link")).unwrap();
 
         let computer = SwhidComputer::new();
-        let swhid = computer.compute_swhid(&file_path).unwrap();
-        
-        assert_eq!(swhid.object_type(), ObjectType::Content);
-        assert_eq!(swhid.hash().len(), 20);
+        let tree = computer.compute_swhid_tree(temp_dir.path()).unwrap();
+
+        // One entry per object on disk, plus the root
+        let objects = 1 + count_objects(temp_dir.path());
+        assert_eq!(tree.len(), objects);
+        assert_eq!(
+            tree[Path::new("")],
+            computer.compute_directory_swhid(temp_dir.path()).unwrap()
+        );
+        for file in ["README", "src/lib.rs", "src/util/mod.rs"] {
+            assert_eq!(
+                tree[Path::new(file)],
+                computer.compute_file_swhid(temp_dir.path().join(file)).unwrap()
+            );
+        }
+        assert_eq!(
+            tree[Path::new("src/util")],
+            computer.compute_directory_swhid(temp_dir.path().join("src/util")).unwrap()
+        );
+        #[cfg(unix)]
+        assert_eq!(tree[Path::new("link")], computer.compute_content_swhid(b"README").unwrap());
+
+        // The result is a manifest that the tree verifies against
+        assert!(computer.verify_tree_manifest(temp_dir.path(), &tree).unwrap().is_ok());
+    }
+
+    fn count_objects(dir: &Path) -> usize {
+        fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| {
+                let entry = entry.unwrap();
+                if entry.file_type().unwrap().is_dir() {
+                    1 + count_objects(&entry.path())
+                } else {
+                    1
+                }
+            })
+            .sum()
+    }
+
+    #[test]
+    fn test_swhid_computer_compute_directory_swhid() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("test.txt");
+        fs::write(&file_path, b"test content").unwrap();
+
+        let computer = SwhidComputer::new();
+        let swhid = computer.compute_directory_swhid(temp_dir.path()).unwrap();
+        
+        assert_eq!(swhid.object_type(), ObjectType::Directory);
+        assert_eq!(swhid.digest().len(), 20);
+    }
+
+    #[test]
+    fn test_swhid_computer_compute_subtree_swhid() {
+        let temp_dir = TempDir::new().unwrap();
+        let sub_dir = temp_dir.path().join("src").join("nested");
+        fs::create_dir_all(&sub_dir).unwrap();
+        fs::write(sub_dir.join("lib.rs"), b"fn main() {}").unwrap();
+        fs::write(temp_dir.path().join("README"), b"readme").unwrap();
+
+        let computer = SwhidComputer::new();
+        let subtree = computer.compute_subtree_swhid(temp_dir.path(), "src").unwrap();
+        assert_eq!(subtree.object_type(), ObjectType::Directory);
+
+        let root = Directory::from_disk(temp_dir.path(), &[]).unwrap();
+        let src_entry = root.entries().iter().find(|e| e.name == b"src").unwrap();
+        assert_eq!(subtree.digest(), &src_entry.target);
+
+        let nested = computer.compute_subtree_swhid(temp_dir.path(), "src/nested").unwrap();
+        assert_eq!(nested, computer.compute_directory_swhid(&sub_dir).unwrap());
+
+        assert!(computer.compute_subtree_swhid(temp_dir.path(), "../elsewhere").is_err());
+        assert!(computer.compute_subtree_swhid(temp_dir.path(), "README").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_swhid_computer_compute_directory_best_effort() {
+        use std::os::unix::net::UnixListener;
+
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join("a.txt"), b"a").unwrap();
+        fs::write(temp_dir.path().join("b.txt"), b"b").unwrap();
+        let socket_path = temp_dir.path().join("c.sock");
+        let _listener = UnixListener::bind(&socket_path).unwrap();
+
+        let computer = SwhidComputer::new();
+        let (swhid, errors) = computer.compute_directory_best_effort(temp_dir.path());
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].0, socket_path);
+
+        fs::remove_file(&socket_path).unwrap();
+        assert_eq!(swhid, computer.compute_directory_swhid(temp_dir.path()).unwrap());
+
+        // Without errors the result is the regular directory SWHID
+        let (swhid, errors) = computer.compute_directory_best_effort(temp_dir.path());
+        assert!(errors.is_empty());
+        assert_eq!(swhid, computer.compute_directory_swhid(temp_dir.path()).unwrap());
+    }
+
+    #[test]
+    fn test_swhid_computer_hash_algorithm() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::create_dir(temp_dir.path().join("src")).unwrap();
+        fs::write(temp_dir.path().join("src/lib.rs"), b"lib\n").unwrap();
+        fs::write(temp_dir.path().join("README"), b"readme\n").unwrap();
+
+        // Plain SHA-1 only differs on collision attacks
+        let checked = SwhidComputer::new();
+        let plain = SwhidComputer::new().with_hash_algorithm(V1HashAlgorithm::Sha1);
+        assert_eq!(
+            plain.compute_directory_swhid(temp_dir.path()).unwrap(),
+            checked.compute_directory_swhid(temp_dir.path()).unwrap()
+        );
+        let readme = temp_dir.path().join("README");
+        assert_eq!(plain.compute_swhid(&readme).unwrap(), checked.compute_swhid(&readme).unwrap());
+        assert_eq!(plain.compute_content_swhid(b"x").unwrap(), checked.compute_content_swhid(b"x").unwrap());
+        assert_eq!(
+            plain.compute_directory_best_effort(temp_dir.path()).0,
+            checked.compute_directory_best_effort(temp_dir.path()).0
+        );
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_swhid_computer_parallelism() {
+        let temp_dir = TempDir::new().unwrap();
+        for d in 0..8 {
+            let sub = temp_dir.path().join(format!("dir_{}", d));
+            fs::create_dir_all(sub.join("nested")).unwrap();
+            for f in 0..20 {
+                fs::write(sub.join(format!("file_{}.txt", f)), format!("{} {}", d, f)).unwrap();
+            }
+            fs::write(sub.join("nested").join("leaf"), format!("leaf {}", d)).unwrap();
+            fs::write(sub.join("nested.txt"), b"sorts before nested/").unwrap();
+        }
+        fs::write(temp_dir.path().join("skip.tmp"), b"excluded").unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::{symlink, PermissionsExt};
+            let script = temp_dir.path().join("dir_0").join("run.sh");
+            fs::write(&script, b"#!/bin/sh\n").unwrap();
+            fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+            symlink("dir_1/file_1.txt", temp_dir.path().join("link")).unwrap();
+        }
+
+        let excludes = vec!["*.tmp".to_string()];
+        let sequential = SwhidComputer::new()
+            .with_exclude_patterns(&excludes)
+            .compute_directory_swhid(temp_dir.path())
+            .unwrap();
+        for threads in [0, 1, 4] {
+            let computer = SwhidComputer::new()
+                .with_exclude_patterns(&excludes)
+                .with_parallelism(threads);
+            assert_eq!(computer.compute_directory_swhid(temp_dir.path()).unwrap(), sequential);
+            assert_eq!(computer.compute_swhid(temp_dir.path()).unwrap(), sequential);
+        }
+        let capped = SwhidComputer::new()
+            .with_exclude_patterns(&excludes)
+            .with_parallelism(0)
+            .with_max_concurrency(2);
+        assert_eq!(capped.compute_directory_swhid(temp_dir.path()).unwrap(), sequential);
+    }
+
+    #[cfg(feature = "unicode")]
+    #[test]
+    fn test_swhid_computer_unicode_normalization() {
+        use unicode_normalization::UnicodeNormalization;
+
+        let nfc_name: String = "caf\u{e9}.txt".nfc().collect();
+        let nfd_name: String = "caf\u{e9}.txt".nfd().collect();
+        assert_ne!(nfc_name, nfd_name);
+
+        let nfc_dir = TempDir::new().unwrap();
+        fs::write(nfc_dir.path().join(&nfc_name), b"content").unwrap();
+        let nfd_dir = TempDir::new().unwrap();
+        fs::write(nfd_dir.path().join(&nfd_name), b"content").unwrap();
+
+        // Raw bytes differ, so the default identity differs
+        let computer = SwhidComputer::new();
+        assert_ne!(
+            computer.compute_directory_swhid(nfc_dir.path()).unwrap(),
+            computer.compute_directory_swhid(nfd_dir.path()).unwrap()
+        );
+
+        let computer = SwhidComputer::new().with_unicode_normalization(NormalizationForm::Nfc);
+        let from_nfc = computer.compute_directory_swhid(nfc_dir.path()).unwrap();
+        assert_eq!(from_nfc, computer.compute_directory_swhid(nfd_dir.path()).unwrap());
+        // Normalizing an already-NFC tree is a no-op
+        assert_eq!(from_nfc, SwhidComputer::new().compute_directory_swhid(nfc_dir.path()).unwrap());
+    }
+
+    #[test]
+    fn test_swhid_computer_walk() {
+        let temp_dir = TempDir::new().unwrap();
+        let sub_dir = temp_dir.path().join("sub");
+        fs::create_dir(&sub_dir).unwrap();
+        fs::write(sub_dir.join("nested.txt"), b"nested").unwrap();
+        fs::write(temp_dir.path().join("top.txt"), b"top").unwrap();
+
+        let computer = SwhidComputer::new();
+        let mut visited = Vec::new();
+        let root = computer.walk(temp_dir.path(), |entry| visited.push(entry)).unwrap();
+
+        assert_eq!(root, computer.compute_directory_swhid(temp_dir.path()).unwrap());
+        assert_eq!(visited.len(), 4);
+        // The root is reported last, after its children
+        assert_eq!(visited[3].path, temp_dir.path());
+        assert_eq!(visited[3].swhid, root);
+
+        let nested = visited.iter().find(|e| e.path == sub_dir.join("nested.txt")).unwrap();
+        assert_eq!(nested.swhid, computer.compute_file_swhid(sub_dir.join("nested.txt")).unwrap());
+        let sub = visited.iter().find(|e| e.path == sub_dir).unwrap();
+        assert_eq!(sub.swhid, computer.compute_directory_swhid(&sub_dir).unwrap());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_swhid_computer_walk_entry_types() {
+        use crate::directory::EntryType;
+
+        // Linux rejects empty symlink targets, so collide on a non-empty one
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join("file"), b"target").unwrap();
+        std::os::unix::fs::symlink("target", temp_dir.path().join("link")).unwrap();
+
+        let mut visited = Vec::new();
+        SwhidComputer::new()
+            .walk(temp_dir.path(), |entry| visited.push(entry))
+            .unwrap();
+
+        let file = visited.iter().find(|e| e.path == temp_dir.path().join("file")).unwrap();
+        let link = visited.iter().find(|e| e.path == temp_dir.path().join("link")).unwrap();
+        assert_eq!(file.swhid, link.swhid);
+        assert_eq!(file.entry_type, EntryType::File);
+        assert_eq!(link.entry_type, EntryType::Symlink);
+        assert_eq!(visited.last().unwrap().entry_type, EntryType::Directory);
+    }
+
+    #[test]
+    fn test_swhid_computer_walk_record_mtime() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join("file.txt"), b"content").unwrap();
+
+        let mut visited = Vec::new();
+        SwhidComputer::new()
+            .walk(temp_dir.path(), |entry| visited.push(entry))
+            .unwrap();
+        assert!(visited.iter().all(|e| e.mtime.is_none()));
+
+        let mut recorded = Vec::new();
+        SwhidComputer::new()
+            .with_record_mtime(true)
+            .walk(temp_dir.path(), |entry| recorded.push(entry))
+            .unwrap();
+        assert!(recorded.iter().all(|e| e.mtime.is_some()));
+
+        // Recording mtimes never changes the SWHIDs
+        let swhids: Vec<_> = visited.iter().map(|e| &e.swhid).collect();
+        let recorded_swhids: Vec<_> = recorded.iter().map(|e| &e.swhid).collect();
+        assert_eq!(swhids, recorded_swhids);
+    }
+
+    #[test]
+    fn test_swhid_computer_compute_directory_of() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::create_dir(temp_dir.path().join("sub")).unwrap();
+        fs::write(temp_dir.path().join("a.txt"), b"a").unwrap();
+        fs::write(temp_dir.path().join("sub").join("b.txt"), b"b").unwrap();
+        fs::write(temp_dir.path().join("c.txt"), b"c").unwrap();
+
+        let computer = SwhidComputer::new();
+        let include = vec![PathBuf::from("a.txt"), PathBuf::from("sub/b.txt")];
+        let filtered = computer.compute_directory_of(temp_dir.path(), &include).unwrap();
+
+        // Same as a tree containing only the two included files
+        let expected_dir = TempDir::new().unwrap();
+        fs::create_dir(expected_dir.path().join("sub")).unwrap();
+        fs::write(expected_dir.path().join("a.txt"), b"a").unwrap();
+        fs::write(expected_dir.path().join("sub").join("b.txt"), b"b").unwrap();
+        let expected = computer.compute_directory_swhid(expected_dir.path()).unwrap();
+        assert_eq!(filtered, expected);
+        assert_ne!(filtered, computer.compute_directory_swhid(temp_dir.path()).unwrap());
+
+        // Including a directory takes its whole subtree
+        let include = vec![PathBuf::from("a.txt"), PathBuf::from("sub")];
+        assert_eq!(computer.compute_directory_of(temp_dir.path(), &include).unwrap(), expected);
+
+        // A path that is both a leaf and a directory prefix is rejected
+        let include = vec![PathBuf::from("sub"), PathBuf::from("sub/b.txt")];
+        assert!(matches!(
+            computer.compute_directory_of(temp_dir.path(), &include),
+            Err(SwhidError::InvalidPath(_))
+        ));
+        let include = vec![PathBuf::from("../a.txt")];
+        assert!(computer.compute_directory_of(temp_dir.path(), &include).is_err());
+    }
+
+    #[test]
+    fn test_swhid_computer_with_scanner_config() {
+        let config_dir = TempDir::new().unwrap();
+        let config_path = config_dir.path().join("global.yml");
+        fs::write(
+            &config_path,
+            concat!(
+                "# swh scanner configuration\n",
+                "scanner:\n",
+                "  server:\n",
+                "    url: https://archive.softwareheritage.org/api/1/\n",
+                "  exclude:\n",
+                "    - \"*.pyc\"\n",
+                "    - 'build'\n",
+                "  disable_global_patterns: false\n",
+            ),
+        )
+        .unwrap();
+
+        let computer = SwhidComputer::new()
+            .with_exclude_patterns(&["*.log".to_string()])
+            .with_scanner_config(&config_path)
+            .unwrap();
+        assert_eq!(computer.exclude_patterns, vec!["*.log", "*.pyc", "build"]);
+
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join("main.py"), b"print()").unwrap();
+        fs::write(temp_dir.path().join("main.pyc"), b"bytecode").unwrap();
+        let filtered = computer.compute_directory_swhid(temp_dir.path()).unwrap();
+
+        fs::remove_file(temp_dir.path().join("main.pyc")).unwrap();
+        let expected = SwhidComputer::new().compute_directory_swhid(temp_dir.path()).unwrap();
+        assert_eq!(filtered, expected);
+    }
+
+    #[test]
+    fn test_parse_scanner_excludes() {
+        let inline = "scanner:\n  exclude: [\"*.o\", target]\n";
+        assert_eq!(parse_scanner_excludes(inline).unwrap(), vec!["*.o", "target"]);
+
+        // Nested keys named `exclude` elsewhere are not scanner excludes
+        let nested = "scanner:\n  server:\n    exclude:\n      - nope\n";
+        assert!(parse_scanner_excludes(nested).unwrap().is_empty());
+
+        let top_level = "exclude:\n- dist\n";
+        assert_eq!(parse_scanner_excludes(top_level).unwrap(), vec!["dist"]);
+
+        assert!(parse_scanner_excludes("scanner:\n  exclude: dist\n").is_err());
+    }
+
+    #[test]
+    fn test_swhid_computer_resolve_lfs() {
+        use sha2::{Digest, Sha256};
+
+        let real = b"the real large file content".to_vec();
+        let pointer = format!(
+            "version https://git-lfs.github.com/spec/v1\noid sha256:{}\nsize {}\n",
+            hex::encode(Sha256::digest(&real)),
+            real.len()
+        );
+        let temp_dir = TempDir::new().unwrap();
+        let pointer_path = temp_dir.path().join("model.bin");
+        fs::write(&pointer_path, &pointer).unwrap();
+
+        // Without a resolver the pointer file itself is hashed
+        let computer = SwhidComputer::new();
+        assert_eq!(
+            computer.compute_file_swhid(&pointer_path).unwrap(),
+            computer.compute_content_swhid(pointer.as_bytes()).unwrap()
+        );
+
+        let store = real.clone();
+        let computer = SwhidComputer::new().with_resolve_lfs(move |_| Ok(store.clone()));
+        let resolved = computer.compute_file_swhid(&pointer_path).unwrap();
+        assert_eq!(resolved, computer.compute_content_swhid(&real).unwrap());
+
+        // The resolver applies within directories too
+        let real_dir = TempDir::new().unwrap();
+        fs::write(real_dir.path().join("model.bin"), &real).unwrap();
+        assert_eq!(
+            computer.compute_directory_swhid(temp_dir.path()).unwrap(),
+            SwhidComputer::new().compute_directory_swhid(real_dir.path()).unwrap()
+        );
+
+        // Resolved bytes must match the pointer
+        let computer = SwhidComputer::new().with_resolve_lfs(|_| Ok(b"wrong".to_vec()));
+        assert!(matches!(
+            computer.compute_file_swhid(&pointer_path),
+            Err(SwhidError::InvalidHash(_))
+        ));
+    }
+
+    #[test]
+    fn test_swhid_computer_file_changed_during_read() {
+        use sha2::{Digest, Sha256};
+        use std::sync::Mutex;
+
+        let real = b"the real large file content".to_vec();
+        let pointer = format!(
+            "version https://git-lfs.github.com/spec/v1\noid sha256:{}\nsize {}\n",
+            hex::encode(Sha256::digest(&real)),
+            real.len()
+        );
+        let temp_dir = TempDir::new().unwrap();
+        let stable = temp_dir.path().join("stable.txt");
+        let live = temp_dir.path().join("live.bin");
+        fs::write(&stable, b"stable\n").unwrap();
+
+        // The LFS resolver runs after the file is read, so it can play a
+        // concurrent writer
+        let writer = |path: PathBuf, real: Vec<u8>| {
+            move |_: &LfsPointer| {
+                fs::write(&path, b"rewritten while being read").unwrap();
+                Ok(real.clone())
+            }
+        };
+
+        let changed = Arc::new(Mutex::new(Vec::new()));
+        let seen = changed.clone();
+        let computer = SwhidComputer::new()
+            .with_resolve_lfs(writer(live.clone(), real.clone()))
+            .with_on_file_changed(move |path| seen.lock().unwrap().push(path.to_path_buf()));
+
+        fs::write(&live, &pointer).unwrap();
+        let swhid = computer.compute_file_swhid(&live).unwrap();
+        assert_eq!(swhid, computer.compute_content_swhid(&real).unwrap());
+        assert_eq!(*changed.lock().unwrap(), vec![live.clone()]);
+
+        // Unchanged files do not warn
+        computer.compute_file_swhid(&stable).unwrap();
+        assert_eq!(changed.lock().unwrap().len(), 1);
+
+        // Strict mode turns the warning into an error
+        let computer = SwhidComputer::new()
+            .with_resolve_lfs(writer(live.clone(), real.clone()))
+            .with_strict_consistency(true);
+        fs::write(&live, &pointer).unwrap();
+        assert!(matches!(
+            computer.compute_file_swhid(&live),
+            Err(SwhidError::InvalidFilePath(path, _)) if path == live
+        ));
+        assert!(computer.compute_file_swhid(&stable).is_ok());
+    }
+
+    #[test]
+    fn test_swhid_computer_ignore_shebang() {
+        let temp_dir = TempDir::new().unwrap();
+        let python = temp_dir.path().join("a.py");
+        let python3 = temp_dir.path().join("b.py");
+        fs::write(&python, "#!/usr/bin/python\nprint('hi')\n").unwrap();
+        fs::write(&python3, "#!/usr/bin/env python3\nprint('hi')\n").unwrap();
+
+        let computer = SwhidComputer::new();
+        assert_ne!(
+            computer.compute_file_swhid(&python).unwrap(),
+            computer.compute_file_swhid(&python3).unwrap()
+        );
+
+        let computer = SwhidComputer::new().with_ignore_shebang(true);
+        let swhid = computer.compute_file_swhid(&python).unwrap();
+        assert_eq!(swhid, computer.compute_file_swhid(&python3).unwrap());
+        assert_eq!(
+            swhid,
+            SwhidComputer::new().compute_content_swhid(b"print('hi')\n").unwrap()
+        );
+        assert_eq!(
+            computer.compute_content_swhid(b"#!/bin/sh\nprint('hi')\n").unwrap(),
+            swhid
+        );
+
+        // Content without a shebang is left untouched
+        assert_eq!(
+            computer.compute_content_swhid(b"# comment\n").unwrap(),
+            SwhidComputer::new().compute_content_swhid(b"# comment\n").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_swhid_computer_verify_tree_manifest() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::create_dir_all(temp_dir.path().join("src/nested")).unwrap();
+        fs::write(temp_dir.path().join("README"), "readme\n").unwrap();
+        fs::write(temp_dir.path().join("src/lib.rs"), "lib\n").unwrap();
+        fs::write(temp_dir.path().join("src/nested/mod.rs"), "nested\n").unwrap();
+
+        let computer = SwhidComputer::new();
+        let mut manifest = BTreeMap::new();
+        let root = computer
+            .walk(temp_dir.path(), |entry| {
+                let path = entry.path.strip_prefix(temp_dir.path()).unwrap().to_path_buf();
+                manifest.insert(path, entry.swhid);
+            })
+            .unwrap();
+        assert_eq!(manifest.get(Path::new("")), Some(&root));
+
+        let report = computer.verify_tree_manifest(temp_dir.path(), &manifest).unwrap();
+        assert!(report.is_ok());
+        assert_eq!(report.matches.len(), 6);
+
+        // Change one nested file, drop an entry and list a file that does not exist
+        fs::write(temp_dir.path().join("src/nested/mod.rs"), "changed\n").unwrap();
+        let expected = manifest[Path::new("src/nested/mod.rs")].clone();
+        manifest.remove(Path::new("README"));
+        let ghost = computer.compute_content_swhid(b"ghost").unwrap();
+        manifest.insert(PathBuf::from("src/ghost.rs"), ghost);
+
+        let report = computer.verify_tree_manifest(temp_dir.path(), &manifest).unwrap();
+        assert!(!report.is_ok());
+        assert_eq!(report.extra, vec![PathBuf::from("README")]);
+        assert_eq!(report.missing, vec![PathBuf::from("src/ghost.rs")]);
+        let mismatched: Vec<_> = report.mismatches.iter().map(|m| m.path.clone()).collect();
+        // The change propagates to every enclosing directory
+        assert_eq!(
+            mismatched,
+            vec![
+                PathBuf::from(""),
+                PathBuf::from("src"),
+                PathBuf::from("src/nested"),
+                PathBuf::from("src/nested/mod.rs"),
+            ]
+        );
+        assert_eq!(report.mismatches[3].expected, expected);
+        assert_eq!(
+            report.mismatches[3].actual,
+            computer.compute_content_swhid(b"changed\n").unwrap()
+        );
+        assert_eq!(report.matches, vec![PathBuf::from("src/lib.rs")]);
+    }
+
+    #[test]
+    fn test_swhid_computer_diff_trees() {
+        let temp_dir = TempDir::new().unwrap();
+        let (old, new) = (temp_dir.path().join("old"), temp_dir.path().join("new"));
+        for root in [&old, &new] {
+            fs::create_dir_all(root.join("src")).unwrap();
+            fs::write(root.join("README"), "readme\n").unwrap();
+            fs::write(root.join("src/lib.rs"), "lib\n").unwrap();
+        }
+        let computer = SwhidComputer::new();
+        let diff = computer.diff_trees(&old, &new).unwrap();
+        assert!(diff.is_empty());
+        assert!(diff.added.is_empty() && diff.removed.is_empty() && diff.modified.is_empty());
+
+        fs::write(new.join("src/lib.rs"), "changed\n").unwrap();
+        fs::remove_file(new.join("README")).unwrap();
+        fs::create_dir_all(new.join("docs/guide")).unwrap();
+        fs::write(new.join("docs/guide/intro.md"), "intro\n").unwrap();
+        let diff = computer.diff_trees(&old, &new).unwrap();
+        assert!(!diff.is_empty());
+        assert_eq!(diff.old, computer.compute_directory_swhid(&old).unwrap());
+        assert_eq!(diff.new, computer.compute_directory_swhid(&new).unwrap());
+        // Only the topmost added directory, and no enclosing directory of a change
+        assert_eq!(diff.added, vec![PathBuf::from("docs")]);
+        assert_eq!(diff.removed, vec![PathBuf::from("README")]);
+        assert_eq!(diff.modified, vec![PathBuf::from("src/lib.rs")]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_swhid_computer_diff_trees_mode() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = TempDir::new().unwrap();
+        let (old, new) = (temp_dir.path().join("old"), temp_dir.path().join("new"));
+        for root in [&old, &new] {
+            fs::create_dir(root).unwrap();
+            fs::write(root.join("run.sh"), "#!/bin/sh\n").unwrap();
+        }
+        fs::set_permissions(new.join("run.sh"), fs::Permissions::from_mode(0o755)).unwrap();
+        std::os::unix::fs::symlink("run.sh", old.join("link")).unwrap();
+        fs::write(new.join("link"), "run.sh").unwrap();
+
+        // Same contents, but an executable file and a symlink turned into a file
+        let diff = SwhidComputer::new().diff_trees(&old, &new).unwrap();
+        assert!(!diff.is_empty());
+        assert_eq!(diff.modified, vec![PathBuf::from("link"), PathBuf::from("run.sh")]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_swhid_computer_non_utf8_path_error() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let temp_dir = TempDir::new().unwrap();
+        let name = OsStr::from_bytes(b"caf\xe9.txt");
+        fs::write(temp_dir.path().join(name), b"").unwrap();
+
+        let err = SwhidComputer::new()
+            .compute_subtree_swhid(temp_dir.path(), name)
+            .unwrap_err();
+        match &err {
+            SwhidError::InvalidFilePath(path, _) => assert_eq!(path, &temp_dir.path().join(name)),
+            other => panic!("unexpected error: {:?}", other),
+        }
+        // The offending byte is escaped rather than replaced
+        let message = err.to_string();
+        assert!(message.contains("caf\\xE9.txt"), "{}", message);
+        assert!(message.ends_with(": subtree path is not a directory"));
+    }
+
+    #[test]
+    fn test_swhid_computer_name_transform() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::create_dir(temp_dir.path().join("Src")).unwrap();
+        fs::write(temp_dir.path().join("README.md"), b"readme").unwrap();
+        fs::write(temp_dir.path().join("Src").join("Main.RS"), b"main").unwrap();
+
+        let lowered = TempDir::new().unwrap();
+        fs::create_dir(lowered.path().join("src")).unwrap();
+        fs::write(lowered.path().join("readme.md"), b"readme").unwrap();
+        fs::write(lowered.path().join("src").join("main.rs"), b"main").unwrap();
+
+        let computer = SwhidComputer::new().with_name_transform(|name| name.to_ascii_lowercase());
+        let expected = SwhidComputer::new().compute_directory_swhid(lowered.path()).unwrap();
+        assert_eq!(computer.compute_directory_swhid(temp_dir.path()).unwrap(), expected);
+        assert_ne!(
+            SwhidComputer::new().compute_directory_swhid(temp_dir.path()).unwrap(),
+            expected
+        );
+
+        // Names that only differ by case now collide
+        fs::write(temp_dir.path().join("readme.md"), b"other").unwrap();
+        assert!(matches!(
+            computer.compute_directory_swhid(temp_dir.path()),
+            Err(SwhidError::DuplicateEntry(_))
+        ));
+    }
+
+    #[test]
+    fn test_swhid_computer_verify_large_file_streaming() {
+        // A sparse file: large on disk, but never read into memory at once
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("zeros.bin");
+        File::create(&path).unwrap().set_len(64 * 1024 * 1024).unwrap();
+
+        // As computed by `git hash-object`
+        let expected = "swh:1:cnt:51c513d36451ab389b5b3e9bca9b478b84a2e2ce";
+        let computer = SwhidComputer::new();
+        assert_eq!(computer.compute_file_swhid(&path).unwrap().to_string(), expected);
+        assert!(computer.verify_swhid(&path, expected).unwrap());
+
+        // Streaming honors the content options too
+        fs::write(&path, "#!/bin/sh\necho hi\n").unwrap();
+        let computer = SwhidComputer::new().with_ignore_shebang(true);
+        assert_eq!(
+            computer.compute_file_swhid(&path).unwrap(),
+            computer.compute_content_swhid(b"echo hi\n").unwrap()
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_swhid_computer_materialize_symlinks() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::create_dir(temp_dir.path().join("docs")).unwrap();
+        fs::write(temp_dir.path().join("docs").join("guide.md"), b"guide\n").unwrap();
+        std::os::unix::fs::symlink("docs/guide.md", temp_dir.path().join("README.md")).unwrap();
+
+        let copied = TempDir::new().unwrap();
+        fs::create_dir(copied.path().join("docs")).unwrap();
+        fs::write(copied.path().join("docs").join("guide.md"), b"guide\n").unwrap();
+        fs::write(copied.path().join("README.md"), b"guide\n").unwrap();
+        let expected = SwhidComputer::new().compute_directory_swhid(copied.path()).unwrap();
+
+        let computer = SwhidComputer::new().with_materialize_symlinks(true);
+        assert_eq!(computer.compute_directory_swhid(temp_dir.path()).unwrap(), expected);
+        assert_ne!(
+            SwhidComputer::new().compute_directory_swhid(temp_dir.path()).unwrap(),
+            expected
+        );
+
+        std::os::unix::fs::symlink("missing.txt", temp_dir.path().join("dangling")).unwrap();
+        assert!(matches!(
+            computer.compute_directory_swhid(temp_dir.path()),
+            Err(SwhidError::InvalidFilePath(_, _))
+        ));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_swhid_computer_follow_symlinks_in_directory() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::create_dir(temp_dir.path().join("docs")).unwrap();
+        fs::write(temp_dir.path().join("docs").join("guide.md"), b"guide\n").unwrap();
+        std::os::unix::fs::symlink("docs/guide.md", temp_dir.path().join("README.md")).unwrap();
+        std::os::unix::fs::symlink("docs", temp_dir.path().join("manual")).unwrap();
+        std::os::unix::fs::symlink("missing.txt", temp_dir.path().join("dangling")).unwrap();
+
+        let copied = TempDir::new().unwrap();
+        for dir in ["docs", "manual"] {
+            fs::create_dir(copied.path().join(dir)).unwrap();
+            fs::write(copied.path().join(dir).join("guide.md"), b"guide\n").unwrap();
+        }
+        fs::write(copied.path().join("README.md"), b"guide\n").unwrap();
+        std::os::unix::fs::symlink("missing.txt", copied.path().join("dangling")).unwrap();
+        let expected = SwhidComputer::new().compute_directory_swhid(copied.path()).unwrap();
+
+        let computer = SwhidComputer::new().with_follow_symlinks(true);
+        assert_eq!(computer.compute_directory_swhid(temp_dir.path()).unwrap(), expected);
+        assert_ne!(
+            SwhidComputer::new().compute_directory_swhid(temp_dir.path()).unwrap(),
+            expected
+        );
+
+        // A symlink to one of its own ancestors cannot be followed
+        std::os::unix::fs::symlink("..", temp_dir.path().join("docs").join("up")).unwrap();
+        assert!(matches!(
+            computer.compute_directory_swhid(temp_dir.path()),
+            Err(SwhidError::InvalidFilePath(path, _)) if path.ends_with("up")
+        ));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_swhid_computer_special_file_policy() {
+        use std::os::unix::ffi::OsStringExt;
+
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join("README"), b"readme\n").unwrap();
+        let pipe = std::ffi::CString::new(temp_dir.path().join("pipe").into_os_string().into_vec()).unwrap();
+        assert_eq!(unsafe { libc::mkfifo(pipe.as_ptr(), 0o644) }, 0);
+
+        let readme = Content::from_data(b"readme\n".to_vec()).swhid();
+        let mut skipped = Directory::new();
+        skipped.add_file(b"README".to_vec(), &readme, false).unwrap();
+        let mut empty = skipped.clone();
+        empty.add_file(b"pipe".to_vec(), &Content::from_data(Vec::new()).swhid(), false).unwrap();
+
+        // The FIFO is never opened, which would block without a writer
+        assert!(matches!(
+            SwhidComputer::new().compute_directory_swhid(temp_dir.path()),
+            Err(SwhidError::InvalidFilePath(path, _)) if path.ends_with("pipe")
+        ));
+        let computer = SwhidComputer::new().with_special_file_policy(SpecialFilePolicy::Skip);
+        assert_eq!(computer.compute_directory_swhid(temp_dir.path()).unwrap(), skipped.swhid());
+        let computer = SwhidComputer::new().with_special_file_policy(SpecialFilePolicy::HashAsEmpty);
+        assert_eq!(computer.compute_directory_swhid(temp_dir.path()).unwrap(), empty.swhid());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_swhid_computer_broken_symlink_policy() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join("README"), b"readme\n").unwrap();
+        std::os::unix::fs::symlink("missing.txt", temp_dir.path().join("dangling")).unwrap();
+
+        let readme = Content::from_data(b"readme\n".to_vec()).swhid();
+        let mut skipped = Directory::new();
+        skipped.add_file(b"README".to_vec(), &readme, false).unwrap();
+        let mut hashed = skipped.clone();
+        hashed.add_symlink(b"dangling".to_vec(), b"missing.txt").unwrap();
+
+        assert_eq!(
+            SwhidComputer::new().compute_directory_swhid(temp_dir.path()).unwrap(),
+            hashed.swhid()
+        );
+        let computer = SwhidComputer::new().with_broken_symlink_policy(BrokenSymlinkPolicy::Skip);
+        assert_eq!(computer.compute_directory_swhid(temp_dir.path()).unwrap(), skipped.swhid());
+        let computer = SwhidComputer::new().with_broken_symlink_policy(BrokenSymlinkPolicy::Error);
+        assert!(matches!(
+            computer.compute_directory_swhid(temp_dir.path()),
+            Err(SwhidError::InvalidFilePath(path, _)) if path.ends_with("dangling")
+        ));
+
+        // Symlinks that resolve are unaffected
+        fs::remove_file(temp_dir.path().join("dangling")).unwrap();
+        std::os::unix::fs::symlink("README", temp_dir.path().join("link")).unwrap();
+        assert!(computer.compute_directory_swhid(temp_dir.path()).is_ok());
+    }
+
+    #[test]
+    fn test_swhid_computer_skip_vcs() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join("README"), b"readme").unwrap();
+        fs::create_dir(temp_dir.path().join("sub")).unwrap();
+        fs::write(temp_dir.path().join("sub").join("file"), b"file").unwrap();
+        let clean = SwhidComputer::new().compute_directory_swhid(temp_dir.path()).unwrap();
+
+        // A repository directory at the root and a worktree pointer file below
+        fs::create_dir(temp_dir.path().join(".git")).unwrap();
+        fs::write(temp_dir.path().join(".git").join("HEAD"), b"ref: refs/heads/main\n").unwrap();
+        fs::write(temp_dir.path().join("sub").join(".git"), b"gitdir: ../.git/worktrees/sub\n").unwrap();
+
+        let computer = SwhidComputer::new().with_skip_vcs(true);
+        assert_eq!(computer.compute_directory_swhid(temp_dir.path()).unwrap(), clean);
+        assert_ne!(
+            SwhidComputer::new().compute_directory_swhid(temp_dir.path()).unwrap(),
+            clean
+        );
+
+        // Without the .git directory, the .git file alone still pollutes the tree
+        fs::remove_dir_all(temp_dir.path().join(".git")).unwrap();
+        assert_ne!(
+            SwhidComputer::new().compute_directory_swhid(temp_dir.path()).unwrap(),
+            clean
+        );
+        assert_eq!(computer.compute_directory_swhid(temp_dir.path()).unwrap(), clean);
+    }
+
+    #[test]
+    fn test_swhid_computer_auto_detect_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("test.txt");
+        fs::write(&file_path, b"test content").unwrap();
+
+        let computer = SwhidComputer::new();
+        let swhid = computer.compute_swhid(&file_path).unwrap();
+        
+        assert_eq!(swhid.object_type(), ObjectType::Content);
+        assert_eq!(swhid.digest().len(), 20);
     }
 
     #[test]
@@ -175,7 +2145,7 @@ mod tests {
         let swhid = computer.compute_swhid(temp_dir.path()).unwrap();
         
         assert_eq!(swhid.object_type(), ObjectType::Directory);
-        assert_eq!(swhid.hash().len(), 20);
+        assert_eq!(swhid.digest().len(), 20);
     }
 
     #[test]
@@ -195,7 +2165,7 @@ mod tests {
             
             // Should hash the symlink target string by default
             assert_eq!(swhid.object_type(), ObjectType::Content);
-            assert_eq!(swhid.hash().len(), 20);
+            assert_eq!(swhid.digest().len(), 20);
         }
     }
 
@@ -224,4 +2194,243 @@ mod tests {
         let is_valid = computer.verify_swhid(&file_path, wrong_swhid).unwrap();
         assert!(!is_valid);
     }
+
+    #[test]
+    fn test_swhid_computer_verification_qualified() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("main.rs");
+        fs::write(&file_path, b"fn main() {}\n").unwrap();
+
+        let computer = SwhidComputer::new();
+        let swhid = computer.compute_file_swhid(&file_path).unwrap();
+        let qualified = format!("{};origin=https://example.org/repo.git;path=/src/main.rs;lines=1-2", swhid);
+        assert!(computer.verify_swhid(&file_path, &qualified).unwrap());
+        assert_eq!(computer.verify_swhid_detailed(&file_path, &qualified).unwrap(), Verification::Match);
+
+        // Malformed input is still an error rather than a mismatch
+        assert!(computer.verify_swhid(&file_path, &format!("{};path=", swhid)).is_err());
+    }
+
+    #[test]
+    fn test_swhid_computer_verification_detailed() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("test.txt");
+        fs::write(&file_path, b"test content").unwrap();
+
+        let computer = SwhidComputer::new();
+        let actual = computer.compute_file_swhid(&file_path).unwrap();
+
+        let wrong_hash = "swh:1:cnt:0000000000000000000000000000000000000000";
+        let outcome = computer.verify_swhid_detailed(&file_path, wrong_hash).unwrap();
+        assert_eq!(
+            outcome,
+            Verification::HashMismatch { expected: Swhid::from_string(wrong_hash).unwrap(), actual: actual.clone() }
+        );
+        assert!(!outcome.is_match());
+        assert_eq!(outcome.to_string(), format!("hash differs: expected {}, got {}", wrong_hash, actual));
+
+        let wrong_type = format!("swh:1:dir:{}", hex::encode(actual.digest()));
+        let outcome = computer.verify_swhid_detailed(&file_path, &wrong_type).unwrap();
+        assert!(matches!(outcome, Verification::ObjectTypeMismatch { .. }));
+        assert_eq!(outcome.to_string(), "object type differs: expected dir, got cnt");
+    }
+
+    #[test]
+    fn test_swhid_computer_compute_qualified_file_swhids() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::create_dir_all(temp_dir.path().join("src/bin")).unwrap();
+        fs::write(temp_dir.path().join("src/bin/tool.rs"), b"fn main() {}\n").unwrap();
+        fs::write(temp_dir.path().join("src/lib.rs"), b"\n").unwrap();
+        fs::write(temp_dir.path().join("README"), b"readme\n").unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink("README", temp_dir.path().join("README.md")).unwrap();
+
+        let computer = SwhidComputer::new();
+        let root = computer.compute_directory_swhid(temp_dir.path()).unwrap();
+        let swhids = computer.compute_qualified_file_swhids(temp_dir.path()).unwrap();
+        let paths: Vec<&[u8]> = swhids.iter().map(|swhid| swhid.path().unwrap()).collect();
+        assert_eq!(paths, vec![&b"/README"[..], b"/src/bin/tool.rs", b"/src/lib.rs"]);
+        for swhid in &swhids {
+            assert_eq!(swhid.anchor(), Some(&root));
+            assert!(computer.verify_path_qualifier(temp_dir.path(), swhid).unwrap().is_match());
+        }
+        assert_eq!(
+            swhids[0].to_string(),
+            format!("{};anchor={};path=/README", computer.compute_content_swhid(b"readme\n").unwrap(), root)
+        );
+
+        // Paths are the names recorded in the tree
+        let upper = SwhidComputer::new().with_name_transform(|name| name.to_ascii_uppercase());
+        let swhids = upper.compute_qualified_file_swhids(temp_dir.path()).unwrap();
+        assert_eq!(swhids[1].path(), Some(&b"/SRC/BIN/TOOL.RS"[..]));
+    }
+
+    #[test]
+    fn test_swhid_computer_verify_path_qualifier() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::create_dir(temp_dir.path().join("src")).unwrap();
+        fs::write(temp_dir.path().join("src/main.rs"), b"fn main() {}\n").unwrap();
+
+        let computer = SwhidComputer::new();
+        let file = computer.compute_file_swhid(temp_dir.path().join("src/main.rs")).unwrap();
+        let root = computer.compute_directory_swhid(temp_dir.path()).unwrap();
+        let qualified = QualifiedSwhid::new(file.clone())
+            .with_anchor(root.clone())
+            .unwrap()
+            .with_path(b"/src/main.rs".to_vec());
+        assert!(computer.verify_path_qualifier(temp_dir.path(), &qualified).unwrap().is_match());
+
+        let src = QualifiedSwhid::new(file.clone()).with_path(b"/src".to_vec());
+        assert!(matches!(
+            computer.verify_path_qualifier(temp_dir.path(), &src).unwrap().core,
+            Verification::ObjectTypeMismatch { .. }
+        ));
+
+        for path in [&b"/src/lib.rs"[..], b"/../src/main.rs"] {
+            assert!(matches!(
+                computer.verify_path_qualifier(temp_dir.path(), &qualified.clone().with_path(path.to_vec())),
+                Err(SwhidError::InvalidFilePath(..))
+            ));
+        }
+        assert!(matches!(
+            computer.verify_path_qualifier(temp_dir.path(), &QualifiedSwhid::new(file.clone())),
+            Err(SwhidError::InvalidQualifier(_))
+        ));
+
+        // The root must be a checkout of a directory anchor
+        fs::write(temp_dir.path().join("README"), b"readme\n").unwrap();
+        let outcome = computer.verify_path_qualifier(temp_dir.path(), &qualified).unwrap();
+        assert!(outcome.core.is_match());
+        assert!(matches!(outcome.qualifier("anchor"), Some(QualifierCheck::Failed(_))));
+        assert!(!outcome.is_match());
+        let revision_anchor = QualifiedSwhid::new(file.clone())
+            .with_anchor(Swhid::new(ObjectType::Revision, [1u8; 20]))
+            .unwrap()
+            .with_path(b"/src/main.rs".to_vec());
+        let outcome = computer.verify_path_qualifier(temp_dir.path(), &revision_anchor).unwrap();
+        assert!(outcome.is_match());
+        assert_eq!(outcome.qualifier("anchor"), Some(&QualifierCheck::Unchecked));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_swhid_computer_verify_path_qualifier_symlinks() {
+        let outside = TempDir::new().unwrap();
+        fs::write(outside.path().join("secret"), b"secret\n").unwrap();
+        let temp_dir = TempDir::new().unwrap();
+        std::os::unix::fs::symlink(outside.path(), temp_dir.path().join("escape")).unwrap();
+        std::os::unix::fs::symlink(outside.path().join("secret"), temp_dir.path().join("link")).unwrap();
+
+        // A symlinked directory is not followed out of the checkout
+        let computer = SwhidComputer::new().with_follow_symlinks(true);
+        let secret = computer.compute_content_swhid(b"secret\n").unwrap();
+        let through = QualifiedSwhid::new(secret.clone()).with_path(b"/escape/secret".to_vec());
+        assert!(matches!(
+            computer.verify_path_qualifier(temp_dir.path(), &through),
+            Err(SwhidError::InvalidFilePath(..))
+        ));
+
+        // A symlink entry is hashed as the symlink, not its target
+        let link = QualifiedSwhid::new(secret).with_path(b"/link".to_vec());
+        let outcome = computer.verify_path_qualifier(temp_dir.path(), &link).unwrap();
+        let target = computer.compute_content_swhid(outside.path().join("secret").as_os_str().as_encoded_bytes()).unwrap();
+        assert_eq!(outcome.core, Verification::HashMismatch { expected: link.core().clone(), actual: target });
+    }
+
+    #[test]
+    fn test_swhid_computer_verify_qualified_swhid() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::create_dir(temp_dir.path().join("src")).unwrap();
+        fs::write(temp_dir.path().join("src/main.rs"), b"fn main() {\n}\n").unwrap();
+
+        let computer = SwhidComputer::new();
+        let file = computer.compute_file_swhid(temp_dir.path().join("src/main.rs")).unwrap();
+        let root = computer.compute_directory_swhid(temp_dir.path()).unwrap();
+        let cited = format!("{};origin=https://example.org/repo;anchor={};path=/src/main.rs;lines=1-2", file, root);
+        let outcome = computer.verify_qualified_swhid(temp_dir.path(), &cited).unwrap();
+        assert!(outcome.is_match());
+        assert_eq!(
+            outcome.qualifiers,
+            vec![
+                ("origin", QualifierCheck::Unchecked),
+                ("anchor", QualifierCheck::Passed),
+                ("path", QualifierCheck::Passed),
+                ("lines", QualifierCheck::Passed),
+            ]
+        );
+
+        // Without a path qualifier, the path is the object itself
+        let file_path = temp_dir.path().join("src/main.rs");
+        let outcome = computer.verify_qualified_swhid(&file_path, &format!("{};lines=3", file)).unwrap();
+        assert!(outcome.core.is_match());
+        assert!(matches!(outcome.qualifier("lines"), Some(QualifierCheck::Failed(_))));
+        assert!(!outcome.is_match());
+
+        // Lines are not checked against content that does not match
+        let other = computer.compute_content_swhid(b"other\n").unwrap();
+        let outcome = computer.verify_qualified_swhid(&file_path, &format!("{};lines=1", other)).unwrap();
+        assert!(matches!(outcome.core, Verification::HashMismatch { .. }));
+        assert_eq!(outcome.qualifier("lines"), Some(&QualifierCheck::Unchecked));
+
+        fs::write(temp_dir.path().join("README"), b"readme\n").unwrap();
+        let outcome = computer.verify_qualified_swhid(temp_dir.path(), &cited).unwrap();
+        assert!(outcome.core.is_match());
+        assert!(matches!(outcome.qualifier("anchor"), Some(QualifierCheck::Failed(_))));
+        assert!(!outcome.is_match());
+
+        assert!(matches!(
+            computer.verify_qualified_swhid(temp_dir.path(), &format!("{};path=/src/lib.rs", file)),
+            Err(SwhidError::InvalidFilePath(..))
+        ));
+    }
+
+    #[cfg(feature = "v2")]
+    #[test]
+    fn test_swhid_computer_v2() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("script.sh");
+        fs::write(&path, b"#!/bin/sh\nhello\n").unwrap();
+
+        let computer = SwhidComputer::new().with_ignore_shebang(true);
+        let v2 = computer.compute_file_swhid_v2(&path).unwrap();
+        assert_eq!(v2.to_string(), "swh:2:cnt:2cf8d83d9ee29543b34a87727421fdecb7e3f3a183d337639025de576db9ebb4");
+        assert_eq!(computer.compute_content_swhid_v2(b"#!/bin/sh\nhello\n"), v2);
+        assert_eq!(computer.compute_file_swhid(&path).unwrap().version(), 1);
+        assert!(matches!(
+            computer.compute_file_swhid_v2(temp_dir.path().join("missing")),
+            Err(SwhidError::PathIo(..))
+        ));
+    }
+
+    #[test]
+    fn test_swhid_computer_compute_file_swhids() {
+        let temp_dir = TempDir::new().unwrap();
+        let paths: Vec<PathBuf> = (0..20)
+            .map(|i| {
+                let path = temp_dir.path().join(format!("file{}.txt", i));
+                fs::write(&path, format!("content {}\n", i)).unwrap();
+                path
+            })
+            .collect();
+        let mut batch = paths.clone();
+        let missing = temp_dir.path().join("missing.txt");
+        batch.insert(7, missing.clone());
+
+        let computers = [
+            SwhidComputer::new(),
+            #[cfg(feature = "parallel")]
+            SwhidComputer::new().with_parallelism(4),
+        ];
+        for computer in computers {
+            let results = computer.compute_file_swhids(batch.clone());
+            assert_eq!(results.iter().map(|(path, _)| path).collect::<Vec<_>>(), batch.iter().collect::<Vec<_>>());
+            for (path, result) in &results {
+                if *path == missing {
+                    assert!(matches!(result, Err(SwhidError::Io(_))));
+                } else {
+                    assert_eq!(result.as_ref().unwrap(), &computer.compute_file_swhid(path).unwrap());
+                }
+            }
+        }
+    }
 }
@@ -1,13 +1,21 @@
-use std::fs;
+use std::fs::File;
+use std::io::{BufReader, Read};
 use std::path::Path;
 use crate::swhid::{Swhid, ObjectType};
-use crate::hash::sha1_git_hash;
+use crate::hash::{hash_git_object_sha256, V1HashAlgorithm};
+#[cfg(feature = "v2")]
+use crate::hash::{hash_git_object_reader_dual, hash_git_object_with, Sha1CheckedHasher, Sha256Hasher};
 use crate::error::SwhidError;
+use sha2::{Digest, Sha256};
 
 /// Content object representing a file
+///
+/// Content built from bytes in memory retains them; content built from a
+/// reader or a file is hashed as it streams by, and only keeps its length and
+/// hash.
 #[derive(Debug, Clone)]
 pub struct Content {
-    data: Vec<u8>,
+    data: Option<Vec<u8>>,
     length: usize,
     sha1_git: [u8; 20],
 }
@@ -15,25 +23,83 @@ pub struct Content {
 impl Content {
     /// Create content from file data
     pub fn from_data(data: Vec<u8>) -> Self {
-        let length = data.len();
-        let sha1_git = sha1_git_hash(&data);
-        
+        Self::from_data_with(data, V1HashAlgorithm::Sha1Checked)
+    }
+
+    /// Create content from file data, hashed with `algorithm`
+    pub fn from_data_with(data: Vec<u8>, algorithm: V1HashAlgorithm) -> Self {
+        let sha1_git = algorithm.hash_git_object("blob", &data);
         Self {
-            data,
-            length,
+            length: data.len(),
+            data: Some(data),
             sha1_git,
         }
     }
 
-    /// Create content from file path
+    /// Create content by streaming exactly `length` bytes from `reader`
+    ///
+    /// The bytes are fed to the hasher as they are read and then discarded,
+    /// so [`Content::data`] returns `None`. Git hashes the length before the
+    /// bytes, hence it must be known up front; fails with
+    /// `SwhidError::InvalidInput` if the reader yields a different number of
+    /// bytes.
+    pub fn from_reader<R: Read>(reader: R, length: u64) -> Result<Self, SwhidError> {
+        Self::from_reader_with(reader, length, V1HashAlgorithm::Sha1Checked)
+    }
+
+    /// Create content by streaming exactly `length` bytes from `reader`,
+    /// hashed with `algorithm`
+    ///
+    /// Errors are those of [`Content::from_reader`].
+    pub fn from_reader_with<R: Read>(reader: R, length: u64, algorithm: V1HashAlgorithm) -> Result<Self, SwhidError> {
+        let sha1_git = algorithm.hash_git_object_reader("blob", reader, length)?;
+        let length = usize::try_from(length).map_err(|_| {
+            SwhidError::InvalidInput(format!("Content of {} bytes is too large", length))
+        })?;
+        Ok(Self {
+            data: None,
+            length,
+            sha1_git,
+        })
+    }
+
+    /// Create content from an inclusive, 1-based range of lines of `data`
+    ///
+    /// This is non-standard: it identifies a synthetic sub-content, not an
+    /// object the archive stores. Lines are split after each `\n` and keep
+    /// their newline, so the range's bytes are exactly as they appear in
+    /// `data`; a last line without newline stays without. The range is
+    /// clamped to the available lines: lines past the end are ignored, and
+    /// a range starting past the end (or with `start > end`) is empty. Line
+    /// 0 is treated as line 1.
+    pub fn from_line_range(data: &[u8], start: usize, end: usize) -> Self {
+        let start = start.max(1);
+        let selected: Vec<u8> = data
+            .split_inclusive(|&b| b == b'\n')
+            .skip(start - 1)
+            .take((end + 1).saturating_sub(start))
+            .flatten()
+            .copied()
+            .collect();
+        Self::from_data(selected)
+    }
+
+    /// Create content from file path, streaming it as [`Content::from_reader`]
+    ///
+    /// The length is taken from the file's metadata, and the file is never
+    /// held in memory. To retain the bytes, read the file and use
+    /// [`Content::from_data`] instead.
     pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, SwhidError> {
-        let data = fs::read(path)?;
-        Ok(Self::from_data(data))
+        let file = File::open(path)?;
+        let length = file.metadata()?.len();
+        Self::from_reader(BufReader::new(file), length)
     }
 
-    /// Get the raw data
-    pub fn data(&self) -> &[u8] {
-        &self.data
+    /// Get the raw data, if it was retained
+    ///
+    /// Only content created from bytes in memory retains them.
+    pub fn data(&self) -> Option<&[u8]> {
+        self.data.as_deref()
     }
 
     /// Get the length
@@ -46,15 +112,180 @@ impl Content {
         &self.sha1_git
     }
 
+    /// Compute the SHA-256 Git hash, as used by SHA-256 Git repositories
+    ///
+    /// Returns `None` if the data was not retained.
+    pub fn sha256_git(&self) -> Option<[u8; 32]> {
+        self.data.as_ref().map(|data| hash_git_object_sha256("blob", data))
+    }
+
+    /// Compute every hash the archive stores for this content
+    ///
+    /// Returns `None` if the data was not retained; see
+    /// [`MultiHash::from_reader`] to hash streamed data.
+    #[cfg(feature = "multihash")]
+    pub fn multi_hash(&self) -> Option<MultiHash> {
+        self.data.as_deref().map(MultiHash::from_data)
+    }
+
     /// Compute SWHID for this content
     pub fn swhid(&self) -> Swhid {
         Swhid::new(ObjectType::Content, self.sha1_git)
     }
+
+    /// Compute the draft v2 SWHID for this content, from its SHA-256 Git hash
+    ///
+    /// Returns `None` if the data was not retained.
+    #[cfg(feature = "v2")]
+    pub fn swhid_v2(&self) -> Option<Swhid> {
+        self.sha256_git().map(|hash| Swhid::new_v2(ObjectType::Content, hash))
+    }
+
+    /// Compute both the v1 and the draft v2 SWHID of this content, in one pass
+    ///
+    /// Returns `None` if the data was not retained; see
+    /// [`Content::swhids_dual_from_reader`] to hash streamed data.
+    #[cfg(feature = "v2")]
+    pub fn swhids_dual(&self) -> Option<(Swhid, Swhid)> {
+        self.data.as_deref().map(|data| {
+            let (sha1, sha256) = hash_git_object_with::<(Sha1CheckedHasher, Sha256Hasher)>("blob", data);
+            (Swhid::new(ObjectType::Content, sha1), Swhid::new_v2(ObjectType::Content, sha256))
+        })
+    }
+
+    /// Compute both the v1 and the draft v2 SWHID of exactly `length` bytes
+    /// streamed from `reader`
+    ///
+    /// Each chunk is fed to both hashers, so the data is read only once.
+    /// Errors are those of [`Content::from_reader`].
+    #[cfg(feature = "v2")]
+    pub fn swhids_dual_from_reader<R: Read>(reader: R, length: u64) -> Result<(Swhid, Swhid), SwhidError> {
+        let (sha1, sha256) = hash_git_object_reader_dual("blob", reader, length)?;
+        Ok((Swhid::new(ObjectType::Content, sha1), Swhid::new_v2(ObjectType::Content, sha256)))
+    }
+}
+
+/// The hashes the Software Heritage archive stores for each content
+///
+/// All of them are computed in a single pass over the data, so that files
+/// need not be read once per hash.
+#[cfg(feature = "multihash")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MultiHash {
+    /// SHA-1 of the data
+    pub sha1: [u8; 20],
+    /// SHA-1 of the data as a Git blob: the hash of its content SWHID
+    pub sha1_git: [u8; 20],
+    /// SHA-256 of the data
+    pub sha256: [u8; 32],
+    /// BLAKE2s-256 of the data
+    pub blake2s256: [u8; 32],
+    pub length: u64,
+}
+
+#[cfg(feature = "multihash")]
+impl MultiHash {
+    /// Hash data in memory
+    pub fn from_data(data: &[u8]) -> Self {
+        Self::from_reader(data, data.len() as u64).expect("slices yield their length")
+    }
+
+    /// Hash exactly `length` bytes streamed from `reader`
+    ///
+    /// Fails with `SwhidError::InvalidInput` if the reader yields a
+    /// different number of bytes, as [`Content::from_reader`].
+    pub fn from_reader<R: Read>(reader: R, length: u64) -> Result<Self, SwhidError> {
+        use blake2::Blake2s256;
+        use crate::hash::{feed_reader, git_object_header, GitObjectHasher, Sha1CheckedHasher};
+
+        let mut sha1 = Sha1CheckedHasher::new();
+        let mut sha1_git = Sha1CheckedHasher::new();
+        let mut sha256 = Sha256::new();
+        let mut blake2s256 = Blake2s256::new();
+        sha1_git.update(&git_object_header("blob", length as usize));
+        feed_reader(reader, length, |chunk| {
+            sha1.update(chunk);
+            sha1_git.update(chunk);
+            sha256.update(chunk);
+            blake2s256.update(chunk);
+        })?;
+        Ok(Self {
+            sha1: sha1.finalize(),
+            sha1_git: sha1_git.finalize(),
+            sha256: sha256.finalize().into(),
+            blake2s256: blake2s256.finalize().into(),
+            length,
+        })
+    }
+
+    /// Hash a file, streaming it as [`Content::from_file`]
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, SwhidError> {
+        let file = File::open(path)?;
+        let length = file.metadata()?.len();
+        Self::from_reader(BufReader::new(file), length)
+    }
+
+    /// Get the content SWHID
+    pub fn swhid(&self) -> Swhid {
+        Swhid::new(ObjectType::Content, self.sha1_git)
+    }
+}
+
+/// A Git LFS pointer file, as stored in a repository in place of the real content
+///
+/// See <https://github.com/git-lfs/git-lfs/blob/main/docs/spec.md>.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LfsPointer {
+    /// Hex-encoded SHA-256 of the real content
+    pub oid: String,
+    /// Size of the real content in bytes
+    pub size: u64,
+}
+
+impl LfsPointer {
+    /// Pointer files are always smaller than this, per the LFS specification
+    pub const MAX_SIZE: usize = 1024;
+
+    /// Parse a Git LFS pointer file, returning `None` if `data` is not one
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        if data.len() >= Self::MAX_SIZE {
+            return None;
+        }
+        let text = std::str::from_utf8(data).ok()?;
+        let mut lines = text.lines();
+        if !lines.next()?.starts_with("version https://git-lfs.github.com/spec/") {
+            return None;
+        }
+
+        let mut oid = None;
+        let mut size = None;
+        for line in lines {
+            if let Some(value) = line.strip_prefix("oid sha256:") {
+                if value.len() != 64 || !value.bytes().all(|b| b.is_ascii_hexdigit()) {
+                    return None;
+                }
+                oid = Some(value.to_ascii_lowercase());
+            } else if let Some(value) = line.strip_prefix("size ") {
+                size = Some(value.parse().ok()?);
+            }
+        }
+
+        Some(Self {
+            oid: oid?,
+            size: size?,
+        })
+    }
+
+    /// Check that `data` is the content this pointer refers to
+    pub fn matches(&self, data: &[u8]) -> bool {
+        data.len() as u64 == self.size && hex::encode(Sha256::digest(data)) == self.oid
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::fs;
     use tempfile::NamedTempFile;
 
     #[test]
@@ -62,11 +293,20 @@ mod tests {
         let data = b"Hello, World!".to_vec();
         let content = Content::from_data(data.clone());
         
-        assert_eq!(content.data(), data.as_slice());
+        assert_eq!(content.data(), Some(data.as_slice()));
         assert_eq!(content.length(), 13);
         assert_eq!(content.sha1_git().len(), 20);
     }
 
+    #[test]
+    fn test_content_from_data_with() {
+        let data = b"Hello, World!".to_vec();
+        let plain = Content::from_data_with(data.clone(), V1HashAlgorithm::Sha1);
+        assert_eq!(plain.sha1_git(), Content::from_data(data.clone()).sha1_git());
+        let streamed = Content::from_reader_with(&data[..], 13, V1HashAlgorithm::Sha1).unwrap();
+        assert_eq!(streamed.sha1_git(), plain.sha1_git());
+    }
+
     #[test]
     fn test_content_from_file() {
         let temp_file = NamedTempFile::new().unwrap();
@@ -74,8 +314,30 @@ mod tests {
         fs::write(&temp_file, data).unwrap();
         
         let content = Content::from_file(&temp_file).unwrap();
-        assert_eq!(content.data(), data);
+        // Files are streamed, not retained
+        assert_eq!(content.data(), None);
+        assert_eq!(content.length(), data.len());
+        assert_eq!(content.swhid(), Content::from_data(data.to_vec()).swhid());
+    }
+
+    #[test]
+    fn test_content_from_reader() {
+        let data = vec![b'x'; 200_000];
+        let content = Content::from_reader(data.as_slice(), data.len() as u64).unwrap();
+        assert_eq!(content.swhid(), Content::from_data(data.clone()).swhid());
         assert_eq!(content.length(), data.len());
+        assert_eq!(content.data(), None);
+        assert_eq!(content.sha256_git(), None);
+
+        // The length is part of the hash, so it must be exact
+        assert!(matches!(
+            Content::from_reader(data.as_slice(), data.len() as u64 + 1),
+            Err(SwhidError::InvalidInput(_))
+        ));
+        assert!(matches!(
+            Content::from_reader(data.as_slice(), 10),
+            Err(SwhidError::InvalidInput(_))
+        ));
     }
 
     #[test]
@@ -85,7 +347,7 @@ mod tests {
         let swhid = content.swhid();
         
         assert_eq!(swhid.object_type(), ObjectType::Content);
-        assert_eq!(swhid.hash(), content.sha1_git());
+        assert_eq!(swhid.digest(), content.sha1_git());
     }
 
     #[test]
@@ -95,13 +357,124 @@ mod tests {
         let empty_swhid = empty_content.swhid();
         
         // Known hash for empty content
-        assert_eq!(empty_swhid.hash(), &hex::decode("e69de29bb2d1d6434b8b29ae775ad8c2e48c5391").unwrap()[..]);
+        assert_eq!(empty_swhid.digest(), &hex::decode("e69de29bb2d1d6434b8b29ae775ad8c2e48c5391").unwrap()[..]);
         
         let hello_content = Content::from_data(b"Hello, World!".to_vec());
         let hello_swhid = hello_content.swhid();
         
         // Known hash for "Hello, World!"
-        assert_eq!(hello_swhid.hash(), &hex::decode("b45ef6fec89518d314f546fd6c3025367b721684").unwrap()[..]);
+        assert_eq!(hello_swhid.digest(), &hex::decode("b45ef6fec89518d314f546fd6c3025367b721684").unwrap()[..]);
+    }
+
+    #[test]
+    fn test_content_from_line_range() {
+        let data = b"one\ntwo\nthree\nfour\nfive";
+
+        let range = Content::from_line_range(data, 2, 4);
+        assert_eq!(range.data().unwrap(), b"two\nthree\nfour\n");
+        assert_eq!(range.swhid(), Content::from_data(b"two\nthree\nfour\n".to_vec()).swhid());
+
+        assert_eq!(Content::from_line_range(data, 3, 3).data().unwrap(), b"three\n");
+        // The last line has no newline in the original either
+        assert_eq!(Content::from_line_range(data, 5, 5).data().unwrap(), b"five");
+
+        // Ranges are clamped to the available lines
+        assert_eq!(Content::from_line_range(data, 4, 100).data().unwrap(), b"four\nfive");
+        assert_eq!(Content::from_line_range(data, 10, 20).data().unwrap(), b"");
+        assert_eq!(Content::from_line_range(data, 3, 2).data().unwrap(), b"");
+    }
+
+    #[test]
+    fn test_content_sha256_git() {
+        let content = Content::from_data(b"hello\n".to_vec());
+        assert_eq!(
+            hex::encode(content.sha256_git().unwrap()),
+            "2cf8d83d9ee29543b34a87727421fdecb7e3f3a183d337639025de576db9ebb4"
+        );
+        #[cfg(feature = "v2")]
+        assert_eq!(
+            content.swhid_v2().unwrap().to_string(),
+            "swh:2:cnt:2cf8d83d9ee29543b34a87727421fdecb7e3f3a183d337639025de576db9ebb4"
+        );
+    }
+
+    #[cfg(feature = "v2")]
+    #[test]
+    fn test_content_swhids_dual() {
+        /// A reader counting the bytes it yields
+        struct Counting<'a>(&'a [u8], usize);
+        impl Read for Counting<'_> {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                let n = self.0.read(buf)?;
+                self.1 += n;
+                Ok(n)
+            }
+        }
+
+        for data in [&b""[..], b"hello\n", &[0xa5; 200_000]] {
+            let content = Content::from_data(data.to_vec());
+            let expected = (content.swhid(), content.swhid_v2().unwrap());
+            assert_eq!(content.swhids_dual().unwrap(), expected);
+
+            let mut reader = Counting(data, 0);
+            assert_eq!(Content::swhids_dual_from_reader(&mut reader, data.len() as u64).unwrap(), expected);
+            assert_eq!(reader.1, data.len());
+        }
+        assert_eq!(
+            Content::from_data(b"hello\n".to_vec()).swhids_dual().unwrap().1.to_string(),
+            "swh:2:cnt:2cf8d83d9ee29543b34a87727421fdecb7e3f3a183d337639025de576db9ebb4"
+        );
+        assert!(Content::from_reader(&b"hello\n"[..], 6).unwrap().swhids_dual().is_none());
+    }
+
+    #[cfg(feature = "multihash")]
+    #[test]
+    fn test_multi_hash() {
+        let data = b"hello\n";
+        let hashes = MultiHash::from_data(data);
+        assert_eq!(hex::encode(hashes.sha1), "f572d396fae9206628714fb2ce00f72e94f2258f");
+        assert_eq!(hex::encode(hashes.sha1_git), "ce013625030ba8dba906f756967f9e9ca394464a");
+        assert_eq!(
+            hex::encode(hashes.sha256),
+            "5891b5b522d5df086d0ff0b110fbd9d21bb4fc7163af34d08286a2e846f6be03"
+        );
+        assert_eq!(
+            hex::encode(hashes.blake2s256),
+            "3969b3926654065966b6f8d9a65789b0f76d56e1e2ab67dd94faa770959187ca"
+        );
+        assert_eq!(hashes.length, 6);
+
+        let content = Content::from_data(data.to_vec());
+        assert_eq!(hashes.swhid(), content.swhid());
+        assert_eq!(content.multi_hash(), Some(hashes.clone()));
+        assert_eq!(Content::from_reader(&data[..], 6).unwrap().multi_hash(), None);
+
+        let file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(file.path(), data).unwrap();
+        assert_eq!(MultiHash::from_file(file.path()).unwrap(), hashes);
+        assert!(matches!(MultiHash::from_reader(&data[..], 7), Err(SwhidError::InvalidInput(_))));
+    }
+
+    #[test]
+    fn test_lfs_pointer_parse() {
+        let pointer = b"version https://git-lfs.github.com/spec/v1\noid sha256:4d7a214614ab2935c943f9e0ff69d22eadbb8f32b1258daaa5e2ca24d17e2393\nsize 12345\n";
+        let parsed = LfsPointer::parse(pointer).unwrap();
+        assert_eq!(parsed.oid, "4d7a214614ab2935c943f9e0ff69d22eadbb8f32b1258daaa5e2ca24d17e2393");
+        assert_eq!(parsed.size, 12345);
+
+        assert!(LfsPointer::parse(b"Hello, World!").is_none());
+        assert!(LfsPointer::parse(b"version https://git-lfs.github.com/spec/v1\nsize 1\n").is_none());
+    }
+
+    #[test]
+    fn test_lfs_pointer_matches() {
+        let data = b"large binary content";
+        let pointer = LfsPointer {
+            oid: hex::encode(Sha256::digest(data)),
+            size: data.len() as u64,
+        };
+        assert!(pointer.matches(data));
+        assert!(!pointer.matches(b"other content"));
     }
 
     #[test]
@@ -109,7 +482,7 @@ mod tests {
         let large_data = vec![b'a'; 10000];
         let content = Content::from_data(large_data.clone());
         
-        assert_eq!(content.data(), large_data.as_slice());
+        assert_eq!(content.data(), Some(large_data.as_slice()));
         assert_eq!(content.length(), 10000);
         assert_eq!(content.sha1_git().len(), 20);
     }
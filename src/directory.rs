@@ -1,11 +1,15 @@
+use std::collections::{BTreeMap, HashSet};
+use std::ffi::OsStr;
 use std::fs;
-use std::os::unix::fs::MetadataExt;
-use std::os::unix::ffi::OsStrExt;
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Condvar, Mutex, PoisonError};
+use std::time::UNIX_EPOCH;
 use crate::swhid::{Swhid, ObjectType};
 use crate::content::Content;
-use crate::hash::hash_git_object;
+use crate::hash::{hash_git_object, V1HashAlgorithm};
 use crate::error::SwhidError;
+use crate::computer::{BrokenSymlinkPolicy, SpecialFilePolicy, SwhidComputer};
+use crate::git_dir::{find_git_dir, resolve_head};
 
 /// Directory entry types
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -13,6 +17,8 @@ pub enum EntryType {
     File,
     Directory,
     Symlink,
+    /// A Git submodule (gitlink), pointing to a commit
+    Submodule,
 }
 
 impl EntryType {
@@ -21,6 +27,16 @@ impl EntryType {
             EntryType::File => "file",
             EntryType::Directory => "dir",
             EntryType::Symlink => "symlink",
+            EntryType::Submodule => "submodule",
+        }
+    }
+
+    /// Get the type of the object the entry points to, as Git names it
+    pub fn git_object_type(&self) -> &'static str {
+        match self {
+            EntryType::Directory => "tree",
+            EntryType::File | EntryType::Symlink => "blob",
+            EntryType::Submodule => "commit",
         }
     }
 }
@@ -32,6 +48,7 @@ pub enum Permissions {
     Executable = 0o100755,
     Symlink = 0o120000,
     Directory = 0o040000,
+    Submodule = 0o160000,
 }
 
 impl Permissions {
@@ -39,6 +56,7 @@ impl Permissions {
         match mode & 0o170000 {
             0o040000 => Permissions::Directory,
             0o120000 => Permissions::Symlink,
+            0o160000 => Permissions::Submodule,
             _ => {
                 if mode & 0o111 != 0 {
                     Permissions::Executable
@@ -49,9 +67,38 @@ impl Permissions {
         }
     }
 
+    /// Permissions of a regular file, from its metadata
+    ///
+    /// On Unix the executable bits decide; other platforms have no such bit,
+    /// so every file is a regular, non-executable file.
+    #[cfg(unix)]
+    pub(crate) fn from_file_metadata(metadata: &fs::Metadata) -> Self {
+        use std::os::unix::fs::MetadataExt;
+        Self::from_mode(metadata.mode())
+    }
+
+    /// Permissions of a regular file, from its metadata
+    #[cfg(not(unix))]
+    pub(crate) fn from_file_metadata(_metadata: &fs::Metadata) -> Self {
+        Permissions::File
+    }
+
     pub fn as_octal(&self) -> u32 {
         *self as u32
     }
+
+    /// Get the mode string exactly as Git writes it in tree objects
+    ///
+    /// Note that Git omits the leading zero for directories (`40000`, not `040000`).
+    pub fn git_mode_str(&self) -> &'static str {
+        match self {
+            Permissions::File => "100644",
+            Permissions::Executable => "100755",
+            Permissions::Symlink => "120000",
+            Permissions::Directory => "40000",
+            Permissions::Submodule => "160000",
+        }
+    }
 }
 
 /// Directory entry
@@ -74,8 +121,29 @@ impl DirectoryEntry {
     }
 }
 
+/// An object encountered while walking a directory tree
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WalkEntry {
+    /// Path of the object on disk, starting with the walked root
+    pub path: PathBuf,
+    pub swhid: Swhid,
+    /// Kind of entry on disk
+    ///
+    /// Files and symlinks both have content SWHIDs, which collide when a
+    /// file's bytes equal a symlink's target; this tells them apart.
+    pub entry_type: EntryType,
+    /// Mode of the entry in its parent tree; [`Permissions::Directory`] for the root
+    ///
+    /// Only this tells an executable file from a regular one with the same content.
+    pub permissions: Permissions,
+    /// Modification time as a Unix timestamp, if requested and available
+    ///
+    /// Recorded for information only: it never affects any SWHID.
+    pub mtime: Option<i64>,
+}
+
 /// Directory object
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct Directory {
     entries: Vec<DirectoryEntry>,
     hash: Option<[u8; 20]>,
@@ -97,81 +165,113 @@ impl Directory {
         path: P,
         exclude_patterns: &[String],
     ) -> Result<Self, SwhidError> {
-        let path = path.as_ref();
-        let mut entries = Vec::new();
-
-        // Collect and sort directory entries
-        let mut raw_entries: Vec<_> = fs::read_dir(path)?.collect();
-        raw_entries.sort_by(|a, b| {
-            let name_a = a.as_ref().unwrap().file_name();
-            let name_b = b.as_ref().unwrap().file_name();
-            name_a.cmp(&name_b)
-        });
-
-        for entry_result in raw_entries {
-            let entry = entry_result?;
-            let name = entry.file_name();
-            let name_bytes = name.to_string_lossy().as_bytes().to_vec();
-
-            // Skip excluded files and directories
-            if Self::should_exclude(&name_bytes, exclude_patterns) {
-                continue;
-            }
+        let options = SwhidComputer::new().with_exclude_patterns(exclude_patterns);
+        Self::read_disk(path.as_ref(), &options)
+    }
 
-            let metadata = entry.metadata()?;
+    /// Create directory from disk path, skipping entries that cannot be read
+    ///
+    /// Instead of aborting on the first failure, every error is recorded in
+    /// `errors` along with the offending path and the entry is treated as if it
+    /// were absent. The resulting tree is therefore a non-standard, partial
+    /// result whenever `errors` is non-empty.
+    pub fn from_disk_best_effort<P: AsRef<Path>>(
+        path: P,
+        exclude_patterns: &[String],
+        errors: &mut Vec<(PathBuf, SwhidError)>,
+    ) -> Self {
+        let options = SwhidComputer::new().with_exclude_patterns(exclude_patterns);
+        Self::read_disk_best_effort(path.as_ref(), &options, errors)
+    }
 
-            let entry_type = if metadata.is_dir() {
-                EntryType::Directory
-            } else if metadata.is_symlink() {
-                EntryType::Symlink
-            } else {
-                EntryType::File
-            };
+    /// Read a directory from disk using the traversal settings of `options`
+    pub(crate) fn read_disk(path: &Path, options: &SwhidComputer) -> Result<Self, SwhidError> {
+        #[cfg(feature = "parallel")]
+        if let Some(pool) = options.thread_pool()? {
+            return pool.install(|| {
+                let mut walker = DiskWalker::new(options, path, None);
+                walker.parallel = true;
+                walker.read_directory(path).map_err(|err| err.at_path(path))
+            });
+        }
+        DiskWalker::new(options, path, None)
+            .read_directory(path)
+            .map_err(|err| err.at_path(path))
+    }
 
-            let permissions = Permissions::from_mode(metadata.mode());
-
-            // Compute the target hash
-            let target = if entry_type == EntryType::File {
-                let content = Content::from_file(entry.path())?;
-                *content.sha1_git()
-            } else if entry_type == EntryType::Symlink {
-                // Handle symlinks - read the symlink target as content
-                if let Ok(target_path) = fs::read_link(entry.path()) {
-                    let target_bytes = target_path.to_string_lossy().as_bytes().to_vec();
-                    let content = Content::from_data(target_bytes);
-                    *content.sha1_git()
-                } else {
-                    // Skip broken symlinks
-                    continue;
-                }
-            } else {
-                // Directory - use dummy hash for now, will be computed later
-                [0u8; 20]
-            };
+    /// Read a directory from disk, reporting every object to `visitor`
+    ///
+    /// Objects are reported as soon as their SWHID is computed, so children are
+    /// always visited before their parent directory and the root comes last.
+    pub(crate) fn walk_disk(
+        path: &Path,
+        options: &SwhidComputer,
+        visitor: &mut dyn FnMut(WalkEntry),
+    ) -> Result<Self, SwhidError> {
+        let mut walker = DiskWalker::new(options, path, None);
+        walker.visitor = Some(visitor);
+        let mut dir = walker.read_directory(path).map_err(|err| err.at_path(path))?;
+        let swhid = dir.swhid_with(options.hash_algorithm);
+        walker.visit(path, EntryType::Directory, Permissions::Directory, swhid);
+        Ok(dir)
+    }
 
-            let dir_entry = DirectoryEntry::new(name_bytes, entry_type, permissions, target);
-            entries.push(dir_entry);
-        }
+    /// Read the tree entry for a single path on disk, named `name`
+    ///
+    /// Subdirectories are traversed with the settings of `options`.
+    /// Returns `None` for entries that are skipped, such as broken symlinks.
+    pub(crate) fn read_disk_entry(
+        root: &Path,
+        path: &Path,
+        name: Vec<u8>,
+        options: &SwhidComputer,
+    ) -> Result<Option<DirectoryEntry>, SwhidError> {
+        let file_type = fs::symlink_metadata(path)?.file_type();
+        DiskWalker::new(options, root, None).read_entry(path, file_type, name)
+    }
 
+    /// Assemble a directory from its entries, in any order
+    ///
+    /// Entries are sorted according to Git's tree sorting rules. Fails with
+    /// `SwhidError::DuplicateEntry` if two entries share the same name.
+    pub(crate) fn from_entries(mut entries: Vec<DirectoryEntry>) -> Result<Self, SwhidError> {
         // Sort entries according to Git's tree sorting rules
-        entries.sort_by(|a, b| a.name.cmp(&b.name));
-
-        // For directories, we need to compute their hashes recursively
-        for entry in &mut entries {
-            if entry.entry_type == EntryType::Directory {
-                let child_path = path.join(std::ffi::OsStr::from_bytes(&entry.name));
-                let mut child_dir = Directory::from_disk(child_path, exclude_patterns)?;
-                entry.target = child_dir.compute_hash();
-            }
+        entries.sort_by_cached_key(tree_sort_key);
+
+        // Name transformations may map distinct on-disk names to the same bytes.
+        // A file and a directory of the same name need not be adjacent in tree
+        // order, so duplicates are looked up rather than compared pairwise.
+        let mut names = HashSet::with_capacity(entries.len());
+        if let Some(entry) = entries.iter().find(|entry| !names.insert(entry.name.as_slice())) {
+            return Err(SwhidError::DuplicateEntry(
+                String::from_utf8_lossy(&entry.name).into_owned(),
+            ));
         }
 
-        let mut dir = Self {
+        Ok(Self {
             entries,
             hash: None,
-            path: Some(path.to_path_buf()),
-        };
-        dir.path = Some(path.to_path_buf());
-        Ok(dir)
+            path: None,
+        })
+    }
+
+    /// Best-effort variant of [`Directory::read_disk`]
+    pub(crate) fn read_disk_best_effort(
+        path: &Path,
+        options: &SwhidComputer,
+        errors: &mut Vec<(PathBuf, SwhidError)>,
+    ) -> Self {
+        match DiskWalker::new(options, path, Some(errors)).read_directory(path) {
+            Ok(dir) => dir,
+            Err(err) => {
+                errors.push((path.to_path_buf(), err));
+                Self {
+                    entries: Vec::new(),
+                    hash: None,
+                    path: Some(path.to_path_buf()),
+                }
+            }
+        }
     }
 
     /// Get directory entries
@@ -179,33 +279,192 @@ impl Directory {
         &self.entries
     }
 
+    /// Add a regular file entry with the given content SWHID
+    ///
+    /// Entries can be added in any order; they are kept in tree order, so
+    /// the directory hashes exactly as one read from disk. Fails with
+    /// `SwhidError::InvalidInput` if `content` is not a content SWHID, and
+    /// with `SwhidError::InvalidVersion` if it is a v2 SWHID. See [`Directory::add_subdirectory`] for name errors.
+    pub fn add_file(&mut self, name: Vec<u8>, content: &Swhid, executable: bool) -> Result<(), SwhidError> {
+        if content.object_type() != ObjectType::Content {
+            return Err(SwhidError::InvalidInput(format!("{} is not a content SWHID", content)));
+        }
+        let permissions = if executable { Permissions::Executable } else { Permissions::File };
+        self.insert_entry(DirectoryEntry::new(name, EntryType::File, permissions, *content.v1_hash()?))
+    }
+
+    /// Add a symlink entry pointing to `target`
+    ///
+    /// See [`Directory::add_subdirectory`] for name errors.
+    pub fn add_symlink(&mut self, name: Vec<u8>, target: &[u8]) -> Result<(), SwhidError> {
+        let hash = *Content::from_data(target.to_vec()).sha1_git();
+        self.insert_entry(DirectoryEntry::new(name, EntryType::Symlink, Permissions::Symlink, hash))
+    }
+
+    /// Add a subdirectory entry with the given directory SWHID
+    ///
+    /// Fails with `SwhidError::InvalidInput` if `directory` is not a directory
+    /// SWHID, with `SwhidError::InvalidVersion` if it is a v2 SWHID, with
+    /// `SwhidError::InvalidPath` if `name` is empty, `.`, `..`, or
+    /// contains `/` or NUL, and with `SwhidError::DuplicateEntry` if an entry
+    /// of that name already exists.
+    pub fn add_subdirectory(&mut self, name: Vec<u8>, directory: &Swhid) -> Result<(), SwhidError> {
+        if directory.object_type() != ObjectType::Directory {
+            return Err(SwhidError::InvalidInput(format!("{} is not a directory SWHID", directory)));
+        }
+        self.insert_entry(DirectoryEntry::new(
+            name,
+            EntryType::Directory,
+            Permissions::Directory,
+            *directory.v1_hash()?,
+        ))
+    }
+
+    /// Add a submodule (gitlink) entry pointing to the given revision SWHID
+    ///
+    /// Git records a submodule as the commit checked out in it, not as its
+    /// files. Fails with `SwhidError::InvalidInput` if `revision` is not a
+    /// revision SWHID, and with `SwhidError::InvalidVersion` if it is a v2
+    /// SWHID. See [`Directory::add_subdirectory`] for name errors.
+    pub fn add_submodule(&mut self, name: Vec<u8>, revision: &Swhid) -> Result<(), SwhidError> {
+        if revision.object_type() != ObjectType::Revision {
+            return Err(SwhidError::InvalidInput(format!("{} is not a revision SWHID", revision)));
+        }
+        self.insert_entry(DirectoryEntry::new(
+            name,
+            EntryType::Submodule,
+            Permissions::Submodule,
+            *revision.v1_hash()?,
+        ))
+    }
+
+    /// Insert an entry at its place in tree order
+    fn insert_entry(&mut self, entry: DirectoryEntry) -> Result<(), SwhidError> {
+        let name = &entry.name;
+        if name.is_empty() || name == b"." || name == b".." || name.contains(&b'/') || name.contains(&0) {
+            return Err(SwhidError::InvalidPath(format!(
+                "Invalid entry name: {:?}",
+                String::from_utf8_lossy(name)
+            )));
+        }
+        if self.entries.iter().any(|existing| existing.name == *name) {
+            return Err(SwhidError::DuplicateEntry(String::from_utf8_lossy(name).into_owned()));
+        }
+
+        let key = tree_sort_key(&entry);
+        let position = self.entries.partition_point(|existing| tree_sort_key(existing) < key);
+        self.entries.insert(position, entry);
+        self.hash = None;
+        Ok(())
+    }
+
+    /// Take ownership of the directory entries, in tree order
+    ///
+    /// The directory hash is lost with the directory, so compute it first
+    /// with [`Directory::swhid`] if it is still needed.
+    pub fn into_entries(self) -> Vec<DirectoryEntry> {
+        self.entries
+    }
+
     /// Compute the directory hash
     pub fn compute_hash(&mut self) -> [u8; 20] {
+        self.compute_hash_with(V1HashAlgorithm::Sha1Checked)
+    }
+
+    /// Compute the directory hash with `algorithm`
+    ///
+    /// Only hashes of the default algorithm are kept for later calls.
+    pub fn compute_hash_with(&mut self, algorithm: V1HashAlgorithm) -> [u8; 20] {
+        if algorithm != V1HashAlgorithm::default() {
+            return algorithm.hash_git_object("tree", &self.tree_object());
+        }
         if let Some(hash) = self.hash {
             return hash;
         }
 
+        let hash = algorithm.hash_git_object("tree", &self.tree_object());
+        self.hash = Some(hash);
+        hash
+    }
+
+    /// Compute the SWHID of a tree of regular files given as a path → bytes map
+    ///
+    /// Each key is a file path relative to the root; intermediate directories
+    /// are created as needed and every file gets the non-executable mode.
+    /// Fails with `SwhidError::InvalidPath` if a key is not a relative path,
+    /// or if a path is used both as a file and as a directory (e.g. `a` and
+    /// `a/b`).
+    pub fn from_map(map: BTreeMap<PathBuf, Vec<u8>>) -> Result<Swhid, SwhidError> {
+        let mut tree = TreeNode::new();
+        for (path, data) in map {
+            let mut names = relative_components(&path).unwrap_or_default();
+            let Some(name) = names.pop() else {
+                return Err(SwhidError::InvalidPath(format!(
+                    "Not a relative file path: {}",
+                    path.display()
+                )));
+            };
+            let content = Content::from_data(data);
+            let entry = DirectoryEntry::new(name, EntryType::File, Permissions::File, *content.sha1_git());
+            tree.insert(&names, entry)?;
+        }
+        Ok(tree.into_directory(V1HashAlgorithm::Sha1Checked)?.swhid())
+    }
+
+    /// Serialize the entries as the body of a Git tree object
+    ///
+    /// These are the exact bytes [`Directory::compute_hash`] hashes, as
+    /// `hash_git_object("tree", &directory.tree_object())`, with the entries
+    /// in tree order.
+    pub fn tree_object(&self) -> Vec<u8> {
         let mut components = Vec::new();
 
         for entry in &self.entries {
             // Format: perms + space + name + null + target
             // Use exact string format as per SWHID specification
-            let perms_str = match entry.permissions {
-                Permissions::File => "100644",
-                Permissions::Executable => "100755", 
-                Permissions::Symlink => "120000",
-                Permissions::Directory => "40000",
-            };
-            components.extend_from_slice(perms_str.as_bytes());
+            components.extend_from_slice(entry.permissions.git_mode_str().as_bytes());
             components.push(b' ');
             components.extend_from_slice(&entry.name);
             components.push(0);
             components.extend_from_slice(&entry.target);
         }
 
-        let hash = hash_git_object("tree", &components);
-        self.hash = Some(hash);
-        hash
+        components
+    }
+
+    /// Format the entries as `git cat-file -p` prints a tree object
+    ///
+    /// Each entry is a line such as `100644 blob <hex>\tREADME.md`, in the
+    /// order they are hashed. Names that are not UTF-8 are shown lossily.
+    pub fn cat_file(&self) -> String {
+        self.entries
+            .iter()
+            .map(|entry| {
+                format!(
+                    "{:06o} {} {}\t{}\n",
+                    entry.permissions.as_octal(),
+                    entry.entry_type.git_object_type(),
+                    hex::encode(entry.target),
+                    String::from_utf8_lossy(&entry.name)
+                )
+            })
+            .collect()
+    }
+
+    /// Compute the SWHID of a tree stored as Git tree objects
+    ///
+    /// `lookup` returns the body of the tree object with the given id. The
+    /// root and every tree it references are fetched and checked to hash to
+    /// their id, failing with `SwhidError::InvalidHash` otherwise and with
+    /// `SwhidError::InvalidInput` if one is missing or malformed. Blobs and
+    /// submodule commits are not looked up, so they are trusted.
+    pub fn from_tree_objects<F>(lookup: F, root: [u8; 20]) -> Result<Swhid, SwhidError>
+    where
+        F: Fn([u8; 20]) -> Option<Vec<u8>>,
+    {
+        let mut verified = HashSet::new();
+        verify_tree_object(&lookup, root, &mut verified)?;
+        Ok(Swhid::new(ObjectType::Directory, root))
     }
 
     /// Compute SWHID for this directory
@@ -214,52 +473,715 @@ impl Directory {
         Swhid::new(ObjectType::Directory, hash)
     }
 
+    /// Compute SWHID for this directory, hashed with `algorithm`
+    ///
+    /// See [`Directory::compute_hash_with`].
+    pub fn swhid_with(&mut self, algorithm: V1HashAlgorithm) -> Swhid {
+        Swhid::new(ObjectType::Directory, self.compute_hash_with(algorithm))
+    }
+
     /// Get the path associated with this directory
     pub fn path(&self) -> Option<&Path> {
         self.path.as_deref()
     }
 
     /// Check if entry should be excluded based on patterns
-    fn should_exclude(name: &[u8], patterns: &[String]) -> bool {
+    ///
+    /// `relative_path` is the entry's path from the traversal root, with `/`
+    /// separators, for patterns that match whole paths.
+    pub(crate) fn should_exclude(name: &[u8], relative_path: &str, patterns: &[String]) -> bool {
         let name_str = String::from_utf8_lossy(name);
-        should_exclude_str(&name_str, patterns)
+        should_exclude_str(&name_str, relative_path, patterns)
+    }
+}
+
+impl IntoIterator for Directory {
+    type Item = DirectoryEntry;
+    type IntoIter = std::vec::IntoIter<DirectoryEntry>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.into_entries().into_iter()
+    }
+}
+
+/// Check that a stored tree object and all trees below it match their ids
+fn verify_tree_object<F>(
+    lookup: &F,
+    id: [u8; 20],
+    verified: &mut HashSet<[u8; 20]>,
+) -> Result<(), SwhidError>
+where
+    F: Fn([u8; 20]) -> Option<Vec<u8>>,
+{
+    // Identical subtrees are shared, so each one only needs checking once
+    if !verified.insert(id) {
+        return Ok(());
+    }
+
+    let data = lookup(id).ok_or_else(|| {
+        SwhidError::InvalidInput(format!("Missing tree object {}", hex::encode(id)))
+    })?;
+    let actual = hash_git_object("tree", &data);
+    if actual != id {
+        return Err(SwhidError::InvalidHash(format!(
+            "Tree object {} hashes to {}",
+            hex::encode(id),
+            hex::encode(actual)
+        )));
     }
+
+    for entry in parse_tree_object(&data)? {
+        if entry.entry_type == EntryType::Directory {
+            verify_tree_object(lookup, entry.target, verified)?;
+        }
+    }
+    Ok(())
+}
+
+/// Parse the body of a Git tree object into its entries
+pub(crate) fn parse_tree_object(mut data: &[u8]) -> Result<Vec<DirectoryEntry>, SwhidError> {
+    let malformed = || SwhidError::InvalidInput("Malformed tree object".to_string());
+    let mut entries = Vec::new();
+
+    while !data.is_empty() {
+        let space = data.iter().position(|&b| b == b' ').ok_or_else(malformed)?;
+        let (permissions, entry_type) = match &data[..space] {
+            b"100644" => (Permissions::File, EntryType::File),
+            b"100755" => (Permissions::Executable, EntryType::File),
+            b"120000" => (Permissions::Symlink, EntryType::Symlink),
+            b"40000" => (Permissions::Directory, EntryType::Directory),
+            b"160000" => (Permissions::Submodule, EntryType::Submodule),
+            mode => {
+                return Err(SwhidError::InvalidInput(format!(
+                    "Unsupported tree entry mode: {}",
+                    String::from_utf8_lossy(mode)
+                )));
+            }
+        };
+        data = &data[space + 1..];
+
+        let nul = data.iter().position(|&b| b == 0).ok_or_else(malformed)?;
+        let name = data[..nul].to_vec();
+        data = &data[nul + 1..];
+
+        if data.len() < 20 {
+            return Err(malformed());
+        }
+        let mut target = [0u8; 20];
+        target.copy_from_slice(&data[..20]);
+        data = &data[20..];
+
+        entries.push(DirectoryEntry::new(name, entry_type, permissions, target));
+    }
+
+    Ok(entries)
+}
+
+/// Key ordering entries within a tree object
+///
+/// Git sorts directories as if their name ended with `/`, so a directory
+/// `lib` comes after a file `lib.d`. Keys are computed once per entry rather
+/// than on every comparison.
+fn tree_sort_key(entry: &DirectoryEntry) -> Vec<u8> {
+    let mut key = entry.name.clone();
+    if entry.entry_type == EntryType::Directory {
+        key.push(b'/');
+    }
+    key
+}
+
+/// Nested in-memory tree, assembled into directories from its leaves
+///
+/// Intermediate directories are created on demand as leaves are inserted.
+#[derive(Debug, Default)]
+pub(crate) struct TreeNode {
+    children: BTreeMap<Vec<u8>, TreeChild>,
+}
+
+#[derive(Debug)]
+enum TreeChild {
+    /// A fully-formed entry: a file, a symlink, or an already-hashed subtree
+    Leaf(DirectoryEntry),
+    Node(TreeNode),
+}
+
+impl TreeNode {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Insert `entry` under the intermediate directories named by `parents`
+    ///
+    /// Fails with `SwhidError::InvalidPath` if a path is used both as a leaf
+    /// and as a directory, and with `SwhidError::DuplicateEntry` if the same
+    /// leaf is inserted twice.
+    pub(crate) fn insert(&mut self, parents: &[Vec<u8>], entry: DirectoryEntry) -> Result<(), SwhidError> {
+        let node = self.directory(parents)?;
+        match node.children.get(&entry.name) {
+            None => {
+                node.children.insert(entry.name.clone(), TreeChild::Leaf(entry));
+                Ok(())
+            }
+            Some(TreeChild::Node(_)) => Err(SwhidError::InvalidPath(format!(
+                "{} is both a file and a directory",
+                display_components(&[parents, &[entry.name]].concat())
+            ))),
+            Some(TreeChild::Leaf(_)) => Err(SwhidError::DuplicateEntry(
+                display_components(&[parents, &[entry.name]].concat()),
+            )),
+        }
+    }
+
+    /// Get the directory named by `path`, creating it and its parents as needed
+    ///
+    /// Fails with `SwhidError::InvalidPath` if part of `path` is a leaf.
+    pub(crate) fn directory(&mut self, path: &[Vec<u8>]) -> Result<&mut TreeNode, SwhidError> {
+        let mut node = self;
+        for (depth, parent) in path.iter().enumerate() {
+            let child = node
+                .children
+                .entry(parent.clone())
+                .or_insert_with(|| TreeChild::Node(TreeNode::new()));
+            node = match child {
+                TreeChild::Node(child) => child,
+                TreeChild::Leaf(_) => {
+                    return Err(SwhidError::InvalidPath(format!(
+                        "{} is both a file and a directory",
+                        display_components(&path[..=depth])
+                    )));
+                }
+            };
+        }
+        Ok(node)
+    }
+
+    /// Assemble the tree bottom-up into a directory, hashing subdirectories
+    /// with `algorithm`
+    pub(crate) fn into_directory(self, algorithm: V1HashAlgorithm) -> Result<Directory, SwhidError> {
+        let mut entries = Vec::with_capacity(self.children.len());
+        for (name, child) in self.children {
+            let entry = match child {
+                TreeChild::Leaf(entry) => entry,
+                TreeChild::Node(node) => {
+                    let hash = node.into_directory(algorithm)?.compute_hash_with(algorithm);
+                    DirectoryEntry::new(name, EntryType::Directory, Permissions::Directory, hash)
+                }
+            };
+            entries.push(entry);
+        }
+        Directory::from_entries(entries)
+    }
+}
+
+/// Encode a file name, or symlink target, as the bytes stored in a tree
+///
+/// On Unix these are the raw bytes of the name, as Git stores them, whether
+/// or not they are valid UTF-8.
+#[cfg(unix)]
+pub(crate) fn name_bytes(name: &OsStr) -> Vec<u8> {
+    use std::os::unix::ffi::OsStrExt;
+    name.as_bytes().to_vec()
+}
+
+/// Encode a file name, or symlink target, as the bytes stored in a tree
+///
+/// Other platforms have no raw byte form, so names are stored as UTF-8, as
+/// Git for Windows does. Unpaired surrogates are encoded lossily, with
+/// U+FFFD replacing each of them.
+#[cfg(not(unix))]
+pub(crate) fn name_bytes(name: &OsStr) -> Vec<u8> {
+    name.to_string_lossy().as_bytes().to_vec()
+}
+
+/// Decode a name stored in a tree into a path, the inverse of [`name_bytes`]
+#[cfg(unix)]
+pub(crate) fn name_path(name: &[u8]) -> PathBuf {
+    use std::os::unix::ffi::OsStrExt;
+    PathBuf::from(OsStr::from_bytes(name))
+}
+
+/// Decode a name stored in a tree into a path, as UTF-8
+///
+/// Invalid UTF-8 is decoded lossily, with U+FFFD replacing it.
+#[cfg(not(unix))]
+pub(crate) fn name_path(name: &[u8]) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(name).into_owned())
+}
+
+/// Split a path relative to some root into its names
+///
+/// Returns `None` if the path is absolute or climbs out of the root.
+pub(crate) fn relative_components(path: &Path) -> Option<Vec<Vec<u8>>> {
+    let mut names = Vec::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(name) => names.push(name_bytes(name)),
+            Component::CurDir => {}
+            _ => return None,
+        }
+    }
+    Some(names)
 }
 
-/// Check if entry should be excluded based on patterns (string version)
-/// Uses shell pattern matching like Python's fnmatch
-fn should_exclude_str(name: &str, patterns: &[String]) -> bool {
-    for pattern in patterns {
-        if matches_pattern(name, pattern) {
-            return true;
+/// Render path components for error messages
+fn display_components(components: &[Vec<u8>]) -> String {
+    components
+        .iter()
+        .map(|c| String::from_utf8_lossy(c))
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// A directory entry listed on disk but not yet hashed
+struct PendingEntry {
+    path: PathBuf,
+    file_type: std::io::Result<fs::FileType>,
+    name: Vec<u8>,
+}
+
+/// State threaded through a recursive on-disk traversal
+struct DiskWalker<'a> {
+    options: &'a SwhidComputer,
+    /// Root of the traversal, which path-based exclude patterns are relative to
+    root: PathBuf,
+    /// When set, per-entry errors are collected here instead of aborting
+    errors: Option<&'a mut Vec<(PathBuf, SwhidError)>>,
+    /// When set, called with every object as soon as its SWHID is known
+    visitor: Option<&'a mut dyn FnMut(WalkEntry)>,
+    /// Hash the entries of each directory concurrently, on the current
+    /// rayon pool; requires no visitor and no error collection
+    #[cfg(feature = "parallel")]
+    parallel: bool,
+    /// Canonical paths of the directories being read, from the root down,
+    /// to detect cycles when following symlinks
+    ancestors: Vec<PathBuf>,
+    /// Cap on the files open at once, shared by every thread of the traversal
+    open_files: Option<Arc<OpenFileLimit>>,
+}
+
+/// Counting semaphore capping the number of files open at the same time
+///
+/// Set with [`SwhidComputer::with_max_concurrency`]. A slot is held while a
+/// directory is listed or a file hashed, never while waiting for another
+/// slot, so concurrent traversals cannot deadlock.
+#[derive(Debug)]
+pub(crate) struct OpenFileLimit {
+    max: usize,
+    /// Files open now, and the most ever open at once
+    state: Mutex<(usize, usize)>,
+    released: Condvar,
+}
+
+impl OpenFileLimit {
+    pub(crate) fn new(max: usize) -> Self {
+        Self {
+            max: max.max(1),
+            state: Mutex::new((0, 0)),
+            released: Condvar::new(),
+        }
+    }
+
+    /// Wait for a free slot, held until the permit is dropped
+    pub(crate) fn acquire(&self) -> OpenFilePermit<'_> {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        while state.0 >= self.max {
+            state = self.released.wait(state).unwrap_or_else(PoisonError::into_inner);
+        }
+        state.0 += 1;
+        state.1 = state.1.max(state.0);
+        OpenFilePermit(self)
+    }
+
+    /// Most files ever open at once
+    #[cfg(test)]
+    pub(crate) fn peak(&self) -> usize {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner).1
+    }
+}
+
+/// A slot of an [`OpenFileLimit`]
+pub(crate) struct OpenFilePermit<'a>(&'a OpenFileLimit);
+
+impl Drop for OpenFilePermit<'_> {
+    fn drop(&mut self) {
+        self.0.state.lock().unwrap_or_else(PoisonError::into_inner).0 -= 1;
+        self.0.released.notify_one();
+    }
+}
+
+impl<'a> DiskWalker<'a> {
+    fn new(
+        options: &'a SwhidComputer,
+        root: &Path,
+        errors: Option<&'a mut Vec<(PathBuf, SwhidError)>>,
+    ) -> Self {
+        Self {
+            options,
+            root: root.to_path_buf(),
+            errors,
+            visitor: None,
+            #[cfg(feature = "parallel")]
+            parallel: false,
+            ancestors: Vec::new(),
+            open_files: options.max_concurrency.map(|max| Arc::new(OpenFileLimit::new(max))),
+        }
+    }
+
+    /// Path of an entry from the traversal root, with `/` separators
+    fn relative_path(&self, path: &Path) -> String {
+        let relative = path.strip_prefix(&self.root).unwrap_or(path);
+        relative
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+
+    /// Report an object to the visitor, if any
+    fn visit(&mut self, path: &Path, entry_type: EntryType, permissions: Permissions, swhid: Swhid) {
+        if let Some(visitor) = self.visitor.as_mut() {
+            let mtime = if self.options.record_mtime {
+                modification_time(path)
+            } else {
+                None
+            };
+            visitor(WalkEntry {
+                path: path.to_path_buf(),
+                swhid,
+                entry_type,
+                permissions,
+                mtime,
+            });
+        }
+    }
+
+    /// Propagate an error, or record it and carry on in best-effort mode
+    fn recover(&mut self, path: PathBuf, err: SwhidError) -> Result<(), SwhidError> {
+        match self.errors {
+            Some(ref mut errors) => {
+                errors.push((path, err));
+                Ok(())
+            }
+            None => Err(err.at_path(&path)),
+        }
+    }
+
+    fn read_directory(&mut self, path: &Path) -> Result<Directory, SwhidError> {
+        if !self.options.follow_symlinks {
+            return self.read_directory_entries(path);
+        }
+        self.ancestors.push(fs::canonicalize(path)?);
+        let result = self.read_directory_entries(path);
+        self.ancestors.pop();
+        result
+    }
+
+    fn read_directory_entries(&mut self, path: &Path) -> Result<Directory, SwhidError> {
+        // Entries are sorted once, by tree order, when the directory is assembled
+        let mut pending = Vec::new();
+        {
+            // The directory stays open while it is listed, not while its
+            // entries are read; a `DirEntry` keeps it open, so none is kept
+            let open_files = self.open_files.clone();
+            let _permit = open_files.as_deref().map(OpenFileLimit::acquire);
+            for entry_result in fs::read_dir(path)? {
+                let entry = match entry_result {
+                    Ok(entry) => entry,
+                    Err(err) => {
+                        self.recover(path.to_path_buf(), err.into())?;
+                        continue;
+                    }
+                };
+                let name = entry.file_name();
+                let name_bytes = name_bytes(&name);
+
+                // Skip excluded files and directories
+                let entry_path = entry.path();
+                if self.options.is_skipped(&name_bytes, &self.relative_path(&entry_path)) {
+                    continue;
+                }
+
+                // Classify with the entry's own file type, which never follows
+                // symlinks, so a link to a file or directory stays a symlink
+                pending.push(PendingEntry {
+                    file_type: entry.file_type(),
+                    name: self.options.entry_name(name_bytes),
+                    path: entry_path,
+                });
+            }
+        }
+
+        #[cfg(feature = "parallel")]
+        let entries = if self.parallel {
+            self.read_entries_parallel(pending)?
+        } else {
+            self.read_entries(pending)?
+        };
+        #[cfg(not(feature = "parallel"))]
+        let entries = self.read_entries(pending)?;
+
+        let mut dir = Directory::from_entries(entries)?;
+        dir.path = Some(path.to_path_buf());
+        Ok(dir)
+    }
+
+    fn read_entries(&mut self, pending: Vec<PendingEntry>) -> Result<Vec<DirectoryEntry>, SwhidError> {
+        let mut entries = Vec::with_capacity(pending.len());
+        for entry in pending {
+            let result = entry
+                .file_type
+                .map_err(SwhidError::from)
+                .and_then(|file_type| self.read_entry(&entry.path, file_type, entry.name));
+            match result {
+                Ok(Some(dir_entry)) => entries.push(dir_entry),
+                Ok(None) => {}
+                Err(err) => self.recover(entry.path, err)?,
+            }
+        }
+        Ok(entries)
+    }
+
+    /// Hash entries concurrently; on failure, the error of the first failing
+    /// entry in directory order is returned, as in sequential traversal
+    #[cfg(feature = "parallel")]
+    fn read_entries_parallel(&self, pending: Vec<PendingEntry>) -> Result<Vec<DirectoryEntry>, SwhidError> {
+        use rayon::prelude::*;
+
+        let (options, root, ancestors, open_files) = (self.options, self.root.as_path(), &self.ancestors, &self.open_files);
+        let results: Vec<_> = pending
+            .into_par_iter()
+            .map(|entry| {
+                let mut walker = DiskWalker::new(options, root, None);
+                walker.parallel = true;
+                walker.ancestors = ancestors.clone();
+                walker.open_files = open_files.clone();
+                entry
+                    .file_type
+                    .map_err(SwhidError::from)
+                    .and_then(|file_type| walker.read_entry(&entry.path, file_type, entry.name))
+                    .map_err(|err| err.at_path(&entry.path))
+            })
+            .collect();
+        results.into_iter().filter_map(Result::transpose).collect()
+    }
+
+    /// Build the tree entry for a single path, or `None` to skip it
+    fn read_entry(
+        &mut self,
+        path: &Path,
+        file_type: fs::FileType,
+        name: Vec<u8>,
+    ) -> Result<Option<DirectoryEntry>, SwhidError> {
+        // Devices, FIFOs and sockets have no content, and must not be read
+        let special = !(file_type.is_symlink() || file_type.is_dir() || file_type.is_file());
+        if special {
+            match self.options.special_file_policy {
+                SpecialFilePolicy::Skip => return Ok(None),
+                SpecialFilePolicy::Error => {
+                    return Err(SwhidError::InvalidFilePath(
+                        path.to_path_buf(),
+                        "special files such as devices, FIFOs and sockets cannot be hashed".to_string(),
+                    ))
+                }
+                SpecialFilePolicy::HashAsEmpty => {}
+            }
+        }
+
+        let mut entry_type = if file_type.is_symlink() {
+            EntryType::Symlink
+        } else if file_type.is_dir() {
+            EntryType::Directory
+        } else {
+            EntryType::File
+        };
+
+        // A followed or materialized symlink is hashed as what it points to
+        let follow = self.options.follow_symlinks;
+        if entry_type == EntryType::Symlink && (follow || self.options.materialize_symlinks) {
+            let reason = match fs::metadata(path) {
+                Ok(metadata) if metadata.is_file() => {
+                    entry_type = EntryType::File;
+                    None
+                }
+                Ok(metadata) if metadata.is_dir() && follow => {
+                    if self.ancestors.contains(&fs::canonicalize(path)?) {
+                        Some("symlink cycle cannot be followed")
+                    } else {
+                        entry_type = EntryType::Directory;
+                        None
+                    }
+                }
+                Ok(_) if follow => Some("only symlinks to regular files and directories can be followed"),
+                Ok(_) => Some("only symlinks to regular files can be materialized"),
+                // Nothing to follow: a dangling symlink stays a symlink
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound && follow => None,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                    Some("dangling symlink cannot be materialized")
+                }
+                Err(e) => return Err(e.into()),
+            };
+            if let Some(reason) = reason {
+                return Err(SwhidError::InvalidFilePath(path.to_path_buf(), reason.to_string()));
+            }
+        }
+
+        // A nested Git checkout is recorded as the commit it has checked out
+        if entry_type == EntryType::Directory
+            && self.options.submodules
+            && fs::symlink_metadata(path.join(".git")).is_ok()
+        {
+            entry_type = EntryType::Submodule;
         }
+
+        let permissions = match entry_type {
+            EntryType::Directory => Permissions::Directory,
+            EntryType::Submodule => Permissions::Submodule,
+            EntryType::Symlink => Permissions::Symlink,
+            EntryType::File if special => Permissions::File,
+            // Follows followed and materialized symlinks; other files are not symlinks
+            EntryType::File => Permissions::from_file_metadata(&fs::metadata(path)?),
+        };
+
+        // Compute the target hash
+        let target = match entry_type {
+            EntryType::File if special => *Content::from_data_with(Vec::new(), self.options.hash_algorithm).sha1_git(),
+            EntryType::File => {
+                let _permit = self.open_files.as_deref().map(OpenFileLimit::acquire);
+                self.options.file_hash(path)?
+            }
+            EntryType::Symlink => {
+                match self.options.broken_symlink_policy {
+                    BrokenSymlinkPolicy::Hash => {}
+                    BrokenSymlinkPolicy::Skip if !path.exists() => return Ok(None),
+                    BrokenSymlinkPolicy::Error if !path.exists() => {
+                        return Err(SwhidError::InvalidFilePath(
+                            path.to_path_buf(),
+                            "symlink target does not exist".to_string(),
+                        ))
+                    }
+                    BrokenSymlinkPolicy::Skip | BrokenSymlinkPolicy::Error => {}
+                }
+                // The symlink target is hashed as content
+                let target = fs::read_link(path)?;
+                *Content::from_data_with(name_bytes(target.as_os_str()), self.options.hash_algorithm).sha1_git()
+            }
+            EntryType::Directory => {
+                let mut child_dir = self.read_directory(path)?;
+                child_dir.compute_hash_with(self.options.hash_algorithm)
+            }
+            EntryType::Submodule => {
+                let _permit = self.open_files.as_deref().map(OpenFileLimit::acquire);
+                resolve_head(&find_git_dir(path)?)?
+            }
+        };
+
+        let object_type = match entry_type {
+            EntryType::Directory => ObjectType::Directory,
+            EntryType::File | EntryType::Symlink => ObjectType::Content,
+            EntryType::Submodule => ObjectType::Revision,
+        };
+        self.visit(path, entry_type, permissions, Swhid::new(object_type, target));
+
+        Ok(Some(DirectoryEntry::new(name, entry_type, permissions, target)))
     }
-    false
 }
 
-/// Simple shell pattern matching for common patterns
+/// Modification time of a path (not following symlinks) as a Unix timestamp
+fn modification_time(path: &Path) -> Option<i64> {
+    let modified = fs::symlink_metadata(path).ok()?.modified().ok()?;
+    match modified.duration_since(UNIX_EPOCH) {
+        Ok(after) => i64::try_from(after.as_secs()).ok(),
+        Err(before) => i64::try_from(before.duration().as_secs()).ok().map(|secs| -secs),
+    }
+}
+
+/// Check if an entry is excluded by any of `patterns`
+///
+/// Patterns containing a `/` are matched against the entry's path relative
+/// to the traversal root (e.g. `build/*`); other patterns are matched against
+/// the entry's name alone (e.g. `*.tmp`).
+fn should_exclude_str(name: &str, relative_path: &str, patterns: &[String]) -> bool {
+    patterns.iter().any(|pattern| {
+        let subject = if pattern.contains('/') { relative_path } else { name };
+        matches_pattern(subject, pattern)
+    })
+}
+
+/// Shell-style pattern matching, like Python's `fnmatch.fnmatchcase`
+///
+/// `*` matches any run of characters, including `/`; `?` matches any single
+/// character; `[...]` matches one character of a class, which may contain
+/// ranges (`a-z`) and be negated with a leading `!`. An unterminated `[`
+/// matches itself.
 fn matches_pattern(name: &str, pattern: &str) -> bool {
-    // Exact match
-    if name == pattern {
-        return true;
-    }
-    
-    // Handle basic wildcard patterns
-    if pattern.starts_with('*') && pattern.ends_with('*') {
-        // *pattern*
-        let inner = &pattern[1..pattern.len()-1];
-        return name.contains(inner);
-    } else if pattern.starts_with('*') {
-        // *pattern
-        let suffix = &pattern[1..];
-        return name.ends_with(suffix);
-    } else if pattern.ends_with('*') {
-        // pattern*
-        let prefix = &pattern[..pattern.len()-1];
-        return name.starts_with(prefix);
-    }
-    
-    false
+    let name: Vec<char> = name.chars().collect();
+    let pattern: Vec<char> = pattern.chars().collect();
+    let (mut n, mut p) = (0, 0);
+    // Pattern position after the last `*`, and the name position it resumes at
+    let mut star: Option<(usize, usize)> = None;
+
+    while n < name.len() {
+        let next = match pattern.get(p) {
+            Some('*') => {
+                star = Some((p + 1, n));
+                p += 1;
+                continue;
+            }
+            Some('?') => Some(p + 1),
+            Some('[') => match match_class(&pattern[p..], name[n]) {
+                Some((true, len)) => Some(p + len),
+                Some((false, _)) => None,
+                None => (name[n] == '[').then_some(p + 1),
+            },
+            Some(&c) => (c == name[n]).then_some(p + 1),
+            None => None,
+        };
+
+        match (next, star) {
+            (Some(next), _) => {
+                p = next;
+                n += 1;
+            }
+            // Let the last `*` absorb one more character and retry
+            (None, Some((star_p, star_n))) => {
+                star = Some((star_p, star_n + 1));
+                p = star_p;
+                n = star_n + 1;
+            }
+            (None, None) => return false,
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Match `c` against the character class at the start of `pattern`
+///
+/// Returns whether it matched and the length of the class, or `None` if the
+/// `[` is not terminated and must be taken literally.
+fn match_class(pattern: &[char], c: char) -> Option<(bool, usize)> {
+    let mut start = 1;
+    let negated = pattern.get(start) == Some(&'!');
+    if negated {
+        start += 1;
+    }
+    // A `]` first in the class is a member, not its end
+    let search_from = if pattern.get(start) == Some(&']') { start + 1 } else { start };
+    let end = search_from + pattern.get(search_from..)?.iter().position(|&ch| ch == ']')?;
+
+    let items = &pattern[start..end];
+    let mut matched = false;
+    let mut i = 0;
+    while i < items.len() {
+        if i + 2 < items.len() && items[i + 1] == '-' {
+            matched |= items[i] <= c && c <= items[i + 2];
+            i += 3;
+        } else {
+            matched |= items[i] == c;
+            i += 1;
+        }
+    }
+    Some((matched != negated, end + 1))
 }
 
 #[cfg(test)]
@@ -296,7 +1218,7 @@ mod tests {
         let swhid = dir.swhid();
         
         assert_eq!(swhid.object_type(), ObjectType::Directory);
-        assert_eq!(swhid.hash().len(), 20);
+        assert_eq!(swhid.digest().len(), 20);
     }
 
     #[test]
@@ -352,6 +1274,66 @@ mod tests {
         assert_eq!(entries[0].name, b"include.txt");
     }
 
+    #[test]
+    fn test_directory_in_memory_builder_matches_disk() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::create_dir(temp_dir.path().join("src")).unwrap();
+        fs::write(temp_dir.path().join("src").join("main.rs"), b"fn main() {}\n").unwrap();
+        fs::write(temp_dir.path().join("src.rs"), b"mod src;\n").unwrap();
+        fs::write(temp_dir.path().join("README.md"), b"readme\n").unwrap();
+
+        let cnt = |data: &[u8]| Content::from_data(data.to_vec()).swhid();
+        let mut src = Directory::new();
+        src.add_file(b"main.rs".to_vec(), &cnt(b"fn main() {}\n"), false).unwrap();
+
+        // Added out of tree order on purpose; `src` sorts after `src.rs`
+        let mut root = Directory::new();
+        root.add_subdirectory(b"src".to_vec(), &src.swhid()).unwrap();
+        root.add_file(b"src.rs".to_vec(), &cnt(b"mod src;\n"), false).unwrap();
+        root.add_file(b"README.md".to_vec(), &cnt(b"readme\n"), false).unwrap();
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::{symlink, PermissionsExt};
+            let script = temp_dir.path().join("run.sh");
+            fs::write(&script, b"#!/bin/sh\n").unwrap();
+            fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+            symlink("README.md", temp_dir.path().join("link")).unwrap();
+
+            root.add_symlink(b"link".to_vec(), b"README.md").unwrap();
+            root.add_file(b"run.sh".to_vec(), &cnt(b"#!/bin/sh\n"), true).unwrap();
+        }
+
+        let mut disk = Directory::from_disk(temp_dir.path(), &[]).unwrap();
+        assert_eq!(root.swhid(), disk.swhid());
+        let names: Vec<_> = root.entries().iter().map(|e| e.name.clone()).collect();
+        let disk_names: Vec<_> = disk.entries().iter().map(|e| e.name.clone()).collect();
+        assert_eq!(names, disk_names);
+    }
+
+    #[test]
+    fn test_directory_in_memory_builder_errors() {
+        let cnt = Content::from_data(b"x".to_vec()).swhid();
+        let mut dir = Directory::new();
+        let before = dir.swhid();
+
+        assert!(matches!(dir.add_subdirectory(b"a".to_vec(), &cnt), Err(SwhidError::InvalidInput(_))));
+        assert!(matches!(dir.add_file(b"a".to_vec(), &before, false), Err(SwhidError::InvalidInput(_))));
+        for name in [&b""[..], b".", b"..", b"a/b", b"a\0b"] {
+            assert!(matches!(dir.add_file(name.to_vec(), &cnt, false), Err(SwhidError::InvalidPath(_))));
+        }
+        #[cfg(feature = "v2")]
+        {
+            let v2 = Swhid::new_v2(ObjectType::Content, [1; 32]);
+            assert!(matches!(dir.add_file(b"a".to_vec(), &v2, false), Err(SwhidError::InvalidVersion(_))));
+        }
+
+        dir.add_file(b"a".to_vec(), &cnt, false).unwrap();
+        assert_ne!(dir.swhid(), before);
+        assert!(matches!(dir.add_subdirectory(b"a".to_vec(), &before), Err(SwhidError::DuplicateEntry(_))));
+        assert!(matches!(dir.add_symlink(b"a".to_vec(), b"target"), Err(SwhidError::DuplicateEntry(_))));
+    }
+
     #[test]
     fn test_directory_permissions() {
         let temp_dir = TempDir::new().unwrap();
@@ -380,4 +1362,444 @@ mod tests {
             assert_eq!(exec_entry.permissions, Permissions::Executable);
         }
     }
+
+    #[cfg(not(unix))]
+    #[test]
+    fn test_directory_permissions_without_exec_bits() {
+        // Without executable bits, even a Windows executable is a regular file
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join("tool.exe"), b"MZ").unwrap();
+
+        let dir = Directory::from_disk(temp_dir.path(), &[]).unwrap();
+        assert_eq!(dir.entries()[0].permissions, Permissions::File);
+    }
+
+    #[test]
+    fn test_name_bytes_utf8() {
+        assert_eq!(name_bytes(OsStr::new("caf\u{e9}.txt")), "caf\u{e9}.txt".as_bytes());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_directory_non_utf8_names() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let temp_dir = TempDir::new().unwrap();
+        let latin1 = OsStr::from_bytes(b"caf\xe9.txt");
+        fs::write(temp_dir.path().join(latin1), b"hello\n").unwrap();
+        fs::write(temp_dir.path().join("caf\u{e9}.txt"), b"hi\n").unwrap();
+
+        let mut dir = Directory::from_disk(temp_dir.path(), &[]).unwrap();
+        assert_eq!(dir.entries()[1].name, b"caf\xe9.txt");
+        // As computed by `git write-tree`
+        assert_eq!(hex::encode(dir.compute_hash()), "37d98dc6f78d31f91c15676c00d504e425b0e9a7");
+
+        // Distinct invalid names no longer collide once decoded
+        fs::write(temp_dir.path().join(OsStr::from_bytes(b"\xff")), b"").unwrap();
+        fs::write(temp_dir.path().join(OsStr::from_bytes(b"\xfe")), b"").unwrap();
+        assert_eq!(Directory::from_disk(temp_dir.path(), &[]).unwrap().entries().len(), 4);
+
+        // Symlink targets are hashed as their raw bytes too
+        std::os::unix::fs::symlink(latin1, temp_dir.path().join("link")).unwrap();
+        let dir = Directory::from_disk(temp_dir.path(), &[]).unwrap();
+        let link = dir.entries().iter().find(|entry| entry.name == b"link").unwrap();
+        assert_eq!(link.target, *Content::from_data(b"caf\xe9.txt".to_vec()).sha1_git());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_directory_symlink_classification() {
+        use std::os::unix::fs::symlink;
+
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join("target.txt"), b"target content").unwrap();
+        fs::create_dir(temp_dir.path().join("target_dir")).unwrap();
+        symlink("target.txt", temp_dir.path().join("file_link")).unwrap();
+        symlink("target_dir", temp_dir.path().join("dir_link")).unwrap();
+
+        let dir = Directory::from_disk(temp_dir.path(), &[]).unwrap();
+        let entries = dir.entries();
+
+        let file_link = entries.iter().find(|e| e.name == b"file_link").unwrap();
+        assert_eq!(file_link.entry_type, EntryType::Symlink);
+        assert_eq!(file_link.permissions, Permissions::Symlink);
+        assert_eq!(file_link.target, *Content::from_data(b"target.txt".to_vec()).sha1_git());
+
+        let dir_link = entries.iter().find(|e| e.name == b"dir_link").unwrap();
+        assert_eq!(dir_link.entry_type, EntryType::Symlink);
+        assert_eq!(dir_link.permissions, Permissions::Symlink);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_directory_from_disk_best_effort() {
+        use std::os::unix::net::UnixListener;
+
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join("readable.txt"), b"readable").unwrap();
+        fs::create_dir(temp_dir.path().join("sub")).unwrap();
+        fs::write(temp_dir.path().join("sub").join("nested.txt"), b"nested").unwrap();
+        // Sockets cannot be hashed by default, even when running as root
+        let socket_path = temp_dir.path().join("sub").join("unreadable.sock");
+        let _listener = UnixListener::bind(&socket_path).unwrap();
+
+        // Without best effort, the error names the deep entry that failed
+        match Directory::from_disk(temp_dir.path(), &[]) {
+            Err(SwhidError::InvalidFilePath(p, _)) => assert_eq!(p, socket_path),
+            other => panic!("unexpected result: {:?}", other.map(|_| ())),
+        }
+
+        let mut errors = Vec::new();
+        let mut dir = Directory::from_disk_best_effort(temp_dir.path(), &[], &mut errors);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].0, socket_path);
+        assert!(matches!(errors[0].1, SwhidError::InvalidFilePath(..)));
+
+        // The partial result is the tree with the unreadable entry left out
+        fs::remove_file(&socket_path).unwrap();
+        let mut expected = Directory::from_disk(temp_dir.path(), &[]).unwrap();
+        assert_eq!(dir.swhid(), expected.swhid());
+
+        // Following a symlink loop is an I/O error, even when running as root
+        let loop_path = temp_dir.path().join("sub").join("loop");
+        std::os::unix::fs::symlink("loop", &loop_path).unwrap();
+        let options = SwhidComputer::new().with_follow_symlinks(true);
+        match Directory::read_disk(temp_dir.path(), &options) {
+            Err(err @ SwhidError::PathIo(..)) => {
+                assert!(matches!(err, SwhidError::PathIo(ref p, _) if p == &loop_path));
+                assert!(std::error::Error::source(&err).is_some());
+            }
+            other => panic!("unexpected result: {:?}", other.map(|_| ())),
+        }
+        let mut errors = Vec::new();
+        let mut dir = Directory::read_disk_best_effort(temp_dir.path(), &options, &mut errors);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].0, loop_path);
+        assert!(matches!(errors[0].1, SwhidError::Io(_)));
+        assert_eq!(dir.swhid(), expected.swhid());
+    }
+
+    #[test]
+    fn test_directory_tree_object_and_cat_file() {
+        let readme = Content::from_data(b"readme\n".to_vec()).swhid();
+        let mut sub = Directory::new();
+        sub.add_file(b"lib.rs".to_vec(), &readme, false).unwrap();
+        let mut dir = Directory::new();
+        dir.add_file(b"run.sh".to_vec(), &readme, true).unwrap();
+        dir.add_subdirectory(b"src".to_vec(), &sub.swhid()).unwrap();
+        dir.add_symlink(b"link".to_vec(), b"run.sh").unwrap();
+        dir.add_file(b"README.md".to_vec(), &readme, false).unwrap();
+
+        assert_eq!(hash_git_object("tree", &dir.tree_object()), dir.compute_hash());
+        assert_eq!(
+            dir.cat_file(),
+            format!(
+                "100644 blob {readme}\tREADME.md\n\
+                 120000 blob {link}\tlink\n\
+                 100755 blob {readme}\trun.sh\n\
+                 040000 tree {sub}\tsrc\n",
+                readme = hex::encode(readme.digest()),
+                link = hex::encode(Content::from_data(b"run.sh".to_vec()).sha1_git()),
+                sub = hex::encode(sub.swhid().digest()),
+            )
+        );
+        assert_eq!(Directory::new().cat_file(), "");
+    }
+
+    #[test]
+    fn test_directory_from_tree_objects() {
+        let blob = hash_git_object("blob", b"hello\n");
+        let subtree = Directory::from_entries(vec![DirectoryEntry::new(
+            b"hello.txt".to_vec(),
+            EntryType::File,
+            Permissions::File,
+            blob,
+        )])
+        .unwrap();
+        let sub_id = hash_git_object("tree", &subtree.tree_object());
+        let mut root = Directory::from_entries(vec![
+            DirectoryEntry::new(b"a".to_vec(), EntryType::File, Permissions::Executable, blob),
+            DirectoryEntry::new(b"sub".to_vec(), EntryType::Directory, Permissions::Directory, sub_id),
+        ])
+        .unwrap();
+        let root_id = root.compute_hash();
+
+        let mut objects = std::collections::HashMap::new();
+        objects.insert(sub_id, subtree.tree_object());
+        objects.insert(root_id, root.tree_object());
+
+        let swhid = Directory::from_tree_objects(|id| objects.get(&id).cloned(), root_id).unwrap();
+        assert_eq!(swhid, root.swhid());
+
+        // A tampered subtree no longer hashes to the id its parent records
+        let mut tampered = objects.clone();
+        let mut bytes = subtree.tree_object();
+        bytes[0] = b'1';
+        bytes[5] = b'5';
+        tampered.insert(sub_id, bytes);
+        assert!(matches!(
+            Directory::from_tree_objects(|id| tampered.get(&id).cloned(), root_id),
+            Err(SwhidError::InvalidHash(_))
+        ));
+
+        // A missing subtree cannot be verified
+        objects.remove(&sub_id);
+        assert!(matches!(
+            Directory::from_tree_objects(|id| objects.get(&id).cloned(), root_id),
+            Err(SwhidError::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    fn test_directory_submodule() {
+        let hello = Content::from_data(b"hello\n".to_vec()).swhid();
+        let commit = Swhid::from_string("swh:1:rev:1e048ab552fec7fed3c552773ce146bcb5e57254").unwrap();
+        let mut dir = Directory::new();
+        dir.add_file(b"lib.c".to_vec(), &hello, false).unwrap();
+        dir.add_submodule(b"lib".to_vec(), &commit).unwrap();
+        assert!(matches!(
+            dir.add_submodule(b"other".to_vec(), &hello),
+            Err(SwhidError::InvalidInput(_))
+        ));
+
+        // As computed by `git mktree`: gitlinks sort as files, before `lib.c`
+        assert_eq!(dir.swhid().to_string(), "swh:1:dir:5e04b5f76ce6265cb000eaea599a5eca9f2e3677");
+        assert_eq!(
+            dir.cat_file(),
+            format!(
+                "160000 commit 1e048ab552fec7fed3c552773ce146bcb5e57254\tlib\n\
+                 100644 blob {}\tlib.c\n",
+                hex::encode(hello.digest())
+            )
+        );
+
+        // Submodule commits are not looked up
+        let tree = dir.tree_object();
+        let swhid = Directory::from_tree_objects(|_| Some(tree.clone()), dir.compute_hash()).unwrap();
+        assert_eq!(swhid, dir.swhid());
+    }
+
+    #[test]
+    fn test_directory_submodule_from_disk() {
+        let commit = "1e048ab552fec7fed3c552773ce146bcb5e57254";
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join("lib.c"), b"hello\n").unwrap();
+        let git_dir = temp_dir.path().join("lib").join(".git");
+        fs::create_dir_all(git_dir.join("refs").join("heads")).unwrap();
+        fs::write(git_dir.join("HEAD"), "ref: refs/heads/main\n").unwrap();
+        fs::write(git_dir.join("refs").join("heads").join("main"), format!("{}\n", commit)).unwrap();
+        fs::write(temp_dir.path().join("lib").join("lib.h"), b"int f();\n").unwrap();
+
+        let computer = SwhidComputer::new().with_submodules(true);
+        assert_eq!(
+            computer.compute_swhid(temp_dir.path()).unwrap().to_string(),
+            "swh:1:dir:5e04b5f76ce6265cb000eaea599a5eca9f2e3677"
+        );
+        // Otherwise the checkout is hashed as a plain directory
+        assert_ne!(
+            SwhidComputer::new().compute_swhid(temp_dir.path()).unwrap().to_string(),
+            "swh:1:dir:5e04b5f76ce6265cb000eaea599a5eca9f2e3677"
+        );
+
+        // Submodules usually have a `.git` file, with a detached `HEAD`
+        fs::remove_dir_all(&git_dir).unwrap();
+        let modules = temp_dir.path().join("modules").join("lib");
+        fs::create_dir_all(&modules).unwrap();
+        fs::write(modules.join("HEAD"), format!("{}\n", commit)).unwrap();
+        fs::write(&git_dir, "gitdir: ../modules/lib\n").unwrap();
+        let computer = computer.with_exclude_patterns(&["modules".to_string()]);
+        assert_eq!(
+            computer.compute_swhid(temp_dir.path()).unwrap().to_string(),
+            "swh:1:dir:5e04b5f76ce6265cb000eaea599a5eca9f2e3677"
+        );
+
+        // A checkout without any commit has nothing to point to
+        fs::write(modules.join("HEAD"), "ref: refs/heads/main\n").unwrap();
+        assert!(matches!(computer.compute_swhid(temp_dir.path()), Err(SwhidError::Git(_))));
+    }
+
+    #[test]
+    fn test_directory_from_map() {
+        let mut map = BTreeMap::new();
+        map.insert(PathBuf::from("README"), b"readme\n".to_vec());
+        map.insert(PathBuf::from("src/lib.rs"), b"lib\n".to_vec());
+        map.insert(PathBuf::from("src/nested/mod.rs"), b"mod\n".to_vec());
+        let swhid = Directory::from_map(map).unwrap();
+
+        let temp_dir = TempDir::new().unwrap();
+        fs::create_dir_all(temp_dir.path().join("src/nested")).unwrap();
+        fs::write(temp_dir.path().join("README"), b"readme\n").unwrap();
+        fs::write(temp_dir.path().join("src/lib.rs"), b"lib\n").unwrap();
+        fs::write(temp_dir.path().join("src/nested/mod.rs"), b"mod\n").unwrap();
+        assert_eq!(swhid, Directory::from_disk(temp_dir.path(), &[]).unwrap().swhid());
+
+        // The empty map is the empty tree
+        assert_eq!(
+            Directory::from_map(BTreeMap::new()).unwrap().to_string(),
+            "swh:1:dir:4b825dc642cb6eb9a060e54bf8d69288fbee4904"
+        );
+    }
+
+    #[test]
+    fn test_directory_from_map_conflicts() {
+        let mut map = BTreeMap::new();
+        map.insert(PathBuf::from("a"), b"file".to_vec());
+        map.insert(PathBuf::from("a/b"), b"nested".to_vec());
+        assert!(matches!(Directory::from_map(map), Err(SwhidError::InvalidPath(_))));
+
+        let mut map = BTreeMap::new();
+        map.insert(PathBuf::from("a/b/c"), b"nested".to_vec());
+        map.insert(PathBuf::from("a/b"), b"file".to_vec());
+        assert!(matches!(Directory::from_map(map), Err(SwhidError::InvalidPath(_))));
+
+        let mut map = BTreeMap::new();
+        map.insert(PathBuf::from("../escape"), b"".to_vec());
+        assert!(matches!(Directory::from_map(map), Err(SwhidError::InvalidPath(_))));
+    }
+
+    #[test]
+    fn test_directory_into_entries() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join("b.txt"), b"b").unwrap();
+        fs::write(temp_dir.path().join("a.txt"), b"a").unwrap();
+        fs::create_dir(temp_dir.path().join("sub")).unwrap();
+
+        let mut dir = Directory::from_disk(temp_dir.path(), &[]).unwrap();
+        let swhid = dir.swhid();
+        let entries = dir.clone().into_entries();
+        assert_eq!(entries.len(), 3);
+
+        // The entries rebuild the same tree
+        let mut rebuilt = Directory::from_entries(entries).unwrap();
+        assert_eq!(rebuilt.swhid(), swhid);
+
+        let names: Vec<Vec<u8>> = dir.into_iter().map(|entry| entry.name).collect();
+        assert_eq!(names, vec![b"a.txt".to_vec(), b"b.txt".to_vec(), b"sub".to_vec()]);
+    }
+
+    #[test]
+    fn test_matches_pattern() {
+        assert!(matches_pattern("file.tmp", "*.tmp"));
+        assert!(!matches_pattern("file.tmp.txt", "*.tmp"));
+        assert!(matches_pattern("file.tmp", "file.tmp"));
+        assert!(matches_pattern("a1.txt", "a?.txt"));
+        assert!(!matches_pattern("a12.txt", "a?.txt"));
+        assert!(matches_pattern("data_7.csv", "data_[0-9].csv"));
+        assert!(!matches_pattern("data_x.csv", "data_[0-9].csv"));
+        assert!(matches_pattern("data_x.csv", "data_[!0-9].csv"));
+        assert!(matches_pattern("a]b", "a[]]b"));
+        assert!(matches_pattern("a[b", "a[b"));
+        assert!(matches_pattern("abcbcd", "a*bcd"));
+        assert!(matches_pattern("", "*"));
+        assert!(!matches_pattern("", "?"));
+        // As in fnmatch, `*` also matches `/`
+        assert!(matches_pattern("build/out/app", "build/*"));
+    }
+
+    #[test]
+    fn test_open_file_limit() {
+        let limit = OpenFileLimit::new(2);
+        std::thread::scope(|scope| {
+            for _ in 0..8 {
+                scope.spawn(|| {
+                    let _permit = limit.acquire();
+                    std::thread::sleep(std::time::Duration::from_millis(10));
+                });
+            }
+        });
+        // Eight threads held a slot for a while, but never more than two at once
+        assert_eq!(limit.peak(), 2);
+        assert_eq!(OpenFileLimit::new(0).acquire().0.max, 1);
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_parallel_walk_bounds_open_files() {
+        let temp_dir = TempDir::new().unwrap();
+        for d in 0..8 {
+            let sub = temp_dir.path().join(format!("dir_{}", d));
+            fs::create_dir_all(sub.join("nested")).unwrap();
+            for f in 0..50 {
+                fs::write(sub.join(format!("file_{}", f)), format!("{} {}", d, f)).unwrap();
+            }
+            fs::write(sub.join("nested").join("leaf"), format!("leaf {}", d)).unwrap();
+        }
+        let expected = SwhidComputer::new().compute_directory_swhid(temp_dir.path()).unwrap();
+
+        let options = SwhidComputer::new().with_parallelism(8).with_max_concurrency(2);
+        let pool = options.thread_pool().unwrap().unwrap();
+        assert_eq!(pool.current_num_threads(), 8);
+        let (dir, open_files) = pool.install(|| {
+            let mut walker = DiskWalker::new(&options, temp_dir.path(), None);
+            walker.parallel = true;
+            (walker.read_directory(temp_dir.path()), walker.open_files.clone().unwrap())
+        });
+        assert_eq!(dir.unwrap().swhid(), expected);
+
+        let peak = open_files.peak();
+        assert!((1..=2).contains(&peak), "{} files open at once", peak);
+    }
+
+    #[test]
+    fn test_directory_exclude_changes_hash() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join("keep.txt"), b"keep").unwrap();
+        fs::write(temp_dir.path().join("skip1.tmp"), b"skip").unwrap();
+        let mut full = Directory::from_disk(temp_dir.path(), &[]).unwrap();
+
+        let clean = TempDir::new().unwrap();
+        fs::write(clean.path().join("keep.txt"), b"keep").unwrap();
+        let mut expected = Directory::from_disk(clean.path(), &[]).unwrap();
+
+        for pattern in ["*.tmp", "skip?.tmp", "skip[0-9].*"] {
+            let mut excluded = Directory::from_disk(temp_dir.path(), &[pattern.to_string()]).unwrap();
+            assert_eq!(excluded.swhid(), expected.swhid(), "{}", pattern);
+            assert_ne!(excluded.swhid(), full.swhid(), "{}", pattern);
+        }
+    }
+
+    #[test]
+    fn test_directory_exclude_relative_path() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::create_dir_all(temp_dir.path().join("build/out")).unwrap();
+        fs::create_dir_all(temp_dir.path().join("src/build")).unwrap();
+        fs::write(temp_dir.path().join("build/out/app"), b"app").unwrap();
+        fs::write(temp_dir.path().join("build/log"), b"log").unwrap();
+        fs::write(temp_dir.path().join("src/build/gen.rs"), b"gen").unwrap();
+
+        // Only the top-level build directory's contents are excluded
+        let dir = Directory::from_disk(temp_dir.path(), &["build/*".to_string()]).unwrap();
+        let names: Vec<&[u8]> = dir.entries().iter().map(|e| e.name.as_slice()).collect();
+        assert_eq!(names, vec![&b"build"[..], &b"src"[..]]);
+
+        let mut build = Directory::from_disk(temp_dir.path().join("build"), &["*".to_string()]).unwrap();
+        assert_eq!(dir.entries()[0].target, build.compute_hash());
+
+        let mut src = Directory::from_disk(temp_dir.path().join("src"), &[]).unwrap();
+        assert_eq!(dir.entries()[1].target, src.compute_hash());
+    }
+
+    #[test]
+    fn test_directory_from_entries_duplicate_across_types() {
+        // In tree order, `a-b` sorts between the file `a` and the directory `a`
+        let entries = vec![
+            DirectoryEntry::new(b"a".to_vec(), EntryType::File, Permissions::File, [0; 20]),
+            DirectoryEntry::new(b"a-b".to_vec(), EntryType::File, Permissions::File, [0; 20]),
+            DirectoryEntry::new(b"a".to_vec(), EntryType::Directory, Permissions::Directory, [0; 20]),
+        ];
+        assert!(matches!(
+            Directory::from_entries(entries),
+            Err(SwhidError::DuplicateEntry(_))
+        ));
+    }
+
+    #[test]
+    fn test_permissions_git_mode_str() {
+        assert_eq!(Permissions::File.git_mode_str(), "100644");
+        assert_eq!(Permissions::Executable.git_mode_str(), "100755");
+        assert_eq!(Permissions::Symlink.git_mode_str(), "120000");
+        // Git omits the leading zero for trees
+        assert_eq!(Permissions::Directory.git_mode_str(), "40000");
+        assert_ne!(Permissions::Directory.git_mode_str(), "040000");
+    }
 } 
\ No newline at end of file
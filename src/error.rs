@@ -1,8 +1,11 @@
 use std::io;
+use std::path::{Path, PathBuf};
 
 #[derive(Debug)]
 pub enum SwhidError {
     Io(io::Error),
+    /// An I/O error on a specific path, e.g. a file deep inside a directory
+    PathIo(PathBuf, io::Error),
     InvalidFormat(String),
     InvalidNamespace(String),
     InvalidVersion(String),
@@ -10,12 +13,23 @@ pub enum SwhidError {
     InvalidHash(String),
     InvalidHashLength(usize),
     InvalidPath(String),
+    /// A path on disk was rejected, with the reason
+    ///
+    /// The path is kept as-is rather than converted to a string, so that
+    /// non-UTF-8 paths are reported without loss.
+    InvalidFilePath(PathBuf, String),
     DuplicateEntry(String),
     UnsupportedOperation(String),
     InvalidQualifier(String),
     InvalidQualifierValue(String),
+    /// A SWHID embedded in a qualifier (e.g. `anchor`) failed to parse
+    InvalidQualifierSwhid(String, Box<SwhidError>),
     UnknownQualifier(String),
     InvalidInput(String),
+    /// A Git repository could not be read, or lacks the requested object
+    Git(String),
+    /// A request to the Software Heritage API failed, or got an unexpected reply
+    Api(String),
 }
 
 impl From<io::Error> for SwhidError {
@@ -24,25 +38,92 @@ impl From<io::Error> for SwhidError {
     }
 }
 
+#[cfg(feature = "git")]
+impl From<git2::Error> for SwhidError {
+    fn from(err: git2::Error) -> Self {
+        SwhidError::Git(err.message().to_string())
+    }
+}
+
 impl std::fmt::Display for SwhidError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             SwhidError::Io(e) => write!(f, "I/O error: {}", e),
+            SwhidError::PathIo(p, e) => write!(f, "I/O error on {:?}: {}", p, e),
             SwhidError::InvalidFormat(s) => write!(f, "Invalid format: {}", s),
             SwhidError::InvalidNamespace(s) => write!(f, "Invalid namespace: {}", s),
             SwhidError::InvalidVersion(s) => write!(f, "Invalid version: {}", s),
             SwhidError::InvalidObjectType(s) => write!(f, "Invalid object type: {}", s),
             SwhidError::InvalidHash(s) => write!(f, "Invalid hash: {}", s),
-            SwhidError::InvalidHashLength(len) => write!(f, "Invalid hash length: {} (expected 40)", len),
+            SwhidError::InvalidHashLength(len) => write!(f, "Invalid hash length: {} hex digits", len),
             SwhidError::InvalidPath(s) => write!(f, "Invalid path: {}", s),
+            // Debug formatting escapes bytes that are not valid UTF-8
+            SwhidError::InvalidFilePath(p, s) => write!(f, "Invalid path {:?}: {}", p, s),
             SwhidError::DuplicateEntry(s) => write!(f, "Duplicate entry: {}", s),
             SwhidError::UnsupportedOperation(s) => write!(f, "Unsupported operation: {}", s),
             SwhidError::InvalidQualifier(s) => write!(f, "Invalid qualifier: {}", s),
             SwhidError::InvalidQualifierValue(s) => write!(f, "Invalid qualifier value: {}", s),
+            SwhidError::InvalidQualifierSwhid(q, e) => write!(f, "Invalid SWHID in {} qualifier: {}", q, e),
             SwhidError::UnknownQualifier(s) => write!(f, "Unknown qualifier: {}", s),
             SwhidError::InvalidInput(s) => write!(f, "Invalid input: {}", s),
+            SwhidError::Git(s) => write!(f, "Git error: {}", s),
+            SwhidError::Api(s) => write!(f, "Software Heritage API error: {}", s),
+        }
+    }
+}
+
+impl std::error::Error for SwhidError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SwhidError::Io(e) | SwhidError::PathIo(_, e) => Some(e),
+            SwhidError::InvalidQualifierSwhid(_, e) => Some(e.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl SwhidError {
+    /// Attach `path` to a bare I/O error; other errors are returned unchanged
+    pub(crate) fn at_path(self, path: &Path) -> Self {
+        match self {
+            SwhidError::Io(e) => SwhidError::PathIo(path.to_path_buf(), e),
+            other => other,
         }
     }
 }
 
-impl std::error::Error for SwhidError {} 
\ No newline at end of file
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_swhid_error_is_send_sync() {
+        // Required to box the error, e.g. as a clap value parser error
+        fn assert_send_sync<T: Send + Sync + 'static>() {}
+        assert_send_sync::<SwhidError>();
+
+        let boxed: Box<dyn std::error::Error + Send + Sync> =
+            SwhidError::InvalidObjectType("xyz".to_string()).into();
+        assert_eq!(boxed.to_string(), "Invalid object type: xyz");
+    }
+
+    #[test]
+    fn test_swhid_error_source() {
+        use std::error::Error;
+
+        let err = SwhidError::from(io::Error::new(io::ErrorKind::NotFound, "gone"));
+        assert_eq!(err.source().unwrap().to_string(), "gone");
+
+        let err = err.at_path(Path::new("deep/file.txt"));
+        assert!(matches!(err, SwhidError::PathIo(ref p, _) if p == Path::new("deep/file.txt")));
+        assert_eq!(err.to_string(), "I/O error on \"deep/file.txt\": gone");
+        assert_eq!(err.source().unwrap().to_string(), "gone");
+
+        let nested = SwhidError::InvalidQualifierSwhid(
+            "anchor".to_string(),
+            Box::new(SwhidError::InvalidHashLength(4)),
+        );
+        assert_eq!(nested.source().unwrap().to_string(), "Invalid hash length: 4 hex digits");
+        assert!(SwhidError::InvalidInput("x".to_string()).source().is_none());
+    }
+}
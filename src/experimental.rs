@@ -0,0 +1,30 @@
+//! Experimental helpers that do not produce standard SWHIDs
+//!
+//! Nothing in this module is covered by the SWHID specification. The API may
+//! change or disappear without notice.
+
+use crate::hash::hash_git_object;
+
+/// Hash `data` as a Git object of an arbitrary type
+///
+/// The result is `SHA1("<git_type> <len>\0" + data)`. Standard SWHIDs only
+/// use the Git types `blob`, `tree`, `commit` and `tag`; any other type
+/// string yields an identifier the archive will never produce. Meant for
+/// prototyping new object kinds.
+pub fn hash_with_type(git_type: &str, data: &[u8]) -> [u8; 20] {
+    hash_git_object(git_type, data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::sha1_git_hash;
+
+    #[test]
+    fn test_hash_with_type() {
+        let data = b"Hello, World!";
+        assert_eq!(hash_with_type("blob", data), sha1_git_hash(data));
+        assert_ne!(hash_with_type("research", data), sha1_git_hash(data));
+        assert_ne!(hash_with_type("research", data), hash_with_type("research2", data));
+    }
+}
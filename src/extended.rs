@@ -0,0 +1,424 @@
+//! Extended SWHIDs, for objects the Software Heritage archive uses internally
+//!
+//! Besides the five core object types, the archive identifies origins
+//! (`ori`) and raw extrinsic metadata objects (`emd`). These are not part of
+//! the SWHID specification: extended SWHIDs may name them, but qualified
+//! SWHIDs and the core types never do.
+
+use std::fmt;
+use std::str::FromStr;
+use crate::error::SwhidError;
+use crate::hash::{hash_git_object, sha1_hash};
+use crate::origin::Origin;
+use crate::revision::write_header;
+use crate::swhid::{ObjectType, Swhid};
+
+/// Object types of extended SWHIDs: the core types, origins and metadata
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ExtendedObjectType {
+    Content,               // "cnt"
+    Directory,             // "dir"
+    Revision,              // "rev"
+    Release,               // "rel"
+    Snapshot,              // "snp"
+    Origin,                // "ori" - Software origins
+    RawExtrinsicMetadata,  // "emd" - Metadata found outside the objects
+}
+
+impl ExtendedObjectType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ExtendedObjectType::Origin => "ori",
+            ExtendedObjectType::RawExtrinsicMetadata => "emd",
+            other => other.core().expect("core object type").as_str(),
+        }
+    }
+
+    /// Get the core object type, unless this is an extended one
+    pub fn core(&self) -> Option<ObjectType> {
+        match self {
+            ExtendedObjectType::Content => Some(ObjectType::Content),
+            ExtendedObjectType::Directory => Some(ObjectType::Directory),
+            ExtendedObjectType::Revision => Some(ObjectType::Revision),
+            ExtendedObjectType::Release => Some(ObjectType::Release),
+            ExtendedObjectType::Snapshot => Some(ObjectType::Snapshot),
+            ExtendedObjectType::Origin | ExtendedObjectType::RawExtrinsicMetadata => None,
+        }
+    }
+}
+
+impl From<ObjectType> for ExtendedObjectType {
+    fn from(object_type: ObjectType) -> Self {
+        match object_type {
+            ObjectType::Content => ExtendedObjectType::Content,
+            ObjectType::Directory => ExtendedObjectType::Directory,
+            ObjectType::Revision => ExtendedObjectType::Revision,
+            ObjectType::Release => ExtendedObjectType::Release,
+            ObjectType::Snapshot => ExtendedObjectType::Snapshot,
+        }
+    }
+}
+
+impl fmt::Display for ExtendedObjectType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for ExtendedObjectType {
+    type Err = SwhidError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ori" => Ok(ExtendedObjectType::Origin),
+            "emd" => Ok(ExtendedObjectType::RawExtrinsicMetadata),
+            _ => s.parse::<ObjectType>().map(ExtendedObjectType::from),
+        }
+    }
+}
+
+/// Extended Software Hash Identifier
+/// Format: swh:1:<object_type>:<40_character_hex_hash>, with `ori` and `emd` types too
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ExtendedSwhid {
+    object_type: ExtendedObjectType,
+    hash: [u8; 20],
+}
+
+impl ExtendedSwhid {
+    /// Create a new extended SWHID
+    pub fn new(object_type: ExtendedObjectType, hash: [u8; 20]) -> Self {
+        Self { object_type, hash }
+    }
+
+    /// Compute the SWHID of an origin
+    ///
+    /// The hash is the SHA-1 of the origin URL, without a Git object header.
+    pub fn from_origin(origin: &Origin) -> Self {
+        Self::new(ExtendedObjectType::Origin, sha1_hash(origin.as_str().as_bytes()))
+    }
+
+    /// Get the object type
+    pub fn object_type(&self) -> ExtendedObjectType {
+        self.object_type
+    }
+
+    /// Get the hash
+    pub fn hash(&self) -> &[u8; 20] {
+        &self.hash
+    }
+
+    /// Get the core SWHID, unless this names an origin or metadata
+    pub fn to_core(&self) -> Option<Swhid> {
+        self.object_type.core().map(|object_type| Swhid::new(object_type, self.hash))
+    }
+}
+
+impl TryFrom<Swhid> for ExtendedSwhid {
+    type Error = SwhidError;
+
+    /// Fails with `SwhidError::InvalidVersion` for a v2 SWHID, since extended
+    /// SWHIDs only exist in the v1 scheme
+    fn try_from(swhid: Swhid) -> Result<Self, Self::Error> {
+        Ok(Self::new(swhid.object_type().into(), *swhid.v1_hash()?))
+    }
+}
+
+impl TryFrom<ExtendedSwhid> for Swhid {
+    type Error = SwhidError;
+
+    /// Fails with `SwhidError::InvalidObjectType` for `ori` and `emd`
+    fn try_from(swhid: ExtendedSwhid) -> Result<Self, Self::Error> {
+        swhid
+            .to_core()
+            .ok_or_else(|| SwhidError::InvalidObjectType(swhid.object_type.to_string()))
+    }
+}
+
+impl fmt::Display for ExtendedSwhid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "swh:1:{}:{}", self.object_type, hex::encode(self.hash))
+    }
+}
+
+impl FromStr for ExtendedSwhid {
+    type Err = SwhidError;
+
+    /// Parse as [`Swhid`] does, also accepting the `ori` and `emd` types
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (object_type, hash) = match s.split(':').collect::<Vec<_>>()[..] {
+            ["swh", "1", object_type @ ("ori" | "emd"), hash] => (object_type, hash),
+            _ => return s.parse::<Swhid>()?.try_into(),
+        };
+        // Check the hash as for core SWHIDs
+        let core = Swhid::from_str(&format!("swh:1:cnt:{}", hash))?;
+        Ok(Self::new(object_type.parse()?, *core.v1_hash()?))
+    }
+}
+
+/// Authority a metadata object was found on, e.g. a forge or a registry
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MetadataAuthority {
+    /// Kind of authority: `deposit_client`, `forge` or `registry`
+    pub authority_type: String,
+    pub url: String,
+}
+
+/// Tool that fetched a metadata object
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MetadataFetcher {
+    pub name: String,
+    pub version: String,
+}
+
+/// Raw extrinsic metadata object: metadata about an object found outside of it
+///
+/// Context headers are written after the required ones, in the order
+/// `origin`, `visit`, `snapshot`, `release`, `revision`, `path`, then
+/// `directory`, as the archive hashes them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RawExtrinsicMetadata {
+    target: ExtendedSwhid,
+    discovery_date: i64,
+    authority: MetadataAuthority,
+    fetcher: MetadataFetcher,
+    format: String,
+    metadata: Vec<u8>,
+    origin: Option<Origin>,
+    visit: Option<u64>,
+    snapshot: Option<Swhid>,
+    release: Option<Swhid>,
+    revision: Option<Swhid>,
+    path: Option<Vec<u8>>,
+    directory: Option<Swhid>,
+}
+
+impl RawExtrinsicMetadata {
+    /// Create a metadata object about `target`, without context
+    ///
+    /// `discovery_date` is in seconds since the Unix epoch; the archive drops
+    /// fractions of seconds before hashing. `format` names the format of
+    /// `metadata`, e.g. `json` or `sword-v2-atom-codemeta`.
+    pub fn new(
+        target: ExtendedSwhid,
+        discovery_date: i64,
+        authority: MetadataAuthority,
+        fetcher: MetadataFetcher,
+        format: impl Into<String>,
+        metadata: Vec<u8>,
+    ) -> Self {
+        Self {
+            target,
+            discovery_date,
+            authority,
+            fetcher,
+            format: format.into(),
+            metadata,
+            origin: None,
+            visit: None,
+            snapshot: None,
+            release: None,
+            revision: None,
+            path: None,
+            directory: None,
+        }
+    }
+
+    /// Set the origin the target was found in
+    pub fn with_origin(mut self, origin: Origin) -> Self {
+        self.origin = Some(origin);
+        self
+    }
+
+    /// Set the number of the origin visit the target was found in
+    pub fn with_visit(mut self, visit: u64) -> Self {
+        self.visit = Some(visit);
+        self
+    }
+
+    /// Set the snapshot the target was found in
+    pub fn with_snapshot(mut self, snapshot: Swhid) -> Self {
+        self.snapshot = Some(snapshot);
+        self
+    }
+
+    /// Set the release the target was found in
+    pub fn with_release(mut self, release: Swhid) -> Self {
+        self.release = Some(release);
+        self
+    }
+
+    /// Set the revision the target was found in
+    pub fn with_revision(mut self, revision: Swhid) -> Self {
+        self.revision = Some(revision);
+        self
+    }
+
+    /// Set the path of the target in its directory
+    pub fn with_path(mut self, path: Vec<u8>) -> Self {
+        self.path = Some(path);
+        self
+    }
+
+    /// Set the directory the target was found in
+    pub fn with_directory(mut self, directory: Swhid) -> Self {
+        self.directory = Some(directory);
+        self
+    }
+
+    /// Get the object the metadata is about
+    pub fn target(&self) -> &ExtendedSwhid {
+        &self.target
+    }
+
+    /// Get the metadata, as fetched
+    pub fn metadata(&self) -> &[u8] {
+        &self.metadata
+    }
+
+    /// Serialize the manifest hashed for the metadata SWHID
+    ///
+    /// Headers are written as in Git objects, then a blank line and the
+    /// metadata as is.
+    pub fn git_object(&self) -> Vec<u8> {
+        let mut headers: Vec<(&[u8], Vec<u8>)> = vec![
+            (b"target", self.target.to_string().into_bytes()),
+            (b"discovery_date", self.discovery_date.to_string().into_bytes()),
+            (
+                b"authority",
+                format!("{} {}", self.authority.authority_type, self.authority.url).into_bytes(),
+            ),
+            (b"fetcher", format!("{} {}", self.fetcher.name, self.fetcher.version).into_bytes()),
+            (b"format", self.format.clone().into_bytes()),
+        ];
+        let swhid = |swhid: &Option<Swhid>| swhid.as_ref().map(|swhid| swhid.to_string().into_bytes());
+        let context = [
+            (&b"origin"[..], self.origin.as_ref().map(|origin| origin.as_str().as_bytes().to_vec())),
+            (b"visit", self.visit.map(|visit| visit.to_string().into_bytes())),
+            (b"snapshot", swhid(&self.snapshot)),
+            (b"release", swhid(&self.release)),
+            (b"revision", swhid(&self.revision)),
+            (b"path", self.path.clone()),
+            (b"directory", swhid(&self.directory)),
+        ];
+        headers.extend(context.into_iter().filter_map(|(key, value)| Some((key, value?))));
+
+        let mut data = Vec::new();
+        for (key, value) in headers {
+            write_header(&mut data, key, &value).expect("header keys are valid");
+        }
+        data.push(b'\n');
+        data.extend_from_slice(&self.metadata);
+        data
+    }
+
+    /// Compute the extended SWHID of this metadata object
+    pub fn swhid(&self) -> ExtendedSwhid {
+        ExtendedSwhid::new(
+            ExtendedObjectType::RawExtrinsicMetadata,
+            hash_git_object("raw_extrinsic_metadata", &self.git_object()),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn swhid(s: &str) -> Swhid {
+        s.parse().unwrap()
+    }
+
+    fn minimal() -> RawExtrinsicMetadata {
+        RawExtrinsicMetadata::new(
+            "swh:1:cnt:568aaf43d83b2c3df8067f3bedbb97d83260be6d".parse().unwrap(),
+            1611574071,
+            MetadataAuthority {
+                authority_type: "forge".to_string(),
+                url: "https://forge.softwareheritage.org/".to_string(),
+            },
+            MetadataFetcher {
+                name: "swh-phabricator-metadata-fetcher".to_string(),
+                version: "0.0.1".to_string(),
+            },
+            "json",
+            br#"{"foo": "bar"}"#.to_vec(),
+        )
+    }
+
+    #[test]
+    fn test_extended_swhid_parse() {
+        for s in [
+            "swh:1:ori:b63a575fe3faab7692c9f38fb09d4bb45651bb0f",
+            "swh:1:emd:5c13f20ba336e44549baf3d7b9305b027ec9f43d",
+            "swh:1:dir:d198bc9d7a6bcf6db04f476d29314f157507d505",
+        ] {
+            assert_eq!(s.parse::<ExtendedSwhid>().unwrap().to_string(), s);
+        }
+
+        let core = swhid("swh:1:rev:309cf2674ee7a0749978cf8265ab91a60aea0f7d");
+        let extended = ExtendedSwhid::try_from(core.clone()).unwrap();
+        assert_eq!(extended.object_type(), ExtendedObjectType::Revision);
+        assert_eq!(Swhid::try_from(extended).unwrap(), core);
+
+        let origin: ExtendedSwhid = "swh:1:ori:b63a575fe3faab7692c9f38fb09d4bb45651bb0f".parse().unwrap();
+        assert_eq!(origin.to_core(), None);
+        assert!(matches!(Swhid::try_from(origin), Err(SwhidError::InvalidObjectType(_))));
+
+        // Core SWHIDs do not take the extended types
+        assert!("swh:1:ori:b63a575fe3faab7692c9f38fb09d4bb45651bb0f".parse::<Swhid>().is_err());
+        for s in ["swh:1:ori:b63a", "swh:2:ori:b63a575fe3faab7692c9f38fb09d4bb45651bb0f", "swh:1:xyz:00"] {
+            assert!(s.parse::<ExtendedSwhid>().is_err(), "{:?} should be rejected", s);
+        }
+    }
+
+    #[cfg(feature = "v2")]
+    #[test]
+    fn test_extended_swhid_rejects_v2() {
+        let v2 = "swh:2:cnt:2cf8d83d9ee29543b34a87727421fdecb7e3f3a183d337639025de576db9ebb4";
+        assert!(matches!(v2.parse::<ExtendedSwhid>(), Err(SwhidError::InvalidVersion(_))));
+        assert!(matches!(ExtendedSwhid::try_from(swhid(v2)), Err(SwhidError::InvalidVersion(_))));
+    }
+
+    // Reference identifiers from the swh-model test suite
+    #[test]
+    fn test_origin_swhid() {
+        let origin = Origin::new("https://github.com/torvalds/linux").unwrap();
+        assert_eq!(
+            ExtendedSwhid::from_origin(&origin).to_string(),
+            "swh:1:ori:b63a575fe3faab7692c9f38fb09d4bb45651bb0f"
+        );
+    }
+
+    #[test]
+    fn test_raw_extrinsic_metadata_swhid() {
+        let metadata = minimal();
+        assert_eq!(
+            metadata.git_object(),
+            b"target swh:1:cnt:568aaf43d83b2c3df8067f3bedbb97d83260be6d\n\
+discovery_date 1611574071\n\
+authority forge https://forge.softwareheritage.org/\n\
+fetcher swh-phabricator-metadata-fetcher 0.0.1\n\
+format json\n\
+\n\
+{\"foo\": \"bar\"}"
+        );
+        assert_eq!(
+            metadata.swhid().to_string(),
+            "swh:1:emd:5c13f20ba336e44549baf3d7b9305b027ec9f43d"
+        );
+
+        let maximal = minimal()
+            .with_directory(swhid(&format!("swh:1:dir:{}", "03".repeat(20))))
+            .with_path(b"/abc/def".to_vec())
+            .with_revision(swhid(&format!("swh:1:rev:{}", "02".repeat(20))))
+            .with_release(swhid(&format!("swh:1:rel:{}", "01".repeat(20))))
+            .with_snapshot(swhid(&format!("swh:1:snp:{}", "00".repeat(20))))
+            .with_visit(42)
+            .with_origin(Origin::new("https://forge.softwareheritage.org/source/swh-model/").unwrap());
+        assert_eq!(
+            maximal.swhid().to_string(),
+            "swh:1:emd:f96966e1093d15236a31fde07e47d5b1c9428049"
+        );
+    }
+}
@@ -0,0 +1,144 @@
+//! External identifiers, mapping the ids of other systems to SWHIDs
+//!
+//! Loaders of the Software Heritage archive record, for each artifact they
+//! load, an [`ExtId`]: the identifier a package manager or forge gives it,
+//! such as a tarball checksum, and the SWHID of what it was loaded as. The
+//! next load finds the artifact again by that identifier.
+
+use crate::hash::hash_git_object;
+use crate::revision::write_header;
+use crate::swhid::Swhid;
+
+/// External identifier of an object, and the SWHID it maps to
+///
+/// Headers are hashed in the order `extid_type`, `extid_version` if not 0,
+/// `extid`, `target`, then `payload_type` and `payload` if set, as by
+/// `extid_git_object` in swh-model.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ExtId {
+    extid_type: String,
+    extid: Vec<u8>,
+    target: Swhid,
+    version: u32,
+    payload: Option<(String, [u8; 20])>,
+}
+
+impl ExtId {
+    /// Map `extid`, an identifier of kind `extid_type`, to `target`
+    ///
+    /// The type names the scheme of the identifier, e.g. `nar-sha256` or
+    /// `package-manifest-sha256`.
+    pub fn new(extid_type: impl Into<String>, extid: Vec<u8>, target: Swhid) -> Self {
+        Self {
+            extid_type: extid_type.into(),
+            extid,
+            target,
+            version: 0,
+            payload: None,
+        }
+    }
+
+    /// Set the version of the scheme the identifier was computed with
+    ///
+    /// Version 0, the default, is not written in the manifest.
+    pub fn with_version(mut self, version: u32) -> Self {
+        self.version = version;
+        self
+    }
+
+    /// Attach a payload: the SHA-1 git hash of a content holding more data,
+    /// and the type of that data
+    pub fn with_payload(mut self, payload_type: impl Into<String>, payload: [u8; 20]) -> Self {
+        self.payload = Some((payload_type.into(), payload));
+        self
+    }
+
+    /// Get the type of the external identifier
+    pub fn extid_type(&self) -> &str {
+        &self.extid_type
+    }
+
+    /// Get the external identifier
+    pub fn extid(&self) -> &[u8] {
+        &self.extid
+    }
+
+    /// Get the SWHID the external identifier maps to
+    pub fn target(&self) -> &Swhid {
+        &self.target
+    }
+
+    /// Get the version of the identifier scheme
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// Get the payload type and hash, if any
+    pub fn payload(&self) -> Option<(&str, &[u8; 20])> {
+        self.payload.as_ref().map(|(payload_type, payload)| (payload_type.as_str(), payload))
+    }
+
+    /// Serialize the manifest hashed for the ExtID id
+    ///
+    /// Headers are written as in Git objects, with no message after them.
+    pub fn git_object(&self) -> Vec<u8> {
+        let mut headers: Vec<(&[u8], Vec<u8>)> = vec![(b"extid_type", self.extid_type.clone().into_bytes())];
+        if self.version != 0 {
+            headers.push((b"extid_version", self.version.to_string().into_bytes()));
+        }
+        headers.push((b"extid", self.extid.clone()));
+        headers.push((b"target", self.target.to_string().into_bytes()));
+        if let Some((payload_type, payload)) = &self.payload {
+            headers.push((b"payload_type", payload_type.clone().into_bytes()));
+            headers.push((b"payload", payload.to_vec()));
+        }
+
+        let mut data = Vec::new();
+        for (key, value) in headers {
+            write_header(&mut data, key, &value).expect("header keys are valid");
+        }
+        data
+    }
+
+    /// Compute the intrinsic id of this ExtID, as the archive stores it
+    ///
+    /// ExtIDs have no SWHID of their own; the id is a Git object hash of
+    /// type `extid`.
+    pub fn id(&self) -> [u8; 20] {
+        hash_git_object("extid", &self.git_object())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::swhid::ObjectType;
+
+    // The first identifier is the reference of the swh-model test suite; the
+    // others hash the manifests swh-model's `extid_git_object` writes
+    #[test]
+    fn test_extid_id() {
+        let extid = ExtId::new("test-type", b"extid".to_vec(), Swhid::new(ObjectType::Directory, [0; 20]));
+        assert_eq!(
+            extid.git_object(),
+            b"extid_type test-type\nextid extid\ntarget swh:1:dir:0000000000000000000000000000000000000000\n"
+        );
+        assert_eq!(hex::encode(extid.id()), "b9295e1931c31e40a7e3e1e967decd1c89426455");
+
+        // Version 0 is the default and is not hashed
+        assert_eq!(extid.clone().with_version(0).id(), extid.id());
+        let versioned = extid.clone().with_version(1);
+        assert!(versioned.git_object().starts_with(b"extid_type test-type\nextid_version 1\nextid extid\n"));
+        assert_eq!(hex::encode(versioned.id()), "ce3c69db55844de2b01a7f5f5f63401e88f39f23");
+
+        // The payload is written as its raw hash bytes
+        let payload: [u8; 20] = hex::decode("257cc5642cb1a054f08cc83f2d943e56fd3ebe99").unwrap().try_into().unwrap();
+        let with_payload = extid.clone().with_payload("test", payload);
+        let mut expected = b"\npayload_type test\npayload ".to_vec();
+        expected.extend_from_slice(&payload);
+        expected.push(b'\n');
+        assert!(with_payload.git_object().ends_with(&expected));
+        assert_eq!(with_payload.payload(), Some(("test", &payload)));
+        assert_eq!(hex::encode(with_payload.id()), "ae75387c23fff095d4df9a9a3e751af7fdae25c9");
+    }
+}
@@ -0,0 +1,639 @@
+//! SWHIDs of objects in local Git repositories
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use git2::{Direction, ErrorCode, ObjectType as GitObjectType, Oid, Remote, Repository, Tree};
+use crate::backend::{GitBackend, GitReference};
+use crate::computer::SwhidComputer;
+use crate::directory::{name_path, Directory, DirectoryEntry, EntryType, Permissions, TreeNode};
+use crate::error::SwhidError;
+use crate::integrity::IntegrityReport;
+use crate::release::Release;
+use crate::revision::Revision;
+use crate::snapshot::Snapshot;
+use crate::swhid::{ObjectType, Swhid};
+
+/// Outcome of [`SwhidComputer::compare_worktree_to_head`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WorktreeComparison {
+    /// Directory SWHID of the working tree
+    pub worktree: Swhid,
+    /// Directory SWHID of the tree of `HEAD`
+    pub head: Swhid,
+    /// Paths whose SWHID differs, or that only one side has, sorted
+    ///
+    /// Relative to the root of the working tree. A directory is only listed
+    /// if nothing below it is, e.g. when it is empty and thus not in Git.
+    pub changed: Vec<PathBuf>,
+}
+
+impl WorktreeComparison {
+    /// Whether the working tree is exactly the tree of `HEAD`
+    pub fn is_pristine(&self) -> bool {
+        self.worktree == self.head
+    }
+}
+
+impl GitBackend for Repository {
+    fn read_object(&self, id: &[u8; 20]) -> Result<(ObjectType, Vec<u8>), SwhidError> {
+        let odb = self.odb()?;
+        let object = odb.read(Oid::from_bytes(id)?)?;
+        Ok((object_type(object.kind())?, object.data().to_vec()))
+    }
+
+    fn object_type(&self, id: &[u8; 20]) -> Result<ObjectType, SwhidError> {
+        object_type(self.odb()?.read_header(Oid::from_bytes(id)?)?.1)
+    }
+
+    fn references(&self) -> Result<Vec<(Vec<u8>, GitReference)>, SwhidError> {
+        let head = self.find_reference("HEAD")?;
+        let mut references = Vec::new();
+        for reference in self.references()?.chain(std::iter::once(Ok(head))) {
+            let reference = reference?;
+            let target = match (reference.symbolic_target_bytes(), reference.target()) {
+                (Some(name), _) => GitReference::Symbolic(name.to_vec()),
+                (None, Some(id)) => GitReference::Direct(object_hash(id)?),
+                (None, None) => {
+                    return Err(SwhidError::Git(format!(
+                        "Reference {} has no target",
+                        String::from_utf8_lossy(reference.name_bytes())
+                    )))
+                }
+            };
+            references.push((reference.name_bytes().to_vec(), target));
+        }
+        Ok(references)
+    }
+
+    /// Resolve anything `git rev-parse` understands, e.g. `HEAD~2`
+    fn resolve(&self, name: &str) -> Result<[u8; 20], SwhidError> {
+        object_hash(self.revparse_single(name)?.id())
+    }
+}
+
+impl Revision {
+    /// Read the commit `revision` resolves to in the repository at `repo`
+    ///
+    /// `revision` is anything `git rev-parse` understands, such as `HEAD`, a
+    /// branch, a tag or a commit id. See [`Revision::from_backend`].
+    pub fn from_repo<P: AsRef<Path>>(repo: P, revision: &str) -> Result<Self, SwhidError> {
+        Revision::from_backend(&Repository::open(repo)?, revision)
+    }
+}
+
+impl Release {
+    /// Read the annotated tag `tag` resolves to in the repository at `repo`
+    ///
+    /// `tag` is usually a tag name, but may be anything `git rev-parse`
+    /// understands. See [`Release::from_backend`].
+    pub fn from_repo<P: AsRef<Path>>(repo: P, tag: &str) -> Result<Self, SwhidError> {
+        Release::from_backend(&Repository::open(repo)?, tag)
+    }
+}
+
+impl Snapshot {
+    /// Build the snapshot of every reference of the repository at `repo`
+    ///
+    /// See [`Snapshot::from_backend`].
+    pub fn from_repo<P: AsRef<Path>>(repo: P) -> Result<Self, SwhidError> {
+        Snapshot::from_backend(&Repository::open(repo)?)
+    }
+
+    /// Build the snapshot of a remote repository without cloning it
+    ///
+    /// This connects to `url` as `git ls-remote` does, over any transport
+    /// Git supports, and only lists the references it advertises. See
+    /// [`Snapshot::from_advertised_refs`] for how they become branches.
+    pub fn from_remote(url: &str) -> Result<Self, SwhidError> {
+        let mut remote = Remote::create_detached(url)?;
+        remote.connect(Direction::Fetch)?;
+        let mut refs = Vec::new();
+        for head in remote.list()? {
+            let reference = match head.symref_target() {
+                Some(branch) => GitReference::Symbolic(branch.as_bytes().to_vec()),
+                None => GitReference::Direct(object_hash(head.oid())?),
+            };
+            refs.push((head.name().as_bytes().to_vec(), reference));
+        }
+        Snapshot::from_advertised_refs(refs)
+    }
+}
+
+impl IntegrityReport {
+    /// Hash every object reachable from the references of the repository at `repo`
+    ///
+    /// See [`IntegrityReport::from_backend`].
+    pub fn from_repo<P: AsRef<Path>>(repo: P) -> Result<Self, SwhidError> {
+        IntegrityReport::from_backend(&Repository::open(repo)?)
+    }
+}
+
+/// Compute the directory SWHID of a subdirectory as recorded in a local Git repository
+///
+/// E.g. `compute_subtree_swhid(repo, "v1.0", "src")` for `src` of a monorepo
+/// at a release; see [`SwhidComputer::compute_repo_subtree_swhid`].
+pub fn compute_subtree_swhid<P: AsRef<Path>, Q: AsRef<Path>>(
+    repo: P,
+    revision: &str,
+    path: Q,
+) -> Result<Swhid, SwhidError> {
+    SwhidComputer::new().compute_repo_subtree_swhid(repo, revision, path)
+}
+
+impl SwhidComputer {
+    /// Compute the revision SWHID of a commit in a local Git repository
+    ///
+    /// See [`Revision::from_repo`] for how `revision` is resolved.
+    pub fn compute_revision_swhid<P: AsRef<Path>>(&self, repo: P, revision: &str) -> Result<Swhid, SwhidError> {
+        Ok(Revision::from_repo(repo, revision)?.swhid())
+    }
+
+    /// Compute the release SWHID of an annotated tag in a local Git repository
+    ///
+    /// See [`Release::from_repo`] for how `tag` is resolved.
+    pub fn compute_release_swhid<P: AsRef<Path>>(&self, repo: P, tag: &str) -> Result<Swhid, SwhidError> {
+        Ok(Release::from_repo(repo, tag)?.swhid())
+    }
+
+    /// Compute the directory SWHID of a subdirectory as recorded in a local Git repository
+    ///
+    /// `revision` is resolved as for [`Revision::from_repo`], to a commit, a
+    /// tag or a tree, and `path` is relative to the root of its tree, e.g.
+    /// `src` for a subdirectory of a monorepo. See [`Directory::from_backend`].
+    pub fn compute_repo_subtree_swhid<P: AsRef<Path>, Q: AsRef<Path>>(
+        &self,
+        repo: P,
+        revision: &str,
+        path: Q,
+    ) -> Result<Swhid, SwhidError> {
+        Directory::from_backend(&Repository::open(repo)?, revision, path.as_ref())
+    }
+
+    /// Compute the snapshot SWHID of the references of a local Git repository
+    ///
+    /// See [`Snapshot::from_repo`] for how references become branches.
+    pub fn compute_snapshot_swhid<P: AsRef<Path>>(&self, repo: P) -> Result<Swhid, SwhidError> {
+        Ok(Snapshot::from_repo(repo)?.swhid())
+    }
+
+    /// Compute the directory SWHID of what is staged in a local Git repository
+    ///
+    /// This is the tree `git write-tree` would record, i.e. what the next
+    /// commit would hold, computed from the index alone: the working tree is
+    /// not read, and nothing is written to the repository. Paths added with
+    /// `git add --intent-to-add` are left out, as Git does. Fails with
+    /// `SwhidError::Git` if the index has unmerged paths.
+    pub fn compute_index_swhid<P: AsRef<Path>>(&self, repo: P) -> Result<Swhid, SwhidError> {
+        let index = Repository::open(repo)?.index()?;
+        let mut tree = TreeNode::new();
+        for entry in index.iter() {
+            let path = String::from_utf8_lossy(&entry.path);
+            if (entry.flags >> 12) & 0x3 != 0 {
+                return Err(SwhidError::Git(format!("Index has unmerged path {}", path)));
+            }
+            // Intent-to-add entries are placeholders for files not staged yet
+            if entry.flags_extended & git2::IndexEntryExtendedFlag::INTENT_TO_ADD.bits() != 0 {
+                continue;
+            }
+            let permissions = Permissions::from_mode(entry.mode);
+            let entry_type = match permissions {
+                Permissions::File | Permissions::Executable => EntryType::File,
+                Permissions::Symlink => EntryType::Symlink,
+                Permissions::Submodule => EntryType::Submodule,
+                Permissions::Directory => {
+                    return Err(SwhidError::Git(format!("Unsupported sparse index entry {}", path)))
+                }
+            };
+            let mut names: Vec<Vec<u8>> = entry.path.split(|&b| b == b'/').map(<[u8]>::to_vec).collect();
+            let name = names.pop().unwrap_or_default();
+            tree.insert(&names, DirectoryEntry::new(name, entry_type, permissions, object_hash(entry.id)?))?;
+        }
+        Ok(tree.into_directory(self.hash_algorithm)?.swhid_with(self.hash_algorithm))
+    }
+
+    /// Compare the working tree of a local Git repository to the tree of `HEAD`
+    ///
+    /// The working tree is hashed with the settings of this computer, leaving
+    /// out `.git` entries, which Git never records. Set
+    /// [`SwhidComputer::with_submodules`] if the repository has submodules,
+    /// so that they hash as in `HEAD`. Ignored and untracked files count as
+    /// changes: the check is whether the working tree has the SWHID of `HEAD`.
+    pub fn compare_worktree_to_head<P: AsRef<Path>>(&self, repo: P) -> Result<WorktreeComparison, SwhidError> {
+        let repo = Repository::open(repo)?;
+        let workdir = repo
+            .workdir()
+            .ok_or_else(|| SwhidError::Git("Bare repository has no working tree".to_string()))?;
+        let mut computer = self.clone();
+        computer.exclude_patterns.push(".git".to_string());
+        let worktree = computer.compute_swhid_tree(workdir)?;
+
+        let mut head = BTreeMap::new();
+        let tree = repo.head()?.peel_to_tree()?;
+        head.insert(PathBuf::new(), Swhid::new(ObjectType::Directory, object_hash(tree.id())?));
+        tree_swhids(&repo, &tree, Path::new(""), &mut head)?;
+
+        let mut changed: Vec<PathBuf> = worktree
+            .iter()
+            .filter(|(path, swhid)| head.get(*path) != Some(*swhid))
+            .map(|(path, _)| path.clone())
+            .chain(head.keys().filter(|path| !worktree.contains_key(*path)).cloned())
+            .collect();
+        changed.sort();
+        // Paths below a directory come right after it: keep the deepest ones
+        let changed = changed
+            .iter()
+            .enumerate()
+            .filter(|(i, path)| !changed.get(i + 1).is_some_and(|next| next.starts_with(path)))
+            .map(|(_, path)| path.clone())
+            .collect();
+
+        let root = Path::new("");
+        Ok(WorktreeComparison {
+            worktree: worktree[root].clone(),
+            head: head[root].clone(),
+            changed,
+        })
+    }
+
+    /// Compute the snapshot SWHID of a remote repository without cloning it
+    ///
+    /// See [`Snapshot::from_remote`].
+    pub fn compute_remote_snapshot_swhid(&self, url: &str) -> Result<Swhid, SwhidError> {
+        Ok(Snapshot::from_remote(url)?.swhid())
+    }
+}
+
+/// Look up the tree recorded in `HEAD` for a directory of a working tree
+///
+/// Returns `None` if `path` is not in a working tree, if nothing is checked
+/// out, or if `HEAD` has no tree at that path. See
+/// [`SwhidComputer::with_git_tree_policy`].
+pub(crate) fn recorded_directory_swhid(path: &Path) -> Result<Option<Swhid>, SwhidError> {
+    let repo = match Repository::discover(path) {
+        Ok(repo) => repo,
+        Err(e) if e.code() == ErrorCode::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let Some(workdir) = repo.workdir() else {
+        return Ok(None);
+    };
+    let path = path.canonicalize().map_err(|e| SwhidError::from(e).at_path(path))?;
+    let workdir = workdir.canonicalize().map_err(|e| SwhidError::from(e).at_path(workdir))?;
+    let Ok(relative) = path.strip_prefix(&workdir) else {
+        return Ok(None);
+    };
+    let tree = match repo.head() {
+        Ok(head) => head.peel_to_tree()?,
+        Err(e) if e.code() == ErrorCode::UnbornBranch => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let id = if relative.as_os_str().is_empty() {
+        tree.id()
+    } else {
+        match tree.get_path(relative) {
+            Ok(entry) if entry.kind() == Some(GitObjectType::Tree) => entry.id(),
+            Ok(_) => return Ok(None),
+            Err(e) if e.code() == ErrorCode::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        }
+    };
+    Ok(Some(Swhid::new(ObjectType::Directory, object_hash(id)?)))
+}
+
+/// Add the SWHID of every entry below `tree`, by path under `prefix`
+fn tree_swhids(
+    repo: &Repository,
+    tree: &Tree,
+    prefix: &Path,
+    swhids: &mut BTreeMap<PathBuf, Swhid>,
+) -> Result<(), SwhidError> {
+    for entry in tree.iter() {
+        let path = prefix.join(name_path(entry.name_bytes()));
+        let object_type = object_type(entry.kind().unwrap_or(GitObjectType::Any))?;
+        if object_type == ObjectType::Directory {
+            tree_swhids(repo, &repo.find_tree(entry.id())?, &path, swhids)?;
+        }
+        swhids.insert(path, Swhid::new(object_type, object_hash(entry.id())?));
+    }
+    Ok(())
+}
+
+/// Map a Git object type to the type of its SWHID
+fn object_type(kind: GitObjectType) -> Result<ObjectType, SwhidError> {
+    match kind {
+        GitObjectType::Blob => Ok(ObjectType::Content),
+        GitObjectType::Tree => Ok(ObjectType::Directory),
+        GitObjectType::Commit => Ok(ObjectType::Revision),
+        GitObjectType::Tag => Ok(ObjectType::Release),
+        other => Err(SwhidError::Git(format!("Unexpected object type {}", other))),
+    }
+}
+
+/// Convert a Git object id to a SHA-1 hash
+fn object_hash(id: Oid) -> Result<[u8; 20], SwhidError> {
+    id.as_bytes()
+        .try_into()
+        .map_err(|_| SwhidError::InvalidHashLength(id.as_bytes().len() * 2))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::snapshot::SnapshotTarget;
+    use git2::{Signature, Time};
+    use tempfile::TempDir;
+
+    /// A repository with two commits on its default branch
+    fn sample_repo() -> (TempDir, Oid, Oid) {
+        let temp_dir = TempDir::new().unwrap();
+        let repo = Repository::init(temp_dir.path()).unwrap();
+        let signature = Signature::new("Jane Doe", "jane@example.org", &Time::new(1_700_000_000, 60)).unwrap();
+
+        let mut builder = repo.treebuilder(None).unwrap();
+        builder.insert("README", repo.blob(b"readme\n").unwrap(), 0o100644).unwrap();
+        let tree = repo.find_tree(builder.write().unwrap()).unwrap();
+        let first = repo.commit(Some("HEAD"), &signature, &signature, "Initial commit\n", &tree, &[]).unwrap();
+        let parent = repo.find_commit(first).unwrap();
+        let second = repo
+            .commit(Some("HEAD"), &signature, &signature, "Second\n\nWith body\n", &tree, &[&parent])
+            .unwrap();
+        (temp_dir, first, second)
+    }
+
+    #[test]
+    fn test_revision_from_repo() {
+        let (temp_dir, first, second) = sample_repo();
+        let computer = SwhidComputer::new();
+
+        let swhid = computer.compute_revision_swhid(temp_dir.path(), "HEAD").unwrap();
+        assert_eq!(swhid.to_string(), format!("swh:1:rev:{}", second));
+        assert_eq!(
+            computer.compute_revision_swhid(temp_dir.path(), "HEAD~1").unwrap().to_string(),
+            format!("swh:1:rev:{}", first)
+        );
+
+        let revision = Revision::from_repo(temp_dir.path(), &second.to_string()).unwrap();
+        assert_eq!(revision.swhid(), swhid);
+        assert_eq!(revision.parents(), vec![computer.compute_revision_swhid(temp_dir.path(), "HEAD~1").unwrap()]);
+        assert!(revision.git_object().ends_with(b"\n\nSecond\n\nWith body\n"));
+    }
+
+    #[test]
+    fn test_revision_walk_in_repo() {
+        use crate::revwalk::{RevisionWalk, WalkOrder};
+
+        let (temp_dir, first, second) = sample_repo();
+        let repo = Repository::open(temp_dir.path()).unwrap();
+        let walk = RevisionWalk::new(&repo, &["HEAD"], WalkOrder::Topological).unwrap();
+        let history: Vec<_> = walk.map(|item| item.unwrap().1.to_string()).collect();
+        assert_eq!(history, vec![format!("swh:1:rev:{}", second), format!("swh:1:rev:{}", first)]);
+    }
+
+    #[test]
+    fn test_integrity_report_from_repo() {
+        let (temp_dir, _, _) = sample_repo();
+        let report = IntegrityReport::from_repo(temp_dir.path()).unwrap();
+        // Two commits sharing a tree with a single blob
+        assert_eq!(report.checked, 4);
+        assert!(report.is_ok());
+    }
+
+    #[test]
+    fn test_git_tree_policy() {
+        use crate::computer::GitTreePolicy;
+        use std::fs;
+
+        let temp_dir = TempDir::new().unwrap();
+        let repo = Repository::init(temp_dir.path()).unwrap();
+        fs::create_dir(temp_dir.path().join("src")).unwrap();
+        fs::write(temp_dir.path().join("src/main.rs"), b"fn main() {}\n").unwrap();
+        fs::write(temp_dir.path().join("README"), b"readme\n").unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_all(["*"], git2::IndexAddOption::DEFAULT, None).unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let signature = Signature::new("Jane Doe", "jane@example.org", &Time::new(1_700_000_000, 0)).unwrap();
+        repo.commit(Some("HEAD"), &signature, &signature, "Initial commit\n", &tree, &[]).unwrap();
+        let src_tree = tree.get_path(Path::new("src")).unwrap().id();
+
+        let recompute = SwhidComputer::new().with_skip_vcs(true);
+        let trust = recompute.clone().with_git_tree_policy(GitTreePolicy::Trust);
+        let root = recompute.compute_directory_swhid(temp_dir.path()).unwrap();
+        assert_eq!(root.to_string(), format!("swh:1:dir:{}", tree.id()));
+        assert_eq!(trust.compute_directory_swhid(temp_dir.path()).unwrap(), root);
+        assert_eq!(
+            trust.compute_swhid(temp_dir.path().join("src")).unwrap().to_string(),
+            format!("swh:1:dir:{}", src_tree)
+        );
+
+        // Uncommitted changes are only seen when recomputing
+        fs::write(temp_dir.path().join("src/main.rs"), b"fn main() { todo!() }\n").unwrap();
+        assert_eq!(trust.compute_directory_swhid(temp_dir.path()).unwrap(), root);
+        assert_ne!(recompute.compute_directory_swhid(temp_dir.path()).unwrap(), root);
+
+        // Directories unknown to HEAD are hashed from disk
+        fs::create_dir(temp_dir.path().join("untracked")).unwrap();
+        fs::write(temp_dir.path().join("untracked/file"), b"data\n").unwrap();
+        let untracked = temp_dir.path().join("untracked");
+        assert_eq!(
+            trust.compute_directory_swhid(&untracked).unwrap(),
+            recompute.compute_directory_swhid(&untracked).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_index_swhid() {
+        use git2::{IndexEntry, IndexTime};
+        use std::fs;
+
+        let temp_dir = TempDir::new().unwrap();
+        let repo = Repository::init(temp_dir.path()).unwrap();
+        let computer = SwhidComputer::new();
+        let empty = computer.compute_index_swhid(temp_dir.path()).unwrap();
+        assert_eq!(empty.to_string(), "swh:1:dir:4b825dc642cb6eb9a060e54bf8d69288fbee4904");
+
+        fs::create_dir(temp_dir.path().join("src")).unwrap();
+        fs::write(temp_dir.path().join("src/main.rs"), b"fn main() {}\n").unwrap();
+        fs::write(temp_dir.path().join("README"), b"readme\n").unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_all(["*"], git2::IndexAddOption::DEFAULT, None).unwrap();
+        index.write().unwrap();
+
+        // Changes not staged are not seen
+        fs::write(temp_dir.path().join("README"), b"changed\n").unwrap();
+        let staged = computer.compute_index_swhid(temp_dir.path()).unwrap();
+        assert_eq!(staged.to_string(), format!("swh:1:dir:{}", index.write_tree().unwrap()));
+
+        let conflict = IndexEntry {
+            ctime: IndexTime::new(0, 0),
+            mtime: IndexTime::new(0, 0),
+            dev: 0,
+            ino: 0,
+            mode: 0o100644,
+            uid: 0,
+            gid: 0,
+            file_size: 0,
+            id: repo.blob(b"ours\n").unwrap(),
+            flags: (2 << 12) | 8,
+            flags_extended: 0,
+            path: b"conflict".to_vec(),
+        };
+        index.add(&conflict).unwrap();
+        index.write().unwrap();
+        assert!(matches!(computer.compute_index_swhid(temp_dir.path()), Err(SwhidError::Git(_))));
+    }
+
+    #[test]
+    fn test_compare_worktree_to_head() {
+        use std::fs;
+
+        let temp_dir = TempDir::new().unwrap();
+        let repo = Repository::init(temp_dir.path()).unwrap();
+        fs::create_dir(temp_dir.path().join("src")).unwrap();
+        fs::write(temp_dir.path().join("src/main.rs"), b"fn main() {}\n").unwrap();
+        fs::write(temp_dir.path().join("src/lib.rs"), b"\n").unwrap();
+        fs::write(temp_dir.path().join("README"), b"readme\n").unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_all(["*"], git2::IndexAddOption::DEFAULT, None).unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let signature = Signature::new("Jane Doe", "jane@example.org", &Time::new(1_700_000_000, 0)).unwrap();
+        repo.commit(Some("HEAD"), &signature, &signature, "Initial commit\n", &tree, &[]).unwrap();
+
+        let computer = SwhidComputer::new();
+        let comparison = computer.compare_worktree_to_head(temp_dir.path()).unwrap();
+        assert!(comparison.is_pristine());
+        assert_eq!(comparison.head.to_string(), format!("swh:1:dir:{}", tree.id()));
+        assert!(comparison.changed.is_empty());
+
+        fs::write(temp_dir.path().join("src/main.rs"), b"fn main() { todo!() }\n").unwrap();
+        fs::remove_file(temp_dir.path().join("README")).unwrap();
+        fs::create_dir(temp_dir.path().join("empty")).unwrap();
+        let comparison = computer.compare_worktree_to_head(temp_dir.path()).unwrap();
+        assert!(!comparison.is_pristine());
+        assert_eq!(comparison.head.to_string(), format!("swh:1:dir:{}", tree.id()));
+        assert_eq!(
+            comparison.changed,
+            vec![PathBuf::from("README"), PathBuf::from("empty"), PathBuf::from("src/main.rs")]
+        );
+    }
+
+    #[test]
+    fn test_repo_subtree_swhid() {
+        let (temp_dir, first, _) = sample_repo();
+        let repo = Repository::open(temp_dir.path()).unwrap();
+        let mut builder = repo.treebuilder(None).unwrap();
+        builder.insert("main.rs", repo.blob(b"fn main() {}\n").unwrap(), 0o100644).unwrap();
+        let src = builder.write().unwrap();
+        let mut builder = repo.treebuilder(Some(&repo.head().unwrap().peel_to_tree().unwrap())).unwrap();
+        builder.insert("src", src, 0o040000).unwrap();
+        let tree = repo.find_tree(builder.write().unwrap()).unwrap();
+        let signature = Signature::new("Jane Doe", "jane@example.org", &Time::new(1_700_000_000, 0)).unwrap();
+        let parent = repo.head().unwrap().peel_to_commit().unwrap();
+        repo.commit(Some("HEAD"), &signature, &signature, "Add sources\n", &tree, &[&parent]).unwrap();
+
+        let computer = SwhidComputer::new();
+        assert_eq!(
+            computer.compute_repo_subtree_swhid(temp_dir.path(), "HEAD", "src").unwrap().to_string(),
+            format!("swh:1:dir:{}", src)
+        );
+        assert_eq!(
+            compute_subtree_swhid(temp_dir.path(), "HEAD", "src").unwrap(),
+            computer.compute_repo_subtree_swhid(temp_dir.path(), "HEAD", "src").unwrap()
+        );
+        assert_eq!(
+            computer.compute_repo_subtree_swhid(temp_dir.path(), "HEAD", "").unwrap().to_string(),
+            format!("swh:1:dir:{}", tree.id())
+        );
+        assert!(matches!(
+            computer.compute_repo_subtree_swhid(temp_dir.path(), &first.to_string(), "src"),
+            Err(SwhidError::InvalidFilePath(..))
+        ));
+    }
+
+    #[test]
+    fn test_release_from_repo() {
+        let (temp_dir, _, second) = sample_repo();
+        let repo = Repository::open(temp_dir.path()).unwrap();
+        let tagger = Signature::new("Jane Doe", "jane@example.org", &Time::new(1_700_000_000, 60)).unwrap();
+        let target = repo.find_object(second, None).unwrap();
+        let tag = repo.tag("v1.0", &target, &tagger, "Release 1.0\n", false).unwrap();
+        repo.tag_lightweight("nightly", &target, false).unwrap();
+
+        let computer = SwhidComputer::new();
+        let swhid = computer.compute_release_swhid(temp_dir.path(), "v1.0").unwrap();
+        assert_eq!(swhid.to_string(), format!("swh:1:rel:{}", tag));
+
+        let release = Release::from_repo(temp_dir.path(), "v1.0").unwrap();
+        assert_eq!(release.name(), b"v1.0");
+        assert_eq!(release.target(), &computer.compute_revision_swhid(temp_dir.path(), "HEAD").unwrap());
+        assert!(release.git_object().ends_with(b"\n\nRelease 1.0\n"));
+
+        assert!(matches!(
+            Release::from_repo(temp_dir.path(), "nightly"),
+            Err(SwhidError::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    fn test_snapshot_from_repo() {
+        let (temp_dir, first, second) = sample_repo();
+        let repo = Repository::open(temp_dir.path()).unwrap();
+        let tagger = Signature::new("Jane Doe", "jane@example.org", &Time::new(1_700_000_000, 60)).unwrap();
+        let tag = repo.tag("v1.0", &repo.find_object(second, None).unwrap(), &tagger, "Release 1.0\n", false).unwrap();
+        repo.tag_lightweight("v0.1", &repo.find_object(first, None).unwrap(), false).unwrap();
+        let head = repo.head().unwrap().name().unwrap().to_string();
+
+        let id = |oid: Oid| object_hash(oid).unwrap();
+        let mut expected = Snapshot::new();
+        for (name, target) in [
+            ("HEAD", SnapshotTarget::Alias(head.clone().into_bytes())),
+            (head.as_str(), SnapshotTarget::Object(Swhid::new(ObjectType::Revision, id(second)))),
+            ("refs/tags/v0.1", SnapshotTarget::Object(Swhid::new(ObjectType::Revision, id(first)))),
+            ("refs/tags/v1.0", SnapshotTarget::Object(Swhid::new(ObjectType::Release, id(tag)))),
+        ] {
+            expected.add_branch(name.as_bytes().to_vec(), target).unwrap();
+        }
+
+        assert_eq!(Snapshot::from_repo(temp_dir.path()).unwrap(), expected);
+        assert_eq!(
+            SwhidComputer::new().compute_snapshot_swhid(temp_dir.path()).unwrap(),
+            expected.swhid()
+        );
+    }
+
+    #[test]
+    fn test_snapshot_from_remote() {
+        let (temp_dir, first, second) = sample_repo();
+        let repo = Repository::open(temp_dir.path()).unwrap();
+        let tagger = Signature::new("Jane Doe", "jane@example.org", &Time::new(1_700_000_000, 60)).unwrap();
+        repo.tag("v1.0", &repo.find_object(second, None).unwrap(), &tagger, "Release 1.0\n", false).unwrap();
+        repo.tag_lightweight("v0.1", &repo.find_object(first, None).unwrap(), false).unwrap();
+
+        let url = format!("file://{}", temp_dir.path().display());
+        let snapshot = Snapshot::from_remote(&url).unwrap();
+        assert_eq!(snapshot, Snapshot::from_repo(temp_dir.path()).unwrap());
+        assert_eq!(
+            SwhidComputer::new().compute_remote_snapshot_swhid(&url).unwrap(),
+            snapshot.swhid()
+        );
+
+        assert!(matches!(
+            Snapshot::from_remote(&format!("file://{}/missing", temp_dir.path().display())),
+            Err(SwhidError::Git(_))
+        ));
+    }
+
+    #[test]
+    fn test_revision_from_repo_errors() {
+        let (temp_dir, _, _) = sample_repo();
+        assert!(matches!(
+            Revision::from_repo(temp_dir.path(), "no-such-branch"),
+            Err(SwhidError::Git(_))
+        ));
+
+        let not_a_repo = TempDir::new().unwrap();
+        assert!(matches!(
+            Revision::from_repo(not_a_repo.path(), "HEAD"),
+            Err(SwhidError::Git(_))
+        ));
+    }
+}
@@ -0,0 +1,132 @@
+//! Locating a Git directory and reading its references from disk
+//!
+//! References are plain files, so reading them needs no Git library nor any
+//! decompression: this is shared by [`crate::GitBackend`] implementations and
+//! by the submodule support of directory traversal.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::backend::GitReference;
+use crate::directory::name_bytes;
+use crate::error::SwhidError;
+use crate::hash::parse_object_id;
+
+/// Find the Git directory of a working tree or bare repository
+///
+/// `path` is either a working tree, whose `.git` may be a directory or a
+/// `gitdir:` file, or the Git directory itself.
+pub(crate) fn find_git_dir(path: &Path) -> Result<PathBuf, SwhidError> {
+    if path.join("HEAD").is_file() && path.join("objects").is_dir() {
+        return Ok(path.to_path_buf());
+    }
+    let dot_git = path.join(".git");
+    if dot_git.is_dir() {
+        return Ok(dot_git);
+    }
+    if dot_git.is_file() {
+        // Linked working trees and submodules point to their Git directory
+        let content = fs::read_to_string(&dot_git).map_err(|e| SwhidError::from(e).at_path(&dot_git))?;
+        if let Some(git_dir) = content.trim_end().strip_prefix("gitdir: ") {
+            return Ok(path.join(git_dir));
+        }
+    }
+    Err(SwhidError::Git(format!("Not a Git repository: {}", path.display())))
+}
+
+/// Read every reference of a Git directory by full name, including `HEAD`
+///
+/// Loose references override packed ones. Linked working trees have their
+/// own `HEAD`, but share the other references of their `commondir`.
+pub(crate) fn read_references(git_dir: &Path) -> Result<BTreeMap<Vec<u8>, GitReference>, SwhidError> {
+    let common_dir = common_dir(git_dir);
+    let mut references = BTreeMap::new();
+
+    let packed_refs = common_dir.join("packed-refs");
+    if packed_refs.is_file() {
+        let content = fs::read(&packed_refs).map_err(|e| SwhidError::from(e).at_path(&packed_refs))?;
+        // `#` starts the header, and `^` the object a tag above peels to
+        for line in content.split(|&b| b == b'\n') {
+            if line.is_empty() || line.starts_with(b"#") || line.starts_with(b"^") {
+                continue;
+            }
+            let id = line.get(..40).and_then(parse_object_id);
+            match (id, line.get(40..).and_then(|rest| rest.strip_prefix(b" "))) {
+                (Some(id), Some(name)) => references.insert(name.to_vec(), GitReference::Direct(id)),
+                _ => return Err(corrupt(&packed_refs, "has an invalid line")),
+            };
+        }
+    }
+    let refs_dir = common_dir.join("refs");
+    if refs_dir.is_dir() {
+        read_loose_references(&refs_dir, b"refs/", &mut references)?;
+    }
+    references.insert(b"HEAD".to_vec(), read_reference_file(&git_dir.join("HEAD"))?);
+
+    Ok(references)
+}
+
+/// Get the directory holding the objects and shared references of a Git directory
+///
+/// This is the Git directory itself, except for linked working trees.
+pub(crate) fn common_dir(git_dir: &Path) -> PathBuf {
+    match fs::read_to_string(git_dir.join("commondir")) {
+        Ok(common_dir) => git_dir.join(common_dir.trim_end()),
+        Err(_) => git_dir.to_path_buf(),
+    }
+}
+
+/// Resolve `HEAD` to the commit checked out, following symbolic references
+pub(crate) fn resolve_head(git_dir: &Path) -> Result<[u8; 20], SwhidError> {
+    let references = read_references(git_dir)?;
+    let mut name = &b"HEAD"[..];
+    // Bounded, in case symbolic references form a cycle
+    for _ in 0..=references.len() {
+        match references.get(name) {
+            Some(GitReference::Direct(id)) => return Ok(*id),
+            Some(GitReference::Symbolic(other)) => name = other,
+            None => break,
+        }
+    }
+    Err(SwhidError::Git(format!(
+        "HEAD of {} does not point to a commit",
+        git_dir.display()
+    )))
+}
+
+/// Read a reference file: a symbolic reference or an object id
+fn read_reference_file(path: &Path) -> Result<GitReference, SwhidError> {
+    let content = fs::read(path).map_err(|e| SwhidError::from(e).at_path(path))?;
+    let content = content.strip_suffix(b"\n").unwrap_or(&content);
+    if let Some(name) = content.strip_prefix(b"ref: ") {
+        return Ok(GitReference::Symbolic(name.to_vec()));
+    }
+    parse_object_id(content)
+        .map(GitReference::Direct)
+        .ok_or_else(|| corrupt(path, "is not a valid reference"))
+}
+
+/// Add the loose references below `dir`, named from `prefix`
+fn read_loose_references(
+    dir: &Path,
+    prefix: &[u8],
+    references: &mut BTreeMap<Vec<u8>, GitReference>,
+) -> Result<(), SwhidError> {
+    for entry in fs::read_dir(dir).map_err(|e| SwhidError::from(e).at_path(dir))? {
+        let entry = entry?;
+        let mut name = prefix.to_vec();
+        name.extend_from_slice(&name_bytes(&entry.file_name()));
+        if entry.file_type()?.is_dir() {
+            name.push(b'/');
+            read_loose_references(&entry.path(), &name, references)?;
+        } else {
+            references.insert(name, read_reference_file(&entry.path())?);
+        }
+    }
+    Ok(())
+}
+
+/// Report a file of a Git directory as corrupt
+pub(crate) fn corrupt(path: &Path, reason: &str) -> SwhidError {
+    SwhidError::Git(format!("{} {}", path.display(), reason))
+}
@@ -0,0 +1,684 @@
+//! A read-only, pure-Rust reader for Git repositories
+//!
+//! Only what [`GitBackend`] needs is supported: loose objects, version 2
+//! pack indexes with their packfiles (including deltas), loose and packed
+//! references. No Git library is linked; objects are inflated with
+//! `miniz_oxide`.
+
+use std::fs::{self, File};
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use miniz_oxide::inflate::decompress_to_vec_zlib;
+use miniz_oxide::inflate::stream::{inflate, InflateState};
+use miniz_oxide::{DataFormat, MZError, MZFlush, MZStatus};
+use crate::backend::{GitBackend, GitReference};
+use crate::error::SwhidError;
+use crate::git_dir::{common_dir, corrupt, find_git_dir, read_references};
+use crate::swhid::ObjectType;
+
+/// Deltas may refer to other deltas; real packs stay far below this depth
+const MAX_DELTA_DEPTH: usize = 10_000;
+
+/// Objects announcing a larger size get their buffer grown as they inflate
+const MAX_PREALLOCATED_SIZE: usize = 1 << 24;
+
+/// A Git repository read directly from its `.git` directory
+///
+/// Repositories using SHA-256 object ids, and objects only reachable through
+/// alternates, are not supported.
+#[derive(Debug)]
+pub struct GitReader {
+    git_dir: PathBuf,
+    objects_dir: PathBuf,
+    packs: Vec<PackIndex>,
+}
+
+/// The index of a packfile, loaded in memory
+#[derive(Debug)]
+struct PackIndex {
+    pack: PathBuf,
+    /// Object ids, sorted
+    ids: Vec<[u8; 20]>,
+    /// Offset of each object in the packfile, in the order of `ids`
+    offsets: Vec<u64>,
+}
+
+impl GitReader {
+    /// Open the repository at `path`
+    ///
+    /// `path` is either a working tree, whose `.git` may be a directory or a
+    /// `gitdir:` file, or the Git directory itself, as for bare repositories.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, SwhidError> {
+        let path = path.as_ref();
+        let git_dir = find_git_dir(path)?;
+        let config = fs::read_to_string(git_dir.join("config")).unwrap_or_default();
+        if config
+            .lines()
+            .any(|line| line.replace(char::is_whitespace, "").eq_ignore_ascii_case("objectformat=sha256"))
+        {
+            return Err(SwhidError::UnsupportedOperation(
+                "SHA-256 repositories cannot be read".to_string(),
+            ));
+        }
+
+        let mut packs = Vec::new();
+        let pack_dir = common_dir(&git_dir).join("objects").join("pack");
+        if pack_dir.is_dir() {
+            for entry in fs::read_dir(&pack_dir).map_err(|e| SwhidError::from(e).at_path(&pack_dir))? {
+                let index = entry?.path();
+                if index.extension().is_some_and(|extension| extension == "idx") {
+                    packs.push(PackIndex::load(&index)?);
+                }
+            }
+        }
+        Ok(Self {
+            objects_dir: common_dir(&git_dir).join("objects"),
+            git_dir,
+            packs,
+        })
+    }
+
+    /// Read a loose object, if there is one with this id
+    fn read_loose_object(&self, id: &[u8; 20]) -> Result<Option<(ObjectType, Vec<u8>)>, SwhidError> {
+        let hex = hex::encode(id);
+        let path = self.objects_dir.join(&hex[..2]).join(&hex[2..]);
+        let compressed = match fs::read(&path) {
+            Ok(compressed) => compressed,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(SwhidError::from(e).at_path(&path)),
+        };
+        let data = decompress_to_vec_zlib(&compressed).map_err(|_| corrupt(&path, "cannot be inflated"))?;
+
+        let header_end = data.iter().position(|&b| b == 0).ok_or_else(|| corrupt(&path, "has no header"))?;
+        let header = std::str::from_utf8(&data[..header_end]).map_err(|_| corrupt(&path, "has an invalid header"))?;
+        let (kind, size) = header.split_once(' ').ok_or_else(|| corrupt(&path, "has an invalid header"))?;
+        let object_type = match kind {
+            "blob" => ObjectType::Content,
+            "tree" => ObjectType::Directory,
+            "commit" => ObjectType::Revision,
+            "tag" => ObjectType::Release,
+            _ => return Err(corrupt(&path, "has an unknown type")),
+        };
+        if size.parse::<usize>().ok() != Some(data.len() - header_end - 1) {
+            return Err(corrupt(&path, "does not match its size"));
+        }
+        Ok(Some((object_type, data[header_end + 1..].to_vec())))
+    }
+
+    /// Read an object from any source, following delta bases at most `depth` deep
+    fn read_object_at_depth(&self, id: &[u8; 20], depth: usize) -> Result<(ObjectType, Vec<u8>), SwhidError> {
+        for pack in &self.packs {
+            if let Some(offset) = pack.offset(id) {
+                return self.read_packed_object(pack, offset, depth);
+            }
+        }
+        self.read_loose_object(id)?
+            .ok_or_else(|| SwhidError::Git(format!("Missing object {}", hex::encode(id))))
+    }
+
+    /// Read the object at `offset` in a packfile, resolving deltas
+    fn read_packed_object(
+        &self,
+        pack: &PackIndex,
+        offset: u64,
+        depth: usize,
+    ) -> Result<(ObjectType, Vec<u8>), SwhidError> {
+        if depth > MAX_DELTA_DEPTH {
+            return Err(corrupt(&pack.pack, "has a delta chain that is too deep"));
+        }
+        let mut file = File::open(&pack.pack).map_err(|e| SwhidError::from(e).at_path(&pack.pack))?;
+        file.seek(SeekFrom::Start(offset))?;
+        let mut reader = BufReader::new(file);
+
+        let mut byte = read_byte(&mut reader)?;
+        let kind = (byte >> 4) & 0x7;
+        let mut size = usize::from(byte & 0xf);
+        let mut shift = 4;
+        while byte & 0x80 != 0 {
+            byte = read_byte(&mut reader)?;
+            size |= usize::from(byte & 0x7f).checked_shl(shift).ok_or_else(|| corrupt(&pack.pack, "has an invalid size"))?;
+            shift += 7;
+        }
+
+        let object_type = match kind {
+            1 => ObjectType::Revision,
+            2 => ObjectType::Directory,
+            3 => ObjectType::Content,
+            4 => ObjectType::Release,
+            6 => {
+                // The base is earlier in the same pack, at a relative offset
+                let mut byte = read_byte(&mut reader)?;
+                let mut distance = u64::from(byte & 0x7f);
+                while byte & 0x80 != 0 {
+                    byte = read_byte(&mut reader)?;
+                    distance = ((distance + 1) << 7) | u64::from(byte & 0x7f);
+                }
+                let base_offset = offset
+                    .checked_sub(distance)
+                    .ok_or_else(|| corrupt(&pack.pack, "has a delta before its start"))?;
+                let delta = inflate_exact(&mut reader, size, &pack.pack)?;
+                let (object_type, base) = self.read_packed_object(pack, base_offset, depth + 1)?;
+                return Ok((object_type, apply_delta(&base, &delta, &pack.pack)?));
+            }
+            7 => {
+                // The base is named by id, and may be anywhere
+                let mut base_id = [0u8; 20];
+                reader.read_exact(&mut base_id)?;
+                let delta = inflate_exact(&mut reader, size, &pack.pack)?;
+                let (object_type, base) = self.read_object_at_depth(&base_id, depth + 1)?;
+                return Ok((object_type, apply_delta(&base, &delta, &pack.pack)?));
+            }
+            _ => return Err(corrupt(&pack.pack, "has an unknown object type")),
+        };
+        Ok((object_type, inflate_exact(&mut reader, size, &pack.pack)?))
+    }
+}
+
+impl GitBackend for GitReader {
+    fn read_object(&self, id: &[u8; 20]) -> Result<(ObjectType, Vec<u8>), SwhidError> {
+        self.read_object_at_depth(id, 0)
+    }
+
+    fn references(&self) -> Result<Vec<(Vec<u8>, GitReference)>, SwhidError> {
+        Ok(read_references(&self.git_dir)?.into_iter().collect())
+    }
+}
+
+impl PackIndex {
+    /// Load a version 2 pack index; its packfile has the same name, with `.pack`
+    fn load(path: &Path) -> Result<Self, SwhidError> {
+        let data = fs::read(path).map_err(|e| SwhidError::from(e).at_path(path))?;
+        if data.get(..8) != Some(&b"\xfftOc\x00\x00\x00\x02"[..]) {
+            return Err(corrupt(path, "is not a version 2 pack index"));
+        }
+        let word = |index: usize| -> Result<u32, SwhidError> {
+            data.get(index..index + 4)
+                .map(|bytes| u32::from_be_bytes(bytes.try_into().expect("4 bytes")))
+                .ok_or_else(|| corrupt(path, "is truncated"))
+        };
+
+        // The last fan-out entry is the number of objects
+        let count = word(8 + 255 * 4)? as usize;
+        let ids_start = 8 + 256 * 4;
+        let offsets_start = ids_start + count * 24;
+        let large_offsets_start = offsets_start + count * 4;
+
+        let ids = data
+            .get(ids_start..ids_start + count * 20)
+            .ok_or_else(|| corrupt(path, "is truncated"))?
+            .chunks_exact(20)
+            .map(|id| id.try_into().expect("20 bytes"))
+            .collect();
+        let offsets = (0..count)
+            .map(|i| {
+                let offset = word(offsets_start + i * 4)?;
+                if offset & 0x8000_0000 == 0 {
+                    return Ok(u64::from(offset));
+                }
+                // Offsets past 2 GiB are stored in a separate table
+                let index = large_offsets_start + (offset & 0x7fff_ffff) as usize * 8;
+                Ok((u64::from(word(index)?) << 32) | u64::from(word(index + 4)?))
+            })
+            .collect::<Result<_, SwhidError>>()?;
+
+        Ok(Self {
+            pack: path.with_extension("pack"),
+            ids,
+            offsets,
+        })
+    }
+
+    /// Find the offset of an object in the packfile
+    fn offset(&self, id: &[u8; 20]) -> Option<u64> {
+        self.ids.binary_search(id).ok().map(|index| self.offsets[index])
+    }
+}
+
+/// Inflate a zlib stream of exactly `size` bytes from `reader`
+///
+/// `size` comes from the pack and is not trusted: the output grows as the
+/// stream inflates, and a stream of another size fails with an
+/// `io::ErrorKind::InvalidData` error.
+fn inflate_exact<R: Read>(reader: &mut R, size: usize, path: &Path) -> Result<Vec<u8>, SwhidError> {
+    let wrong_size = || {
+        let err = io::Error::new(io::ErrorKind::InvalidData, format!("object is not of its announced size {}", size));
+        SwhidError::PathIo(path.to_path_buf(), err)
+    };
+    let mut state = InflateState::new_boxed(DataFormat::Zlib);
+    let mut output = Vec::with_capacity(size.min(MAX_PREALLOCATED_SIZE));
+    let mut inflated = [0u8; 8192];
+    let mut input = [0u8; 8192];
+    loop {
+        let read = reader.read(&mut input)?;
+        let mut chunk = &input[..read];
+        loop {
+            let result = inflate(&mut state, chunk, &mut inflated, MZFlush::None);
+            chunk = &chunk[result.bytes_consumed..];
+            if result.bytes_written > size - output.len() {
+                return Err(wrong_size());
+            }
+            output.extend_from_slice(&inflated[..result.bytes_written]);
+            match result.status {
+                Ok(MZStatus::StreamEnd) if output.len() == size => return Ok(output),
+                Ok(MZStatus::StreamEnd) => return Err(wrong_size()),
+                Ok(_) | Err(MZError::Buf) => {}
+                Err(_) => return Err(corrupt(path, "cannot be inflated")),
+            }
+            // Stop once the input is used up and no output is pending
+            if result.bytes_consumed == 0 && result.bytes_written == 0 {
+                break;
+            }
+        }
+        if read == 0 {
+            return Err(corrupt(path, "is truncated"));
+        }
+    }
+}
+
+/// Rebuild an object from its delta base and the delta instructions
+fn apply_delta(base: &[u8], delta: &[u8], path: &Path) -> Result<Vec<u8>, SwhidError> {
+    let invalid = || corrupt(path, "has an invalid delta");
+    let mut position = 0;
+    let mut next = || -> Result<u8, SwhidError> {
+        let byte = *delta.get(position).ok_or_else(invalid)?;
+        position += 1;
+        Ok(byte)
+    };
+    let mut size = || -> Result<usize, SwhidError> {
+        let (mut value, mut shift) = (0usize, 0u32);
+        loop {
+            let byte = next()?;
+            value |= usize::from(byte & 0x7f).checked_shl(shift).ok_or_else(invalid)?;
+            shift += 7;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+    };
+    let (base_size, target_size) = (size()?, size()?);
+    if base_size != base.len() {
+        return Err(invalid());
+    }
+
+    // The size is untrusted until the delta is applied
+    let mut target = Vec::with_capacity(target_size.min(MAX_PREALLOCATED_SIZE));
+    while position < delta.len() {
+        let op = delta[position];
+        position += 1;
+        if op & 0x80 != 0 {
+            // Copy a range of the base, with only the non-zero bytes of its
+            // offset and length stored
+            let (mut offset, mut length) = (0usize, 0usize);
+            for i in 0..4 {
+                if op & (1 << i) != 0 {
+                    offset |= usize::from(*delta.get(position).ok_or_else(invalid)?) << (8 * i);
+                    position += 1;
+                }
+            }
+            for i in 0..3 {
+                if op & (0x10 << i) != 0 {
+                    length |= usize::from(*delta.get(position).ok_or_else(invalid)?) << (8 * i);
+                    position += 1;
+                }
+            }
+            if length == 0 {
+                length = 0x10000;
+            }
+            let end = offset.checked_add(length).ok_or_else(invalid)?;
+            target.extend_from_slice(base.get(offset..end).ok_or_else(invalid)?);
+        } else if op != 0 {
+            // Insert the next `op` bytes of the delta
+            let end = position + usize::from(op);
+            target.extend_from_slice(delta.get(position..end).ok_or_else(invalid)?);
+            position = end;
+        } else {
+            return Err(invalid());
+        }
+    }
+    if target.len() != target_size {
+        return Err(invalid());
+    }
+    Ok(target)
+}
+
+fn read_byte<R: Read>(reader: &mut R) -> Result<u8, SwhidError> {
+    let mut byte = [0u8; 1];
+    reader.read_exact(&mut byte)?;
+    Ok(byte[0])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::hash_git_object;
+    use crate::release::Release;
+    use crate::revision::Revision;
+    use crate::snapshot::{Snapshot, SnapshotTarget};
+    use crate::swhid::Swhid;
+    use miniz_oxide::deflate::compress_to_vec_zlib;
+    use tempfile::TempDir;
+
+    const COMMIT: &[u8] = b"tree 37d98dc6f78d31f91c15676c00d504e425b0e9a7\n\
+author Jane Doe <jane@example.org> 1700000000 +0100\n\
+committer Jane Doe <jane@example.org> 1700000000 +0100\n\
+\n\
+Initial commit\n";
+
+    /// An object stored in a hand-built packfile
+    enum PackEntry {
+        Full(u8, &'static [u8]),
+        /// A delta on an earlier entry of the pack, by position
+        OfsDelta(usize, Vec<u8>),
+        RefDelta([u8; 20], Vec<u8>),
+    }
+
+    /// A bare repository whose `HEAD` points to `refs/heads/main`
+    fn empty_repo() -> TempDir {
+        let temp_dir = TempDir::new().unwrap();
+        fs::create_dir_all(temp_dir.path().join("objects/pack")).unwrap();
+        fs::create_dir_all(temp_dir.path().join("refs/heads")).unwrap();
+        fs::write(temp_dir.path().join("HEAD"), "ref: refs/heads/main\n").unwrap();
+        temp_dir
+    }
+
+    fn write_loose_object(git_dir: &Path, kind: &str, data: &[u8]) -> [u8; 20] {
+        let id = hash_git_object(kind, data);
+        let hex = hex::encode(id);
+        let dir = git_dir.join("objects").join(&hex[..2]);
+        fs::create_dir_all(&dir).unwrap();
+        let mut object = format!("{} {}\0", kind, data.len()).into_bytes();
+        object.extend_from_slice(data);
+        fs::write(dir.join(&hex[2..]), compress_to_vec_zlib(&object, 6)).unwrap();
+        id
+    }
+
+    /// Write `entries`, with the ids of the objects they hold, as a pack and its index
+    fn write_pack(git_dir: &Path, entries: Vec<([u8; 20], PackEntry)>) {
+        let mut pack = b"PACK\x00\x00\x00\x02".to_vec();
+        pack.extend_from_slice(&(entries.len() as u32).to_be_bytes());
+        let mut offsets: Vec<usize> = Vec::new();
+        for (_, entry) in &entries {
+            offsets.push(pack.len());
+            let (kind, data) = match entry {
+                PackEntry::Full(kind, data) => (*kind, data.to_vec()),
+                PackEntry::OfsDelta(_, delta) | PackEntry::RefDelta(_, delta) => {
+                    (if matches!(entry, PackEntry::OfsDelta(..)) { 6 } else { 7 }, delta.clone())
+                }
+            };
+            let mut size = data.len() >> 4;
+            let mut byte = (kind << 4) | (data.len() & 0xf) as u8;
+            while size > 0 {
+                pack.push(byte | 0x80);
+                byte = (size & 0x7f) as u8;
+                size >>= 7;
+            }
+            pack.push(byte);
+            match entry {
+                PackEntry::OfsDelta(base, _) => {
+                    let mut distance = offsets.last().unwrap() - offsets[*base];
+                    let mut encoded = vec![(distance & 0x7f) as u8];
+                    distance >>= 7;
+                    while distance > 0 {
+                        distance -= 1;
+                        encoded.insert(0, 0x80 | (distance & 0x7f) as u8);
+                        distance >>= 7;
+                    }
+                    pack.extend_from_slice(&encoded);
+                }
+                PackEntry::RefDelta(base, _) => pack.extend_from_slice(base),
+                PackEntry::Full(..) => {}
+            }
+            pack.extend_from_slice(&compress_to_vec_zlib(&data, 6));
+        }
+        pack.extend_from_slice(&[0; 20]);
+
+        let mut sorted: Vec<([u8; 20], usize)> = entries.iter().map(|(id, _)| *id).zip(offsets).collect();
+        sorted.sort();
+        let mut index = b"\xfftOc\x00\x00\x00\x02".to_vec();
+        for byte in 0..=255u8 {
+            let count = sorted.iter().filter(|(id, _)| id[0] <= byte).count() as u32;
+            index.extend_from_slice(&count.to_be_bytes());
+        }
+        sorted.iter().for_each(|(id, _)| index.extend_from_slice(id));
+        sorted.iter().for_each(|_| index.extend_from_slice(&[0; 4]));
+        sorted.iter().for_each(|(_, offset)| index.extend_from_slice(&(*offset as u32).to_be_bytes()));
+        index.extend_from_slice(&[0; 40]);
+
+        fs::write(git_dir.join("objects/pack/pack-test.pack"), pack).unwrap();
+        fs::write(git_dir.join("objects/pack/pack-test.idx"), index).unwrap();
+    }
+
+    #[test]
+    fn test_read_loose_objects() {
+        let repo = empty_repo();
+        let blob = write_loose_object(repo.path(), "blob", b"hello\n");
+        let commit = write_loose_object(repo.path(), "commit", COMMIT);
+        fs::write(repo.path().join("refs/heads/main"), format!("{}\n", hex::encode(commit))).unwrap();
+
+        let reader = GitReader::open(repo.path()).unwrap();
+        assert_eq!(reader.read_object(&blob).unwrap(), (ObjectType::Content, b"hello\n".to_vec()));
+        assert_eq!(
+            Revision::from_backend(&reader, "HEAD").unwrap().swhid(),
+            Swhid::new(ObjectType::Revision, commit)
+        );
+        assert!(matches!(reader.read_object(&[0; 20]), Err(SwhidError::Git(_))));
+
+        // A tampered object no longer hashes to its id
+        let hex = hex::encode(commit);
+        fs::write(
+            repo.path().join("objects").join(&hex[..2]).join(&hex[2..]),
+            compress_to_vec_zlib(b"commit 4\0tree", 6),
+        )
+        .unwrap();
+        assert!(Revision::from_backend(&reader, "main").is_err());
+    }
+
+    #[test]
+    fn test_read_packed_objects() {
+        let repo = empty_repo();
+        let hello = hash_git_object("blob", b"hello world\n");
+        let there = hash_git_object("blob", b"hello there world\n");
+        let base = write_loose_object(repo.path(), "blob", b"base\n");
+        let more = hash_git_object("blob", b"base\nmore\n");
+        let empty = hash_git_object("blob", b"");
+        let commit = hash_git_object("commit", COMMIT);
+
+        let mut there_delta = vec![12, 18, 0x91, 0, 6, 6];
+        there_delta.extend_from_slice(b"there ");
+        there_delta.extend_from_slice(&[0x91, 6, 6]);
+        let mut more_delta = vec![5, 10, 0x90, 5, 5];
+        more_delta.extend_from_slice(b"more\n");
+        write_pack(
+            repo.path(),
+            vec![
+                (hello, PackEntry::Full(3, b"hello world\n")),
+                (commit, PackEntry::Full(1, COMMIT)),
+                (there, PackEntry::OfsDelta(0, there_delta)),
+                (more, PackEntry::RefDelta(base, more_delta)),
+                (empty, PackEntry::Full(3, b"")),
+            ],
+        );
+
+        let reader = GitReader::open(repo.path()).unwrap();
+        for (id, data) in [
+            (hello, &b"hello world\n"[..]),
+            (there, b"hello there world\n"),
+            (more, b"base\nmore\n"),
+            (empty, b""),
+        ] {
+            assert_eq!(reader.read_object(&id).unwrap(), (ObjectType::Content, data.to_vec()));
+        }
+        assert_eq!(reader.object_type(&commit).unwrap(), ObjectType::Revision);
+        assert_eq!(
+            Revision::from_backend(&reader, &hex::encode(commit)).unwrap().swhid(),
+            Swhid::new(ObjectType::Revision, commit)
+        );
+    }
+
+    #[test]
+    fn test_inflate_exact_untrusted_size() {
+        let data = b"hello world\n".repeat(2000);
+        let compressed = compress_to_vec_zlib(&data, 6);
+        let path = Path::new("pack");
+        assert_eq!(inflate_exact(&mut &compressed[..], data.len(), path).unwrap(), data);
+        for size in [0, data.len() - 1, data.len() + 1, usize::MAX] {
+            match inflate_exact(&mut &compressed[..], size, path) {
+                Err(SwhidError::PathIo(_, err)) => assert_eq!(err.kind(), io::ErrorKind::InvalidData),
+                other => panic!("size {}: {:?}", size, other),
+            }
+        }
+        assert!(matches!(
+            inflate_exact(&mut &compressed[..compressed.len() / 2], data.len(), path),
+            Err(SwhidError::Git(_))
+        ));
+    }
+
+    #[test]
+    fn test_invalid_delta() {
+        let repo = empty_repo();
+        let hello = hash_git_object("blob", b"hello world\n");
+        let broken = [1; 20];
+        // Announces a target of 2^62 bytes, which must not be allocated up front
+        let mut huge_delta = vec![12];
+        huge_delta.extend_from_slice(&[0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x40]);
+        huge_delta.extend_from_slice(&[0x90, 12]);
+        for delta in [vec![12, 5, 0], huge_delta] {
+            write_pack(
+                repo.path(),
+                vec![
+                    (hello, PackEntry::Full(3, b"hello world\n")),
+                    (broken, PackEntry::OfsDelta(0, delta)),
+                ],
+            );
+            let reader = GitReader::open(repo.path()).unwrap();
+            assert!(matches!(reader.read_object(&broken), Err(SwhidError::Git(_))));
+        }
+    }
+
+    #[test]
+    fn test_references() {
+        let repo = empty_repo();
+        let commit = write_loose_object(repo.path(), "commit", COMMIT);
+        let tag_data = format!(
+            "object {}\ntype commit\ntag v1.0\ntagger Jane Doe <jane@example.org> 1700000000 +0100\n\nRelease 1.0\n",
+            hex::encode(commit)
+        );
+        let tag = write_loose_object(repo.path(), "tag", tag_data.as_bytes());
+
+        // The loose `main` overrides the packed one
+        fs::write(
+            repo.path().join("packed-refs"),
+            format!(
+                "# pack-refs with: peeled fully-peeled sorted \n{0} refs/heads/main\n{1} refs/tags/v1.0\n^{0}\n",
+                hex::encode([7; 20]),
+                hex::encode(tag)
+            ),
+        )
+        .unwrap();
+        fs::write(repo.path().join("refs/heads/main"), format!("{}\n", hex::encode(commit))).unwrap();
+
+        let reader = GitReader::open(repo.path()).unwrap();
+        let mut expected = Snapshot::new();
+        for (name, target) in [
+            ("HEAD", SnapshotTarget::Alias(b"refs/heads/main".to_vec())),
+            ("refs/heads/main", SnapshotTarget::Object(Swhid::new(ObjectType::Revision, commit))),
+            ("refs/tags/v1.0", SnapshotTarget::Object(Swhid::new(ObjectType::Release, tag))),
+        ] {
+            expected.add_branch(name.as_bytes().to_vec(), target).unwrap();
+        }
+        assert_eq!(Snapshot::from_backend(&reader).unwrap(), expected);
+        assert_eq!(
+            Release::from_backend(&reader, "v1.0").unwrap().swhid(),
+            Swhid::new(ObjectType::Release, tag)
+        );
+        assert_eq!(
+            Revision::from_backend(&reader, "v1.0").unwrap().swhid(),
+            Swhid::new(ObjectType::Revision, commit)
+        );
+    }
+
+    #[test]
+    fn test_open() {
+        // A working tree whose `.git` is a file, as for linked working trees
+        let repo = empty_repo();
+        let worktree = TempDir::new().unwrap();
+        fs::write(worktree.path().join(".git"), format!("gitdir: {}\n", repo.path().display())).unwrap();
+        assert_eq!(GitReader::open(worktree.path()).unwrap().git_dir, repo.path());
+
+        let not_a_repo = TempDir::new().unwrap();
+        assert!(matches!(GitReader::open(not_a_repo.path()), Err(SwhidError::Git(_))));
+
+        fs::write(
+            repo.path().join("config"),
+            "[core]\n\trepositoryformatversion = 1\n[extensions]\n\tobjectFormat = sha256\n",
+        )
+        .unwrap();
+        assert!(matches!(
+            GitReader::open(repo.path()),
+            Err(SwhidError::UnsupportedOperation(_))
+        ));
+    }
+
+    /// Objects packed by libgit2, with its own deltas, read the same as through libgit2
+    #[cfg(feature = "git")]
+    #[test]
+    fn test_matches_libgit2() {
+        use git2::{Repository, Signature, Time};
+        use std::io::Write;
+
+        let temp_dir = TempDir::new().unwrap();
+        let repo = Repository::init(temp_dir.path()).unwrap();
+        let signature = Signature::new("Jane Doe", "jane@example.org", &Time::new(1_700_000_000, 60)).unwrap();
+        let mut parents = Vec::new();
+        let text: String = (0..200).map(|i| format!("line {}\n", i)).collect();
+        for i in 0..3 {
+            let mut builder = repo.treebuilder(None).unwrap();
+            let blob = repo.blob(format!("{}change {}\n", text, i).as_bytes()).unwrap();
+            builder.insert("file.txt", blob, 0o100644).unwrap();
+            let tree = repo.find_tree(builder.write().unwrap()).unwrap();
+            let parent: Vec<_> = parents.iter().map(|id| repo.find_commit(*id).unwrap()).collect();
+            let parent: Vec<_> = parent.iter().collect();
+            let commit = repo.commit(Some("HEAD"), &signature, &signature, "Commit\n", &tree, &parent).unwrap();
+            parents = vec![commit];
+        }
+        let head = repo.find_object(parents[0], None).unwrap();
+        repo.tag("v1.0", &head, &signature, "Release 1.0\n", false).unwrap();
+
+        // Move every object to a pack
+        let mut builder = repo.packbuilder().unwrap();
+        let mut walk = repo.revwalk().unwrap();
+        walk.push_head().unwrap();
+        builder.insert_walk(&mut walk).unwrap();
+        builder.insert_object(repo.refname_to_id("refs/tags/v1.0").unwrap(), None).unwrap();
+        let mut buffer = git2::Buf::new();
+        builder.write_buf(&mut buffer).unwrap();
+        let odb = repo.odb().unwrap();
+        let mut writer = odb.packwriter().unwrap();
+        writer.write_all(&buffer).unwrap();
+        writer.commit().unwrap();
+        let mut ids = Vec::new();
+        odb.foreach(|id| {
+            ids.push(*id);
+            true
+        })
+        .unwrap();
+        for entry in fs::read_dir(repo.path().join("objects")).unwrap() {
+            let entry = entry.unwrap();
+            if entry.file_name().len() == 2 {
+                fs::remove_dir_all(entry.path()).unwrap();
+            }
+        }
+
+        let reader = GitReader::open(temp_dir.path()).unwrap();
+        for id in ids {
+            let object = odb.read(id).unwrap();
+            let id: [u8; 20] = id.as_bytes().try_into().unwrap();
+            assert_eq!(reader.read_object(&id).unwrap().1, object.data());
+        }
+        assert_eq!(Snapshot::from_backend(&reader).unwrap(), Snapshot::from_repo(temp_dir.path()).unwrap());
+        assert_eq!(
+            Revision::from_backend(&reader, "HEAD").unwrap(),
+            Revision::from_repo(temp_dir.path(), "HEAD").unwrap()
+        );
+    }
+}
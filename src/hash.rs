@@ -1,20 +1,237 @@
-use sha1_checked::{Sha1, Digest};
+use std::io::{ErrorKind, Read};
+use sha1_checked::Sha1;
+use sha2::{Digest, Sha256};
+use crate::error::SwhidError;
+
+/// Incremental hash function for Git objects
+///
+/// Each implementation is one digest algorithm. The `_with` functions, such
+/// as [`hash_git_object_with`], hash objects with the implementation chosen
+/// at compile time, and [`HashAlgorithm`] chooses one at runtime. SWHIDs use
+/// [`Sha1CheckedHasher`], which the other functions of this module use.
+pub trait GitObjectHasher {
+    /// Digest produced, e.g. `[u8; 20]`
+    type Output;
+
+    /// Start a new hash
+    fn new() -> Self;
+
+    /// Feed data to the hash
+    fn update(&mut self, data: &[u8]);
+
+    /// Finish the hash
+    fn finalize(self) -> Self::Output;
+}
+
+/// SHA-1 with collision detection, as Git's SHA1DC and the SWHID spec require
+pub struct Sha1CheckedHasher(Sha1);
+
+impl Sha1CheckedHasher {
+    /// Finish the hash, reporting whether a collision attack was detected
+    ///
+    /// On detection, the hash returned is the safe hash of SHA1DC rather than
+    /// the plain SHA-1 one, so it never matches the id of a colliding object.
+    pub fn finalize_checked(self) -> ([u8; 20], bool) {
+        let result = self.0.try_finalize();
+        ((*result.hash()).into(), result.has_collision())
+    }
+}
+
+impl GitObjectHasher for Sha1CheckedHasher {
+    type Output = [u8; 20];
+
+    fn new() -> Self {
+        Self(Sha1::new())
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        Digest::update(&mut self.0, data);
+    }
+
+    fn finalize(self) -> [u8; 20] {
+        self.0.finalize().into()
+    }
+}
+
+/// Plain SHA-1, without collision detection
+///
+/// Faster than [`Sha1CheckedHasher`], and equal to it except on objects
+/// crafted for a collision attack. Only for input that is trusted.
+pub struct Sha1Hasher(Sha1);
+
+impl GitObjectHasher for Sha1Hasher {
+    type Output = [u8; 20];
+
+    fn new() -> Self {
+        Self(Sha1::builder().detect_collision(false).build())
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        Digest::update(&mut self.0, data);
+    }
+
+    fn finalize(self) -> [u8; 20] {
+        self.0.finalize().into()
+    }
+}
+
+/// SHA-256, as in SHA-256 Git repositories
+pub struct Sha256Hasher(Sha256);
+
+impl GitObjectHasher for Sha256Hasher {
+    type Output = [u8; 32];
+
+    fn new() -> Self {
+        Self(Sha256::new())
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        Digest::update(&mut self.0, data);
+    }
+
+    fn finalize(self) -> [u8; 32] {
+        self.0.finalize().into()
+    }
+}
+
+/// Two hashes of the same data, computed in a single pass
+impl<A: GitObjectHasher, B: GitObjectHasher> GitObjectHasher for (A, B) {
+    type Output = (A::Output, B::Output);
+
+    fn new() -> Self {
+        (A::new(), B::new())
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        self.0.update(data);
+        self.1.update(data);
+    }
+
+    fn finalize(self) -> Self::Output {
+        (self.0.finalize(), self.1.finalize())
+    }
+}
+
+/// Digest algorithm of a [`GitObjectHasher`], to choose one at runtime
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum HashAlgorithm {
+    /// [`Sha1CheckedHasher`], as SWHIDs use
+    #[default]
+    Sha1Checked,
+    /// [`Sha1Hasher`]
+    Sha1,
+    /// [`Sha256Hasher`]
+    Sha256,
+}
+
+impl HashAlgorithm {
+    /// Length of the digests, in bytes
+    pub fn output_len(&self) -> usize {
+        match self {
+            HashAlgorithm::Sha1Checked | HashAlgorithm::Sha1 => 20,
+            HashAlgorithm::Sha256 => 32,
+        }
+    }
+
+    /// Hash a Git object (header + data), as [`hash_git_object_with`]
+    pub fn hash_git_object(&self, git_type: &str, data: &[u8]) -> Vec<u8> {
+        match self {
+            HashAlgorithm::Sha1Checked => hash_git_object_with::<Sha1CheckedHasher>(git_type, data).to_vec(),
+            HashAlgorithm::Sha1 => hash_git_object_with::<Sha1Hasher>(git_type, data).to_vec(),
+            HashAlgorithm::Sha256 => hash_git_object_with::<Sha256Hasher>(git_type, data).to_vec(),
+        }
+    }
+
+    /// Hash a streamed Git object, as [`hash_git_object_reader_with`]
+    pub fn hash_git_object_reader<R: Read>(
+        &self,
+        git_type: &str,
+        reader: R,
+        length: u64,
+    ) -> Result<Vec<u8>, SwhidError> {
+        Ok(match self {
+            HashAlgorithm::Sha1Checked => {
+                hash_git_object_reader_with::<Sha1CheckedHasher, R>(git_type, reader, length)?.to_vec()
+            }
+            HashAlgorithm::Sha1 => hash_git_object_reader_with::<Sha1Hasher, R>(git_type, reader, length)?.to_vec(),
+            HashAlgorithm::Sha256 => {
+                hash_git_object_reader_with::<Sha256Hasher, R>(git_type, reader, length)?.to_vec()
+            }
+        })
+    }
+}
+
+/// A [`HashAlgorithm`] whose digests fit v1 SWHIDs, to hash trees with
+///
+/// See [`SwhidComputer::with_hash_algorithm`](crate::SwhidComputer::with_hash_algorithm).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum V1HashAlgorithm {
+    /// [`Sha1CheckedHasher`], as SWHIDs use
+    #[default]
+    Sha1Checked,
+    /// [`Sha1Hasher`]
+    Sha1,
+}
+
+impl V1HashAlgorithm {
+    /// Hash a Git object (header + data) into the 20 bytes of a v1 SWHID
+    pub fn hash_git_object(&self, git_type: &str, data: &[u8]) -> [u8; 20] {
+        match self {
+            V1HashAlgorithm::Sha1Checked => hash_git_object_with::<Sha1CheckedHasher>(git_type, data),
+            V1HashAlgorithm::Sha1 => hash_git_object_with::<Sha1Hasher>(git_type, data),
+        }
+    }
+
+    /// Hash a streamed Git object, as [`hash_git_object_reader_with`]
+    pub fn hash_git_object_reader<R: Read>(
+        &self,
+        git_type: &str,
+        reader: R,
+        length: u64,
+    ) -> Result<[u8; 20], SwhidError> {
+        match self {
+            V1HashAlgorithm::Sha1Checked => hash_git_object_reader_with::<Sha1CheckedHasher, R>(git_type, reader, length),
+            V1HashAlgorithm::Sha1 => hash_git_object_reader_with::<Sha1Hasher, R>(git_type, reader, length),
+        }
+    }
+}
+
+impl From<V1HashAlgorithm> for HashAlgorithm {
+    fn from(algorithm: V1HashAlgorithm) -> Self {
+        match algorithm {
+            V1HashAlgorithm::Sha1Checked => HashAlgorithm::Sha1Checked,
+            V1HashAlgorithm::Sha1 => HashAlgorithm::Sha1,
+        }
+    }
+}
+
+impl TryFrom<HashAlgorithm> for V1HashAlgorithm {
+    type Error = SwhidError;
+
+    /// Fails with `SwhidError::UnsupportedOperation` for [`HashAlgorithm::Sha256`]
+    fn try_from(algorithm: HashAlgorithm) -> Result<Self, Self::Error> {
+        match algorithm {
+            HashAlgorithm::Sha1Checked => Ok(V1HashAlgorithm::Sha1Checked),
+            HashAlgorithm::Sha1 => Ok(V1HashAlgorithm::Sha1),
+            HashAlgorithm::Sha256 => Err(SwhidError::UnsupportedOperation(format!(
+                "{:?} hashes do not fit v1 SWHIDs",
+                algorithm
+            ))),
+        }
+    }
+}
 
 /// Git-style SHA1 hash computation (collision-resistant)
 /// Uses SHA1-checked to prevent SHATTERED-style attacks as required by SWHID spec
 pub fn sha1_git_hash(data: &[u8]) -> [u8; 20] {
-    let mut hasher = Sha1::new();
-    let header = format!("blob {}\0", data.len());
-    hasher.update(header.as_bytes());
-    hasher.update(data);
-    hasher.finalize().into()
+    hash_git_object("blob", data)
 }
 
 /// Standard SHA1 hash computation (collision-resistant)
 pub fn sha1_hash(data: &[u8]) -> [u8; 20] {
-    let mut hasher = Sha1::new();
+    let mut hasher = Sha1CheckedHasher::new();
     hasher.update(data);
-    hasher.finalize().into()
+    hasher.finalize()
 }
 
 /// Git object header formatting
@@ -22,14 +239,125 @@ pub fn git_object_header(git_type: &str, length: usize) -> Vec<u8> {
     format!("{} {}\0", git_type, length).into_bytes()
 }
 
+/// Hash a Git object (header + data) with the hasher `H`
+pub fn hash_git_object_with<H: GitObjectHasher>(git_type: &str, data: &[u8]) -> H::Output {
+    let mut hasher = H::new();
+    hasher.update(&git_object_header(git_type, data.len()));
+    hasher.update(data);
+    hasher.finalize()
+}
+
 /// Hash a Git object (header + data) using SHA1-checked
 /// This ensures compatibility with Git's SHA1DC usage and SWHID spec compliance
 pub fn hash_git_object(git_type: &str, data: &[u8]) -> [u8; 20] {
-    let mut hasher = Sha1::new();
-    let header = git_object_header(git_type, data.len());
-    hasher.update(&header);
+    hash_git_object_with::<Sha1CheckedHasher>(git_type, data)
+}
+
+/// Hash a Git object (header + data), reporting whether a collision attack was detected
+///
+/// See [`Sha1CheckedHasher::finalize_checked`].
+pub(crate) fn hash_git_object_checked(git_type: &str, data: &[u8]) -> ([u8; 20], bool) {
+    let mut hasher = Sha1CheckedHasher::new();
+    hasher.update(&git_object_header(git_type, data.len()));
     hasher.update(data);
-    hasher.finalize().into()
+    hasher.finalize_checked()
+}
+
+/// Hash a Git object (header + data) with SHA-256, as in SHA-256 Git repositories
+pub fn hash_git_object_sha256(git_type: &str, data: &[u8]) -> [u8; 32] {
+    hash_git_object_with::<Sha256Hasher>(git_type, data)
+}
+
+/// Hash a Git object streamed from a reader of known length with the hasher `H`
+///
+/// The header is written from `length` up front, then the reader is consumed
+/// until EOF. Fails with `SwhidError::InvalidInput` if the reader yields a
+/// different number of bytes than announced.
+pub fn hash_git_object_reader_with<H: GitObjectHasher, R: Read>(
+    git_type: &str,
+    reader: R,
+    length: u64,
+) -> Result<H::Output, SwhidError> {
+    let mut hasher = H::new();
+    hasher.update(format!("{} {}\0", git_type, length).as_bytes());
+    feed_reader(reader, length, |chunk| hasher.update(chunk))?;
+    Ok(hasher.finalize())
+}
+
+/// Hash a Git object whose data is streamed from a reader of known length
+///
+/// As [`hash_git_object_reader_with`], using SHA1-checked.
+pub fn hash_git_object_reader<R: Read>(
+    git_type: &str,
+    reader: R,
+    length: u64,
+) -> Result<[u8; 20], SwhidError> {
+    hash_git_object_reader_with::<Sha1CheckedHasher, R>(git_type, reader, length)
+}
+
+/// Hash a streamed Git object with both SHA-1 and SHA-256 in a single pass
+///
+/// Each chunk read is fed to both hashers, so the data is only read once.
+/// Errors are the same as for [`hash_git_object_reader`].
+pub fn hash_git_object_reader_dual<R: Read>(
+    git_type: &str,
+    reader: R,
+    length: u64,
+) -> Result<([u8; 20], [u8; 32]), SwhidError> {
+    hash_git_object_reader_with::<(Sha1CheckedHasher, Sha256Hasher), R>(git_type, reader, length)
+}
+
+/// Parse a 40-digit hex Git object id, as found in commit and tag objects
+pub(crate) fn parse_object_id(hex: &[u8]) -> Option<[u8; 20]> {
+    let mut id = [0u8; 20];
+    hex::decode_to_slice(hex, &mut id).ok()?;
+    Some(id)
+}
+
+/// Split the headers of a commit or tag object into keys and values
+///
+/// Headers end at the first empty line, before the message. Continuation
+/// lines of multi-line headers, such as signatures, have an empty key.
+pub(crate) fn git_object_headers(data: &[u8]) -> impl Iterator<Item = (&[u8], &[u8])> {
+    data.split(|&b| b == b'\n')
+        .take_while(|line| !line.is_empty())
+        .map(|line| match line.iter().position(|&b| b == b' ') {
+            Some(space) => (&line[..space], &line[space + 1..]),
+            None => (line, &[][..]),
+        })
+}
+
+/// Pass exactly `length` bytes from `reader` to `update`, chunk by chunk
+pub(crate) fn feed_reader<R: Read>(
+    mut reader: R,
+    length: u64,
+    mut update: impl FnMut(&[u8]),
+) -> Result<(), SwhidError> {
+    let mut buffer = [0u8; 64 * 1024];
+    let mut read_total: u64 = 0;
+    loop {
+        let n = match reader.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        };
+        read_total += n as u64;
+        if read_total > length {
+            return Err(SwhidError::InvalidInput(format!(
+                "Reader yielded more than the expected {} bytes", length
+            )));
+        }
+        update(&buffer[..n]);
+    }
+
+    if read_total != length {
+        return Err(SwhidError::InvalidInput(format!(
+            "Reader yielded {} bytes, expected {}", read_total, length
+        )));
+    }
+
+    Ok(())
 }
 
 #[cfg(test)]
@@ -99,6 +427,67 @@ mod tests {
         assert_ne!(blob_hash, tree_hash);
     }
 
+    #[test]
+    fn test_hash_git_object_reader() {
+        let data = b"Hello, World!";
+        let hash = hash_git_object_reader("blob", &data[..], data.len() as u64).unwrap();
+        assert_eq!(hash, sha1_git_hash(data));
+
+        let too_few = hash_git_object_reader("blob", &data[..], data.len() as u64 + 1);
+        assert!(matches!(too_few, Err(SwhidError::InvalidInput(_))));
+
+        let too_many = hash_git_object_reader("blob", &data[..], data.len() as u64 - 1);
+        assert!(matches!(too_many, Err(SwhidError::InvalidInput(_))));
+    }
+
+    #[test]
+    fn test_hash_git_object_sha256_known_values() {
+        // As computed by `git hash-object` in a repository using SHA-256
+        let empty = hash_git_object_sha256("blob", b"");
+        assert_eq!(hex::encode(empty), "473a0f4c3be8a93681a267e3b1e9a7dcda1185436fe141f7749120a303721813");
+
+        let hello = hash_git_object_sha256("blob", b"hello\n");
+        assert_eq!(hex::encode(hello), "2cf8d83d9ee29543b34a87727421fdecb7e3f3a183d337639025de576db9ebb4");
+    }
+
+    #[test]
+    fn test_hash_git_object_reader_dual() {
+        for data in [&b""[..], b"hello\n", &[b'x'; 200 * 1024]] {
+            let (sha1, sha256) = hash_git_object_reader_dual("blob", data, data.len() as u64).unwrap();
+            assert_eq!(sha1, hash_git_object("blob", data));
+            assert_eq!(sha256, hash_git_object_sha256("blob", data));
+        }
+
+        let too_few = hash_git_object_reader_dual("blob", &b"abc"[..], 4);
+        assert!(matches!(too_few, Err(SwhidError::InvalidInput(_))));
+    }
+
+    #[test]
+    fn test_git_object_hashers() {
+        let data = b"hello\n";
+        let sha1 = hash_git_object_with::<Sha1Hasher>("blob", data);
+        assert_eq!(sha1, hash_git_object("blob", data));
+        assert_eq!(hash_git_object_with::<Sha256Hasher>("blob", data), hash_git_object_sha256("blob", data));
+
+        for algorithm in [HashAlgorithm::Sha1Checked, HashAlgorithm::Sha1, HashAlgorithm::Sha256] {
+            let hash = algorithm.hash_git_object("blob", data);
+            assert_eq!(hash.len(), algorithm.output_len());
+            assert_eq!(algorithm.hash_git_object_reader("blob", &data[..], 6).unwrap(), hash);
+        }
+        assert_eq!(HashAlgorithm::default().hash_git_object("blob", data), sha1);
+        assert_eq!(HashAlgorithm::Sha256.hash_git_object("blob", data), hash_git_object_sha256("blob", data));
+
+        assert_eq!(V1HashAlgorithm::Sha1.hash_git_object("blob", data), sha1);
+        assert_eq!(V1HashAlgorithm::Sha1Checked.hash_git_object_reader("blob", &data[..], 6).unwrap(), sha1);
+        for algorithm in [V1HashAlgorithm::Sha1Checked, V1HashAlgorithm::Sha1] {
+            assert_eq!(V1HashAlgorithm::try_from(HashAlgorithm::from(algorithm)).unwrap(), algorithm);
+        }
+        assert!(matches!(
+            V1HashAlgorithm::try_from(HashAlgorithm::Sha256),
+            Err(SwhidError::UnsupportedOperation(_))
+        ));
+    }
+
     #[test]
     fn test_sha1_hash() {
         let data = b"test data";
@@ -0,0 +1,173 @@
+//! Checking the objects of a repository by hashing them again
+//!
+//! [`IntegrityReport::from_backend`] reads every object reachable from the
+//! references of a [`GitBackend`] and hashes it with this crate rather than
+//! trusting its id. This audits the repository and checks that this crate
+//! hashes objects as Git does. Hashing uses SHA-1 collision detection, as
+//! Git's SHA1DC does, so objects crafted for a collision attack are found too.
+
+use std::collections::HashSet;
+use crate::backend::{GitBackend, GitReference};
+use crate::directory::{parse_tree_object, EntryType};
+use crate::error::SwhidError;
+use crate::hash::hash_git_object_checked;
+use crate::release::Release;
+use crate::revision::Revision;
+use crate::swhid::ObjectType;
+
+/// An object that does not hash to its id
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ObjectMismatch {
+    /// Id the object is stored under
+    pub id: [u8; 20],
+    pub object_type: ObjectType,
+    /// Id the object hashes to
+    pub actual: [u8; 20],
+    /// Whether the object carries a SHA-1 collision attack
+    pub collision: bool,
+}
+
+/// Outcome of [`IntegrityReport::from_backend`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IntegrityReport {
+    /// Number of objects hashed
+    pub checked: usize,
+    /// Objects that do not hash to their id, sorted by id
+    pub mismatches: Vec<ObjectMismatch>,
+}
+
+impl IntegrityReport {
+    /// Whether every object hashes to its id
+    pub fn is_ok(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+
+    /// Hash every object reachable from the references of `backend`
+    ///
+    /// Tags lead to their target, commits to their tree and parents, and
+    /// trees to their entries, except submodule commits, which belong to
+    /// another repository. Objects that do not hash to their id are
+    /// reported, and what they point to is not followed. Unreachable objects
+    /// are not checked. Fails if an object cannot be read, or if one that
+    /// hashes to its id cannot be parsed.
+    pub fn from_backend<B: GitBackend + ?Sized>(backend: &B) -> Result<Self, SwhidError> {
+        let mut report = IntegrityReport::default();
+        let mut pending = Vec::new();
+        for (_, reference) in backend.references()? {
+            // Symbolic references name another reference, which is listed too
+            if let GitReference::Direct(id) = reference {
+                pending.push(id);
+            }
+        }
+
+        let mut seen = HashSet::new();
+        while let Some(id) = pending.pop() {
+            if !seen.insert(id) {
+                continue;
+            }
+            let (object_type, data) = backend.read_object(&id)?;
+            let git_type = match object_type {
+                ObjectType::Content => "blob",
+                ObjectType::Directory => "tree",
+                ObjectType::Revision => "commit",
+                ObjectType::Release => "tag",
+                ObjectType::Snapshot => {
+                    return Err(SwhidError::Git(format!("Object {} is not a Git object", hex::encode(id))))
+                }
+            };
+            let (actual, collision) = hash_git_object_checked(git_type, &data);
+            report.checked += 1;
+            if actual != id {
+                report.mismatches.push(ObjectMismatch { id, object_type, actual, collision });
+                continue;
+            }
+
+            match object_type {
+                ObjectType::Directory => pending.extend(
+                    parse_tree_object(&data)?
+                        .into_iter()
+                        .filter(|entry| entry.entry_type != EntryType::Submodule)
+                        .map(|entry| entry.target),
+                ),
+                ObjectType::Revision => {
+                    let revision = Revision::from_git_object(data)?;
+                    pending.push(*revision.directory().v1_hash()?);
+                    for parent in revision.parents() {
+                        pending.push(*parent.v1_hash()?);
+                    }
+                }
+                ObjectType::Release => pending.push(*Release::from_git_object(data)?.target().v1_hash()?),
+                _ => {}
+            }
+        }
+
+        report.mismatches.sort_by_key(|mismatch| mismatch.id);
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::tests::MemoryBackend;
+    use crate::hash::hash_git_object;
+
+    /// A tag of a commit whose tree holds a file and a submodule
+    fn sample_backend() -> (MemoryBackend, [u8; 20], [u8; 20]) {
+        let mut backend = MemoryBackend::default();
+        let blob = backend.add_object(ObjectType::Content, "blob", b"hello\n");
+        let mut tree = b"160000 lib\0".to_vec();
+        tree.extend_from_slice(&[0x42; 20]);
+        tree.extend_from_slice(b"100644 lib.c\0");
+        tree.extend_from_slice(&blob);
+        let tree = backend.add_object(ObjectType::Directory, "tree", &tree);
+        let commit = format!(
+            "tree {}\nauthor Jane Doe <jane@example.org> 1700000000 +0000\n\
+             committer Jane Doe <jane@example.org> 1700000000 +0000\n\nFirst\n",
+            hex::encode(tree)
+        );
+        let commit = backend.add_object(ObjectType::Revision, "commit", commit.as_bytes());
+        let tag = format!("object {}\ntype commit\ntag v1.0\n\nRelease\n", hex::encode(commit));
+        let tag = backend.add_object(ObjectType::Release, "tag", tag.as_bytes());
+        backend.add_reference("HEAD", GitReference::Symbolic(b"refs/heads/main".to_vec()));
+        backend.add_reference("refs/heads/main", GitReference::Direct(commit));
+        backend.add_reference("refs/tags/v1.0", GitReference::Direct(tag));
+        (backend, blob, commit)
+    }
+
+    #[test]
+    fn test_integrity_report() {
+        let (backend, _, _) = sample_backend();
+        let report = IntegrityReport::from_backend(&backend).unwrap();
+        assert!(report.is_ok());
+        // The submodule commit is not looked up
+        assert_eq!(report.checked, 4);
+    }
+
+    #[test]
+    fn test_integrity_report_mismatches() {
+        let (mut backend, blob, commit) = sample_backend();
+        backend.objects.insert(blob, (ObjectType::Content, b"tampered\n".to_vec()));
+        let report = IntegrityReport::from_backend(&backend).unwrap();
+        assert_eq!(
+            report.mismatches,
+            vec![ObjectMismatch {
+                id: blob,
+                object_type: ObjectType::Content,
+                actual: hash_git_object("blob", b"tampered\n"),
+                collision: false,
+            }]
+        );
+        assert!(!report.is_ok());
+
+        // What a mismatching commit points to is not followed
+        backend.objects.insert(commit, (ObjectType::Revision, b"tree 0\n".to_vec()));
+        let report = IntegrityReport::from_backend(&backend).unwrap();
+        assert_eq!(report.checked, 2);
+        assert_eq!(report.mismatches.len(), 1);
+        assert_eq!(report.mismatches[0].id, commit);
+
+        backend.objects.remove(&commit);
+        assert!(matches!(IntegrityReport::from_backend(&backend), Err(SwhidError::Git(_))));
+    }
+}
@@ -7,13 +7,19 @@
 //! 
 //! - **Content SWHID**: Compute SWHIDs for individual files
 //! - **Directory SWHID**: Compute SWHIDs for directory trees
+//! - **Revision SWHID**: Compute SWHIDs for Git commits, from a commit object,
+//!   from metadata with `RevisionBuilder`, or, with the `git` feature, from a
+//!   local repository
+//! - **Release SWHID**: Compute SWHIDs for Git annotated tags, likewise, with
+//!   `ReleaseBuilder` for metadata
+//! - **Snapshot SWHID**: Compute SWHIDs for the branches of a repository
 //! - **Basic SWHID**: Core SWHID format: `swh:1:obj_type:hash`
 //! - **Qualified SWHID**: SWHIDs with qualifiers like origin, path, lines, bytes
 //! 
 //! ## Usage
 //! 
 //! ```rust
-//! use swhid::{Swhid, ObjectType, QualifiedSwhid};
+//! use swhid::{Origin, Swhid, ObjectType, QualifiedSwhid};
 //! 
 //! // Create a SWHID manually
 //! let hash = [0u8; 20];
@@ -22,7 +28,7 @@
 //! 
 //! // Create a qualified SWHID
 //! let qualified = QualifiedSwhid::new(swhid)
-//!     .with_origin("https://github.com/user/repo".to_string())
+//!     .with_origin(Origin::new("https://github.com/user/repo").unwrap())
 //!     .with_path(b"/src/main.rs".to_vec())
 //!     .with_lines(10, Some(20))
 //!     .with_bytes(5, Some(10));
@@ -30,18 +36,85 @@
 //! // Parse a SWHID from string
 //! let parsed = Swhid::from_string("swh:1:dir:0000000000000000000000000000000000000000").unwrap();
 //! assert_eq!(parsed.object_type(), ObjectType::Directory);
+//! 
+//! // Parse a qualified SWHID; qualifiers are emitted back in canonical order
+//! let qualified = QualifiedSwhid::from_string(
+//!     "swh:1:cnt:0000000000000000000000000000000000000000;lines=10-20;origin=https://example.org",
+//! ).unwrap();
+//! assert_eq!(qualified.core().object_type(), ObjectType::Content);
+//! assert_eq!(qualified.lines(), Some((10, Some(20))));
+//! assert_eq!(
+//!     qualified.to_string(),
+//!     "swh:1:cnt:0000000000000000000000000000000000000000;origin=https://example.org;lines=10-20",
+//! );
 //! ```
 //! 
 
 pub mod swhid;
+pub mod origin;
 pub mod hash;
 pub mod content;
 pub mod directory;
 pub mod error;
 pub mod computer;
+pub mod revision;
+pub mod release;
+pub mod snapshot;
+pub mod backend;
+pub mod revwalk;
+pub mod integrity;
+pub mod experimental;
+#[cfg(feature = "serde")]
+pub mod serde_compact;
+#[cfg(feature = "serde")]
+mod serde_impls;
+#[cfg(any(feature = "tar", feature = "zip"))]
+mod archive;
+mod git_dir;
+#[cfg(feature = "git")]
+mod git;
+#[cfg(feature = "git-reader")]
+mod git_reader;
+#[cfg(feature = "extended")]
+pub mod extended;
+#[cfg(feature = "extended")]
+pub mod extid;
+#[cfg(feature = "json")]
+pub mod manifest;
+#[cfg(feature = "api")]
+pub mod api;
 
-pub use swhid::{Swhid, ObjectType, QualifiedSwhid};
+pub use swhid::{Swhid, ObjectType, QualifiedSwhid, QualifiedSwhidBuilder, ParsedSwhid, min_unique_prefix_len, peek_object_type};
 pub use error::SwhidError;
-pub use computer::SwhidComputer;
-pub use content::Content;
-pub use directory::Directory; 
\ No newline at end of file
+pub use origin::Origin;
+pub use computer::{
+    BrokenSymlinkPolicy, ManifestMismatch, ManifestReport, QualifiedVerification, QualifierCheck,
+    SpecialFilePolicy, SwhidComputer, TreeDiff, Verification, VCS_NAMES,
+};
+#[cfg(feature = "unicode")]
+pub use computer::NormalizationForm;
+#[cfg(feature = "git")]
+pub use computer::GitTreePolicy;
+pub use content::{Content, LfsPointer};
+pub use hash::{HashAlgorithm, V1HashAlgorithm};
+#[cfg(feature = "multihash")]
+pub use content::MultiHash;
+pub use directory::{Directory, EntryType, WalkEntry};
+pub use revision::{GitSignature, Revision, RevisionBuilder};
+pub use release::{Release, ReleaseBuilder};
+pub use snapshot::{Snapshot, SnapshotTarget};
+pub use backend::{GitBackend, GitReference};
+pub use revwalk::{RevisionWalk, WalkOrder};
+pub use integrity::{IntegrityReport, ObjectMismatch};
+#[cfg(feature = "git")]
+pub use git::{compute_subtree_swhid, WorktreeComparison};
+#[cfg(feature = "git-reader")]
+pub use git_reader::GitReader;
+#[cfg(feature = "extended")]
+pub use extended::{ExtendedObjectType, ExtendedSwhid, MetadataAuthority, MetadataFetcher, RawExtrinsicMetadata};
+#[cfg(feature = "extended")]
+pub use extid::ExtId;
+#[cfg(feature = "json")]
+pub use manifest::TreeManifest;
+#[cfg(feature = "api")]
+pub use api::ArchiveClient;
\ No newline at end of file
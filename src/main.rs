@@ -1,12 +1,24 @@
-use clap::Parser;
-use std::io::Read;
-use std::path::Path;
-use swhid::SwhidComputer;
+use clap::error::ErrorKind;
+use clap::parser::ValueSource;
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Component, Path, PathBuf};
+use swhid::directory::Permissions;
+use swhid::{
+    EntryType, ManifestReport, ObjectType, QualifiedSwhid, QualifiedVerification, QualifierCheck, Swhid, SwhidComputer,
+    WalkEntry,
+};
 
 #[derive(Parser)]
-#[command(name = "swhid-cli")]
+#[command(name = "swhid")]
 #[command(about = "Compute Software Hash Identifiers (SWHID)")]
+#[command(after_help = "Exit status: 0 if everything matched, 1 if a SWHID or tree did not match, 2 on errors.")]
 struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// Type of object to identify
     #[arg(short, long, default_value = "auto")]
     obj_type: String,
@@ -23,10 +35,23 @@ struct Cli {
     #[arg(long, default_value = "true")]
     filename: bool,
 
-    /// Exclude directories using glob patterns
-    #[arg(short, long)]
+    /// Exclude files and directories matching glob patterns
+    #[arg(short, long, global = true)]
     exclude: Vec<String>,
 
+    /// Also identify every file and subdirectory of directory objects
+    #[arg(short, long, conflicts_with = "verify")]
+    recursive: bool,
+
+    /// Format of the results, with any subcommand
+    #[arg(long, global = true, value_enum, default_value_t = OutputFormat::Plain)]
+    output: OutputFormat,
+
+    /// Same as `--output jsonl`
+    #[cfg(feature = "json")]
+    #[arg(long, global = true, conflicts_with = "output")]
+    jsonl: bool,
+
     /// Reference identifier to be compared with computed one
     #[arg(short, long)]
     verify: Option<String>,
@@ -50,128 +75,482 @@ struct Cli {
     objects: Vec<String>,
 }
 
-#[cfg(feature = "git")]
-mod git_support {
-    use super::*;
-    use git2::Repository;
+#[derive(Subcommand)]
+enum Command {
+    /// Compute the SWHID of files and directories, auto-detecting their type
+    ///
+    /// `-` reads a content from standard input, e.g. `curl ... | swhid
+    /// identify -`.
+    Identify {
+        /// Follow symlinks (violates SWHID specification)
+        #[arg(long)]
+        dereference: bool,
 
-    pub fn compute_git_revision_swhid(repo_path: &str, revision: &str) -> Result<String, Box<dyn std::error::Error>> {
-        let repo = Repository::open(repo_path)?;
-        let commit = repo.revparse_single(revision)?.peel_to_commit()?;
-        
-        // Format as Git commit object
-        let tree_id = commit.tree_id().to_string();
-        let parent_ids: Vec<String> = commit.parents().map(|p| p.id().to_string()).collect();
-        let author = commit.author().to_string();
-        let committer = commit.committer().to_string();
-        let message = commit.message().unwrap_or("").to_string();
-        
-        // Create Git commit object format
-        let mut commit_data = format!("tree {}\n", tree_id);
-        for parent_id in parent_ids {
-            commit_data.push_str(&format!("parent {}\n", parent_id));
-        }
-        commit_data.push_str(&format!("author {}\n", author));
-        commit_data.push_str(&format!("committer {}\n", committer));
-        commit_data.push_str("\n");
-        commit_data.push_str(&message);
-        
-        // Compute SHA1 hash
-        let swhid = swhid::hash::hash_git_object("commit", commit_data.as_bytes());
-        Ok(format!("swh:1:rev:{}", hex::encode(swhid)))
+        /// Also identify every file and subdirectory of directories, printing
+        /// each as it is computed
+        #[arg(short, long)]
+        recursive: bool,
+
+        /// Also identify the paths listed in a file, one per line, or `-` for
+        /// standard input
+        #[arg(long, value_name = "LIST")]
+        files_from: Option<PathBuf>,
+
+        /// Files and directories to identify
+        #[arg(required_unless_present = "files_from")]
+        paths: Vec<String>,
+    },
+    /// Verify that a file or directory has the given SWHID
+    ///
+    /// The SWHID may be qualified. With a `path` qualifier, `<PATH>` is a
+    /// checkout of the anchor and the qualifier locates the object; `anchor`
+    /// and `lines` are checked where possible.
+    ///
+    /// With `--manifest`, verifies every entry of a manifest, as `check`
+    /// reads it, against the tree under `--root` instead, and prints one
+    /// `PASS` or `FAIL` line per entry.
+    Verify {
+        /// Manifest file listing the paths and SWHIDs to verify
+        #[arg(long, conflicts_with_all = ["path", "swhid"])]
+        manifest: Option<PathBuf>,
+
+        /// Root of the tree the manifest paths are relative to [default: .]
+        #[arg(long, conflicts_with_all = ["path", "swhid"])]
+        root: Option<PathBuf>,
+
+        /// File or directory to verify
+        #[arg(required_unless_present = "manifest")]
+        path: Option<PathBuf>,
+
+        /// Expected SWHID
+        #[arg(required_unless_present = "manifest")]
+        swhid: Option<String>,
+    },
+    /// Parse a SWHID and print its parts, one `<name>\t<value>` per line
+    Parse {
+        /// SWHID to parse, possibly qualified
+        swhid: String,
+    },
+    /// Convert between SWHIDs and Software Heritage archive URLs
+    ///
+    /// Prints the archive URL of each SWHID, which may be qualified, and the
+    /// SWHID of each archive URL, one per line.
+    Url {
+        /// SWHIDs or archive URLs
+        #[arg(required = true)]
+        inputs: Vec<String>,
+    },
+    /// Verify a directory tree against a manifest of expected SWHIDs
+    ///
+    /// Each manifest line is `<swhid> <relative-path>`, separated by a space or
+    /// a tab; `.` names the root itself. Blank lines and lines starting with
+    /// `#` are ignored. Fails if any entry mismatches or is missing.
+    Check {
+        /// Manifest file listing the expected SWHIDs
+        #[arg(long)]
+        manifest: PathBuf,
+
+        /// Root of the tree to verify
+        #[arg(long)]
+        root: PathBuf,
+    },
+    /// List a directory tree recursively, indented, as `git ls-tree` does
+    ///
+    /// Each line is `<mode> <type> <hash>\t<name>`, the entries a directory
+    /// SWHID is computed from, in the order they are hashed. Names are
+    /// indented by two spaces per level; the first line is the root itself.
+    Tree {
+        /// Directory to list
+        path: PathBuf,
+    },
+    /// List the entries of a directory, as `git ls-tree` does
+    ///
+    /// Each line is `<mode> <type> <hash>\t<name>`, in the order the entries
+    /// are hashed into the directory SWHID.
+    Ls {
+        /// Directory to list
+        path: PathBuf,
+    },
+    /// Create or verify a JSON manifest of the SWHIDs of a directory tree
+    #[cfg(feature = "json")]
+    Manifest {
+        #[command(subcommand)]
+        command: ManifestCommand,
+    },
+    /// Identify the directory a source archive extracts to, without extracting it
+    ///
+    /// Prints `<swhid>\t<archive>` for each archive. Tar archives, plain or
+    /// gzip-compressed, need the `tar` feature, and zip archives the `zip`
+    /// feature. If all members are under a single top-level directory, the
+    /// SWHID is that directory's.
+    #[cfg(any(feature = "tar", feature = "zip"))]
+    Archive {
+        /// Archive files
+        #[arg(required = true)]
+        archives: Vec<PathBuf>,
+    },
+    /// Query the Software Heritage web API
+    #[cfg(feature = "api")]
+    Api {
+        /// Base URL of the API, e.g. of a mirror
+        #[arg(long, default_value = swhid::api::API_URL)]
+        api_url: String,
+
+        /// Authentication token; defaults to the `SWH_AUTH_TOKEN` environment variable
+        #[arg(long)]
+        token: Option<String>,
+
+        /// SWHIDs sent per request
+        #[arg(long, default_value_t = swhid::api::KNOWN_BATCH_SIZE)]
+        batch_size: usize,
+
+        #[command(subcommand)]
+        command: ApiCommand,
+    },
+    /// Show which paths make the SWHIDs of two directories differ
+    ///
+    /// Prints one `<status>\t<path>` line per difference, sorted by path:
+    /// `A` for paths only `NEW` has, `D` for paths only `OLD` has, and `M`
+    /// for paths whose content, kind or mode changed. An added or removed
+    /// directory is listed alone, and a directory is not listed for changes
+    /// below it. Fails if the trees differ.
+    Diff {
+        /// Old directory
+        old: PathBuf,
+
+        /// New directory
+        new: PathBuf,
+    },
+}
+
+/// Subcommands of `manifest`
+#[cfg(feature = "json")]
+#[derive(Subcommand)]
+enum ManifestCommand {
+    /// Write the root SWHID and the SWHID of every path of a directory tree
+    Create {
+        /// Directory to record
+        dir: PathBuf,
+
+        /// Write the manifest to this file rather than to standard output
+        #[arg(short = 'o', long)]
+        output_file: Option<PathBuf>,
+    },
+    /// Check a directory tree against a manifest written by `manifest create`
+    ///
+    /// Prints a line for each path that differs, as `check` does, and fails
+    /// if the tree does not match.
+    Verify {
+        /// Directory to verify
+        dir: PathBuf,
+
+        /// Manifest file
+        manifest: PathBuf,
+    },
+}
+
+/// Subcommands of `api`
+#[cfg(feature = "api")]
+#[derive(Subcommand)]
+enum ApiCommand {
+    /// Check which local files and directories are already archived
+    ///
+    /// Prints `known` or `unknown`, the SWHID and the path, tab-separated,
+    /// for each path.
+    Known {
+        /// Check every file, symlink and subdirectory below directories too
+        #[arg(short, long)]
+        recursive: bool,
+
+        /// Files and directories to check
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+    },
+}
+
+/// Format of the results
+///
+/// Structured formats give each result a record. Identified objects have a
+/// `path` (none for standard input), a `type` (`content`, `directory`,
+/// `revision`, `release` or `snapshot`), a `swhid` and a `length` (the size
+/// in bytes of contents, none for other objects); other subcommands document
+/// their own fields. Summary lines are only printed in plain output.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum OutputFormat {
+    /// Lines meant to be read, e.g. `<swhid>\t<path>`
+    Plain,
+    /// One JSON object per line
+    #[cfg(feature = "json")]
+    Jsonl,
+    /// A JSON array of objects
+    #[cfg(feature = "json")]
+    Json,
+    /// Comma-separated values, after a header line
+    Csv,
+}
+
+impl Cli {
+    /// The format chosen by `--output`, or `--jsonl`
+    fn output_format(&self) -> OutputFormat {
+        #[cfg(feature = "json")]
+        if self.jsonl {
+            return OutputFormat::Jsonl;
+        }
+        self.output
     }
+}
 
-    pub fn compute_git_release_swhid(repo_path: &str, tag_name: &str) -> Result<String, Box<dyn std::error::Error>> {
-        let repo = Repository::open(repo_path)?;
-        let tag = repo.find_tag(repo.revparse_single(tag_name)?.id())?;
-        
-        // Format as Git tag object
-        let target_id = tag.target_id().to_string();
-        let target_type = if tag.target().unwrap().kind() == Some(git2::ObjectType::Commit) {
-            "commit"
-        } else {
-            "tree"
-        };
-        let tagger = tag.tagger().unwrap().to_string();
-        let message = tag.message().unwrap_or("").to_string();
-        
-        // Create Git tag object format
-        let mut tag_data = format!("object {}\n", target_id);
-        tag_data.push_str(&format!("type {}\n", target_type));
-        tag_data.push_str(&format!("tag {}\n", tag_name));
-        tag_data.push_str(&format!("tagger {}\n", tagger));
-        tag_data.push_str("\n");
-        tag_data.push_str(&message);
-        
-        // Compute SHA1 hash
-        let swhid = swhid::hash::hash_git_object("tag", tag_data.as_bytes());
-        Ok(format!("swh:1:rel:{}", hex::encode(swhid)))
+/// The value of a record field
+enum Value {
+    Null,
+    Bool(bool),
+    Number(u64),
+    Text(String),
+}
+
+impl From<&str> for Value {
+    fn from(text: &str) -> Self {
+        Value::Text(text.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(text: String) -> Self {
+        Value::Text(text)
     }
+}
 
-    pub fn compute_git_snapshot_swhid(repo_path: &str) -> Result<String, Box<dyn std::error::Error>> {
-        let repo = Repository::open(repo_path)?;
-        let mut refs_data = String::new();
-        
-        // Get all references
-        let refs = repo.references()?;
-        for reference in refs {
-            let reference = reference?;
-            let name = reference.name().unwrap_or("").to_string();
-            let target_id = reference.target().unwrap().to_string();
-            
-            // Check if it's a branch or tag reference
-            if name.starts_with("refs/heads/") || name.starts_with("refs/tags/") {
-                refs_data.push_str(&format!("{} {}\n", name, target_id));
+impl From<&Swhid> for Value {
+    fn from(swhid: &Swhid) -> Self {
+        Value::Text(swhid.to_string())
+    }
+}
+
+impl From<bool> for Value {
+    fn from(flag: bool) -> Self {
+        Value::Bool(flag)
+    }
+}
+
+impl From<u64> for Value {
+    fn from(number: u64) -> Self {
+        Value::Number(number)
+    }
+}
+
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Value::Null, Into::into)
+    }
+}
+
+/// A result, as named fields in a fixed order
+///
+/// Every record of a subcommand has the same fields, so that they line up
+/// as CSV columns.
+#[derive(Default)]
+struct Record {
+    fields: Vec<(&'static str, Value)>,
+}
+
+impl Record {
+    fn with(mut self, key: &'static str, value: impl Into<Value>) -> Self {
+        self.fields.push((key, value.into()));
+        self
+    }
+
+    /// The record of an identified object
+    fn object(path: Option<&str>, swhid: &Swhid, length: Option<u64>) -> Self {
+        Record::default()
+            .with("path", path)
+            .with("type", object_type_name(swhid.object_type()))
+            .with("swhid", swhid)
+            .with("length", length)
+    }
+}
+
+#[cfg(feature = "json")]
+impl serde::Serialize for Value {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Value::Null => serializer.serialize_none(),
+            Value::Bool(flag) => serializer.serialize_bool(*flag),
+            Value::Number(number) => serializer.serialize_u64(*number),
+            Value::Text(text) => serializer.serialize_str(text),
+        }
+    }
+}
+
+#[cfg(feature = "json")]
+impl serde::Serialize for Record {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeMap;
+
+        let mut map = serializer.serialize_map(Some(self.fields.len()))?;
+        for (key, value) in &self.fields {
+            map.serialize_entry(key, value)?;
+        }
+        map.end()
+    }
+}
+
+/// Prints results in an output format, flushing each so consumers see it immediately
+struct Printer<W: Write> {
+    out: W,
+    format: OutputFormat,
+    /// Number of records printed so far
+    count: usize,
+    /// Whether a whole document was printed instead of records
+    #[cfg(feature = "json")]
+    document: bool,
+}
+
+impl<W: Write> Printer<W> {
+    fn new(out: W, format: OutputFormat) -> Self {
+        Self {
+            out,
+            format,
+            count: 0,
+            #[cfg(feature = "json")]
+            document: false,
+        }
+    }
+
+    /// Print a result, as the `plain` line in plain output and as `record` otherwise
+    fn print(&mut self, plain: &str, record: &Record) -> std::io::Result<()> {
+        match self.format {
+            OutputFormat::Plain => writeln!(self.out, "{}", plain)?,
+            #[cfg(feature = "json")]
+            OutputFormat::Jsonl | OutputFormat::Json => {
+                if self.format == OutputFormat::Json {
+                    write!(self.out, "{}", if self.count == 0 { "[\n  " } else { ",\n  " })?;
+                }
+                serde_json::to_writer(&mut self.out, record)?;
+                if self.format == OutputFormat::Jsonl {
+                    writeln!(self.out)?;
+                }
+            }
+            OutputFormat::Csv => {
+                if self.count == 0 {
+                    let header: Vec<&str> = record.fields.iter().map(|(key, _)| *key).collect();
+                    writeln!(self.out, "{}", header.join(","))?;
+                }
+                let values: Vec<String> = record
+                    .fields
+                    .iter()
+                    .map(|(_, value)| match value {
+                        Value::Null => String::new(),
+                        Value::Bool(flag) => flag.to_string(),
+                        Value::Number(number) => number.to_string(),
+                        Value::Text(text) => csv_field(text),
+                    })
+                    .collect();
+                writeln!(self.out, "{}", values.join(","))?;
             }
         }
-        
-        // Compute SHA1 hash
-        let swhid = swhid::hash::hash_git_object("snapshot", refs_data.as_bytes());
-        Ok(format!("swh:1:snp:{}", hex::encode(swhid)))
+        self.count += 1;
+        self.out.flush()
     }
+
+    /// Print a line of plain output only, such as a summary
+    fn plain(&mut self, line: &str) -> std::io::Result<()> {
+        if self.format == OutputFormat::Plain {
+            writeln!(self.out, "{}", line)?;
+            self.out.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Print a whole document, such as a manifest, which is the entire output
+    #[cfg(feature = "json")]
+    fn document(&mut self, text: &str) -> std::io::Result<()> {
+        self.document = true;
+        write!(self.out, "{}", text)?;
+        self.out.flush()
+    }
+
+    /// Close the output, once every record is printed
+    fn finish(mut self) -> std::io::Result<()> {
+        #[cfg(feature = "json")]
+        if self.format == OutputFormat::Json && !self.document {
+            writeln!(self.out, "{}", if self.count == 0 { "[]" } else { "\n]" })?;
+        }
+        self.out.flush()
+    }
+}
+
+/// Print an identified object
+fn print_object<W: Write>(
+    printer: &mut Printer<W>,
+    path: Option<&str>,
+    swhid: &Swhid,
+    length: Option<u64>,
+) -> std::io::Result<()> {
+    let plain = match path {
+        Some(path) => format!("{}\t{}", swhid, path),
+        None => swhid.to_string(),
+    };
+    printer.print(&plain, &Record::object(path, swhid, length))
 }
 
+/// Name of an object type in structured output
+fn object_type_name(object_type: ObjectType) -> &'static str {
+    match object_type {
+        ObjectType::Content => "content",
+        ObjectType::Directory => "directory",
+        ObjectType::Revision => "revision",
+        ObjectType::Release => "release",
+        ObjectType::Snapshot => "snapshot",
+    }
+}
+
+/// Quote a CSV field if it holds a separator, quote or line break
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+/// Size in bytes of a file, or of a symlink target unless `follow`
+fn content_length(path: &Path, follow: bool) -> Option<u64> {
+    let metadata = if follow { fs::metadata(path) } else { fs::symlink_metadata(path) };
+    metadata.ok().map(|metadata| metadata.len())
+}
+
+/// Identify an object, giving its SWHID and, for contents, its length
+///
+/// `follow_symlinks` must be the setting of `computer`.
 fn identify_object(
-    obj_type: &str,
+    computer: &SwhidComputer,
     follow_symlinks: bool,
-    exclude_patterns: &[String],
+    obj_type: &str,
     obj: &str,
     #[cfg(feature = "git")] revision: Option<&str>,
     #[cfg(feature = "git")] release: Option<&str>,
     #[cfg(feature = "git")] snapshot: bool,
-) -> Result<String, Box<dyn std::error::Error>> {
-    let computer = SwhidComputer::new()
-        .with_follow_symlinks(follow_symlinks)
-        .with_exclude_patterns(exclude_patterns);
-
+) -> Result<(Swhid, Option<u64>), Box<dyn std::error::Error>> {
     let obj_type = if obj_type == "auto" {
         // Check Git flags first
         #[cfg(feature = "git")]
         if snapshot {
-            return git_support::compute_git_snapshot_swhid(obj);
+            return Ok((computer.compute_snapshot_swhid(obj)?, None));
         }
         #[cfg(feature = "git")]
         if let Some(rev) = revision {
-            return git_support::compute_git_revision_swhid(obj, rev);
+            return Ok((computer.compute_revision_swhid(obj, rev)?, None));
         }
         #[cfg(feature = "git")]
         if let Some(rel) = release {
-            return git_support::compute_git_release_swhid(obj, rel);
+            return Ok((computer.compute_release_swhid(obj, rel)?, None));
         }
         
         // Fall back to auto-detection
-        if obj == "-" {
-            "content"
-        } else if Path::new(obj).is_file() {
+        if obj == "-" || Path::new(obj).is_file() {
             "content"
         } else if Path::new(obj).is_dir() {
-            // Check if it's a Git repository
-            let git_path = Path::new(obj).join(".git");
-            if git_path.exists() && git_path.is_dir() {
-                "directory"
-            } else {
-                "directory"
-            }
+            "directory"
         } else {
             return Err("cannot detect object type".into());
         }
@@ -185,20 +564,20 @@ fn identify_object(
                 let mut content = Vec::new();
                 std::io::stdin().read_to_end(&mut content)?;
                 let swhid = computer.compute_content_swhid(&content)?;
-                Ok(swhid.to_string())
+                Ok((swhid, Some(content.len() as u64)))
             } else {
                 let swhid = computer.compute_swhid(obj)?;
-                Ok(swhid.to_string())
+                Ok((swhid, content_length(Path::new(obj), follow_symlinks)))
             }
         }
         "directory" => {
             let swhid = computer.compute_directory_swhid(obj)?;
-            Ok(swhid.to_string())
+            Ok((swhid, None))
         }
         #[cfg(feature = "git")]
         "revision" => {
             if let Some(rev) = revision {
-                git_support::compute_git_revision_swhid(obj, rev)
+                Ok((computer.compute_revision_swhid(obj, rev)?, None))
             } else {
                 Err("revision specified but no revision provided".into())
             }
@@ -206,29 +585,562 @@ fn identify_object(
         #[cfg(feature = "git")]
         "release" => {
             if let Some(rel) = release {
-                git_support::compute_git_release_swhid(obj, rel)
+                Ok((computer.compute_release_swhid(obj, rel)?, None))
             } else {
                 Err("release specified but no release provided".into())
             }
         }
         #[cfg(feature = "git")]
         "snapshot" => {
-            git_support::compute_git_snapshot_swhid(obj)
+            Ok((computer.compute_snapshot_swhid(obj)?, None))
         }
         _ => Err("invalid object type".into()),
     }
 }
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let cli = Cli::parse();
+/// Identify a directory and everything below it, printing each result as it is computed
+fn identify_recursive<W: Write>(
+    computer: &SwhidComputer,
+    obj: &str,
+    printer: &mut Printer<W>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut write_result = Ok(());
+    computer.walk(obj, |entry| {
+        if write_result.is_ok() {
+            let path = entry.path.to_string_lossy();
+            let length = match entry.entry_type {
+                EntryType::File => content_length(&entry.path, true),
+                EntryType::Symlink => content_length(&entry.path, false),
+                EntryType::Directory | EntryType::Submodule => None,
+            };
+            write_result = print_object(printer, Some(&path), &entry.swhid, length);
+        }
+    })?;
+    Ok(write_result?)
+}
+
+/// Read a list of paths, one per line, from a file or `-` for standard input
+///
+/// Blank lines are skipped, and a `\r` before each line break is dropped.
+fn read_path_list(list: &Path) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let text = if list == Path::new("-") {
+        let mut text = String::new();
+        std::io::stdin().read_to_string(&mut text)?;
+        text
+    } else {
+        fs::read_to_string(list).map_err(|e| format!("{}: {}", list.display(), e))?
+    };
+    Ok(text
+        .lines()
+        .map(|line| line.strip_suffix('\r').unwrap_or(line))
+        .filter(|line| !line.is_empty())
+        .map(str::to_string)
+        .collect())
+}
+
+/// Parse a manifest of `<swhid> <relative-path>` lines
+fn parse_manifest(text: &str) -> Result<BTreeMap<PathBuf, Swhid>, Box<dyn std::error::Error>> {
+    let mut manifest = BTreeMap::new();
+    for (number, entry) in manifest_lines(text) {
+        let (swhid, path) = entry.ok_or_else(|| format!("manifest line {}: expected `<swhid> <path>`", number))?;
+        let swhid = Swhid::from_string(swhid)
+            .map_err(|e| format!("manifest line {}: {}", number, e))?;
+        // `./a` and `a` name the same entry, and `.` the root
+        let path: PathBuf = Path::new(path)
+            .components()
+            .filter(|c| *c != Component::CurDir)
+            .collect();
+        manifest.insert(path, swhid);
+    }
+    Ok(manifest)
+}
+
+/// Split the lines of a manifest into their SWHID and path
+///
+/// Blank lines and comments are skipped. Each remaining line comes with its
+/// 1-based number, and `None` if it has no separator.
+fn manifest_lines(text: &str) -> impl Iterator<Item = (usize, Option<(&str, &str)>)> {
+    text.lines()
+        .enumerate()
+        .map(|(number, line)| (number + 1, line.trim_end_matches('\r')))
+        .filter(|(_, line)| !line.trim().is_empty() && !line.starts_with('#'))
+        .map(|(number, line)| (number, line.split_once([' ', '\t'])))
+}
+
+/// Why a SWHID did not verify: its core or the qualifiers that do not hold
+fn failure_reason(outcome: &QualifiedVerification) -> String {
+    if outcome.core.is_match() {
+        let failed: Vec<&str> = outcome
+            .qualifiers
+            .iter()
+            .filter(|(_, check)| matches!(check, QualifierCheck::Failed(_)))
+            .map(|(key, _)| *key)
+            .collect();
+        format!("qualifier {} does not hold", failed.join(", "))
+    } else {
+        outcome.core.to_string()
+    }
+}
+
+/// The record of a verification, with a `status` of `pass` or `fail`
+fn verify_record(path: Option<&str>, swhid: Option<&str>, status: &str, reason: Option<String>) -> Record {
+    Record::default()
+        .with("path", path)
+        .with("swhid", swhid)
+        .with("status", status)
+        .with("reason", reason)
+}
+
+/// Verify a tree against a manifest, printing one `PASS` or `FAIL` line per entry
+///
+/// Returns whether every entry matched; unlisted paths are ignored.
+fn verify_manifest<W: Write>(
+    computer: &SwhidComputer,
+    manifest: &Path,
+    root: &Path,
+    printer: &mut Printer<W>,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    let text = fs::read_to_string(manifest).map_err(|e| format!("{}: {}", manifest.display(), e))?;
+    let manifest = parse_manifest(&text)?;
+    let report = computer.verify_tree_manifest(root, &manifest)?;
+
+    let mut failures: BTreeMap<&Path, String> = report
+        .mismatches
+        .iter()
+        .map(|mismatch| (mismatch.path.as_path(), format!("got {}", mismatch.actual)))
+        .collect();
+    failures.extend(report.missing.iter().map(|path| (path.as_path(), "not found".to_string())));
+    for (path, swhid) in &manifest {
+        // The root is under the empty path
+        let name = if path.as_os_str().is_empty() { ".".into() } else { path.to_string_lossy() };
+        let swhid = swhid.to_string();
+        match failures.remove(path.as_path()) {
+            None => printer.print(&format!("PASS\t{}", name), &verify_record(Some(&name), Some(&swhid), "pass", None))?,
+            Some(reason) => printer.print(
+                &format!("FAIL\t{}\t{}", name, reason),
+                &verify_record(Some(&name), Some(&swhid), "fail", Some(reason)),
+            )?,
+        }
+    }
+    let failed = report.mismatches.len() + report.missing.len();
+    printer.plain(&format!("{} passed, {} failed", report.matches.len(), failed))?;
+    Ok(failed == 0)
+}
+
+/// Verify a path against a possibly qualified SWHID, returning whether it matched
+fn verify_path<W: Write>(
+    computer: &SwhidComputer,
+    path: &Path,
+    swhid: &str,
+    printer: &mut Printer<W>,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    let outcome = computer.verify_qualified_swhid(path, swhid)?;
+    for (key, check) in &outcome.qualifiers {
+        match check {
+            QualifierCheck::Passed => printer.plain(&format!("{}\tpassed", key))?,
+            QualifierCheck::Failed(reason) => printer.plain(&format!("{}\tfailed: {}", key, reason))?,
+            QualifierCheck::Unchecked => printer.plain(&format!("{}\tunchecked", key))?,
+        }
+    }
+    let path_name = path.to_string_lossy();
+    if outcome.is_match() {
+        printer.print(
+            "✓ SWHID verification successful",
+            &verify_record(Some(&path_name), Some(swhid), "pass", None),
+        )?;
+    } else {
+        let reason = failure_reason(&outcome);
+        let plain = if outcome.core.is_match() {
+            "✗ SWHID verification failed: a qualifier does not hold".to_string()
+        } else {
+            format!("✗ SWHID verification failed: {}", outcome.core)
+        };
+        printer.print(&plain, &verify_record(Some(&path_name), Some(swhid), "fail", Some(reason)))?;
+    }
+    Ok(outcome.is_match())
+}
+
+/// Print the entries of a directory as `git ls-tree` does, and with
+/// `recursive` those of its subdirectories, indented, after the root itself
+///
+/// Records have the `path`, the octal `mode`, the Git object `type` and the
+/// `swhid` of each entry.
+fn print_tree<W: Write>(
+    computer: &SwhidComputer,
+    root: &Path,
+    recursive: bool,
+    printer: &mut Printer<W>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut children: BTreeMap<PathBuf, Vec<WalkEntry>> = BTreeMap::new();
+    let swhid = computer.walk(root, |entry| {
+        if let (Some(parent), true) = (entry.path.parent(), entry.path != root) {
+            children.entry(parent.to_path_buf()).or_default().push(entry);
+        }
+    })?;
+    // Directories are hashed in the order of their entries' names, a
+    // subdirectory sorting as if its name ended with `/`
+    for entries in children.values_mut() {
+        entries.sort_by_cached_key(|entry| {
+            let mut key = entry.path.file_name().unwrap_or_default().as_encoded_bytes().to_vec();
+            if entry.entry_type == EntryType::Directory {
+                key.push(b'/');
+            }
+            key
+        });
+    }
+
+    if recursive {
+        let root_name = root.display().to_string();
+        print_tree_line(printer, Permissions::Directory, EntryType::Directory, &swhid, 0, &root_name, &root_name)?;
+    }
+    print_tree_entries(printer, &children, root, usize::from(recursive), recursive)?;
+    Ok(())
+}
+
+/// Print the entries of `dir`, indented by `depth` levels, and with `recursive`
+/// those of its subdirectories
+fn print_tree_entries<W: Write>(
+    printer: &mut Printer<W>,
+    children: &BTreeMap<PathBuf, Vec<WalkEntry>>,
+    dir: &Path,
+    depth: usize,
+    recursive: bool,
+) -> std::io::Result<()> {
+    for entry in children.get(dir).into_iter().flatten() {
+        let name = entry.path.file_name().unwrap_or_default().to_string_lossy();
+        let path = entry.path.to_string_lossy();
+        print_tree_line(printer, entry.permissions, entry.entry_type, &entry.swhid, depth, &name, &path)?;
+        if recursive && entry.entry_type == EntryType::Directory {
+            print_tree_entries(printer, children, &entry.path, depth + 1, recursive)?;
+        }
+    }
+    Ok(())
+}
+
+/// Print one `git ls-tree` line, with the name indented by `depth` levels
+fn print_tree_line<W: Write>(
+    printer: &mut Printer<W>,
+    permissions: Permissions,
+    entry_type: EntryType,
+    swhid: &Swhid,
+    depth: usize,
+    name: &str,
+    path: &str,
+) -> std::io::Result<()> {
+    let mode = format!("{:06o}", permissions.as_octal());
+    let plain = format!(
+        "{} {} {}\t{}{}",
+        mode,
+        entry_type.git_object_type(),
+        hex::encode(swhid.digest()),
+        "  ".repeat(depth),
+        name
+    );
+    let record = Record::default()
+        .with("path", path)
+        .with("mode", mode)
+        .with("type", entry_type.git_object_type())
+        .with("swhid", swhid);
+    printer.print(&plain, &record)
+}
+
+/// Print the parts of a SWHID, as one line per part or a single record
+fn print_parsed<W: Write>(swhid: &str, printer: &mut Printer<W>) -> Result<(), Box<dyn std::error::Error>> {
+    let qualified = QualifiedSwhid::from_string(swhid)?;
+    let core = qualified.core();
+    let mut record = Record::default()
+        .with("namespace", "swh")
+        .with("version", core.version().to_string())
+        .with("object_type", core.object_type().to_string())
+        .with("hash", hex::encode(core.digest()));
+    for (key, value) in qualified.qualifiers() {
+        record = record.with(key, value.to_string());
+    }
+    let plain: Vec<String> = record
+        .fields
+        .iter()
+        .map(|(key, value)| match value {
+            Value::Text(text) => format!("{}\t{}", key, text),
+            _ => unreachable!("every part is text"),
+        })
+        .collect();
+    printer.print(&plain.join("\n"), &record)?;
+    Ok(())
+}
+
+/// Check a tree against a manifest, returning whether it passed
+fn check_manifest<W: Write>(
+    computer: &SwhidComputer,
+    manifest: &Path,
+    root: &Path,
+    printer: &mut Printer<W>,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    let manifest = parse_manifest(&std::fs::read_to_string(manifest)?)?;
+    let report = computer.verify_tree_manifest(root, &manifest)?;
+    Ok(print_manifest_report(&report, printer)?)
+}
+
+/// Print the paths that differ from a manifest and a summary, returning
+/// whether the tree passed
+///
+/// Records have the `path`, a `status` of `mismatch`, `missing` or `extra`,
+/// and the `expected` and `actual` SWHIDs where known.
+fn print_manifest_report<W: Write>(report: &ManifestReport, printer: &mut Printer<W>) -> std::io::Result<bool> {
+    let record = |path: &Path, status: &str, expected: Option<&Swhid>, actual: Option<&Swhid>| {
+        Record::default()
+            .with("path", path.to_string_lossy().as_ref())
+            .with("status", status)
+            .with("expected", expected)
+            .with("actual", actual)
+    };
+    for mismatch in &report.mismatches {
+        // The root is under the empty path
+        let path = if mismatch.path.as_os_str().is_empty() { Path::new(".") } else { &mismatch.path };
+        printer.print(
+            &format!("MISMATCH\t{}\texpected {}, got {}", path.display(), mismatch.expected, mismatch.actual),
+            &record(path, "mismatch", Some(&mismatch.expected), Some(&mismatch.actual)),
+        )?;
+    }
+    for path in &report.missing {
+        printer.print(&format!("MISSING\t{}", path.display()), &record(path, "missing", None, None))?;
+    }
+    for path in &report.extra {
+        printer.print(&format!("EXTRA\t{}", path.display()), &record(path, "extra", None, None))?;
+    }
+    printer.plain(&format!(
+        "{} matched, {} mismatched, {} missing, {} extra",
+        report.matches.len(),
+        report.mismatches.len(),
+        report.missing.len(),
+        report.extra.len()
+    ))?;
+
+    Ok(report.mismatches.is_empty() && report.missing.is_empty())
+}
+
+/// Exit status of errors, as opposed to mismatches
+const ERROR_STATUS: i32 = 2;
+
+/// Reject the top-level options of the legacy interface given with a subcommand
+///
+/// Global options, such as `--output`, may come before or after a subcommand.
+fn reject_legacy_args(matches: &clap::ArgMatches) -> Result<(), clap::Error> {
+    if matches.subcommand().is_none() {
+        return Ok(());
+    }
+    let mut command = Cli::command();
+    let legacy = command
+        .get_arguments()
+        .filter(|arg| !arg.is_global_set())
+        .find(|arg| matches.value_source(arg.get_id().as_str()) == Some(ValueSource::CommandLine))
+        .map(|arg| match arg.get_long() {
+            Some(long) => format!("--{}", long),
+            None => format!("<{}>", arg.get_id().as_str().to_uppercase()),
+        });
+    match legacy {
+        Some(arg) => Err(command.error(ErrorKind::ArgumentConflict, format!("'{}' cannot be used with a subcommand", arg))),
+        None => Ok(()),
+    }
+}
+
+fn main() {
+    let matches = Cli::command().get_matches();
+    let cli = reject_legacy_args(&matches)
+        .and_then(|()| Cli::from_arg_matches(&matches))
+        .unwrap_or_else(|e| e.exit());
+    let mut printer = Printer::new(std::io::stdout().lock(), cli.output_format());
+    let status = run(&cli, &mut printer);
+    // Close a JSON array even when failing midway
+    let finished = printer.finish();
+    let status = status.and_then(|status| Ok(finished.map(|()| status)?)).unwrap_or_else(|e| {
+        eprintln!("Error: {}", e);
+        ERROR_STATUS
+    });
+    std::process::exit(status);
+}
+
+/// Run the command line, returning the exit status
+fn run<W: Write>(cli: &Cli, printer: &mut Printer<W>) -> Result<i32, Box<dyn std::error::Error>> {
+    match &cli.command {
+        Some(Command::Check { manifest, root }) => {
+            let computer = SwhidComputer::new().with_exclude_patterns(&cli.exclude);
+            return Ok(i32::from(!check_manifest(&computer, manifest, root, printer)?));
+        }
+        Some(Command::Tree { path }) => {
+            let computer = SwhidComputer::new().with_exclude_patterns(&cli.exclude);
+            print_tree(&computer, path, true, printer)?;
+            return Ok(0);
+        }
+        Some(Command::Ls { path }) => {
+            let computer = SwhidComputer::new().with_exclude_patterns(&cli.exclude);
+            print_tree(&computer, path, false, printer)?;
+            return Ok(0);
+        }
+        #[cfg(feature = "json")]
+        Some(Command::Manifest { command: ManifestCommand::Create { dir, output_file } }) => {
+            // The manifest is JSON whatever `--output` is
+            let computer = SwhidComputer::new().with_exclude_patterns(&cli.exclude);
+            let json = computer.compute_tree_manifest(dir)?.to_json()?;
+            match output_file {
+                Some(output_file) => fs::write(output_file, json)?,
+                None => printer.document(&json)?,
+            }
+            return Ok(0);
+        }
+        #[cfg(feature = "json")]
+        Some(Command::Manifest { command: ManifestCommand::Verify { dir, manifest } }) => {
+            let computer = SwhidComputer::new().with_exclude_patterns(&cli.exclude);
+            let manifest = swhid::TreeManifest::from_json(&fs::read_to_string(manifest)?)?;
+            let passed = print_manifest_report(&computer.verify_manifest_file(dir, &manifest)?, printer)?;
+            return Ok(i32::from(!passed));
+        }
+        #[cfg(any(feature = "tar", feature = "zip"))]
+        Some(Command::Archive { archives }) => {
+            let computer = SwhidComputer::new().with_exclude_patterns(&cli.exclude);
+            for archive in archives {
+                let swhid = computer.compute_archive_swhid(archive)?;
+                print_object(printer, Some(&archive.to_string_lossy()), &swhid, None)?;
+            }
+            return Ok(0);
+        }
+        #[cfg(feature = "api")]
+        Some(Command::Api { api_url, token, batch_size, command: ApiCommand::Known { recursive, paths } }) => {
+            let computer = SwhidComputer::new().with_exclude_patterns(&cli.exclude);
+            let mut objects = Vec::new();
+            for path in paths {
+                if *recursive && path.is_dir() {
+                    // The root is under the empty path, and comes first
+                    for (relative, swhid) in computer.compute_swhid_tree(path)? {
+                        let object = if relative.as_os_str().is_empty() { path.clone() } else { path.join(relative) };
+                        objects.push((object, swhid));
+                    }
+                } else {
+                    objects.push((path.clone(), computer.compute_swhid(path)?));
+                }
+            }
+
+            let mut client = swhid::ArchiveClient::new().with_base_url(api_url).with_batch_size(*batch_size);
+            if let Some(token) = token.clone().or_else(|| std::env::var("SWH_AUTH_TOKEN").ok()) {
+                client = client.with_token(token);
+            }
+            let swhids: Vec<Swhid> = objects.iter().map(|(_, swhid)| swhid.clone()).collect();
+            for ((path, swhid), known) in objects.iter().zip(client.known(&swhids)?) {
+                let status = if known { "known" } else { "unknown" };
+                let record = Record::default()
+                    .with("path", path.to_string_lossy().as_ref())
+                    .with("type", object_type_name(swhid.object_type()))
+                    .with("swhid", swhid)
+                    .with("known", known);
+                printer.print(&format!("{}\t{}\t{}", status, swhid, path.display()), &record)?;
+            }
+            return Ok(0);
+        }
+        Some(Command::Diff { old, new }) => {
+            let computer = SwhidComputer::new().with_exclude_patterns(&cli.exclude);
+            let diff = computer.diff_trees(old, new)?;
+            let mut changes: Vec<(&Path, char)> = diff
+                .added
+                .iter()
+                .map(|path| (path.as_path(), 'A'))
+                .chain(diff.removed.iter().map(|path| (path.as_path(), 'D')))
+                .chain(diff.modified.iter().map(|path| (path.as_path(), 'M')))
+                .collect();
+            changes.sort();
+            for (path, status) in changes {
+                let name = match status {
+                    'A' => "added",
+                    'D' => "removed",
+                    _ => "modified",
+                };
+                let record = Record::default()
+                    .with("path", path.to_string_lossy().as_ref())
+                    .with("status", name);
+                printer.print(&format!("{}\t{}", status, path.display()), &record)?;
+            }
+            return Ok(i32::from(!diff.is_empty()));
+        }
+        Some(Command::Identify { dereference, recursive, files_from, paths }) => {
+            // Standard input can only be read once, as a content or a list
+            let stdin_uses = paths.iter().filter(|obj| *obj == "-").count()
+                + usize::from(files_from.as_deref() == Some(Path::new("-")));
+            if stdin_uses > 1 {
+                return Err("standard input can only be read once".into());
+            }
+            let mut paths = paths.clone();
+            if let Some(list) = files_from {
+                paths.extend(read_path_list(list)?);
+            }
+            // One computer for every path, however many are listed
+            let computer = SwhidComputer::new()
+                .with_follow_symlinks(*dereference)
+                .with_exclude_patterns(&cli.exclude);
+            for obj in &paths {
+                if *recursive && Path::new(obj).is_dir() {
+                    identify_recursive(&computer, obj, printer)?;
+                    continue;
+                }
+                let (swhid, length) = identify_object(
+                    &computer,
+                    *dereference,
+                    "auto",
+                    obj,
+                    #[cfg(feature = "git")]
+                    None,
+                    #[cfg(feature = "git")]
+                    None,
+                    #[cfg(feature = "git")]
+                    false,
+                )?;
+                let path = (obj != "-").then_some(obj.as_str());
+                print_object(printer, path, &swhid, length)?;
+            }
+            return Ok(0);
+        }
+        Some(Command::Verify { manifest: Some(manifest), root, .. }) => {
+            let computer = SwhidComputer::new().with_exclude_patterns(&cli.exclude);
+            let root = root.as_deref().unwrap_or(Path::new("."));
+            return Ok(i32::from(!verify_manifest(&computer, manifest, root, printer)?));
+        }
+        Some(Command::Verify { path: Some(path), swhid: Some(swhid), .. }) => {
+            let computer = SwhidComputer::new().with_exclude_patterns(&cli.exclude);
+            return Ok(i32::from(!verify_path(&computer, path, swhid, printer)?));
+        }
+        Some(Command::Verify { .. }) => unreachable!("clap requires a path and a SWHID without a manifest"),
+        Some(Command::Parse { swhid }) => {
+            print_parsed(swhid, printer)?;
+            return Ok(0);
+        }
+        Some(Command::Url { inputs }) => {
+            for input in inputs {
+                let (swhid, url) = if input.starts_with("swh:") {
+                    (input.clone(), QualifiedSwhid::from_string(input)?.to_archive_url())
+                } else {
+                    (QualifiedSwhid::from_archive_url(input)?.to_string(), input.clone())
+                };
+                let plain = if input.starts_with("swh:") { &url } else { &swhid };
+                let record = Record::default().with("swhid", swhid.as_str()).with("url", url.as_str());
+                printer.print(plain, &record)?;
+            }
+            return Ok(0);
+        }
+        None => {}
+    }
 
     let follow_symlinks = cli.dereference;
+    let computer = SwhidComputer::new()
+        .with_follow_symlinks(follow_symlinks)
+        .with_exclude_patterns(&cli.exclude);
 
     for obj in &cli.objects {
-        let result = identify_object(
-            &cli.obj_type, 
-            follow_symlinks, 
-            &cli.exclude, 
+        if cli.recursive && Path::new(obj).is_dir() {
+            identify_recursive(&computer, obj, printer)?;
+            continue;
+        }
+
+        let (swhid, length) = identify_object(
+            &computer,
+            follow_symlinks,
+            &cli.obj_type,
             obj,
             #[cfg(feature = "git")]
             cli.revision.as_deref(),
@@ -242,20 +1154,26 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             let computer = SwhidComputer::new();
             let is_valid = computer.verify_swhid(obj, verify_swhid)?;
             if is_valid {
-                println!("✓ SWHID verification successful");
+                printer.print(
+                    "✓ SWHID verification successful",
+                    &verify_record(Some(obj), Some(verify_swhid), "pass", None),
+                )?;
             } else {
-                println!("✗ SWHID verification failed");
-                std::process::exit(1);
+                printer.print(
+                    "✗ SWHID verification failed",
+                    &verify_record(Some(obj), Some(verify_swhid), "fail", Some(format!("computed {}", swhid))),
+                )?;
+                return Ok(1);
             }
         } else {
             let display_path = if cli.filename && obj != "-" {
-                format!("\t{}", obj)
+                Some(obj.as_str())
             } else {
-                "".to_string()
+                None
             };
-            println!("{}{}", result, display_path);
+            print_object(printer, display_path, &swhid, length)?;
         }
     }
 
-    Ok(())
+    Ok(0)
 }
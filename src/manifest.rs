@@ -0,0 +1,172 @@
+//! Portable JSON manifests of the SWHIDs of a directory tree
+//!
+//! A [`TreeManifest`] records the directory SWHID of a tree along with the
+//! SWHID of every object in it, so that a release can be checked file by
+//! file long after it was published, e.g. with
+//! [`SwhidComputer::verify_tree_manifest`].
+
+use std::collections::BTreeMap;
+use std::path::{Component, Path, PathBuf};
+
+use serde_json::{Map, Value};
+
+use crate::computer::{ManifestReport, SwhidComputer};
+use crate::error::SwhidError;
+use crate::swhid::{ObjectType, Swhid};
+
+/// Version of the manifest format written by [`TreeManifest::to_json`]
+pub const MANIFEST_VERSION: u64 = 1;
+
+/// The SWHIDs of a directory tree, as written to a JSON manifest
+///
+/// In JSON, a manifest is an object with the format `version`, the `root`
+/// SWHID and the `entries` mapping each path, relative to the root and with
+/// `/` separators, to its SWHID.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TreeManifest {
+    /// Directory SWHID of the root
+    pub root: Swhid,
+    /// SWHID of every file, symlink and subdirectory, by path relative to the root
+    pub entries: BTreeMap<PathBuf, Swhid>,
+}
+
+impl TreeManifest {
+    /// Serialize the manifest as pretty-printed JSON
+    ///
+    /// Fails with `SwhidError::InvalidFilePath` if a path is not UTF-8.
+    pub fn to_json(&self) -> Result<String, SwhidError> {
+        let mut entries = Map::new();
+        for (path, swhid) in &self.entries {
+            entries.insert(manifest_path(path)?, Value::String(swhid.to_string()));
+        }
+        let mut manifest = Map::new();
+        manifest.insert("version".to_string(), Value::from(MANIFEST_VERSION));
+        manifest.insert("root".to_string(), Value::String(self.root.to_string()));
+        manifest.insert("entries".to_string(), Value::Object(entries));
+
+        let mut json = serde_json::to_string_pretty(&Value::Object(manifest))
+            .map_err(|e| SwhidError::InvalidInput(e.to_string()))?;
+        json.push('\n');
+        Ok(json)
+    }
+
+    /// Parse a manifest written by [`TreeManifest::to_json`]
+    ///
+    /// Paths must be relative and stay below the root.
+    pub fn from_json(json: &str) -> Result<Self, SwhidError> {
+        let invalid = |reason: &str| SwhidError::InvalidFormat(format!("manifest: {}", reason));
+        let value: Value = serde_json::from_str(json).map_err(|e| invalid(&e.to_string()))?;
+
+        match value.get("version").and_then(Value::as_u64) {
+            Some(MANIFEST_VERSION) => {}
+            Some(version) => return Err(invalid(&format!("unsupported version {}", version))),
+            None => return Err(invalid("missing version")),
+        }
+        let root = value
+            .get("root")
+            .and_then(Value::as_str)
+            .ok_or_else(|| invalid("missing root"))?;
+        let root = Swhid::from_string(root)?;
+        if root.object_type() != ObjectType::Directory {
+            return Err(invalid("root is not a directory SWHID"));
+        }
+
+        let mut entries = BTreeMap::new();
+        let listed = value
+            .get("entries")
+            .and_then(Value::as_object)
+            .ok_or_else(|| invalid("missing entries"))?;
+        for (path, swhid) in listed {
+            let swhid = swhid
+                .as_str()
+                .ok_or_else(|| invalid(&format!("SWHID of {} is not a string", path)))?;
+            let relative = Path::new(path);
+            if !relative.components().all(|c| matches!(c, Component::Normal(_))) {
+                return Err(invalid(&format!("path {} is not below the root", path)));
+            }
+            entries.insert(relative.to_path_buf(), Swhid::from_string(swhid)?);
+        }
+        Ok(Self { root, entries })
+    }
+}
+
+/// Path of a manifest entry, with `/` separators
+fn manifest_path(path: &Path) -> Result<String, SwhidError> {
+    let names: Option<Vec<&str>> = path.components().map(|c| c.as_os_str().to_str()).collect();
+    names
+        .map(|names| names.join("/"))
+        .ok_or_else(|| SwhidError::InvalidFilePath(path.to_path_buf(), "not valid UTF-8".to_string()))
+}
+
+impl SwhidComputer {
+    /// Compute the manifest of a directory tree
+    ///
+    /// The counterpart of [`SwhidComputer::verify_manifest_file`].
+    pub fn compute_tree_manifest<P: AsRef<Path>>(&self, root: P) -> Result<TreeManifest, SwhidError> {
+        let mut entries = self.compute_swhid_tree(root)?;
+        let root = entries
+            .remove(Path::new(""))
+            .expect("the root is part of its own tree");
+        Ok(TreeManifest { root, entries })
+    }
+
+    /// Verify a directory tree against a manifest
+    ///
+    /// The root is checked too, under the empty path, so any change to the
+    /// tree shows up as a mismatch even where the manifest lists no path.
+    pub fn verify_manifest_file<P: AsRef<Path>>(
+        &self,
+        root: P,
+        manifest: &TreeManifest,
+    ) -> Result<ManifestReport, SwhidError> {
+        let mut expected = manifest.entries.clone();
+        expected.insert(PathBuf::new(), manifest.root.clone());
+        self.verify_tree_manifest(root, &expected)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_tree_manifest_round_trip() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::create_dir(temp_dir.path().join("src")).unwrap();
+        fs::write(temp_dir.path().join("src/lib.rs"), "lib\n").unwrap();
+        fs::write(temp_dir.path().join("README"), "readme\n").unwrap();
+
+        let computer = SwhidComputer::new();
+        let manifest = computer.compute_tree_manifest(temp_dir.path()).unwrap();
+        assert_eq!(manifest.root, computer.compute_directory_swhid(temp_dir.path()).unwrap());
+        assert_eq!(manifest.entries.len(), 3);
+
+        let json = manifest.to_json().unwrap();
+        assert!(json.contains(&format!("\"src/lib.rs\": \"{}\"", manifest.entries[Path::new("src/lib.rs")])));
+        assert_eq!(TreeManifest::from_json(&json).unwrap(), manifest);
+        assert!(computer.verify_manifest_file(temp_dir.path(), &manifest).unwrap().is_ok());
+
+        fs::write(temp_dir.path().join("src/lib.rs"), "changed\n").unwrap();
+        let report = computer.verify_manifest_file(temp_dir.path(), &manifest).unwrap();
+        let mismatched: Vec<_> = report.mismatches.iter().map(|m| m.path.clone()).collect();
+        assert_eq!(mismatched, vec![PathBuf::new(), PathBuf::from("src"), PathBuf::from("src/lib.rs")]);
+    }
+
+    #[test]
+    fn test_tree_manifest_from_json_invalid() {
+        let root = "swh:1:dir:4b825dc642cb6eb9a060e54bf8d69288fbee4904";
+        let cnt = "swh:1:cnt:e69de29bb2d1d6434b8b29ae775ad8c2e48c5391";
+        let parse = |json: String| TreeManifest::from_json(&json);
+
+        assert!(parse(format!(r#"{{"version": 1, "root": "{}", "entries": {{"a": "{}"}}}}"#, root, cnt)).is_ok());
+        assert!(parse(format!(r#"{{"version": 2, "root": "{}", "entries": {{}}}}"#, root)).is_err());
+        assert!(parse(format!(r#"{{"version": 1, "root": "{}", "entries": {{}}}}"#, cnt)).is_err());
+        assert!(parse(format!(r#"{{"version": 1, "root": "{}"}}"#, root)).is_err());
+        assert!(parse(format!(r#"{{"version": 1, "root": "{}", "entries": {{"../a": "{}"}}}}"#, root, cnt)).is_err());
+        assert!(parse(format!(r#"{{"version": 1, "root": "{}", "entries": {{"/a": "{}"}}}}"#, root, cnt)).is_err());
+        assert!(parse(format!(r#"{{"version": 1, "root": "{}", "entries": {{"a": 1}}}}"#, root)).is_err());
+        assert!(parse("not json".to_string()).is_err());
+    }
+}
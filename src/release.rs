@@ -0,0 +1,239 @@
+use crate::swhid::{Swhid, ObjectType};
+use crate::hash::{git_object_headers, hash_git_object, parse_object_id};
+use crate::error::SwhidError;
+use crate::revision::{write_header, GitSignature};
+
+/// Release object, i.e. a Git annotated tag
+///
+/// A release is identified by the hash of its Git tag object, so the object
+/// is kept byte for byte: the tagger, any signature and the message all take
+/// part in the hash.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Release {
+    target: Swhid,
+    name: Vec<u8>,
+    git_object: Vec<u8>,
+    hash: [u8; 20],
+}
+
+impl Release {
+    /// Create a release from the body of a Git tag object
+    ///
+    /// Fails with `SwhidError::InvalidInput` if the headers do not start with
+    /// `object`, `type` and `tag`, or name an invalid target.
+    pub fn from_git_object(data: Vec<u8>) -> Result<Self, SwhidError> {
+        let invalid = |reason: &str| SwhidError::InvalidInput(format!("Tag object {}", reason));
+        let (target, target_type, name) = {
+            let mut headers = git_object_headers(&data);
+            let target = match headers.next() {
+                Some((b"object", value)) => parse_object_id(value).ok_or_else(|| {
+                    invalid(&format!("has an invalid object id: {}", String::from_utf8_lossy(value)))
+                })?,
+                _ => return Err(invalid("must start with its object")),
+            };
+            let target_type = match headers.next() {
+                Some((b"type", b"blob")) => ObjectType::Content,
+                Some((b"type", b"tree")) => ObjectType::Directory,
+                Some((b"type", b"commit")) => ObjectType::Revision,
+                Some((b"type", b"tag")) => ObjectType::Release,
+                Some((b"type", other)) => {
+                    return Err(invalid(&format!("has an invalid type: {}", String::from_utf8_lossy(other))))
+                }
+                _ => return Err(invalid("must have a type after its object")),
+            };
+            let name = match headers.next() {
+                Some((b"tag", name)) => name.to_vec(),
+                _ => return Err(invalid("must have a tag name after its type")),
+            };
+            (target, target_type, name)
+        };
+
+        Ok(Self {
+            target: Swhid::new(target_type, target),
+            name,
+            hash: hash_git_object("tag", &data),
+            git_object: data,
+        })
+    }
+
+    /// Get the SWHID of the object this release points to
+    pub fn target(&self) -> &Swhid {
+        &self.target
+    }
+
+    /// Get the tag name
+    pub fn name(&self) -> &[u8] {
+        &self.name
+    }
+
+    /// Get the body of the Git tag object, as hashed
+    pub fn git_object(&self) -> &[u8] {
+        &self.git_object
+    }
+
+    /// Compute SWHID for this release
+    pub fn swhid(&self) -> Swhid {
+        Swhid::new(ObjectType::Release, self.hash)
+    }
+}
+
+/// Builder of Git tag objects, for releases that are not in a repository
+///
+/// Headers are written in the order Git writes them: `object`, `type`,
+/// `tag`, then `tagger` if set.
+#[derive(Debug, Clone)]
+pub struct ReleaseBuilder {
+    target: Swhid,
+    name: Vec<u8>,
+    tagger: Option<GitSignature>,
+    message: Vec<u8>,
+}
+
+impl ReleaseBuilder {
+    /// Start a release named `name`, pointing to `target`
+    ///
+    /// The type of the target object is the type of its SWHID.
+    pub fn new(target: Swhid, name: Vec<u8>) -> Self {
+        Self {
+            target,
+            name,
+            tagger: None,
+            message: Vec::new(),
+        }
+    }
+
+    /// Set the tagger; tags made by old Git versions have none
+    pub fn with_tagger(mut self, tagger: GitSignature) -> Self {
+        self.tagger = Some(tagger);
+        self
+    }
+
+    /// Set the message, written as is after the headers and a blank line
+    pub fn with_message(mut self, message: Vec<u8>) -> Self {
+        self.message = message;
+        self
+    }
+
+    /// Serialize the tag object and create its release
+    ///
+    /// Fails with `SwhidError::InvalidInput` if the target is a snapshot,
+    /// which Git objects cannot point to, or if the name is empty or spans
+    /// several lines. Fails with `SwhidError::InvalidVersion` if the target is
+    /// a v2 SWHID.
+    pub fn build(self) -> Result<Release, SwhidError> {
+        let target_type = match self.target.object_type() {
+            ObjectType::Content => "blob",
+            ObjectType::Directory => "tree",
+            ObjectType::Revision => "commit",
+            ObjectType::Release => "tag",
+            ObjectType::Snapshot => {
+                return Err(SwhidError::InvalidInput(format!("A tag cannot point to {}", self.target)))
+            }
+        };
+        if self.name.is_empty() || self.name.contains(&b'\n') {
+            return Err(SwhidError::InvalidInput(format!(
+                "Invalid tag name: {:?}",
+                String::from_utf8_lossy(&self.name)
+            )));
+        }
+
+        let mut data = format!("object {}\ntype {}\n", hex::encode(self.target.v1_hash()?), target_type).into_bytes();
+        write_header(&mut data, b"tag", &self.name)?;
+        if let Some(tagger) = &self.tagger {
+            write_header(&mut data, b"tagger", &tagger.to_git_bytes())?;
+        }
+        data.push(b'\n');
+        data.extend_from_slice(&self.message);
+        Release::from_git_object(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TAG: &[u8] = b"object 1e048ab552fec7fed3c552773ce146bcb5e57254\n\
+type commit\n\
+tag v1.0\n\
+tagger Jane Doe <jane@example.org> 1700000000 +0100\n\
+\n\
+Release 1.0\n";
+
+    #[test]
+    fn test_release_from_git_object() {
+        let release = Release::from_git_object(TAG.to_vec()).unwrap();
+        // As computed by `git tag -a`
+        assert_eq!(
+            release.swhid().to_string(),
+            "swh:1:rel:4901ae90ff16cb21929886986ae321c781560d2e"
+        );
+        assert_eq!(
+            release.target().to_string(),
+            "swh:1:rev:1e048ab552fec7fed3c552773ce146bcb5e57254"
+        );
+        assert_eq!(release.name(), b"v1.0");
+        assert_eq!(release.git_object(), TAG);
+
+        // Tags may point to any kind of object, and may have no tagger
+        let tree_tag = b"object 37d98dc6f78d31f91c15676c00d504e425b0e9a7\ntype tree\ntag snapshot-tree\n\nTree\n";
+        let release = Release::from_git_object(tree_tag.to_vec()).unwrap();
+        assert_eq!(release.target().object_type(), ObjectType::Directory);
+    }
+
+    #[test]
+    fn test_release_from_git_object_invalid() {
+        for data in [
+            &b""[..],
+            b"type commit\ntag v1.0\n",
+            b"object 1e048ab5\ntype commit\ntag v1.0\n",
+            b"object 1e048ab552fec7fed3c552773ce146bcb5e57254\ntype note\ntag v1.0\n",
+            b"object 1e048ab552fec7fed3c552773ce146bcb5e57254\ntype commit\n\nNo name\n",
+        ] {
+            assert!(matches!(
+                Release::from_git_object(data.to_vec()),
+                Err(SwhidError::InvalidInput(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_release_builder() {
+        let target = Swhid::from_string("swh:1:rev:1e048ab552fec7fed3c552773ce146bcb5e57254").unwrap();
+        let tagger = GitSignature::new(b"Jane Doe".to_vec(), b"jane@example.org".to_vec(), 1_700_000_000, 60).unwrap();
+        let release = ReleaseBuilder::new(target.clone(), b"v1.0".to_vec())
+            .with_tagger(tagger)
+            .with_message(b"Release 1.0\n".to_vec())
+            .build()
+            .unwrap();
+        assert_eq!(release.git_object(), TAG);
+        assert_eq!(
+            release.swhid().to_string(),
+            "swh:1:rel:4901ae90ff16cb21929886986ae321c781560d2e"
+        );
+
+        let tree = Swhid::from_string("swh:1:dir:37d98dc6f78d31f91c15676c00d504e425b0e9a7").unwrap();
+        let release = ReleaseBuilder::new(tree, b"snapshot-tree".to_vec())
+            .with_message(b"Tree\n".to_vec())
+            .build()
+            .unwrap();
+        assert_eq!(
+            release.git_object(),
+            b"object 37d98dc6f78d31f91c15676c00d504e425b0e9a7\ntype tree\ntag snapshot-tree\n\nTree\n"
+        );
+
+        let snapshot = Swhid::from_string("swh:1:snp:1a8893e6a86f444e8be8e7bda6cb34fb1735a00e").unwrap();
+        for builder in [
+            ReleaseBuilder::new(snapshot, b"v1.0".to_vec()),
+            ReleaseBuilder::new(target.clone(), Vec::new()),
+            ReleaseBuilder::new(target, b"v1.0\nv2.0".to_vec()),
+        ] {
+            assert!(matches!(builder.build(), Err(SwhidError::InvalidInput(_))));
+        }
+
+        #[cfg(feature = "v2")]
+        assert!(matches!(
+            ReleaseBuilder::new(Swhid::new_v2(ObjectType::Revision, [1; 32]), b"v1.0".to_vec()).build(),
+            Err(SwhidError::InvalidVersion(_))
+        ));
+    }
+}
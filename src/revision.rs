@@ -0,0 +1,415 @@
+use crate::swhid::{Swhid, ObjectType};
+use crate::hash::{git_object_headers, hash_git_object, parse_object_id};
+use crate::error::SwhidError;
+
+/// Revision object, i.e. a Git commit
+///
+/// A revision is identified by the hash of its Git commit object, so the
+/// object is kept byte for byte: signatures, extra headers and the message
+/// all take part in the hash.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Revision {
+    tree: [u8; 20],
+    parents: Vec<[u8; 20]>,
+    git_object: Vec<u8>,
+    hash: [u8; 20],
+}
+
+impl Revision {
+    /// Create a revision from the body of a Git commit object
+    ///
+    /// Fails with `SwhidError::InvalidInput` if the headers do not start with
+    /// a single `tree`, or name an invalid tree or parent id.
+    pub fn from_git_object(data: Vec<u8>) -> Result<Self, SwhidError> {
+        let mut tree = None;
+        let mut parents = Vec::new();
+        for (key, value) in git_object_headers(&data) {
+            match key {
+                b"tree" if tree.is_none() && parents.is_empty() => tree = Some(object_id(value, "tree")?),
+                b"tree" => return Err(SwhidError::InvalidInput("Commit object has a misplaced tree".to_string())),
+                b"parent" if tree.is_some() => parents.push(object_id(value, "parent")?),
+                _ if tree.is_none() => {
+                    return Err(SwhidError::InvalidInput("Commit object must start with its tree".to_string()))
+                }
+                _ => {}
+            }
+        }
+        let tree = tree.ok_or_else(|| SwhidError::InvalidInput("Commit object has no tree".to_string()))?;
+
+        Ok(Self {
+            tree,
+            parents,
+            hash: hash_git_object("commit", &data),
+            git_object: data,
+        })
+    }
+
+    /// Get the SWHID of the root directory of this revision
+    pub fn directory(&self) -> Swhid {
+        Swhid::new(ObjectType::Directory, self.tree)
+    }
+
+    /// Get the SWHIDs of the parent revisions, in commit order
+    pub fn parents(&self) -> Vec<Swhid> {
+        self.parents
+            .iter()
+            .map(|parent| Swhid::new(ObjectType::Revision, *parent))
+            .collect()
+    }
+
+    /// Get the commit time, as a Unix timestamp, from the `committer` header
+    ///
+    /// Returns `None` if the header is missing or has no valid timestamp.
+    pub fn committer_timestamp(&self) -> Option<i64> {
+        let (_, committer) = git_object_headers(&self.git_object).find(|(key, _)| *key == b"committer")?;
+        // The identity may contain spaces, but ends with the time and time zone
+        let mut fields = committer.rsplit(|&b| b == b' ');
+        fields.next()?;
+        std::str::from_utf8(fields.next()?).ok()?.parse().ok()
+    }
+
+    /// Get the body of the Git commit object, as hashed
+    pub fn git_object(&self) -> &[u8] {
+        &self.git_object
+    }
+
+    /// Compute SWHID for this revision
+    pub fn swhid(&self) -> Swhid {
+        Swhid::new(ObjectType::Revision, self.hash)
+    }
+}
+
+/// An identity with a date, as in the author or committer line of a commit
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GitSignature {
+    name: Vec<u8>,
+    email: Vec<u8>,
+    timestamp: i64,
+    offset: i32,
+    negative_utc: bool,
+}
+
+impl GitSignature {
+    /// Create a signature at `timestamp`, a Unix timestamp, in a time zone
+    /// `offset` minutes east of UTC
+    ///
+    /// Fails with `SwhidError::InvalidInput` if `name` or `email` contain
+    /// `<`, `>` or a newline, or if the offset is not within a day.
+    pub fn new(name: Vec<u8>, email: Vec<u8>, timestamp: i64, offset: i32) -> Result<Self, SwhidError> {
+        for (field, value) in [("name", &name), ("email", &email)] {
+            if value.iter().any(|b| matches!(b, b'<' | b'>' | b'\n')) {
+                return Err(SwhidError::InvalidInput(format!(
+                    "Invalid signature {}: {}",
+                    field,
+                    String::from_utf8_lossy(value)
+                )));
+            }
+        }
+        if offset.unsigned_abs() >= 24 * 60 {
+            return Err(SwhidError::InvalidInput(format!("Invalid time zone offset: {} minutes", offset)));
+        }
+        Ok(Self {
+            name,
+            email,
+            timestamp,
+            offset,
+            negative_utc: false,
+        })
+    }
+
+    /// Write a zero offset as `-0000` rather than `+0000`
+    ///
+    /// Some tools record `-0000` for an unknown time zone, and the object
+    /// hash depends on it. Has no effect on a non-zero offset.
+    pub fn with_negative_utc(mut self, negative_utc: bool) -> Self {
+        self.negative_utc = negative_utc;
+        self
+    }
+
+    /// Serialize as in a Git object header, e.g. `Jane Doe <jane@example.org> 1700000000 +0100`
+    pub(crate) fn to_git_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.name.clone();
+        bytes.extend_from_slice(b" <");
+        bytes.extend_from_slice(&self.email);
+        let sign = if self.offset < 0 || (self.offset == 0 && self.negative_utc) { '-' } else { '+' };
+        let offset = self.offset.unsigned_abs();
+        bytes.extend_from_slice(
+            format!("> {} {}{:02}{:02}", self.timestamp, sign, offset / 60, offset % 60).as_bytes(),
+        );
+        bytes
+    }
+}
+
+/// Builder of Git commit objects, for revisions that are not in a repository
+///
+/// Headers are written in the order Git writes them: `tree`, `parent`s,
+/// `author`, `committer`, then any extra headers.
+#[derive(Debug, Clone)]
+pub struct RevisionBuilder {
+    directory: Swhid,
+    parents: Vec<Swhid>,
+    author: Option<GitSignature>,
+    committer: Option<GitSignature>,
+    extra_headers: Vec<(Vec<u8>, Vec<u8>)>,
+    message: Vec<u8>,
+}
+
+impl RevisionBuilder {
+    /// Start a revision of the root directory `directory`
+    pub fn new(directory: Swhid) -> Self {
+        Self {
+            directory,
+            parents: Vec::new(),
+            author: None,
+            committer: None,
+            extra_headers: Vec::new(),
+            message: Vec::new(),
+        }
+    }
+
+    /// Add a parent revision, after the ones already added
+    pub fn with_parent(mut self, parent: Swhid) -> Self {
+        self.parents.push(parent);
+        self
+    }
+
+    /// Set the author
+    pub fn with_author(mut self, author: GitSignature) -> Self {
+        self.author = Some(author);
+        self
+    }
+
+    /// Set the committer
+    pub fn with_committer(mut self, committer: GitSignature) -> Self {
+        self.committer = Some(committer);
+        self
+    }
+
+    /// Add a header after the committer, such as `encoding` or `gpgsig`
+    ///
+    /// A value spanning several lines is written as Git does, with each line
+    /// after the first indented by a space.
+    pub fn with_extra_header(mut self, key: Vec<u8>, value: Vec<u8>) -> Self {
+        self.extra_headers.push((key, value));
+        self
+    }
+
+    /// Set the message, written as is after the headers and a blank line
+    pub fn with_message(mut self, message: Vec<u8>) -> Self {
+        self.message = message;
+        self
+    }
+
+    /// Serialize the commit object and create its revision
+    ///
+    /// Fails with `SwhidError::InvalidInput` if the directory or a parent has
+    /// the wrong SWHID type, if the author or committer is missing, or if an
+    /// extra header key is empty or contains a space or newline, and with
+    /// `SwhidError::InvalidVersion` if either is a v2 SWHID.
+    pub fn build(self) -> Result<Revision, SwhidError> {
+        if self.directory.object_type() != ObjectType::Directory {
+            return Err(SwhidError::InvalidInput(format!("{} is not a directory SWHID", self.directory)));
+        }
+        let mut data = format!("tree {}\n", hex::encode(self.directory.v1_hash()?)).into_bytes();
+        for parent in &self.parents {
+            if parent.object_type() != ObjectType::Revision {
+                return Err(SwhidError::InvalidInput(format!("{} is not a revision SWHID", parent)));
+            }
+            data.extend_from_slice(format!("parent {}\n", hex::encode(parent.v1_hash()?)).as_bytes());
+        }
+        for (key, signature) in [("author", &self.author), ("committer", &self.committer)] {
+            let signature = signature
+                .as_ref()
+                .ok_or_else(|| SwhidError::InvalidInput(format!("Commit object has no {}", key)))?;
+            write_header(&mut data, key.as_bytes(), &signature.to_git_bytes())?;
+        }
+        for (key, value) in &self.extra_headers {
+            write_header(&mut data, key, value)?;
+        }
+        data.push(b'\n');
+        data.extend_from_slice(&self.message);
+        Revision::from_git_object(data)
+    }
+}
+
+/// Write a header line, indenting the continuation lines of its value
+pub(crate) fn write_header(data: &mut Vec<u8>, key: &[u8], value: &[u8]) -> Result<(), SwhidError> {
+    if key.is_empty() || key.iter().any(|b| matches!(b, b' ' | b'\n')) {
+        return Err(SwhidError::InvalidInput(format!(
+            "Invalid header key: {:?}",
+            String::from_utf8_lossy(key)
+        )));
+    }
+    data.extend_from_slice(key);
+    data.push(b' ');
+    for (i, line) in value.split(|&b| b == b'\n').enumerate() {
+        if i > 0 {
+            data.extend_from_slice(b"\n ");
+        }
+        data.extend_from_slice(line);
+    }
+    data.push(b'\n');
+    Ok(())
+}
+
+/// Parse the hex object id of a commit header
+fn object_id(hex: &[u8], header: &str) -> Result<[u8; 20], SwhidError> {
+    parse_object_id(hex).ok_or_else(|| {
+        SwhidError::InvalidInput(format!(
+            "Invalid {} id in commit object: {}",
+            header,
+            String::from_utf8_lossy(hex)
+        ))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COMMIT: &[u8] = b"tree 37d98dc6f78d31f91c15676c00d504e425b0e9a7\n\
+parent a9483e1e52cd91ea9974c409aee7080d6c58f316\n\
+author Jane Doe <jane@example.org> 1700000000 +0100\n\
+committer Jane Doe <jane@example.org> 1700000000 +0100\n\
+\n\
+Second\n\
+\n\
+With body\n";
+
+    #[test]
+    fn test_revision_from_git_object() {
+        let revision = Revision::from_git_object(COMMIT.to_vec()).unwrap();
+        // As computed by `git commit-tree`
+        assert_eq!(
+            revision.swhid().to_string(),
+            "swh:1:rev:1e048ab552fec7fed3c552773ce146bcb5e57254"
+        );
+        assert_eq!(
+            revision.directory().to_string(),
+            "swh:1:dir:37d98dc6f78d31f91c15676c00d504e425b0e9a7"
+        );
+        assert_eq!(
+            revision.parents(),
+            vec![Swhid::from_string("swh:1:rev:a9483e1e52cd91ea9974c409aee7080d6c58f316").unwrap()]
+        );
+        assert_eq!(revision.git_object(), COMMIT);
+        assert_eq!(revision.committer_timestamp(), Some(1_700_000_000));
+
+        let no_committer = b"tree 37d98dc6f78d31f91c15676c00d504e425b0e9a7\n\nNo committer\n";
+        assert_eq!(Revision::from_git_object(no_committer.to_vec()).unwrap().committer_timestamp(), None);
+    }
+
+    #[test]
+    fn test_revision_from_git_object_invalid() {
+        for data in [
+            &b""[..],
+            b"author Jane Doe <jane@example.org> 1700000000 +0100\n",
+            b"tree 37d98dc6\n",
+            b"tree 37d98dc6f78d31f91c15676c00d504e425b0e9a7\nparent xyz\n",
+            b"tree 37d98dc6f78d31f91c15676c00d504e425b0e9a7\ntree 37d98dc6f78d31f91c15676c00d504e425b0e9a7\n",
+        ] {
+            assert!(matches!(
+                Revision::from_git_object(data.to_vec()),
+                Err(SwhidError::InvalidInput(_))
+            ));
+        }
+
+        // A `tree` line in the message is not a header
+        let mut data = COMMIT.to_vec();
+        data.extend_from_slice(b"tree 37d98dc6f78d31f91c15676c00d504e425b0e9a7\n");
+        assert!(Revision::from_git_object(data).is_ok());
+    }
+
+    fn jane(timestamp: i64, offset: i32) -> GitSignature {
+        GitSignature::new(b"Jane Doe".to_vec(), b"jane@example.org".to_vec(), timestamp, offset).unwrap()
+    }
+
+    #[test]
+    fn test_revision_builder() {
+        let directory = Swhid::from_string("swh:1:dir:37d98dc6f78d31f91c15676c00d504e425b0e9a7").unwrap();
+        let parent = Swhid::from_string("swh:1:rev:a9483e1e52cd91ea9974c409aee7080d6c58f316").unwrap();
+        let revision = RevisionBuilder::new(directory.clone())
+            .with_parent(parent.clone())
+            .with_author(jane(1_700_000_000, 60))
+            .with_committer(jane(1_700_000_000, 60))
+            .with_message(b"Second\n\nWith body\n".to_vec())
+            .build()
+            .unwrap();
+        assert_eq!(revision.git_object(), COMMIT);
+        assert_eq!(
+            revision.swhid().to_string(),
+            "swh:1:rev:1e048ab552fec7fed3c552773ce146bcb5e57254"
+        );
+
+        // Extra headers follow the committer, with continuation lines indented
+        let revision = RevisionBuilder::new(directory)
+            .with_author(jane(0, -90))
+            .with_committer(jane(1_700_000_000, 0))
+            .with_extra_header(b"gpgsig".to_vec(), b"-----BEGIN-----\nabc\n-----END-----".to_vec())
+            .build()
+            .unwrap();
+        assert_eq!(
+            revision.git_object(),
+            &b"tree 37d98dc6f78d31f91c15676c00d504e425b0e9a7\n\
+author Jane Doe <jane@example.org> 0 -0130\n\
+committer Jane Doe <jane@example.org> 1700000000 +0000\n\
+gpgsig -----BEGIN-----\n abc\n -----END-----\n\
+\n"[..]
+        );
+    }
+
+    #[test]
+    fn test_revision_builder_negative_utc() {
+        let directory = Swhid::from_string("swh:1:dir:37d98dc6f78d31f91c15676c00d504e425b0e9a7").unwrap();
+        let unknown = jane(1_700_000_000, 0).with_negative_utc(true);
+        let revision = RevisionBuilder::new(directory)
+            .with_author(unknown.clone())
+            .with_committer(unknown)
+            .with_message(b"Unknown time zone\n".to_vec())
+            .build()
+            .unwrap();
+        assert_eq!(
+            revision.git_object(),
+            &b"tree 37d98dc6f78d31f91c15676c00d504e425b0e9a7\n\
+author Jane Doe <jane@example.org> 1700000000 -0000\n\
+committer Jane Doe <jane@example.org> 1700000000 -0000\n\
+\n\
+Unknown time zone\n"[..]
+        );
+        assert_eq!(
+            revision.swhid().to_string(),
+            "swh:1:rev:a5c59eb82ded902307febdc2c0efdd6624209d01"
+        );
+        assert_ne!(jane(0, 0).with_negative_utc(true), jane(0, 0));
+        assert_eq!(jane(0, 60).with_negative_utc(true).to_git_bytes(), jane(0, 60).to_git_bytes());
+    }
+
+    #[test]
+    fn test_revision_builder_invalid() {
+        let directory = Swhid::from_string("swh:1:dir:37d98dc6f78d31f91c15676c00d504e425b0e9a7").unwrap();
+        let content = Swhid::from_string("swh:1:cnt:e69de29bb2d1d6434b8b29ae775ad8c2e48c5391").unwrap();
+        let complete = RevisionBuilder::new(directory.clone())
+            .with_author(jane(0, 0))
+            .with_committer(jane(0, 0));
+        for builder in [
+            RevisionBuilder::new(content.clone()).with_author(jane(0, 0)).with_committer(jane(0, 0)),
+            complete.clone().with_parent(content),
+            complete.clone().with_extra_header(b"bad key".to_vec(), b"value".to_vec()),
+            RevisionBuilder::new(directory).with_author(jane(0, 0)),
+        ] {
+            assert!(matches!(builder.build(), Err(SwhidError::InvalidInput(_))));
+        }
+
+        assert!(GitSignature::new(b"Jane <Doe>".to_vec(), b"jane@example.org".to_vec(), 0, 0).is_err());
+        assert!(GitSignature::new(b"Jane Doe".to_vec(), b"jane@example.org".to_vec(), 0, 24 * 60).is_err());
+        assert!(GitSignature::new(b"Jane Doe".to_vec(), b"jane@example.org".to_vec(), 0, i32::MIN).is_err());
+    }
+
+    #[cfg(feature = "v2")]
+    #[test]
+    fn test_revision_builder_rejects_v2() {
+        let directory = Swhid::new_v2(ObjectType::Directory, [1; 32]);
+        let builder = RevisionBuilder::new(directory).with_author(jane(0, 0)).with_committer(jane(0, 0));
+        assert!(matches!(builder.build(), Err(SwhidError::InvalidVersion(_))));
+    }
+}
@@ -0,0 +1,260 @@
+//! Walking the commit history of a repository
+//!
+//! [`RevisionWalk`] yields the revision SWHID of every commit reachable from
+//! some starting points, through any [`GitBackend`], so that a whole project
+//! history can be exported.
+
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use crate::backend::GitBackend;
+use crate::error::SwhidError;
+use crate::revision::Revision;
+use crate::swhid::Swhid;
+
+/// Order in which a [`RevisionWalk`] yields commits
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum WalkOrder {
+    /// Never a parent before all of its children, newest commits first
+    /// otherwise, as `git rev-list --date-order`
+    ///
+    /// The whole history is read before the first commit is yielded.
+    #[default]
+    Topological,
+    /// By committer date, newest first, as `git rev-list`
+    ///
+    /// Commits are read as they are reached. A parent may come before one of
+    /// its children if their clocks disagree.
+    Date,
+}
+
+/// Iterator over the commits reachable from some starting points
+///
+/// Yields each commit once, as its object id and revision SWHID. Commits are
+/// hashed as they are read, and fail with `SwhidError::InvalidHash` if one
+/// does not hash to its id. The walk stops after the first error.
+pub struct RevisionWalk<'a, B: GitBackend + ?Sized> {
+    backend: &'a B,
+    /// Commits ready to be yielded, the greatest first
+    queue: BinaryHeap<Pending>,
+    /// Commits queued or yielded so far, in date order; every commit in
+    /// topological order
+    seen: HashSet<[u8; 20]>,
+    /// In topological order, the number of children of each commit not
+    /// yielded yet
+    children: HashMap<[u8; 20], usize>,
+    /// In topological order, the commits read but not queued yet
+    waiting: HashMap<[u8; 20], Revision>,
+    order: WalkOrder,
+    /// An error to yield once the commit read before it has been yielded
+    error: Option<SwhidError>,
+    sequence: u64,
+}
+
+/// A commit in the queue, ordered by committer date, then first queued
+struct Pending {
+    timestamp: i64,
+    sequence: Reverse<u64>,
+    revision: Revision,
+}
+
+impl<'a, B: GitBackend + ?Sized> RevisionWalk<'a, B> {
+    /// Start walking from the commits the names in `starts` resolve to
+    ///
+    /// Names are resolved by [`GitBackend::resolve`], and tags are peeled to
+    /// the commit they point to, as for [`Revision::from_backend`].
+    pub fn new(backend: &'a B, starts: &[&str], order: WalkOrder) -> Result<Self, SwhidError> {
+        let mut walk = Self {
+            backend,
+            queue: BinaryHeap::new(),
+            seen: HashSet::new(),
+            children: HashMap::new(),
+            waiting: HashMap::new(),
+            order,
+            error: None,
+            sequence: 0,
+        };
+        let mut tips = Vec::new();
+        for name in starts {
+            let revision = Revision::from_backend(backend, name)?;
+            if walk.seen.insert(*revision.swhid().v1_hash()?) {
+                tips.push(revision);
+            }
+        }
+        match order {
+            WalkOrder::Date => tips.into_iter().for_each(|revision| walk.enqueue(revision)),
+            WalkOrder::Topological => {
+                // Read the whole history, counting the children of each commit
+                let tip_ids = tips
+                    .iter()
+                    .map(|tip| tip.swhid().v1_hash().copied())
+                    .collect::<Result<Vec<_>, _>>()?;
+                let mut stack = tip_ids.clone();
+                walk.waiting.extend(tip_ids.iter().copied().zip(tips));
+                while let Some(id) = stack.pop() {
+                    for parent in walk.waiting[&id].parents() {
+                        let parent = *parent.v1_hash()?;
+                        *walk.children.entry(parent).or_default() += 1;
+                        if walk.seen.insert(parent) {
+                            walk.waiting.insert(parent, backend.read_revision(&parent)?);
+                            stack.push(parent);
+                        }
+                    }
+                }
+                // Tips reachable from other tips wait for their children
+                for id in tip_ids {
+                    if !walk.children.contains_key(&id) {
+                        let tip = walk.waiting.remove(&id).expect("tips are read");
+                        walk.enqueue(tip);
+                    }
+                }
+            }
+        }
+        Ok(walk)
+    }
+
+    /// Queue a commit to be yielded
+    fn enqueue(&mut self, revision: Revision) {
+        self.sequence += 1;
+        self.queue.push(Pending {
+            timestamp: revision.committer_timestamp().unwrap_or(i64::MIN),
+            sequence: Reverse(self.sequence),
+            revision,
+        });
+    }
+
+    /// Queue the parents of a commit that has just been yielded
+    fn queue_parents(&mut self, revision: &Revision) -> Result<(), SwhidError> {
+        for parent in revision.parents() {
+            let id = *parent.v1_hash()?;
+            match self.order {
+                WalkOrder::Date => {
+                    if self.seen.insert(id) {
+                        let parent = self.backend.read_revision(&id)?;
+                        self.enqueue(parent);
+                    }
+                }
+                WalkOrder::Topological => {
+                    let count = self.children.get_mut(&id).expect("parents are counted");
+                    *count -= 1;
+                    if *count == 0 {
+                        let parent = self.waiting.remove(&id).expect("history is read");
+                        self.enqueue(parent);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<B: GitBackend + ?Sized> Iterator for RevisionWalk<'_, B> {
+    type Item = Result<([u8; 20], Swhid), SwhidError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(error) = self.error.take() {
+            self.queue.clear();
+            return Some(Err(error));
+        }
+        let revision = self.queue.pop()?.revision;
+        if let Err(error) = self.queue_parents(&revision) {
+            self.error = Some(error);
+        }
+        let swhid = revision.swhid();
+        Some(swhid.v1_hash().copied().map(|id| (id, swhid)))
+    }
+}
+
+impl PartialEq for Pending {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Pending {}
+
+impl PartialOrd for Pending {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Pending {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.timestamp, self.sequence).cmp(&(other.timestamp, other.sequence))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::tests::MemoryBackend;
+    use crate::backend::GitReference;
+    use crate::swhid::ObjectType;
+
+    fn add_commit(backend: &mut MemoryBackend, parents: &[[u8; 20]], timestamp: i64) -> [u8; 20] {
+        let mut data = b"tree 4b825dc642cb6eb9a060e54bf8d69288fbee4904\n".to_vec();
+        for parent in parents {
+            data.extend_from_slice(format!("parent {}\n", hex::encode(parent)).as_bytes());
+        }
+        let identity = format!("Jane Doe <jane@example.org> {} +0000", timestamp);
+        data.extend_from_slice(format!("author {0}\ncommitter {0}\n\nCommit\n", identity).as_bytes());
+        backend.add_object(ObjectType::Revision, "commit", &data)
+    }
+
+    /// A merge of two branches, where the older branch forked from a commit
+    /// whose clock is ahead of it
+    fn sample_history() -> (MemoryBackend, [[u8; 20]; 4]) {
+        let mut backend = MemoryBackend::default();
+        let fork = add_commit(&mut backend, &[], 250);
+        let newer = add_commit(&mut backend, &[fork], 300);
+        let older = add_commit(&mut backend, &[fork], 200);
+        let merge = add_commit(&mut backend, &[newer, older], 400);
+        backend.add_reference("refs/heads/main", GitReference::Direct(merge));
+        backend.add_reference("refs/heads/older", GitReference::Direct(older));
+        (backend, [merge, newer, older, fork])
+    }
+
+    fn ids<B: GitBackend>(walk: RevisionWalk<'_, B>) -> Vec<[u8; 20]> {
+        walk.map(|item| {
+            let (id, swhid) = item.unwrap();
+            assert_eq!(swhid, Swhid::new(ObjectType::Revision, id));
+            id
+        })
+        .collect()
+    }
+
+    #[test]
+    fn test_revision_walk_orders() {
+        let (backend, [merge, newer, older, fork]) = sample_history();
+        let topological = RevisionWalk::new(&backend, &["main"], WalkOrder::Topological).unwrap();
+        assert_eq!(ids(topological), vec![merge, newer, older, fork]);
+        let date = RevisionWalk::new(&backend, &["main"], WalkOrder::Date).unwrap();
+        assert_eq!(ids(date), vec![merge, newer, fork, older]);
+
+        // A starting point reachable from another is still yielded once,
+        // after its children in topological order
+        let walk = RevisionWalk::new(&backend, &["older", "main"], WalkOrder::Topological).unwrap();
+        assert_eq!(ids(walk), vec![merge, newer, older, fork]);
+        let walk = RevisionWalk::new(&backend, &["older"], WalkOrder::Date).unwrap();
+        assert_eq!(ids(walk), vec![older, fork]);
+    }
+
+    #[test]
+    fn test_revision_walk_errors() {
+        let (mut backend, [merge, _, _, fork]) = sample_history();
+        backend.objects.remove(&fork);
+        assert!(matches!(
+            RevisionWalk::new(&backend, &["main"], WalkOrder::Topological),
+            Err(SwhidError::Git(_))
+        ));
+
+        // Commits read so far are yielded before the error, then the walk stops
+        let mut walk = RevisionWalk::new(&backend, &["main"], WalkOrder::Date).unwrap();
+        assert_eq!(walk.next().unwrap().unwrap().0, merge);
+        assert!(walk.next().unwrap().is_ok());
+        assert!(matches!(walk.next(), Some(Err(SwhidError::Git(_)))));
+        assert!(walk.next().is_none());
+
+        assert!(RevisionWalk::new(&backend, &["no-such-branch"], WalkOrder::Date).is_err());
+    }
+}
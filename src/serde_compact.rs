@@ -0,0 +1,69 @@
+//! Serde support for the compact binary form of [`Swhid`]
+//!
+//! Use with `#[serde(with = "swhid::serde_compact")]` on a `Swhid` field to
+//! store it as the 21 bytes of [`Swhid::to_compact_bytes`] instead of its
+//! string form. Binary formats store the bytes as-is; text formats such as
+//! JSON store them as an array of numbers.
+
+use std::fmt;
+use serde::de::{self, SeqAccess, Visitor};
+use serde::ser;
+use serde::{Deserializer, Serializer};
+use crate::swhid::Swhid;
+
+/// Serialize a SWHID as its compact bytes
+///
+/// Fails for a v2 SWHID, which has no compact form.
+pub fn serialize<S: Serializer>(swhid: &Swhid, serializer: S) -> Result<S::Ok, S::Error> {
+    let bytes = swhid.to_compact_bytes().map_err(ser::Error::custom)?;
+    serializer.serialize_bytes(&bytes)
+}
+
+/// Deserialize a SWHID from its compact bytes
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Swhid, D::Error> {
+    deserializer.deserialize_bytes(CompactVisitor)
+}
+
+struct CompactVisitor;
+
+impl<'de> Visitor<'de> for CompactVisitor {
+    type Value = Swhid;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} bytes of a compact SWHID", Swhid::COMPACT_LEN)
+    }
+
+    fn visit_bytes<E: de::Error>(self, bytes: &[u8]) -> Result<Swhid, E> {
+        Swhid::from_compact_bytes(bytes).map_err(E::custom)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Swhid, A::Error> {
+        let mut bytes = Vec::with_capacity(Swhid::COMPACT_LEN);
+        while let Some(byte) = seq.next_element::<u8>()? {
+            bytes.push(byte);
+        }
+        Swhid::from_compact_bytes(&bytes).map_err(de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::swhid::ObjectType;
+
+    #[test]
+    fn test_serde_compact_round_trip() {
+        let swhid = Swhid::new(ObjectType::Release, [0xab; 20]);
+
+        let mut json = Vec::new();
+        serialize(&swhid, &mut serde_json::Serializer::new(&mut json)).unwrap();
+        let mut deserializer = serde_json::Deserializer::from_slice(&json);
+        assert_eq!(deserialize(&mut deserializer).unwrap(), swhid);
+    }
+
+    #[test]
+    fn test_serde_compact_wrong_length() {
+        let mut deserializer = serde_json::Deserializer::from_str("[1, 2, 3]");
+        assert!(deserialize(&mut deserializer).is_err());
+    }
+}
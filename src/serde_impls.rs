@@ -0,0 +1,84 @@
+//! Serde support for SWHID types, in their canonical string form
+
+use serde::de::{self, Deserialize, Deserializer};
+use serde::ser::{Serialize, Serializer};
+use crate::swhid::{ObjectType, QualifiedSwhid, Swhid};
+
+impl Serialize for ObjectType {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for ObjectType {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse::<ObjectType>().map_err(de::Error::custom)
+    }
+}
+
+impl Serialize for Swhid {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Swhid {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        Swhid::from_string(&s).map_err(de::Error::custom)
+    }
+}
+
+impl Serialize for QualifiedSwhid {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for QualifiedSwhid {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        QualifiedSwhid::from_string(&s).map_err(de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serde_swhid_round_trip() {
+        let swhids = vec![
+            Swhid::from_string("swh:1:cnt:94a9ed024d3859793618152ea559a168bbcbb5e2").unwrap(),
+            Swhid::from_string("swh:1:dir:d198bc9d7a6bcf6db04f476d29314f157507d505").unwrap(),
+        ];
+        let json = serde_json::to_string(&swhids).unwrap();
+        assert_eq!(
+            json,
+            r#"["swh:1:cnt:94a9ed024d3859793618152ea559a168bbcbb5e2","swh:1:dir:d198bc9d7a6bcf6db04f476d29314f157507d505"]"#
+        );
+        assert_eq!(serde_json::from_str::<Vec<Swhid>>(&json).unwrap(), swhids);
+    }
+
+    #[test]
+    fn test_serde_object_type_and_qualified() {
+        assert_eq!(serde_json::to_string(&ObjectType::Release).unwrap(), r#""rel""#);
+        assert_eq!(serde_json::from_str::<ObjectType>(r#""snp""#).unwrap(), ObjectType::Snapshot);
+
+        let s = "swh:1:cnt:94a9ed024d3859793618152ea559a168bbcbb5e2;origin=https://example.org;lines=1-2";
+        let qualified = QualifiedSwhid::from_string(s).unwrap();
+        let json = serde_json::to_string(&qualified).unwrap();
+        assert_eq!(json, format!("\"{}\"", s));
+        assert_eq!(serde_json::from_str::<QualifiedSwhid>(&json).unwrap(), qualified);
+    }
+
+    #[test]
+    fn test_serde_invalid_string() {
+        let err = serde_json::from_str::<Swhid>(r#""swh:1:cnt:abcd""#).unwrap_err();
+        assert!(err.to_string().contains("Invalid hash length: 4"));
+        assert!(serde_json::from_str::<Swhid>("42").is_err());
+        assert!(serde_json::from_str::<ObjectType>(r#""blob""#).is_err());
+        assert!(serde_json::from_str::<QualifiedSwhid>(r#""swh:1:cnt:94a9ed024d3859793618152ea559a168bbcbb5e2;unknown=1""#).is_err());
+    }
+}
@@ -0,0 +1,147 @@
+use std::collections::BTreeMap;
+use crate::swhid::{Swhid, ObjectType};
+use crate::hash::hash_git_object;
+use crate::error::SwhidError;
+
+/// What a snapshot branch points to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SnapshotTarget {
+    /// An object, e.g. the revision at the tip of a branch
+    Object(Swhid),
+    /// Another branch of the same snapshot, by name, e.g. `HEAD`
+    Alias(Vec<u8>),
+    /// Nothing: the branch exists but has no target
+    Dangling,
+}
+
+/// Snapshot object: the full state of a repository's branches
+///
+/// Branches are kept sorted by name, in the order they are hashed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Snapshot {
+    branches: BTreeMap<Vec<u8>, SnapshotTarget>,
+}
+
+impl Snapshot {
+    /// Create a new snapshot without branches
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a branch
+    ///
+    /// Aliases may point to branches that are not (yet) in the snapshot.
+    /// Fails with `SwhidError::DuplicateEntry` if a branch with that name
+    /// already exists, and with `SwhidError::InvalidVersion` if the target is
+    /// a v2 SWHID.
+    pub fn add_branch(&mut self, name: Vec<u8>, target: SnapshotTarget) -> Result<(), SwhidError> {
+        if let SnapshotTarget::Object(swhid) = &target {
+            swhid.v1_hash()?;
+        }
+        if self.branches.contains_key(&name) {
+            return Err(SwhidError::DuplicateEntry(String::from_utf8_lossy(&name).into_owned()));
+        }
+        self.branches.insert(name, target);
+        Ok(())
+    }
+
+    /// Get the branches, sorted by name
+    pub fn branches(&self) -> &BTreeMap<Vec<u8>, SnapshotTarget> {
+        &self.branches
+    }
+
+    /// Serialize the branches as the manifest hashed for the snapshot SWHID
+    ///
+    /// Each branch is `<target type> <name>\0<length>:<target>`, where the
+    /// target is the raw object id, the aliased branch name, or empty for a
+    /// dangling branch.
+    pub fn git_object(&self) -> Vec<u8> {
+        let mut manifest = Vec::new();
+        for (name, target) in &self.branches {
+            let (target_type, target): (&str, &[u8]) = match target {
+                SnapshotTarget::Object(swhid) => (object_type_name(swhid.object_type()), swhid.digest()),
+                SnapshotTarget::Alias(branch) => ("alias", branch),
+                SnapshotTarget::Dangling => ("dangling", &[]),
+            };
+            manifest.extend_from_slice(target_type.as_bytes());
+            manifest.push(b' ');
+            manifest.extend_from_slice(name);
+            manifest.push(0);
+            manifest.extend_from_slice(format!("{}:", target.len()).as_bytes());
+            manifest.extend_from_slice(target);
+        }
+        manifest
+    }
+
+    /// Compute SWHID for this snapshot
+    pub fn swhid(&self) -> Swhid {
+        Swhid::new(ObjectType::Snapshot, hash_git_object("snapshot", &self.git_object()))
+    }
+}
+
+/// Name of a branch target type in the snapshot manifest
+fn object_type_name(object_type: ObjectType) -> &'static str {
+    match object_type {
+        ObjectType::Content => "content",
+        ObjectType::Directory => "directory",
+        ObjectType::Revision => "revision",
+        ObjectType::Release => "release",
+        ObjectType::Snapshot => "snapshot",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn object(object_type: ObjectType, hex: &str) -> SnapshotTarget {
+        SnapshotTarget::Object(Swhid::new(object_type, hex::decode(hex).unwrap().try_into().unwrap()))
+    }
+
+    // Reference identifiers from the swh-model test suite
+    #[test]
+    fn test_snapshot_swhid() {
+        assert_eq!(
+            Snapshot::new().swhid().to_string(),
+            "swh:1:snp:1a8893e6a86f444e8be8e7bda6cb34fb1735a00e"
+        );
+
+        let mut dangling = Snapshot::new();
+        dangling.add_branch(b"HEAD".to_vec(), SnapshotTarget::Dangling).unwrap();
+        assert_eq!(dangling.swhid().to_string(), "swh:1:snp:c84502e821eb21ed84e9fd3ec40973abc8b32353");
+
+        let mut unresolved = Snapshot::new();
+        unresolved.add_branch(b"foo".to_vec(), SnapshotTarget::Alias(b"bar".to_vec())).unwrap();
+        assert_eq!(unresolved.swhid().to_string(), "swh:1:snp:84b4548ea486e4b0a7933fa541ff1503a0afe1e0");
+
+        let mut all_types = Snapshot::new();
+        for (name, target) in [
+            ("directory", object(ObjectType::Directory, "1bd0e65f7d2ff14ae994de17a1e7fe65111dcad8")),
+            ("content", object(ObjectType::Content, "fe95a46679d128ff167b7c55df5d02356c5a1ae1")),
+            ("alias", SnapshotTarget::Alias(b"revision".to_vec())),
+            ("revision", object(ObjectType::Revision, "aafb16d69fd30ff58afdd69036a26047f3aebdc6")),
+            ("release", object(ObjectType::Release, "7045404f3d1c54e6473c71bbb716529fbad4be24")),
+            ("snapshot", object(ObjectType::Snapshot, "1a8893e6a86f444e8be8e7bda6cb34fb1735a00e")),
+            ("dangling", SnapshotTarget::Dangling),
+        ] {
+            all_types.add_branch(name.as_bytes().to_vec(), target).unwrap();
+        }
+        assert_eq!(all_types.swhid().to_string(), "swh:1:snp:6e65b86363953b780d92b0a928f3e8fcdd10db36");
+    }
+
+    #[test]
+    fn test_snapshot_duplicate_branch() {
+        let mut snapshot = Snapshot::new();
+        snapshot.add_branch(b"HEAD".to_vec(), SnapshotTarget::Dangling).unwrap();
+        assert!(matches!(
+            snapshot.add_branch(b"HEAD".to_vec(), SnapshotTarget::Alias(b"refs/heads/main".to_vec())),
+            Err(SwhidError::DuplicateEntry(_))
+        ));
+
+        #[cfg(feature = "v2")]
+        assert!(matches!(
+            snapshot.add_branch(b"main".to_vec(), SnapshotTarget::Object(Swhid::new_v2(ObjectType::Revision, [1; 32]))),
+            Err(SwhidError::InvalidVersion(_))
+        ));
+    }
+}
@@ -1,5 +1,12 @@
 use std::fmt;
+use std::ops::Range;
+use std::str::FromStr;
 use crate::error::SwhidError;
+use crate::hash::sha1_git_hash;
+use crate::origin::Origin;
+
+/// Base URL of the Software Heritage archive, under which SWHIDs resolve
+pub const ARCHIVE_URL: &str = "https://archive.softwareheritage.org/";
 
 /// Software Hash object types (Core SWHID)
 /// According to the official SWHID specification v1.2
@@ -23,7 +30,24 @@ impl ObjectType {
         }
     }
 
+    /// Parse an object type, e.g. `cnt`
+    #[deprecated(note = "use `str::parse` or `FromStr::from_str` instead")]
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(s: &str) -> Result<Self, SwhidError> {
+        s.parse()
+    }
+}
+
+impl fmt::Display for ObjectType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for ObjectType {
+    type Err = SwhidError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
             "cnt" => Ok(ObjectType::Content),
             "dir" => Ok(ObjectType::Directory),
@@ -35,18 +59,26 @@ impl ObjectType {
     }
 }
 
-impl fmt::Display for ObjectType {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.as_str())
-    }
-}
-
 /// Core Software Hash Identifier
 /// Format: swh:1:<object_type>:<40_character_hex_hash>
+///
+/// With the `v2` feature, this may also be an identifier of the draft v2
+/// scheme, `swh:2:<object_type>:<64_character_hex_hash>`, whose hashes are
+/// SHA-256 Git object hashes.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Swhid {
     object_type: ObjectType,
-    hash: [u8; 20],
+    digest: Digest,
+}
+
+/// Hash of a SWHID, by scheme version
+///
+/// Without the `v2` feature this is just the 20 bytes of the SHA-1 hash.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Digest {
+    Sha1([u8; 20]),
+    #[cfg(feature = "v2")]
+    Sha256([u8; 32]),
 }
 
 impl Swhid {
@@ -54,7 +86,18 @@ impl Swhid {
     pub fn new(object_type: ObjectType, hash: [u8; 20]) -> Self {
         Self {
             object_type,
-            hash,
+            digest: Digest::Sha1(hash),
+        }
+    }
+
+    /// Create a SWHID of the draft v2 scheme, from a SHA-256 Git object hash
+    ///
+    /// The v2 scheme is not stable: its identifiers may change with the draft.
+    #[cfg(feature = "v2")]
+    pub fn new_v2(object_type: ObjectType, hash: [u8; 32]) -> Self {
+        Self {
+            object_type,
+            digest: Digest::Sha256(hash),
         }
     }
 
@@ -63,13 +106,245 @@ impl Swhid {
         self.object_type
     }
 
+    /// Get the scheme version: 1, or 2 for the draft v2 scheme
+    pub fn version(&self) -> u8 {
+        match self.digest {
+            Digest::Sha1(_) => 1,
+            #[cfg(feature = "v2")]
+            Digest::Sha256(_) => 2,
+        }
+    }
+
     /// Get the hash
+    ///
+    /// # Panics
+    ///
+    /// For a v2 SWHID, whose hash has 32 bytes.
+    #[deprecated(note = "panics on v2 SWHIDs; use `sha1` or `digest` instead")]
     pub fn hash(&self) -> &[u8; 20] {
-        &self.hash
+        self.sha1()
+            .unwrap_or_else(|| panic!("{} does not have a SHA-1 hash", self))
+    }
+
+    /// Get the SHA-1 hash, or `None` for a v2 SWHID
+    pub fn sha1(&self) -> Option<&[u8; 20]> {
+        match &self.digest {
+            Digest::Sha1(hash) => Some(hash),
+            #[cfg(feature = "v2")]
+            Digest::Sha256(_) => None,
+        }
+    }
+
+    /// Get the SHA-1 hash, failing with `SwhidError::InvalidVersion` for a
+    /// v2 SWHID, e.g. when it would go into a Git object
+    pub(crate) fn v1_hash(&self) -> Result<&[u8; 20], SwhidError> {
+        self.sha1()
+            .ok_or_else(|| SwhidError::InvalidVersion(self.version().to_string()))
+    }
+
+    /// Get the hash bytes of any version: 20 for v1, 32 for v2
+    pub fn digest(&self) -> &[u8] {
+        match &self.digest {
+            Digest::Sha1(hash) => hash,
+            #[cfg(feature = "v2")]
+            Digest::Sha256(hash) => hash,
+        }
+    }
+
+    /// Get the URL browsing this object in the Software Heritage archive
+    pub fn to_archive_url(&self) -> String {
+        format!("{}{}", ARCHIVE_URL, self)
+    }
+
+    /// Format this SWHID with its hash shortened to `len` hex digits
+    ///
+    /// Abbreviated SWHIDs are for display only and cannot be parsed back.
+    /// `len` is capped at the full 40 digits, or 64 for v2.
+    pub fn abbreviated(&self, len: usize) -> String {
+        let hash = hex::encode(self.digest());
+        format!("swh:{}:{}:{}", self.version(), self.object_type, &hash[..len.min(hash.len())])
     }
 
     /// Parse SWHID from string
+    ///
+    /// Equivalent to `s.parse::<Swhid>()`.
     pub fn from_string(s: &str) -> Result<Self, SwhidError> {
+        s.parse()
+    }
+
+    /// Parse a SWHID from a spreadsheet cell, tolerating quotes and padding
+    ///
+    /// Surrounding whitespace and one pair of matching double or single
+    /// quotes are removed before parsing with [`Swhid::from_string`]. Use
+    /// `from_string` when input must already be canonical.
+    pub fn from_cell(s: &str) -> Result<Self, SwhidError> {
+        let mut cell = s.trim();
+        for quote in ['"', '\''] {
+            if let Some(inner) = cell
+                .strip_prefix(quote)
+                .and_then(|rest| rest.strip_suffix(quote))
+            {
+                cell = inner.trim();
+                break;
+            }
+        }
+        Self::from_string(cell)
+    }
+
+    /// Length of the compact binary form
+    pub const COMPACT_LEN: usize = 21;
+
+    /// Encode this SWHID in its compact binary form
+    ///
+    /// The layout is one object type byte (`1` for `cnt`, `2` for `dir`, `3`
+    /// for `rev`, `4` for `rel`, `5` for `snp`) followed by the 20 raw hash
+    /// bytes. The namespace and version are implied: the form only stores
+    /// `swh:1` identifiers, so a v2 SWHID fails with
+    /// `SwhidError::InvalidVersion`.
+    pub fn to_compact_bytes(&self) -> Result<[u8; 21], SwhidError> {
+        let mut bytes = [0u8; Self::COMPACT_LEN];
+        bytes[0] = match self.object_type {
+            ObjectType::Content => 1,
+            ObjectType::Directory => 2,
+            ObjectType::Revision => 3,
+            ObjectType::Release => 4,
+            ObjectType::Snapshot => 5,
+        };
+        bytes[1..].copy_from_slice(self.v1_hash()?);
+        Ok(bytes)
+    }
+
+    /// Decode a SWHID from the compact binary form of [`Swhid::to_compact_bytes`]
+    pub fn from_compact_bytes(bytes: &[u8]) -> Result<Self, SwhidError> {
+        if bytes.len() != Self::COMPACT_LEN {
+            return Err(SwhidError::InvalidFormat(format!(
+                "compact SWHID must be {} bytes, got {}",
+                Self::COMPACT_LEN,
+                bytes.len()
+            )));
+        }
+        let object_type = match bytes[0] {
+            1 => ObjectType::Content,
+            2 => ObjectType::Directory,
+            3 => ObjectType::Revision,
+            4 => ObjectType::Release,
+            5 => ObjectType::Snapshot,
+            other => return Err(SwhidError::InvalidObjectType(format!("{:#04x}", other))),
+        };
+        let mut hash = [0u8; 20];
+        hash.copy_from_slice(&bytes[1..]);
+        Ok(Self::new(object_type, hash))
+    }
+
+    /// Parse a (possibly qualified) SWHID into its full grammar, with spans
+    ///
+    /// The core identifier is validated exactly as in [`Swhid::from_string`],
+    /// but must be a v1 identifier. Qualifiers are only split into their key
+    /// and value tokens, so that tools such as linters can report precise
+    /// diagnostics on them.
+    pub fn parse_detailed(s: &str) -> Result<ParsedSwhid, SwhidError> {
+        let core_end = s.find(';').unwrap_or(s.len());
+        let core = &s[..core_end];
+
+        // Split the core on ':' while keeping track of byte offsets
+        let mut fields = Vec::new();
+        let mut start = 0;
+        for part in core.split(':') {
+            fields.push(Spanned::new(part.to_string(), start..start + part.len()));
+            start += part.len() + 1;
+        }
+        if fields.len() != 4 {
+            return Err(SwhidError::InvalidFormat(format!(
+                "SWHID must have 4 parts, got {}: {}", fields.len(), core
+            )));
+        }
+
+        let swhid = Swhid::from_string(core)?;
+        let sha1 = *swhid.v1_hash()?;
+        let hash = fields.pop().unwrap();
+        let object_type = fields.pop().unwrap();
+        let version = fields.pop().unwrap();
+        let namespace = fields.pop().unwrap();
+
+        let mut qualifiers = Vec::new();
+        let mut start = core_end + 1;
+        if core_end < s.len() {
+            for qualifier in s[core_end + 1..].split(';') {
+                let span = start..start + qualifier.len();
+                let (key, value) = qualifier.split_once('=').ok_or_else(|| {
+                    SwhidError::InvalidFormat(format!("Invalid qualifier format: {}", qualifier))
+                })?;
+                let value_start = start + key.len() + 1;
+                qualifiers.push(ParsedQualifier {
+                    key: Spanned::new(key.to_string(), start..start + key.len()),
+                    value: Spanned::new(value.to_string(), value_start..span.end),
+                    span,
+                });
+                start += qualifier.len() + 1;
+            }
+        }
+
+        Ok(ParsedSwhid {
+            namespace,
+            version,
+            object_type: Spanned::new(swhid.object_type, object_type.span),
+            hash: Spanned::new(sha1, hash.span),
+            qualifiers,
+        })
+    }
+}
+
+/// A parsed token together with its byte range in the source string
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Spanned<T> {
+    pub value: T,
+    pub span: Range<usize>,
+}
+
+impl<T> Spanned<T> {
+    pub fn new(value: T, span: Range<usize>) -> Self {
+        Self { value, span }
+    }
+}
+
+/// A qualifier of a parsed SWHID, as it appeared in the source string
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParsedQualifier {
+    pub key: Spanned<String>,
+    pub value: Spanned<String>,
+    /// Range of the whole `key=value` pair
+    pub span: Range<usize>,
+}
+
+/// Every token of a parsed SWHID with its source span
+/// Produced by [`Swhid::parse_detailed`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParsedSwhid {
+    pub namespace: Spanned<String>,
+    pub version: Spanned<String>,
+    pub object_type: Spanned<ObjectType>,
+    pub hash: Spanned<[u8; 20]>,
+    pub qualifiers: Vec<ParsedQualifier>,
+}
+
+impl ParsedSwhid {
+    /// Get the core SWHID
+    pub fn swhid(&self) -> Swhid {
+        Swhid::new(self.object_type.value, self.hash.value)
+    }
+}
+
+impl fmt::Display for Swhid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let hash_hex = hex::encode(self.digest());
+        write!(f, "swh:{}:{}:{}", self.version(), self.object_type, hash_hex)
+    }
+}
+
+impl FromStr for Swhid {
+    type Err = SwhidError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
         let parts: Vec<&str> = s.split(':').collect();
         
         if parts.len() != 4 {
@@ -84,42 +359,104 @@ impl Swhid {
         }
 
         // Check version
-        if parts[1] != "1" {
-            return Err(SwhidError::InvalidVersion(parts[1].to_string()));
-        }
+        let version = parse_version(parts[1])?;
 
         // Parse object type
-        let object_type = ObjectType::from_str(parts[2])?;
+        let object_type: ObjectType = parts[2].parse()?;
 
         // Parse hash
+        let hash_len = if version == 1 { 40 } else { 64 };
+        if parts[3].len() != hash_len {
+            return Err(SwhidError::InvalidHashLength(parts[3].len()));
+        }
+
         let hash_bytes = hex::decode(parts[3])
             .map_err(|e| SwhidError::InvalidHash(e.to_string()))?;
-        
-        if hash_bytes.len() != 20 {
-            return Err(SwhidError::InvalidHashLength(hash_bytes.len()));
-        }
 
-        let mut hash = [0u8; 20];
-        hash.copy_from_slice(&hash_bytes);
+        let digest = match version {
+            #[cfg(feature = "v2")]
+            2 => Digest::Sha256(hash_bytes.try_into().expect("checked hash length")),
+            _ => Digest::Sha1(hash_bytes.try_into().expect("checked hash length")),
+        };
 
-        Ok(Swhid::new(object_type, hash))
+        Ok(Swhid { object_type, digest })
     }
 }
 
-impl fmt::Display for Swhid {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let hash_hex = hex::encode(self.hash);
-        write!(f, "swh:1:{}:{}", self.object_type, hash_hex)
+/// Parse the scheme version of a SWHID; `2` needs the `v2` feature
+fn parse_version(version: &str) -> Result<u8, SwhidError> {
+    match version {
+        "1" => Ok(1),
+        #[cfg(feature = "v2")]
+        "2" => Ok(2),
+        _ => Err(SwhidError::InvalidVersion(version.to_string())),
     }
 }
 
+/// Read the object type of a SWHID string without validating the rest
+///
+/// Only the namespace, version and object type are checked, so this is a
+/// cheap way to dispatch on the type. The hash and any qualifiers are not
+/// looked at: a truncated string such as `swh:1:cnt:b45e` is accepted.
+pub fn peek_object_type(s: &str) -> Result<ObjectType, SwhidError> {
+    let mut parts = s.splitn(4, ':');
+    let (Some(namespace), Some(version), Some(object_type)) = (parts.next(), parts.next(), parts.next()) else {
+        return Err(SwhidError::InvalidFormat(format!(
+            "SWHID must start with swh:1:<object_type>: {}", s
+        )));
+    };
+
+    if namespace != "swh" {
+        return Err(SwhidError::InvalidNamespace(namespace.to_string()));
+    }
+    parse_version(version)?;
+    object_type.parse()
+}
+
+/// Shortest hash prefix length that tells all the given SWHIDs apart
+///
+/// Only SWHIDs of the same version and object type are compared, since both
+/// are part of every abbreviation. Repeated SWHIDs are ignored. The result is at least
+/// 1, so it can be passed directly to [`Swhid::abbreviated`].
+pub fn min_unique_prefix_len(swhids: &[Swhid]) -> usize {
+    let mut sorted: Vec<((u8, ObjectType), String)> = swhids
+        .iter()
+        .map(|swhid| ((swhid.version(), swhid.object_type), hex::encode(swhid.digest())))
+        .collect();
+    sorted.sort_by(|a, b| (a.0 .0, a.0 .1.as_str(), &a.1).cmp(&(b.0 .0, b.0 .1.as_str(), &b.1)));
+    sorted.dedup();
+
+    // In sorted order, a hash shares its longest prefix with a neighbour
+    sorted
+        .windows(2)
+        .filter(|pair| pair[0].0 == pair[1].0)
+        .map(|pair| {
+            let common = pair[0]
+                .1
+                .bytes()
+                .zip(pair[1].1.bytes())
+                .take_while(|(a, b)| a == b)
+                .count();
+            common + 1
+        })
+        .max()
+        .unwrap_or(1)
+}
+
 /// Qualified Software Hash Identifier
 /// Format: swh:1:<object_type>:<hash>[;qualifier=value]*
 /// According to the official SWHID specification v1.2
-#[derive(Debug, Clone, PartialEq, Eq)]
+///
+/// Qualifier values are percent-escaped when displayed and unescaped when
+/// parsed, so that origins and paths holding `;`, spaces or non-ASCII
+/// characters round-trip unambiguously.
+///
+/// Equality compares the qualifiers too; [`QualifiedSwhid::core_eq`] only
+/// compares the objects referenced.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct QualifiedSwhid {
     core: Swhid,
-    origin: Option<String>,
+    origin: Option<Origin>,
     visit: Option<Swhid>,
     anchor: Option<Swhid>,
     path: Option<Vec<u8>>,
@@ -128,6 +465,14 @@ pub struct QualifiedSwhid {
 }
 
 impl QualifiedSwhid {
+    /// Start building a qualified SWHID of `core`, checking each qualifier
+    pub fn builder(core: Swhid) -> QualifiedSwhidBuilder {
+        QualifiedSwhidBuilder {
+            qualified: Self::new(core),
+            error: None,
+        }
+    }
+
     /// Create a new QualifiedSWHID from a core SWHID
     pub fn new(core: Swhid) -> Self {
         Self {
@@ -152,12 +497,66 @@ impl QualifiedSwhid {
     }
 
     /// Get the hash
+    ///
+    /// # Panics
+    ///
+    /// For a v2 SWHID, whose hash has 32 bytes.
+    #[deprecated(note = "panics on v2 SWHIDs; use `sha1` or `digest` instead")]
     pub fn hash(&self) -> &[u8; 20] {
+        #[allow(deprecated)]
         self.core.hash()
     }
 
-    /// Set the origin qualifier
-    pub fn with_origin(mut self, origin: String) -> Self {
+    /// Get the SHA-1 hash, or `None` for a v2 SWHID
+    pub fn sha1(&self) -> Option<&[u8; 20]> {
+        self.core.sha1()
+    }
+
+    /// Get the hash bytes of any version: 20 for v1, 32 for v2
+    pub fn digest(&self) -> &[u8] {
+        self.core.digest()
+    }
+
+    /// Check whether this SWHID references `other`, ignoring qualifiers
+    pub fn same_object(&self, other: &Swhid) -> bool {
+        self.core == *other
+    }
+
+    /// Check whether both SWHIDs reference the same object, ignoring qualifiers
+    ///
+    /// Shorthand for [`QualifiedSwhid::same_object`] with the core of `other`.
+    pub fn core_eq(&self, other: &QualifiedSwhid) -> bool {
+        self.same_object(other.core())
+    }
+
+    /// Drop the qualifiers, keeping the core SWHID
+    pub fn into_core(self) -> Swhid {
+        self.core
+    }
+
+    /// Get the URL browsing this object in the Software Heritage archive,
+    /// in the context the qualifiers give
+    ///
+    /// The URL is [`ARCHIVE_URL`] followed by the SWHID, with `?` and `#` in
+    /// qualifier values escaped too, so they do not end the URL path.
+    pub fn to_archive_url(&self) -> String {
+        let swhid = self.to_string().replace('?', "%3F").replace('#', "%23");
+        format!("{}{}", ARCHIVE_URL, swhid)
+    }
+
+    /// Parse a URL from [`QualifiedSwhid::to_archive_url`]
+    ///
+    /// A trailing `/` is allowed. Fails with `SwhidError::InvalidFormat` for
+    /// URLs outside [`ARCHIVE_URL`].
+    pub fn from_archive_url(url: &str) -> Result<Self, SwhidError> {
+        let swhid = url
+            .strip_prefix(ARCHIVE_URL)
+            .ok_or_else(|| SwhidError::InvalidFormat(format!("{} is not a Software Heritage archive URL", url)))?;
+        Self::from_string(swhid.strip_suffix('/').unwrap_or(swhid))
+    }
+
+    /// Set the origin qualifier, an IRI checked by [`Origin::new`]
+    pub fn with_origin(mut self, origin: Origin) -> Self {
         self.origin = Some(origin);
         self
     }
@@ -194,12 +593,17 @@ impl QualifiedSwhid {
     }
 
     /// Set the lines qualifier
+    ///
+    /// The range is not checked: one ending before it starts is displayed,
+    /// but rejected when parsed back. [`QualifiedSwhid::builder`] checks it.
     pub fn with_lines(mut self, start: u32, end: Option<u32>) -> Self {
         self.lines = Some((start, end));
         self
     }
 
     /// Set the bytes qualifier
+    ///
+    /// As for [`QualifiedSwhid::with_lines`], the range is not checked.
     pub fn with_bytes(mut self, start: u32, end: Option<u32>) -> Self {
         self.bytes = Some((start, end));
         self
@@ -207,7 +611,12 @@ impl QualifiedSwhid {
 
     /// Get the origin qualifier
     pub fn origin(&self) -> Option<&str> {
-        self.origin.as_deref()
+        self.origin.as_ref().map(Origin::as_str)
+    }
+
+    /// Get the origin qualifier as an [`Origin`]
+    pub fn origin_iri(&self) -> Option<&Origin> {
+        self.origin.as_ref()
     }
 
     /// Get the visit qualifier
@@ -222,7 +631,7 @@ impl QualifiedSwhid {
 
     /// Get the path qualifier
     pub fn path(&self) -> Option<&[u8]> {
-        self.path.as_ref().map(|v| v.as_slice())
+        self.path.as_deref()
     }
 
     /// Get the lines qualifier
@@ -235,8 +644,258 @@ impl QualifiedSwhid {
         self.bytes
     }
 
+    /// List the qualifiers that are set, as keys and values, in canonical order
+    ///
+    /// The order is the one of the specification: `origin`, `visit`,
+    /// `anchor`, `path`, `lines`, then `bytes`. This is how they are
+    /// displayed, but values are not escaped here. Paths that are not valid
+    /// UTF-8 are converted lossily.
+    pub fn qualifiers(&self) -> Vec<(&'static str, String)> {
+        self.raw_qualifiers()
+            .into_iter()
+            .map(|(key, value)| (key, String::from_utf8_lossy(&value).into_owned()))
+            .collect()
+    }
+
+    /// List the qualifiers that are set, with values as bytes, in canonical order
+    fn raw_qualifiers(&self) -> Vec<(&'static str, Vec<u8>)> {
+        let range = |(start, end): (u32, Option<u32>)| match end {
+            Some(end) => format!("{}-{}", start, end).into_bytes(),
+            None => start.to_string().into_bytes(),
+        };
+        let mut qualifiers = Vec::new();
+        if let Some(origin) = &self.origin {
+            qualifiers.push(("origin", origin.as_str().as_bytes().to_vec()));
+        }
+        if let Some(visit) = &self.visit {
+            qualifiers.push(("visit", visit.to_string().into_bytes()));
+        }
+        if let Some(anchor) = &self.anchor {
+            qualifiers.push(("anchor", anchor.to_string().into_bytes()));
+        }
+        if let Some(path) = &self.path {
+            qualifiers.push(("path", path.clone()));
+        }
+        if let Some(lines) = self.lines {
+            qualifiers.push(("lines", range(lines)));
+        }
+        if let Some(bytes) = self.bytes {
+            qualifiers.push(("bytes", range(bytes)));
+        }
+        qualifiers
+    }
+
     /// Parse QualifiedSWHID from string
+    ///
+    /// Equivalent to `s.parse::<QualifiedSwhid>()`.
     pub fn from_string(s: &str) -> Result<Self, SwhidError> {
+        s.parse()
+    }
+
+    /// Extract the lines this SWHID cites from the content it identifies
+    ///
+    /// `content` must hash to the core SWHID, which must be a content SWHID;
+    /// otherwise this fails with `SwhidError::InvalidInput`. Lines are
+    /// numbered from 1 and the range is inclusive; the last line keeps its
+    /// newline, if any. Without a `lines` qualifier the whole content is
+    /// returned. Fails with `SwhidError::InvalidQualifierValue` if the range
+    /// starts at 0, ends before it starts or goes past the last line.
+    pub fn extract_lines<'a>(&self, content: &'a [u8]) -> Result<&'a [u8], SwhidError> {
+        if self.core != Swhid::new(ObjectType::Content, sha1_git_hash(content)) {
+            return Err(SwhidError::InvalidInput(format!("Content does not match {}", self.core)));
+        }
+        let Some((start, end)) = self.lines else {
+            return Ok(content);
+        };
+        let end = end.unwrap_or(start);
+
+        // Offsets at which each line starts, then the end of the content
+        let mut offsets = vec![0];
+        offsets.extend(content.iter().enumerate().filter(|(_, &b)| b == b'\n').map(|(i, _)| i + 1));
+        if offsets.last() != Some(&content.len()) {
+            offsets.push(content.len());
+        }
+        let line_count = offsets.len() - 1;
+        if start == 0 || end < start || end as usize > line_count {
+            return Err(SwhidError::InvalidQualifierValue(format!(
+                "lines={}-{} is not within the {} lines of the content",
+                start, end, line_count
+            )));
+        }
+        Ok(&content[offsets[start as usize - 1]..offsets[end as usize]])
+    }
+}
+
+/// Builder of a [`QualifiedSwhid`], checking each qualifier as it is set
+///
+/// Qualifiers are checked as when parsed: `origin` must be an IRI, `visit` a
+/// snapshot, `anchor` a directory, revision, release or snapshot, `path`
+/// not empty, and `lines` and `bytes` ranges may not end before they start.
+/// The first invalid qualifier is reported by [`QualifiedSwhidBuilder::build`].
+/// Setting a qualifier again replaces it.
+#[derive(Debug)]
+pub struct QualifiedSwhidBuilder {
+    qualified: QualifiedSwhid,
+    error: Option<SwhidError>,
+}
+
+impl QualifiedSwhidBuilder {
+    /// Set the origin qualifier, an IRI such as `https://github.com/user/repo`
+    pub fn origin(self, origin: impl Into<String>) -> Self {
+        self.check(Ok(()), |mut qualified| {
+            qualified.origin = Some(Origin::new(origin)?);
+            Ok(qualified)
+        })
+    }
+
+    /// Set the visit qualifier, a snapshot SWHID
+    pub fn visit(self, visit: Swhid) -> Self {
+        self.check(Ok(()), |qualified| qualified.with_visit(visit))
+    }
+
+    /// Set the anchor qualifier, a directory, revision, release or snapshot SWHID
+    pub fn anchor(self, anchor: Swhid) -> Self {
+        self.check(Ok(()), |qualified| qualified.with_anchor(anchor))
+    }
+
+    /// Set the path qualifier, from the root of the anchor
+    pub fn path(self, path: impl Into<Vec<u8>>) -> Self {
+        let path = path.into();
+        let valid = if path.is_empty() {
+            Err(SwhidError::InvalidQualifierValue("path is empty".to_string()))
+        } else {
+            Ok(())
+        };
+        self.check(valid, |qualified| Ok(qualified.with_path(path)))
+    }
+
+    /// Set the lines qualifier, a single line or an inclusive range
+    pub fn lines(self, start: u32, end: Option<u32>) -> Self {
+        self.check(check_range("lines", start, end), |qualified| Ok(qualified.with_lines(start, end)))
+    }
+
+    /// Set the bytes qualifier, a single byte or an inclusive range
+    pub fn bytes(self, start: u32, end: Option<u32>) -> Self {
+        self.check(check_range("bytes", start, end), |qualified| Ok(qualified.with_bytes(start, end)))
+    }
+
+    /// Get the qualified SWHID, or the first invalid qualifier
+    pub fn build(self) -> Result<QualifiedSwhid, SwhidError> {
+        match self.error {
+            Some(error) => Err(error),
+            None => Ok(self.qualified),
+        }
+    }
+
+    /// Apply `set` if `valid` is fine and no qualifier has failed so far
+    fn check<F>(self, valid: Result<(), SwhidError>, set: F) -> Self
+    where
+        F: FnOnce(QualifiedSwhid) -> Result<QualifiedSwhid, SwhidError>,
+    {
+        if self.error.is_some() {
+            return self;
+        }
+        // Once a qualifier fails, only the error is ever looked at
+        let core = self.qualified.core.clone();
+        match valid.and_then(|()| set(self.qualified)) {
+            Ok(qualified) => Self { qualified, error: None },
+            Err(error) => Self { qualified: QualifiedSwhid::new(core), error: Some(error) },
+        }
+    }
+}
+
+/// Parse a `lines` or `bytes` qualifier value, `<start>` or `<start>-<end>`
+///
+/// A range may not end before it starts.
+fn parse_range(key: &str, value: &str) -> Result<(u32, Option<u32>), SwhidError> {
+    let invalid = || SwhidError::InvalidQualifierValue(format!("{}={}", key, value));
+    let (start, end) = match value.split_once('-') {
+        Some((start, end)) => (start, Some(end)),
+        None => (value, None),
+    };
+    let number = |s: &str| {
+        if s.is_empty() || !s.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(invalid());
+        }
+        s.parse::<u32>().map_err(|_| invalid())
+    };
+    let start = number(start)?;
+    let end = end.map(number).transpose()?;
+    check_range(key, start, end)?;
+    Ok((start, end))
+}
+
+/// Check that a `lines` or `bytes` range does not end before it starts
+fn check_range(key: &str, start: u32, end: Option<u32>) -> Result<(), SwhidError> {
+    match end {
+        Some(end) if end < start => Err(SwhidError::InvalidQualifierValue(format!("{}={}-{}", key, start, end))),
+        _ => Ok(()),
+    }
+}
+
+/// Percent-escape a qualifier value for display
+///
+/// `;` separates qualifiers and `%` starts escapes, so both are escaped, as
+/// are whitespace, control characters and non-ASCII bytes, which would make
+/// the identifier ambiguous once copied around. Everything else, `/` and `:`
+/// in particular, is kept as is.
+fn escape_qualifier_value(value: &[u8]) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for &b in value {
+        if b.is_ascii_graphic() && b != b';' && b != b'%' {
+            escaped.push(b as char);
+        } else {
+            escaped.push_str(&format!("%{:02X}", b));
+        }
+    }
+    escaped
+}
+
+/// Decode the percent-escapes of a qualifier value
+///
+/// Fails with `SwhidError::InvalidQualifierValue` on a `%` not followed by
+/// two hex digits.
+fn unescape_qualifier_value(key: &str, value: &str) -> Result<Vec<u8>, SwhidError> {
+    let bytes = value.as_bytes();
+    let mut unescaped = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let byte = bytes
+                .get(i + 1..i + 3)
+                .and_then(|hex| std::str::from_utf8(hex).ok())
+                .filter(|hex| hex.bytes().all(|b| b.is_ascii_hexdigit()))
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                .ok_or_else(|| {
+                    SwhidError::InvalidQualifierValue(format!("{}={} has an invalid escape", key, value))
+                })?;
+            unescaped.push(byte);
+            i += 3;
+        } else {
+            unescaped.push(bytes[i]);
+            i += 1;
+        }
+    }
+    Ok(unescaped)
+}
+
+/// Qualifiers are written in the canonical order of
+/// [`QualifiedSwhid::qualifiers`], whatever order they were set or parsed
+/// in, so equal qualified SWHIDs always have the same string.
+impl fmt::Display for QualifiedSwhid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.core)?;
+        for (key, value) in self.raw_qualifiers() {
+            write!(f, ";{}={}", key, escape_qualifier_value(&value))?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for QualifiedSwhid {
+    type Err = SwhidError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
         // Split by semicolon to separate core SWHID from qualifiers
         let parts: Vec<&str> = s.split(';').collect();
         if parts.is_empty() {
@@ -247,81 +906,48 @@ impl QualifiedSwhid {
         let core = Swhid::from_string(parts[0])?;
         
         let mut qualified = Self::new(core);
+        let mut seen = Vec::new();
 
-        // Parse qualifiers
+        // Parse qualifiers; values may contain `=`, e.g. in origin URLs
         for qualifier in &parts[1..] {
-            let qualifier_parts: Vec<&str> = qualifier.split('=').collect();
-            if qualifier_parts.len() != 2 {
-                return Err(SwhidError::InvalidFormat(format!(
-                    "Invalid qualifier format: {}", qualifier
-                )));
+            let (key, value) = qualifier.split_once('=').ok_or_else(|| {
+                SwhidError::InvalidFormat(format!("Invalid qualifier format: {}", qualifier))
+            })?;
+            if value.is_empty() {
+                return Err(SwhidError::InvalidQualifierValue(format!("{} is empty", key)));
             }
-
-            let key = qualifier_parts[0];
-            let value = qualifier_parts[1];
+            if seen.contains(&key) {
+                return Err(SwhidError::InvalidQualifier(format!("{} is given more than once", key)));
+            }
+            seen.push(key);
 
             match key {
                 "origin" => {
-                    qualified.origin = Some(value.to_string());
+                    let origin = String::from_utf8(unescape_qualifier_value(key, value)?).map_err(|_| {
+                        SwhidError::InvalidQualifierValue(format!("{}={} is not UTF-8", key, value))
+                    })?;
+                    qualified.origin = Some(Origin::new(origin)?);
                 }
                 "visit" => {
-                    let visit_swhid = Swhid::from_string(value)?;
+                    let visit_swhid = Swhid::from_string(value).map_err(|e| {
+                        SwhidError::InvalidQualifierSwhid(key.to_string(), Box::new(e))
+                    })?;
                     qualified = qualified.with_visit(visit_swhid)?;
                 }
                 "anchor" => {
-                    let anchor_swhid = Swhid::from_string(value)?;
+                    let anchor_swhid = Swhid::from_string(value).map_err(|e| {
+                        SwhidError::InvalidQualifierSwhid(key.to_string(), Box::new(e))
+                    })?;
                     qualified = qualified.with_anchor(anchor_swhid)?;
                 }
                 "path" => {
-                    qualified.path = Some(value.as_bytes().to_vec());
+                    qualified.path = Some(unescape_qualifier_value(key, value)?);
                 }
                 "lines" => {
-                    let lines_parts: Vec<&str> = value.split('-').collect();
-                    if lines_parts.len() > 2 {
-                        return Err(SwhidError::InvalidFormat(format!(
-                            "Invalid lines format: {}", value
-                        )));
-                    }
-                    
-                    let start = lines_parts[0].parse::<u32>()
-                        .map_err(|_| SwhidError::InvalidFormat(format!(
-                            "Invalid start line: {}", lines_parts[0]
-                        )))?;
-                    
-                    let end = if lines_parts.len() == 2 {
-                        Some(lines_parts[1].parse::<u32>()
-                            .map_err(|_| SwhidError::InvalidFormat(format!(
-                                "Invalid end line: {}", lines_parts[1]
-                            )))?)
-                    } else {
-                        None
-                    };
-                    
-                    qualified.lines = Some((start, end));
+                    qualified.lines = Some(parse_range(key, value)?);
                 }
                 "bytes" => {
-                    let bytes_parts: Vec<&str> = value.split('-').collect();
-                    if bytes_parts.len() > 2 {
-                        return Err(SwhidError::InvalidFormat(format!(
-                            "Invalid bytes format: {}", value
-                        )));
-                    }
-                    
-                    let start = bytes_parts[0].parse::<u32>()
-                        .map_err(|_| SwhidError::InvalidFormat(format!(
-                            "Invalid start byte: {}", bytes_parts[0]
-                        )))?;
-                    
-                    let end = if bytes_parts.len() == 2 {
-                        Some(bytes_parts[1].parse::<u32>()
-                            .map_err(|_| SwhidError::InvalidFormat(format!(
-                                "Invalid end byte: {}", bytes_parts[1]
-                            )))?)
-                    } else {
-                        None
-                    };
-                    
-                    qualified.bytes = Some((start, end));
+                    qualified.bytes = Some(parse_range(key, value)?);
                 }
                 _ => {
                     return Err(SwhidError::UnknownQualifier(key.to_string()));
@@ -333,52 +959,18 @@ impl QualifiedSwhid {
     }
 }
 
-impl fmt::Display for QualifiedSwhid {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        // Start with core SWHID
-        write!(f, "{}", self.core)?;
-
-        // Add qualifiers in the specified order: origin, visit, anchor, path, lines
-        if let Some(ref origin) = self.origin {
-            write!(f, ";origin={}", origin)?;
-        }
-
-        if let Some(ref visit) = self.visit {
-            write!(f, ";visit={}", visit)?;
-        }
-
-        if let Some(ref anchor) = self.anchor {
-            write!(f, ";anchor={}", anchor)?;
-        }
-
-        if let Some(ref path) = self.path {
-            write!(f, ";path={}", String::from_utf8_lossy(path))?;
-        }
-
-        if let Some((start, end)) = self.lines {
-            match end {
-                Some(end) => write!(f, ";lines={}-{}", start, end)?,
-                None => write!(f, ";lines={}", start)?,
-            }
-        }
-
-        if let Some((start, end)) = self.bytes {
-            match end {
-                Some(end) => write!(f, ";bytes={}-{}", start, end)?,
-                None => write!(f, ";bytes={}", start)?,
-            }
-        }
-
-        Ok(())
-    }
-}
-
 impl From<Swhid> for QualifiedSwhid {
     fn from(swhid: Swhid) -> Self {
         Self::new(swhid)
     }
 }
 
+impl From<QualifiedSwhid> for Swhid {
+    fn from(qualified: QualifiedSwhid) -> Self {
+        qualified.into_core()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -389,7 +981,70 @@ mod tests {
         let swhid = Swhid::new(ObjectType::Content, hash);
         
         assert_eq!(swhid.object_type(), ObjectType::Content);
-        assert_eq!(swhid.hash(), &hash);
+        assert_eq!(swhid.digest(), &hash);
+    }
+
+    #[test]
+    fn test_swhid_compact_bytes_round_trip() {
+        let hash: [u8; 20] = hex::decode("94a9ed024d3859793618152ea559a168bbcbb5e2")
+            .unwrap()
+            .try_into()
+            .unwrap();
+        for object_type in [
+            ObjectType::Content,
+            ObjectType::Directory,
+            ObjectType::Revision,
+            ObjectType::Release,
+            ObjectType::Snapshot,
+        ] {
+            let swhid = Swhid::new(object_type, hash);
+            let bytes = swhid.to_compact_bytes().unwrap();
+            assert_eq!(&bytes[1..], &hash);
+            assert_eq!(Swhid::from_compact_bytes(&bytes).unwrap(), swhid);
+        }
+        assert_eq!(Swhid::new(ObjectType::Directory, hash).to_compact_bytes().unwrap()[0], 2);
+    }
+
+    #[test]
+    fn test_swhid_compact_bytes_invalid() {
+        let bytes = Swhid::new(ObjectType::Content, [7u8; 20]).to_compact_bytes().unwrap();
+        assert!(matches!(Swhid::from_compact_bytes(&bytes[..20]), Err(SwhidError::InvalidFormat(_))));
+        assert!(matches!(Swhid::from_compact_bytes(&[0u8; 22]), Err(SwhidError::InvalidFormat(_))));
+        assert!(matches!(Swhid::from_compact_bytes(&[]), Err(SwhidError::InvalidFormat(_))));
+
+        let mut unknown = bytes;
+        unknown[0] = 0;
+        assert!(matches!(Swhid::from_compact_bytes(&unknown), Err(SwhidError::InvalidObjectType(_))));
+    }
+
+    #[test]
+    fn test_swhid_from_str_and_display_traits() {
+        let s = "swh:1:dir:d198bc9d7a6bcf6db04f476d29314f157507d505";
+        let swhid: Swhid = s.parse().unwrap();
+        assert_eq!(swhid, Swhid::from_string(s).unwrap());
+        assert_eq!(format!("{swhid}"), s);
+        assert!(matches!("swh:1:dir:abcd".parse::<Swhid>(), Err(SwhidError::InvalidHashLength(4))));
+
+        let object_type: ObjectType = "rel".parse().unwrap();
+        assert_eq!(object_type, ObjectType::Release);
+        assert_eq!(format!("{object_type}"), "rel");
+        assert!(matches!("tree".parse::<ObjectType>(), Err(SwhidError::InvalidObjectType(_))));
+
+        let q = "swh:1:cnt:94a9ed024d3859793618152ea559a168bbcbb5e2;origin=https://example.org;lines=1-2";
+        let qualified: QualifiedSwhid = q.parse().unwrap();
+        assert_eq!(qualified, QualifiedSwhid::from_string(q).unwrap());
+        assert_eq!(format!("{qualified}"), q);
+
+        // Generic code bounded on the standard traits
+        fn round_trip<T: FromStr + fmt::Display>(s: &str) -> String
+        where
+            T::Err: fmt::Debug,
+        {
+            s.parse::<T>().unwrap().to_string()
+        }
+        assert_eq!(round_trip::<Swhid>(s), s);
+        assert_eq!(round_trip::<ObjectType>("snp"), "snp");
+        assert_eq!(round_trip::<QualifiedSwhid>(q), q);
     }
 
     #[test]
@@ -405,7 +1060,38 @@ mod tests {
         let swhid = Swhid::from_string("swh:1:cnt:0000000000000000000000000000000000000000").unwrap();
         
         assert_eq!(swhid.object_type(), ObjectType::Content);
-        assert_eq!(swhid.hash(), &[0u8; 20]);
+        assert_eq!(swhid.digest(), &[0u8; 20]);
+    }
+
+    #[cfg(feature = "v2")]
+    #[test]
+    fn test_swhid_v2() {
+        let s = "swh:2:cnt:2cf8d83d9ee29543b34a87727421fdecb7e3f3a183d337639025de576db9ebb4";
+        let swhid = Swhid::from_string(s).unwrap();
+        assert_eq!(swhid.version(), 2);
+        assert_eq!(swhid.object_type(), ObjectType::Content);
+        assert_eq!(hex::encode(swhid.digest()), &s[10..]);
+        assert_eq!(swhid.to_string(), s);
+        assert_eq!(swhid.abbreviated(7), "swh:2:cnt:2cf8d83");
+        assert_eq!(peek_object_type(s).unwrap(), ObjectType::Content);
+        assert_eq!(QualifiedSwhid::from_string(&format!("{};lines=1", s)).unwrap().core(), &swhid);
+
+        // Hashes are as long as the version says, and differ from v1 hashes
+        let v1 = Swhid::new(ObjectType::Content, swhid.digest()[..20].try_into().unwrap());
+        assert_eq!(v1.version(), 1);
+        assert_ne!(v1, swhid);
+        assert_eq!(min_unique_prefix_len(&[v1.clone(), swhid.clone()]), 1);
+        assert!(Swhid::from_string(&format!("swh:2:cnt:{}", hex::encode(v1.digest()))).is_err());
+        assert!(Swhid::from_string(&format!("swh:1:cnt:{}", &s[10..])).is_err());
+        assert!(matches!(Swhid::parse_detailed(s), Err(SwhidError::InvalidVersion(_))));
+        #[allow(deprecated)]
+        let panics = std::panic::catch_unwind(|| *swhid.hash()).is_err();
+        assert!(panics);
+        assert_eq!(swhid.sha1(), None);
+        assert_eq!(v1.sha1().map(|hash| &hash[..]), Some(v1.digest()));
+        let qualified = QualifiedSwhid::new(swhid.clone());
+        assert_eq!((qualified.sha1(), qualified.digest()), (None, swhid.digest()));
+        assert!(matches!(swhid.to_compact_bytes(), Err(SwhidError::InvalidVersion(_))));
     }
 
     #[test]
@@ -447,17 +1133,72 @@ mod tests {
         assert_eq!(snapshot_swhid.to_string(), "swh:1:snp:0000000000000000000000000000000000000000");
     }
 
+    #[test]
+    fn test_swhid_round_trip_all_object_types() {
+        let hash = "94a9ed024d3859793618152ea559a168bbcbb5e2";
+        for object_type in ["cnt", "dir", "rev", "rel", "snp"] {
+            let text = format!("swh:1:{}:{}", object_type, hash);
+            let swhid = Swhid::from_string(&text).unwrap();
+            assert_eq!(swhid.object_type().as_str(), object_type);
+            assert_eq!(hex::encode(swhid.digest()), hash);
+            assert_eq!(swhid.to_string(), text);
+
+            let qualified = format!("{};origin=https://example.org/repo.git", text);
+            assert_eq!(QualifiedSwhid::from_string(&qualified).unwrap().to_string(), qualified);
+        }
+    }
+
     #[test]
     fn test_object_type_parsing() {
         // Test parsing all valid object types
-        assert_eq!(ObjectType::from_str("cnt").unwrap(), ObjectType::Content);
-        assert_eq!(ObjectType::from_str("dir").unwrap(), ObjectType::Directory);
-        assert_eq!(ObjectType::from_str("rev").unwrap(), ObjectType::Revision);
-        assert_eq!(ObjectType::from_str("rel").unwrap(), ObjectType::Release);
-        assert_eq!(ObjectType::from_str("snp").unwrap(), ObjectType::Snapshot);
+        assert_eq!("cnt".parse::<ObjectType>().unwrap(), ObjectType::Content);
+        assert_eq!("dir".parse::<ObjectType>().unwrap(), ObjectType::Directory);
+        assert_eq!("rev".parse::<ObjectType>().unwrap(), ObjectType::Revision);
+        assert_eq!("rel".parse::<ObjectType>().unwrap(), ObjectType::Release);
+        assert_eq!("snp".parse::<ObjectType>().unwrap(), ObjectType::Snapshot);
         
         // Test invalid object type
-        assert!(ObjectType::from_str("invalid").is_err());
+        assert!("invalid".parse::<ObjectType>().is_err());
+
+        // The inherent method is kept for backwards compatibility
+        #[allow(deprecated)]
+        let parsed = ObjectType::from_str("dir");
+        assert_eq!(parsed.unwrap(), ObjectType::Directory);
+    }
+
+    #[test]
+    fn test_swhid_parse_detailed() {
+        let s = "swh:1:cnt:94a9ed024d3859793618152ea559a168bbcbb5e2;origin=https://example.org/repo;lines=9-15";
+        let parsed = Swhid::parse_detailed(s).unwrap();
+
+        assert_eq!(parsed.namespace.value, "swh");
+        assert_eq!(parsed.namespace.span, 0..3);
+        assert_eq!(parsed.version.value, "1");
+        assert_eq!(parsed.version.span, 4..5);
+        assert_eq!(parsed.object_type.value, ObjectType::Content);
+        assert_eq!(parsed.object_type.span, 6..9);
+        assert_eq!(parsed.hash.span, 10..50);
+        assert_eq!(&s[parsed.hash.span.clone()], hex::encode(parsed.hash.value));
+        assert_eq!(parsed.swhid().to_string(), &s[..50]);
+
+        assert_eq!(parsed.qualifiers.len(), 2);
+        let origin = &parsed.qualifiers[0];
+        assert_eq!(origin.key.value, "origin");
+        assert_eq!(&s[origin.key.span.clone()], "origin");
+        assert_eq!(origin.value.value, "https://example.org/repo");
+        assert_eq!(&s[origin.value.span.clone()], "https://example.org/repo");
+        assert_eq!(&s[origin.span.clone()], "origin=https://example.org/repo");
+        let lines = &parsed.qualifiers[1];
+        assert_eq!(&s[lines.key.span.clone()], "lines");
+        assert_eq!(&s[lines.value.span.clone()], "9-15");
+        assert_eq!(lines.span.end, s.len());
+    }
+
+    #[test]
+    fn test_swhid_parse_detailed_invalid() {
+        assert!(Swhid::parse_detailed("swh:1:cnt").is_err());
+        assert!(Swhid::parse_detailed("swh:1:xyz:94a9ed024d3859793618152ea559a168bbcbb5e2").is_err());
+        assert!(Swhid::parse_detailed("swh:1:cnt:94a9ed024d3859793618152ea559a168bbcbb5e2;lines").is_err());
     }
 
     // QualifiedSWHID tests
@@ -479,7 +1220,7 @@ mod tests {
     fn test_qualified_swhid_with_origin() {
         let core = Swhid::new(ObjectType::Content, [0u8; 20]);
         let qualified = QualifiedSwhid::new(core)
-            .with_origin("https://github.com/user/repo".to_string());
+            .with_origin(Origin::new("https://github.com/user/repo").unwrap());
         
         assert_eq!(qualified.origin(), Some("https://github.com/user/repo"));
     }
@@ -573,7 +1314,7 @@ mod tests {
     fn test_qualified_swhid_display() {
         let core = Swhid::new(ObjectType::Content, [0u8; 20]);
         let qualified = QualifiedSwhid::new(core)
-            .with_origin("https://github.com/user/repo".to_string())
+            .with_origin(Origin::new("https://github.com/user/repo").unwrap())
             .with_path(b"/src/main.rs".to_vec())
             .with_lines(10, Some(20))
             .with_bytes(5, Some(10));
@@ -608,6 +1349,434 @@ mod tests {
         assert!(QualifiedSwhid::from_string("swh:1:cnt:0000000000000000000000000000000000000000;unknown=value").is_err());
     }
 
+    #[test]
+    fn test_qualified_swhid_from_string_error_kinds() {
+        let core = "swh:1:cnt:0000000000000000000000000000000000000000";
+        assert!(matches!(
+            QualifiedSwhid::from_string(&format!("{};unknown=value", core)),
+            Err(SwhidError::UnknownQualifier(ref q)) if q == "unknown"
+        ));
+        for value in ["lines=invalid", "lines=1-2-3", "lines=-2", "lines=3-", "lines=+3", "bytes=1-x", "origin="] {
+            assert!(
+                matches!(
+                    QualifiedSwhid::from_string(&format!("{};{}", core, value)),
+                    Err(SwhidError::InvalidQualifierValue(_))
+                ),
+                "{} should be rejected",
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn test_qualified_swhid_round_trip() {
+        let core = Swhid::from_string("swh:1:cnt:4d99d2d18326621ccdd70f5ea66c2e2ac236ad8b").unwrap();
+        let qualified = QualifiedSwhid::new(core.clone())
+            .with_origin(Origin::new("https://gitorious.org/ocamlp3l/ocamlp3l_cvs.git").unwrap())
+            .with_visit(Swhid::from_string("swh:1:snp:d7f1b9eb7ccb596c2622c4780febaa02549830f9").unwrap())
+            .unwrap()
+            .with_anchor(Swhid::from_string("swh:1:rev:2db189928c94d62a3b4757b3eec68f0a4d4113f0").unwrap())
+            .unwrap()
+            .with_path(b"/Examples/SimpleFarm/simplefarm.ml".to_vec())
+            .with_lines(9, Some(15));
+
+        let s = qualified.to_string();
+        assert_eq!(
+            s,
+            "swh:1:cnt:4d99d2d18326621ccdd70f5ea66c2e2ac236ad8b\
+             ;origin=https://gitorious.org/ocamlp3l/ocamlp3l_cvs.git\
+             ;visit=swh:1:snp:d7f1b9eb7ccb596c2622c4780febaa02549830f9\
+             ;anchor=swh:1:rev:2db189928c94d62a3b4757b3eec68f0a4d4113f0\
+             ;path=/Examples/SimpleFarm/simplefarm.ml\
+             ;lines=9-15"
+        );
+        let parsed = QualifiedSwhid::from_string(&s).unwrap();
+        assert_eq!(parsed, qualified);
+        assert_eq!(parsed.core(), &core);
+
+        // Qualifiers are emitted in canonical order whatever order they came in
+        let shuffled = "swh:1:cnt:4d99d2d18326621ccdd70f5ea66c2e2ac236ad8b;lines=9;path=/a;origin=https://example.org";
+        assert_eq!(
+            QualifiedSwhid::from_string(shuffled).unwrap().to_string(),
+            "swh:1:cnt:4d99d2d18326621ccdd70f5ea66c2e2ac236ad8b;origin=https://example.org;path=/a;lines=9"
+        );
+    }
+
+    #[test]
+    fn test_qualified_swhid_validation() {
+        let core = "swh:1:cnt:0000000000000000000000000000000000000000";
+        for value in ["lines=20-10", "bytes=5-4", "origin=example.org/repo", "origin=1http://x", "origin=:x"] {
+            assert!(
+                matches!(
+                    QualifiedSwhid::from_string(&format!("{};{}", core, value)),
+                    Err(SwhidError::InvalidQualifierValue(_))
+                ),
+                "{} should be rejected",
+                value
+            );
+        }
+        assert!(matches!(
+            QualifiedSwhid::from_string(&format!("{};lines=1;path=/a;lines=2", core)),
+            Err(SwhidError::InvalidQualifier(_))
+        ));
+
+        // Values may contain `=`, and single-line ranges are fine
+        let s = format!("{};origin=https://example.org/?repo=a&b=c;lines=4-4", core);
+        let qualified = QualifiedSwhid::from_string(&s).unwrap();
+        assert_eq!(qualified.origin(), Some("https://example.org/?repo=a&b=c"));
+        assert_eq!(qualified.origin_iri().unwrap().scheme(), "https");
+        for origin in ["https://example.org/a%20b%", "https://example.org/<a>", "https:///a"] {
+            let s = format!("{};origin={}", core, origin.replace('%', "%25"));
+            assert!(
+                matches!(QualifiedSwhid::from_string(&s), Err(SwhidError::InvalidQualifierValue(_))),
+                "{} should be rejected",
+                origin
+            );
+        }
+        assert_eq!(qualified.to_string(), s);
+    }
+
+    #[test]
+    fn test_archive_url() {
+        let core = Swhid::from_string("swh:1:cnt:94a9ed024d3859793618152ea559a168bbcbb5e2").unwrap();
+        assert_eq!(
+            core.to_archive_url(),
+            "https://archive.softwareheritage.org/swh:1:cnt:94a9ed024d3859793618152ea559a168bbcbb5e2"
+        );
+
+        let qualified = QualifiedSwhid::new(core.clone())
+            .with_origin(Origin::new("https://example.org/cgit?p=repo.git#top").unwrap())
+            .with_path(b"/src/a b.c".to_vec())
+            .with_lines(9, Some(15));
+        let url = qualified.to_archive_url();
+        assert_eq!(
+            url,
+            format!(
+                "{}{};origin=https://example.org/cgit%3Fp=repo.git%23top;path=/src/a%20b.c;lines=9-15",
+                ARCHIVE_URL, core
+            )
+        );
+        assert_eq!(QualifiedSwhid::from_archive_url(&url).unwrap(), qualified);
+        assert_eq!(QualifiedSwhid::from_archive_url(&format!("{}/", url)).unwrap(), qualified);
+
+        assert!(matches!(
+            QualifiedSwhid::from_archive_url(&format!("https://example.org/{}", core)),
+            Err(SwhidError::InvalidFormat(_))
+        ));
+        assert!(QualifiedSwhid::from_archive_url(ARCHIVE_URL).is_err());
+    }
+
+    #[test]
+    fn test_qualified_swhid_escaping() {
+        let core = Swhid::new(ObjectType::Content, [0u8; 20]);
+        let qualified = QualifiedSwhid::new(core.clone())
+            .with_origin(Origin::new("https://example.org/a;b/caf\u{e9}%20menu").unwrap())
+            .with_path("/src/caf\u{e9} menu;1.txt".as_bytes().to_vec());
+        let s = qualified.to_string();
+        assert_eq!(
+            s,
+            "swh:1:cnt:0000000000000000000000000000000000000000\
+             ;origin=https://example.org/a%3Bb/caf%C3%A9%2520menu\
+             ;path=/src/caf%C3%A9%20menu%3B1.txt"
+        );
+        assert_eq!(QualifiedSwhid::from_string(&s).unwrap(), qualified);
+        // Unescaped values are listed as they are
+        assert_eq!(qualified.qualifiers()[0].1, "https://example.org/a;b/caf\u{e9}%20menu");
+
+        // Paths that are not UTF-8 round-trip too
+        let qualified = QualifiedSwhid::new(core.clone()).with_path(b"/caf\xe9".to_vec());
+        assert_eq!(qualified.to_string(), format!("{};path=/caf%E9", core));
+        assert_eq!(QualifiedSwhid::from_string(&qualified.to_string()).unwrap(), qualified);
+
+        // Escapes are decoded whatever their case, and must be complete
+        let parsed = QualifiedSwhid::from_string(&format!("{};path=/a%2fb%2F", core)).unwrap();
+        assert_eq!(parsed.path(), Some(&b"/a/b/"[..]));
+        for value in ["path=/a%2", "path=/a%zz", "origin=https://x/%ff"] {
+            assert!(
+                matches!(
+                    QualifiedSwhid::from_string(&format!("{};{}", core, value)),
+                    Err(SwhidError::InvalidQualifierValue(_))
+                ),
+                "{} should be rejected",
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn test_qualified_swhid_builder() {
+        let core = Swhid::new(ObjectType::Content, [0u8; 20]);
+        let anchor = Swhid::new(ObjectType::Revision, [1u8; 20]);
+        let qualified = QualifiedSwhid::builder(core.clone())
+            .origin("https://github.com/user/repo")
+            .anchor(anchor.clone())
+            .path("/src/main.rs")
+            .lines(10, Some(20))
+            .build()
+            .unwrap();
+        assert_eq!(
+            qualified,
+            QualifiedSwhid::new(core.clone())
+                .with_origin(Origin::new("https://github.com/user/repo").unwrap())
+                .with_anchor(anchor)
+                .unwrap()
+                .with_path(b"/src/main.rs".to_vec())
+                .with_lines(10, Some(20))
+        );
+
+        let invalid = [
+            QualifiedSwhid::builder(core.clone()).origin("not a uri"),
+            QualifiedSwhid::builder(core.clone()).path(""),
+            QualifiedSwhid::builder(core.clone()).lines(20, Some(10)),
+            QualifiedSwhid::builder(core.clone()).bytes(5, Some(4)),
+        ];
+        for builder in invalid {
+            assert!(matches!(builder.build(), Err(SwhidError::InvalidQualifierValue(_))));
+        }
+
+        // The first error is kept, even if later qualifiers are valid
+        let result = QualifiedSwhid::builder(core.clone())
+            .visit(core.clone())
+            .lines(0, Some(0))
+            .anchor(core)
+            .build();
+        assert!(matches!(result, Err(SwhidError::InvalidQualifier(ref e)) if e.contains("Visit")));
+    }
+
+    #[test]
+    fn test_qualified_swhid_extract_lines() {
+        let content = b"one\ntwo\nthree\nfour";
+        let core = Swhid::new(ObjectType::Content, sha1_git_hash(content));
+        let fragment = |lines: Option<(u32, Option<u32>)>| {
+            let mut qualified = QualifiedSwhid::new(core.clone());
+            if let Some((start, end)) = lines {
+                qualified = qualified.with_lines(start, end);
+            }
+            qualified.extract_lines(content)
+        };
+        assert_eq!(fragment(None).unwrap(), content);
+        assert_eq!(fragment(Some((2, None))).unwrap(), b"two\n");
+        assert_eq!(fragment(Some((2, Some(3)))).unwrap(), b"two\nthree\n");
+        assert_eq!(fragment(Some((3, Some(4)))).unwrap(), b"three\nfour");
+        for lines in [(0, None), (5, None), (4, Some(5)), (4, Some(1)), (3, Some(2))] {
+            assert!(matches!(fragment(Some(lines)), Err(SwhidError::InvalidQualifierValue(_))));
+        }
+
+        // A trailing newline does not start another line
+        let content = b"one\n";
+        let qualified = QualifiedSwhid::new(Swhid::new(ObjectType::Content, sha1_git_hash(content)));
+        assert_eq!(qualified.clone().with_lines(1, None).extract_lines(content).unwrap(), b"one\n");
+        assert!(qualified.with_lines(2, None).extract_lines(content).is_err());
+
+        assert!(matches!(
+            QualifiedSwhid::new(core).with_lines(1, None).extract_lines(b"other"),
+            Err(SwhidError::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    fn test_qualified_swhid_canonical_order() {
+        let qualifiers = [
+            "origin=https://example.org/repo",
+            "visit=swh:1:snp:d7f1b9eb7ccb596c2622c4780febaa02549830f9",
+            "anchor=swh:1:rev:2db189928c94d62a3b4757b3eec68f0a4d4113f0",
+            "path=/src/main.rs",
+            "lines=1-2",
+            "bytes=3-4",
+        ];
+        let core = "swh:1:cnt:4d99d2d18326621ccdd70f5ea66c2e2ac236ad8b";
+        let canonical = format!("{};{}", core, qualifiers.join(";"));
+
+        // Every rotation, and its reverse, gives the canonical string
+        for shift in 0..qualifiers.len() {
+            let mut order = qualifiers.to_vec();
+            order.rotate_left(shift);
+            for order in [order.clone(), order.into_iter().rev().collect()] {
+                let parsed = QualifiedSwhid::from_string(&format!("{};{}", core, order.join(";"))).unwrap();
+                assert_eq!(parsed.to_string(), canonical);
+            }
+        }
+
+        let built = QualifiedSwhid::builder(Swhid::from_string(core).unwrap())
+            .bytes(3, Some(4))
+            .lines(1, Some(2))
+            .path("/src/main.rs")
+            .anchor(Swhid::from_string("swh:1:rev:2db189928c94d62a3b4757b3eec68f0a4d4113f0").unwrap())
+            .visit(Swhid::from_string("swh:1:snp:d7f1b9eb7ccb596c2622c4780febaa02549830f9").unwrap())
+            .origin("https://example.org/repo")
+            .build()
+            .unwrap();
+        assert_eq!(built.to_string(), canonical);
+    }
+
+    #[test]
+    fn test_qualified_swhid_qualifiers() {
+        let core = Swhid::new(ObjectType::Content, [0u8; 20]);
+        assert!(QualifiedSwhid::new(core.clone()).qualifiers().is_empty());
+        let qualified = QualifiedSwhid::new(core)
+            .with_lines(3, None)
+            .with_anchor(Swhid::new(ObjectType::Directory, [1u8; 20]))
+            .unwrap()
+            .with_bytes(1, Some(2))
+            .with_origin(Origin::new("https://example.org").unwrap());
+        assert_eq!(
+            qualified.qualifiers(),
+            vec![
+                ("origin", "https://example.org".to_string()),
+                ("anchor", format!("swh:1:dir:{}", "01".repeat(20))),
+                ("lines", "3".to_string()),
+                ("bytes", "1-2".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_swhid_from_string_hash_length() {
+        // Valid hex of the wrong length reports the length in hex digits
+        let err = Swhid::from_string("swh:1:cnt:abcd").unwrap_err();
+        assert!(matches!(err, SwhidError::InvalidHashLength(4)));
+
+        // Odd lengths are length errors too, not hex decoding errors
+        let err = Swhid::from_string("swh:1:cnt:abc").unwrap_err();
+        assert!(matches!(err, SwhidError::InvalidHashLength(3)));
+
+        // Right length but not hex
+        let err = Swhid::from_string("swh:1:cnt:zz00000000000000000000000000000000000000").unwrap_err();
+        assert!(matches!(err, SwhidError::InvalidHash(_)));
+    }
+
+    #[test]
+    fn test_qualified_swhid_nested_hash_length() {
+        let s = "swh:1:cnt:0000000000000000000000000000000000000000;anchor=swh:1:dir:abcd";
+        let err = QualifiedSwhid::from_string(s).unwrap_err();
+        match &err {
+            SwhidError::InvalidQualifierSwhid(qualifier, inner) => {
+                assert_eq!(qualifier, "anchor");
+                assert!(matches!(**inner, SwhidError::InvalidHashLength(4)));
+            }
+            other => panic!("unexpected error: {:?}", other),
+        }
+        let message = err.to_string();
+        assert!(message.contains("anchor"));
+        assert!(message.contains("Invalid hash length: 4"));
+
+        let s = "swh:1:cnt:0000000000000000000000000000000000000000;visit=swh:1:snp:abcd";
+        assert!(matches!(
+            QualifiedSwhid::from_string(s),
+            Err(SwhidError::InvalidQualifierSwhid(ref q, _)) if q == "visit"
+        ));
+    }
+
+    #[test]
+    fn test_swhid_from_cell() {
+        let expected = Swhid::from_string("swh:1:cnt:b45ef6fec89518d314f546fd6c3025367b721684").unwrap();
+
+        assert_eq!(Swhid::from_cell("\"swh:1:cnt:b45ef6fec89518d314f546fd6c3025367b721684\"").unwrap(), expected);
+        assert_eq!(Swhid::from_cell("  swh:1:cnt:b45ef6fec89518d314f546fd6c3025367b721684\t\r\n").unwrap(), expected);
+        assert_eq!(Swhid::from_cell(" ' swh:1:cnt:b45ef6fec89518d314f546fd6c3025367b721684 ' ").unwrap(), expected);
+
+        // The strict parser still rejects padded input
+        assert!(Swhid::from_string(" swh:1:cnt:b45ef6fec89518d314f546fd6c3025367b721684").is_err());
+
+        assert!(Swhid::from_cell("").is_err());
+        assert!(Swhid::from_cell("\"swh:1:cnt:b45ef6fec89518d314f546fd6c3025367b721684").is_err());
+        assert!(matches!(
+            Swhid::from_cell("\"swh:1:cnt:b45ef6\""),
+            Err(SwhidError::InvalidHashLength(6))
+        ));
+    }
+
+    #[test]
+    fn test_peek_object_type() {
+        let hash = "b45ef6fec89518d314f546fd6c3025367b721684";
+        for (tag, object_type) in [
+            ("cnt", ObjectType::Content),
+            ("dir", ObjectType::Directory),
+            ("rev", ObjectType::Revision),
+            ("rel", ObjectType::Release),
+            ("snp", ObjectType::Snapshot),
+        ] {
+            let s = format!("swh:1:{}:{}", tag, hash);
+            assert_eq!(peek_object_type(&s).unwrap(), object_type);
+        }
+
+        // The hash is not validated
+        assert_eq!(peek_object_type("swh:1:cnt:b45e").unwrap(), ObjectType::Content);
+        assert_eq!(peek_object_type("swh:1:dir:not-hex").unwrap(), ObjectType::Directory);
+        assert_eq!(peek_object_type("swh:1:rev").unwrap(), ObjectType::Revision);
+
+        assert!(matches!(peek_object_type("sha:1:cnt:b45e"), Err(SwhidError::InvalidNamespace(_))));
+        #[cfg(not(feature = "v2"))]
+        assert!(matches!(peek_object_type("swh:2:cnt:b45e"), Err(SwhidError::InvalidVersion(_))));
+        assert!(matches!(peek_object_type("swh:1:xyz:b45e"), Err(SwhidError::InvalidObjectType(_))));
+        assert!(matches!(peek_object_type("swh:1"), Err(SwhidError::InvalidFormat(_))));
+    }
+
+    #[test]
+    fn test_swhid_abbreviated() {
+        let swhid = Swhid::from_string("swh:1:cnt:b45ef6fec89518d314f546fd6c3025367b721684").unwrap();
+        assert_eq!(swhid.abbreviated(6), "swh:1:cnt:b45ef6");
+        assert_eq!(swhid.abbreviated(100), swhid.to_string());
+    }
+
+    #[test]
+    fn test_min_unique_prefix_len() {
+        let swhids: Vec<Swhid> = [
+            "swh:1:cnt:b45ef6fec89518d314f546fd6c3025367b721684",
+            "swh:1:cnt:b45ef70000000000000000000000000000000000",
+            "swh:1:cnt:b45e000000000000000000000000000000000000",
+            "swh:1:cnt:0000000000000000000000000000000000000000",
+        ]
+        .iter()
+        .map(|s| Swhid::from_string(s).unwrap())
+        .collect();
+        // b45ef6... and b45ef7... share five digits
+        assert_eq!(min_unique_prefix_len(&swhids), 6);
+
+        // Hashes of different object types never collide
+        let directory = Swhid::new(ObjectType::Directory, *swhids[0].sha1().unwrap());
+        assert_eq!(min_unique_prefix_len(&[swhids[0].clone(), directory]), 1);
+
+        // Repeated SWHIDs are ignored
+        assert_eq!(min_unique_prefix_len(&[swhids[3].clone(), swhids[3].clone()]), 1);
+        assert_eq!(min_unique_prefix_len(&[]), 1);
+    }
+
+    #[test]
+    fn test_qualified_swhid_same_object() {
+        let core = Swhid::from_string("swh:1:cnt:b45ef6fec89518d314f546fd6c3025367b721684").unwrap();
+        let qualified = QualifiedSwhid::new(core.clone())
+            .with_origin(Origin::new("https://github.com/user/repo").unwrap())
+            .with_lines(1, Some(10));
+
+        assert!(qualified.same_object(&core));
+
+        let other = Swhid::from_string("swh:1:cnt:0000000000000000000000000000000000000000").unwrap();
+        assert!(!qualified.same_object(&other));
+
+        let directory = Swhid::new(ObjectType::Directory, *core.sha1().unwrap());
+        assert!(!qualified.same_object(&directory));
+    }
+
+    #[test]
+    fn test_qualified_swhid_core_eq() {
+        use std::collections::HashSet;
+
+        let core = Swhid::from_string("swh:1:cnt:b45ef6fec89518d314f546fd6c3025367b721684").unwrap();
+        let plain = QualifiedSwhid::new(core.clone());
+        let cited = plain.clone().with_lines(1, Some(10));
+        assert!(plain.core_eq(&cited));
+        assert_ne!(plain, cited);
+        assert!(!plain.core_eq(&QualifiedSwhid::new(Swhid::new(ObjectType::Directory, *core.sha1().unwrap()))));
+
+        // Deduplicate by full identity, or by object
+        let swhids = vec![plain.clone(), cited.clone(), cited.clone()];
+        assert_eq!(swhids.iter().collect::<HashSet<_>>().len(), 2);
+        let objects: HashSet<Swhid> = swhids.into_iter().map(QualifiedSwhid::into_core).collect();
+        assert_eq!(objects, HashSet::from([core.clone()]));
+        assert_eq!(Swhid::from(cited), core);
+    }
+
     #[test]
     fn test_qualified_swhid_from_swhid() {
         let core = Swhid::new(ObjectType::Content, [0u8; 20]);
@@ -0,0 +1,809 @@
+use std::fs;
+use std::process::Command;
+use tempfile::TempDir;
+
+fn swhid_cli() -> Command {
+    Command::new(env!("CARGO_BIN_EXE_swhid"))
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn test_cli_recursive_jsonl() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    fs::create_dir_all(root.join("src/nested")).unwrap();
+    let files = [
+        "README.md",
+        "src/lib.rs",
+        "src/nested/mod.rs",
+        "src/nested/quote\"and\\backslash.txt",
+    ];
+    for file in &files {
+        fs::write(root.join(file), file.as_bytes()).unwrap();
+    }
+
+    let output = swhid_cli()
+        .args(["--recursive", "--jsonl"])
+        .arg(root)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<serde_json::Value> = stdout
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+
+    let contents = lines
+        .iter()
+        .filter(|line| line["swhid"].as_str().unwrap().starts_with("swh:1:cnt:"))
+        .count();
+    assert_eq!(contents, files.len());
+    // Root, src and src/nested
+    assert_eq!(lines.len(), files.len() + 3);
+
+    // The root comes last and matches the non-recursive result
+    let root_line = lines.last().unwrap();
+    assert_eq!(root_line["path"], root.to_str().unwrap());
+    let output = swhid_cli().arg(root).output().unwrap();
+    let plain = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(plain.split('\t').next().unwrap(), root_line["swhid"]);
+
+    let quoted = root.join("src/nested/quote\"and\\backslash.txt");
+    assert!(lines.iter().any(|line| line["path"] == quoted.to_str().unwrap()));
+}
+
+#[test]
+fn test_cli_check_manifest() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path().join("tree");
+    fs::create_dir_all(root.join("src")).unwrap();
+    fs::write(root.join("README.md"), b"readme\n").unwrap();
+    fs::write(root.join("src/main.rs"), b"fn main() {}\n").unwrap();
+
+    let computer = swhid::SwhidComputer::new();
+    let readme = computer.compute_content_swhid(b"readme\n").unwrap();
+    let main = computer.compute_content_swhid(b"fn main() {}\n").unwrap();
+    let tree = computer.compute_directory_swhid(&root).unwrap();
+
+    let passing = temp_dir.path().join("passing.txt");
+    fs::write(
+        &passing,
+        format!("# expected\n{} README.md\n{}\tsrc/main.rs\n{} .\n", readme, main, tree),
+    )
+    .unwrap();
+    let output = swhid_cli()
+        .arg("check")
+        .arg("--manifest")
+        .arg(&passing)
+        .arg("--root")
+        .arg(&root)
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(output.status.success(), "{}", stdout);
+    // The src directory is not listed, which is reported but allowed
+    assert!(stdout.contains("EXTRA\tsrc"));
+    assert!(stdout.ends_with("3 matched, 0 mismatched, 0 missing, 1 extra\n"));
+
+    let failing = temp_dir.path().join("failing.txt");
+    fs::write(
+        &failing,
+        format!("{} README.md\n{} src/main.rs\n{} src/gone.rs\n", main, main, readme),
+    )
+    .unwrap();
+    let output = swhid_cli()
+        .arg("check")
+        .arg("--manifest")
+        .arg(&failing)
+        .arg("--root")
+        .arg(&root)
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stdout.contains("MISMATCH\tREADME.md"));
+    assert!(stdout.contains("MISSING\tsrc/gone.rs"));
+}
+
+#[test]
+fn test_cli_check_exclude() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path().join("tree");
+    fs::create_dir(&root).unwrap();
+    fs::write(root.join("README.md"), b"readme\n").unwrap();
+    let tree = swhid::SwhidComputer::new().compute_directory_swhid(&root).unwrap();
+    fs::write(root.join("build.log"), b"log\n").unwrap();
+
+    let manifest = temp_dir.path().join("manifest.txt");
+    fs::write(&manifest, format!("{} .\n", tree)).unwrap();
+    let check = |exclude: &[&str]| {
+        swhid_cli()
+            .arg("check")
+            .args(exclude)
+            .arg("--manifest")
+            .arg(&manifest)
+            .arg("--root")
+            .arg(&root)
+            .output()
+            .unwrap()
+    };
+    assert_eq!(check(&[]).status.code(), Some(1));
+    let output = check(&["--exclude", "*.log"]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stdout));
+}
+
+#[test]
+fn test_cli_global_options_before_subcommand() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path().join("tree");
+    fs::create_dir(&root).unwrap();
+    fs::write(root.join("README.md"), b"readme\n").unwrap();
+    let tree = swhid::SwhidComputer::new().compute_directory_swhid(&root).unwrap();
+    fs::write(root.join("build.log"), b"log\n").unwrap();
+
+    let output = swhid_cli().args(["--output", "csv", "identify"]).arg(root.join("README.md")).output().unwrap();
+    assert!(output.status.success());
+    assert!(String::from_utf8(output.stdout).unwrap().starts_with("path,type,swhid,length\n"));
+
+    let manifest = temp_dir.path().join("manifest.txt");
+    fs::write(&manifest, format!("{} .\n", tree)).unwrap();
+    let output = swhid_cli()
+        .args(["-e", "*.log", "check", "--manifest"])
+        .arg(&manifest)
+        .arg("--root")
+        .arg(&root)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+    // Options of the legacy interface are not options of the subcommands
+    let output = swhid_cli().args(["--recursive", "identify"]).arg(&root).output().unwrap();
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8(output.stderr).unwrap().contains("'--recursive' cannot be used with a subcommand"));
+}
+
+#[test]
+fn test_cli_legacy_recursive_verify_conflict() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    fs::write(root.join("README.md"), b"readme\n").unwrap();
+    let swhid = swhid::SwhidComputer::new().compute_directory_swhid(root).unwrap();
+
+    // Verification covers the root only, so --recursive is refused
+    let output = swhid_cli().args(["--recursive", "--verify"]).arg(swhid.to_string()).arg(root).output().unwrap();
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8(output.stderr).unwrap().contains("cannot be used with"));
+
+    let output = swhid_cli().arg("--verify").arg(swhid.to_string()).arg(root).output().unwrap();
+    assert!(output.status.success());
+}
+
+#[test]
+fn test_cli_identify_verify_exclude() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    fs::write(root.join("README.md"), b"readme\n").unwrap();
+    fs::write(root.join("scratch.tmp"), b"scratch\n").unwrap();
+
+    let output = swhid_cli().args(["identify", "--exclude", "*.tmp"]).arg(root).output().unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let swhid = stdout.split('\t').next().unwrap();
+
+    let verify = |exclude: &[&str]| swhid_cli().arg("verify").args(exclude).arg(root).arg(swhid).output().unwrap();
+    assert_eq!(verify(&[]).status.code(), Some(1));
+    let output = verify(&["--exclude", "*.tmp"]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stdout));
+}
+
+#[test]
+fn test_cli_identify_verify_parse() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    fs::create_dir(root.join("src")).unwrap();
+    fs::write(root.join("src/main.rs"), b"fn main() {\n}\n").unwrap();
+
+    let computer = swhid::SwhidComputer::new();
+    let file = computer.compute_file_swhid(root.join("src/main.rs")).unwrap();
+    let tree = computer.compute_directory_swhid(root).unwrap();
+
+    let output = swhid_cli().arg("identify").arg(root).arg(root.join("src/main.rs")).output().unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let swhids: Vec<&str> = stdout.lines().map(|line| line.split('\t').next().unwrap()).collect();
+    assert_eq!(swhids, vec![tree.to_string(), file.to_string()]);
+
+    let output = swhid_cli().arg("verify").arg(root.join("src/main.rs")).arg(file.to_string()).output().unwrap();
+    assert!(output.status.success());
+    assert!(String::from_utf8(output.stdout).unwrap().contains("✓"));
+
+    // Qualifiers are checked from the anchor checkout
+    let cited = format!("{};anchor={};path=/src/main.rs;lines=2-3", file, tree);
+    let output = swhid_cli().arg("verify").arg(root).arg(&cited).output().unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(output.status.code(), Some(1), "{}", stdout);
+    assert!(stdout.contains("anchor\tpassed\npath\tpassed\nlines\tfailed: "));
+
+    let output = swhid_cli().arg("verify").arg(root).arg(file.to_string()).output().unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8(output.stdout).unwrap().contains("object type differs"));
+
+    let output = swhid_cli().arg("parse").arg(&cited).output().unwrap();
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        format!(
+            "namespace\tswh\nversion\t1\nobject_type\tcnt\nhash\t{}\nanchor\t{}\npath\t/src/main.rs\nlines\t2-3\n",
+            hex::encode(file.digest()),
+            tree
+        )
+    );
+    assert!(!swhid_cli().args(["parse", "swh:1:cnt:1234"]).output().unwrap().status.success());
+}
+
+#[test]
+fn test_cli_tree_and_ls() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    fs::create_dir(root.join("sub")).unwrap();
+    fs::write(root.join("sub/b"), b"b\n").unwrap();
+    fs::write(root.join("sub.txt"), b"sub\n").unwrap();
+    fs::write(root.join("a"), b"a\n").unwrap();
+
+    // `ls` prints the entries a directory SWHID is hashed from, in hash order
+    let output = swhid_cli().arg("ls").arg(root).output().unwrap();
+    assert!(output.status.success());
+    let listing = swhid::Directory::from_disk(root, &[]).unwrap().cat_file();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), listing);
+    assert!(listing.lines().nth(1).unwrap().ends_with("\tsub.txt"), "{}", listing);
+
+    let computer = swhid::SwhidComputer::new();
+    let output = swhid_cli().arg("tree").arg(root).output().unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines.len(), 5);
+    assert_eq!(
+        lines[0],
+        format!(
+            "040000 tree {}\t{}",
+            hex::encode(computer.compute_directory_swhid(root).unwrap().digest()),
+            root.display()
+        )
+    );
+    assert_eq!(&lines[1..3], &listing.lines().map(|line| line.replace('\t', "\t  ")).collect::<Vec<_>>()[..2]);
+    assert!(lines[3].ends_with("\t  sub"));
+    assert_eq!(
+        lines[4],
+        format!("100644 blob {}\t    b", hex::encode(computer.compute_content_swhid(b"b\n").unwrap().digest()))
+    );
+
+    // Excluded entries are neither listed nor hashed into their parents
+    let output = swhid_cli().args(["ls", "-e", "sub.txt"]).arg(root).output().unwrap();
+    assert!(output.status.success());
+    let listing = swhid::Directory::from_disk(root, &["sub.txt".to_string()]).unwrap().cat_file();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), listing);
+    assert!(!listing.contains("sub.txt"));
+    let output = swhid_cli().args(["tree", "--exclude", "b"]).arg(root).output().unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout.lines().count(), 4);
+    assert_ne!(stdout.lines().next().unwrap(), lines[0]);
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn test_cli_manifest_create_verify() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path().join("release");
+    fs::create_dir_all(root.join("src")).unwrap();
+    fs::write(root.join("src/lib.rs"), b"lib\n").unwrap();
+    let manifest = temp_dir.path().join("manifest.json");
+
+    let output = swhid_cli().args(["manifest", "create"]).arg(&root).arg("-o").arg(&manifest).output().unwrap();
+    assert!(output.status.success());
+    let json = fs::read_to_string(&manifest).unwrap();
+    let recorded = swhid::TreeManifest::from_json(&json).unwrap();
+    assert_eq!(recorded.root, swhid::SwhidComputer::new().compute_directory_swhid(&root).unwrap());
+
+    // Without -o, the same manifest goes to standard output
+    let output = swhid_cli().args(["manifest", "create"]).arg(&root).output().unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), json);
+
+    // The manifest is the whole output, whatever the output format
+    let output = swhid_cli().args(["manifest", "create", "--output", "json"]).arg(&root).output().unwrap();
+    assert!(output.status.success());
+    let value: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(value, serde_json::from_str::<serde_json::Value>(&json).unwrap());
+
+    let output = swhid_cli().args(["manifest", "verify"]).arg(&root).arg(&manifest).output().unwrap();
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "3 matched, 0 mismatched, 0 missing, 0 extra\n");
+
+    // An added file changes the root, even though the manifest does not list it
+    fs::write(root.join("NEWS"), b"news\n").unwrap();
+    let output = swhid_cli().args(["manifest", "verify"]).arg(&root).arg(&manifest).output().unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.starts_with(&format!("MISMATCH\t.\texpected {}", recorded.root)), "{}", stdout);
+    assert!(stdout.contains("EXTRA\tNEWS\n"));
+
+    // Unless it is excluded, both when creating and verifying
+    let output = swhid_cli().args(["manifest", "verify", "-e", "NEWS"]).arg(&root).arg(&manifest).output().unwrap();
+    assert!(output.status.success());
+    let output = swhid_cli().args(["manifest", "create", "--exclude", "NEWS"]).arg(&root).output().unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), json);
+}
+
+#[cfg(feature = "tar")]
+#[test]
+fn test_cli_archive() {
+    let temp_dir = TempDir::new().unwrap();
+    let release = temp_dir.path().join("release");
+    fs::create_dir_all(release.join("src")).unwrap();
+    fs::write(release.join("src/lib.rs"), b"lib\n").unwrap();
+
+    let mut builder = tar::Builder::new(Vec::new());
+    builder.append_dir_all("release-1.0", &release).unwrap();
+    let tarball = temp_dir.path().join("release-1.0.tar");
+    fs::write(&tarball, builder.into_inner().unwrap()).unwrap();
+
+    let output = swhid_cli().arg("archive").arg(&tarball).output().unwrap();
+    assert!(output.status.success());
+    let expected = swhid::SwhidComputer::new().compute_directory_swhid(&release).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), format!("{}\t{}\n", expected, tarball.display()));
+
+    let output = swhid_cli().args(["archive", "-e", "lib.rs"]).arg(&tarball).output().unwrap();
+    assert!(output.status.success());
+    fs::remove_file(release.join("src/lib.rs")).unwrap();
+    let expected = swhid::SwhidComputer::new().compute_directory_swhid(&release).unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), format!("{}\t{}\n", expected, tarball.display()));
+
+    let output = swhid_cli().arg("archive").arg(temp_dir.path().join("missing.tar")).output().unwrap();
+    assert!(!output.status.success());
+    let output = swhid_cli().arg("archive").arg(release.join("src")).output().unwrap();
+    assert!(!output.status.success());
+}
+
+/// Serve one `/known/` request on a local port, answering that only `known`
+/// is archived, and return the API URL
+#[cfg(feature = "api")]
+fn serve_known(known: String) -> String {
+    use std::io::{BufRead, BufReader, Read, Write};
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/api/1/", listener.local_addr().unwrap());
+    std::thread::spawn(move || {
+        let mut stream = BufReader::new(listener.accept().unwrap().0);
+        let mut length = 0;
+        loop {
+            let mut line = String::new();
+            stream.read_line(&mut line).unwrap();
+            let line = line.trim_end();
+            if line.is_empty() {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(": ") {
+                if name.eq_ignore_ascii_case("content-length") {
+                    length = value.parse().unwrap();
+                }
+            }
+        }
+        let mut body = vec![0; length];
+        stream.read_exact(&mut body).unwrap();
+        let swhids: Vec<String> = serde_json::from_slice(&body).unwrap();
+        let reply: serde_json::Map<String, serde_json::Value> = swhids
+            .into_iter()
+            .map(|swhid| {
+                let status = serde_json::json!({ "known": swhid == known });
+                (swhid, status)
+            })
+            .collect();
+        let reply = serde_json::Value::Object(reply).to_string();
+        write!(stream.get_mut(), "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{}", reply.len(), reply).unwrap();
+    });
+    url
+}
+
+#[cfg(feature = "api")]
+#[test]
+fn test_cli_api_known() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path().join("tree");
+    fs::create_dir(&root).unwrap();
+    fs::write(root.join("archived.txt"), b"archived\n").unwrap();
+    fs::write(root.join("new.txt"), b"new\n").unwrap();
+    let computer = swhid::SwhidComputer::new();
+    let archived = computer.compute_file_swhid(root.join("archived.txt")).unwrap();
+    let new = computer.compute_file_swhid(root.join("new.txt")).unwrap();
+    let tree = computer.compute_directory_swhid(&root).unwrap();
+
+    let url = serve_known(archived.to_string());
+    let output = swhid_cli().args(["api", "--api-url", &url, "known", "-r"]).arg(&root).output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        format!(
+            "unknown\t{}\t{}\nknown\t{}\t{}\nunknown\t{}\t{}\n",
+            tree,
+            root.display(),
+            archived,
+            root.join("archived.txt").display(),
+            new,
+            root.join("new.txt").display()
+        )
+    );
+
+    // Nothing listens any more once the request is served
+    let output = swhid_cli().args(["api", "--api-url", &url, "known"]).arg(&root).output().unwrap();
+    assert!(!output.status.success());
+
+    // Excluded files are neither checked nor hashed into their directory
+    let url = serve_known(archived.to_string());
+    let output = swhid_cli()
+        .args(["api", "--api-url", &url, "known", "-r", "-e", "new.txt"])
+        .arg(&root)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let excluded = computer.clone().with_exclude_patterns(&["new.txt".to_string()]);
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        format!(
+            "unknown\t{}\t{}\nknown\t{}\t{}\n",
+            excluded.compute_directory_swhid(&root).unwrap(),
+            root.display(),
+            archived,
+            root.join("archived.txt").display()
+        )
+    );
+}
+
+#[test]
+fn test_cli_diff() {
+    let temp_dir = TempDir::new().unwrap();
+    let (old, new) = (temp_dir.path().join("old"), temp_dir.path().join("new"));
+    for root in [&old, &new] {
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::write(root.join("README"), b"readme\n").unwrap();
+        fs::write(root.join("src/lib.rs"), b"lib\n").unwrap();
+    }
+
+    let output = swhid_cli().arg("diff").arg(&old).arg(&new).output().unwrap();
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+
+    fs::write(new.join("src/lib.rs"), b"changed\n").unwrap();
+    fs::remove_file(new.join("README")).unwrap();
+    fs::create_dir(new.join("docs")).unwrap();
+    fs::write(new.join("docs/intro.md"), b"intro\n").unwrap();
+    let output = swhid_cli().arg("diff").arg(&old).arg(&new).output().unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "D\tREADME\nA\tdocs\nM\tsrc/lib.rs\n");
+
+    // Excluded paths are left out of both trees
+    let output = swhid_cli().args(["diff", "-e", "docs", "-e", "README"]).arg(&old).arg(&new).output().unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "M\tsrc/lib.rs\n");
+}
+
+#[test]
+fn test_cli_url() {
+    let swhid = "swh:1:cnt:94a9ed024d3859793618152ea559a168bbcbb5e2;origin=https://example.org/repo.git;lines=9-15";
+    let url = format!("https://archive.softwareheritage.org/{}", swhid);
+
+    let output = swhid_cli().args(["url", swhid]).output().unwrap();
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), format!("{}\n", url));
+
+    // Both directions at once, a trailing slash being allowed
+    let output = swhid_cli().args(["url", &format!("{}/", url), swhid]).output().unwrap();
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), format!("{}\n{}\n", swhid, url));
+
+    assert!(!swhid_cli().args(["url", "https://example.org/swh:1:cnt:94a9ed024d3859793618152ea559a168bbcbb5e2"]).output().unwrap().status.success());
+}
+
+#[test]
+fn test_cli_verify_manifest() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path();
+    fs::write(root.join("a.txt"), b"a\n").unwrap();
+    fs::write(root.join("b.txt"), b"b\n").unwrap();
+
+    let computer = swhid::SwhidComputer::new();
+    let a = computer.compute_file_swhid(root.join("a.txt")).unwrap();
+    let b = computer.compute_file_swhid(root.join("b.txt")).unwrap();
+    let verify = |manifest: &str| {
+        fs::write(root.join("MANIFEST"), manifest).unwrap();
+        let output = swhid_cli().current_dir(root).args(["verify", "--manifest", "MANIFEST"]).output().unwrap();
+        (output.status.code(), String::from_utf8(output.stdout).unwrap())
+    };
+
+    // Unlisted paths, such as the manifest itself, are ignored
+    let (status, stdout) = verify(&format!("# expected\n{} a.txt\n\n{}\tb.txt\n", a, b));
+    assert_eq!(status, Some(0), "{}", stdout);
+    assert_eq!(stdout, "PASS\ta.txt\nPASS\tb.txt\n2 passed, 0 failed\n");
+
+    let (status, stdout) = verify(&format!("{} a.txt\n{} b.txt\n{} missing.txt\n", a, a, a));
+    assert_eq!(status, Some(1), "{}", stdout);
+    assert_eq!(
+        stdout,
+        format!("PASS\ta.txt\nFAIL\tb.txt\tgot {}\nFAIL\tmissing.txt\tnot found\n1 passed, 2 failed\n", b)
+    );
+
+    // Paths are relative to the root
+    let output = swhid_cli()
+        .args(["verify", "--manifest"])
+        .arg(root.join("MANIFEST"))
+        .arg("--root")
+        .arg(root)
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+
+    // Errors outrank mismatches
+    let (status, _) = verify(&format!("{} b.txt\nnot-a-swhid a.txt\n", a));
+    assert_eq!(status, Some(2));
+    let (status, _) = verify("no-separator\n");
+    assert_eq!(status, Some(2));
+    let output = swhid_cli().current_dir(root).args(["verify", "--manifest", "NONE"]).output().unwrap();
+    assert_eq!(output.status.code(), Some(2));
+
+    // A manifest replaces the path and SWHID arguments
+    let output = swhid_cli().current_dir(root).args(["verify", "--manifest", "MANIFEST", "a.txt"]).output().unwrap();
+    assert!(!output.status.success());
+    let output = swhid_cli().current_dir(root).args(["verify", "a.txt"]).output().unwrap();
+    assert!(!output.status.success());
+    let output = swhid_cli().current_dir(root).args(["verify", "--root", ".", "a.txt", &a.to_string()]).output().unwrap();
+    assert!(!output.status.success());
+}
+
+#[test]
+fn test_cli_identify_recursive() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path().join("tree");
+    fs::create_dir_all(root.join("src/bin")).unwrap();
+    fs::write(root.join("README.md"), b"readme\n").unwrap();
+    fs::write(root.join("src/lib.rs"), b"pub fn f() {}\n").unwrap();
+    fs::write(root.join("src/bin/main.rs"), b"fn main() {}\n").unwrap();
+    let single = temp_dir.path().join("single.txt");
+    fs::write(&single, b"single\n").unwrap();
+
+    let output = swhid_cli().args(["identify", "--recursive"]).arg(&root).arg(&single).output().unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<(&str, &str)> = stdout.lines().map(|line| line.split_once('\t').unwrap()).collect();
+
+    // Every file and directory, children before their parent, then the file
+    let computer = swhid::SwhidComputer::new();
+    assert_eq!(lines.len(), 7);
+    for (swhid, path) in &lines {
+        assert_eq!(*swhid, computer.compute_swhid(path).unwrap().to_string());
+    }
+    let position = |path: &std::path::Path| lines.iter().position(|(_, p)| *p == path.to_str().unwrap()).unwrap();
+    assert!(position(&root.join("src/bin/main.rs")) < position(&root.join("src/bin")));
+    assert!(position(&root.join("src")) < position(&root));
+    assert_eq!(position(&root), 5);
+    assert_eq!(position(&single), 6);
+
+    // Exclusions apply below the root too
+    let output = swhid_cli().args(["identify", "-r", "-e", "bin"]).arg(&root).output().unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout.lines().count(), 4);
+    assert!(!stdout.contains("main.rs"));
+}
+
+#[test]
+fn test_cli_output_formats() {
+    let temp_dir = TempDir::new().unwrap();
+    let root = temp_dir.path().join("tree");
+    fs::create_dir(&root).unwrap();
+    fs::write(root.join("a,b.txt"), b"hello\n").unwrap();
+
+    let computer = swhid::SwhidComputer::new();
+    let file = computer.compute_content_swhid(b"hello\n").unwrap();
+    let tree = computer.compute_directory_swhid(&root).unwrap();
+    let file_path = root.join("a,b.txt");
+
+    #[cfg(feature = "json")]
+    {
+        let output = swhid_cli().args(["identify", "-r", "--output", "json"]).arg(&root).output().unwrap();
+        assert!(output.status.success());
+        let records: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(
+            records,
+            serde_json::json!([
+                {"path": file_path.to_str().unwrap(), "type": "content", "swhid": file.to_string(), "length": 6},
+                {"path": root.to_str().unwrap(), "type": "directory", "swhid": tree.to_string(), "length": null},
+            ])
+        );
+
+        // The top-level options take the same formats
+        let output = swhid_cli().args(["--output", "jsonl"]).arg(&file_path).output().unwrap();
+        let record: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(record, records[0]);
+        let output = swhid_cli().args(["identify", "--jsonl"]).arg(&file_path).output().unwrap();
+        let record: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(record, records[0]);
+        let output = swhid_cli().args(["--output", "json"]).output().unwrap();
+        assert_eq!(output.stdout, b"[]\n");
+    }
+
+    let output = swhid_cli().args(["identify", "--output", "csv"]).arg(&file_path).arg(&root).output().unwrap();
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        format!(
+            "path,type,swhid,length\n\"{}\",content,{},6\n{},directory,{},\n",
+            file_path.display(),
+            file,
+            root.display(),
+            tree
+        )
+    );
+
+    let output = swhid_cli()
+        .args(["identify", "--output", "plain"])
+        .arg(&file_path)
+        .output()
+        .unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), format!("{}\t{}\n", file, file_path.display()));
+}
+
+#[test]
+fn test_cli_identify_files_from() {
+    use std::io::Write;
+    use std::process::Stdio;
+
+    let temp_dir = TempDir::new().unwrap();
+    let paths: Vec<_> = (0..3)
+        .map(|i| {
+            let path = temp_dir.path().join(format!("file{}.txt", i));
+            fs::write(&path, format!("content {}\n", i)).unwrap();
+            path
+        })
+        .collect();
+    let list = temp_dir.path().join("list.txt");
+    fs::write(&list, format!("{}\r\n\n{}\n", paths[1].display(), paths[2].display())).unwrap();
+
+    let computer = swhid::SwhidComputer::new();
+    let expected = |indices: &[usize]| -> String {
+        indices
+            .iter()
+            .map(|&i| format!("{}\t{}\n", computer.compute_file_swhid(&paths[i]).unwrap(), paths[i].display()))
+            .collect()
+    };
+
+    // Listed paths come after the ones given as arguments
+    let output = swhid_cli().arg("identify").arg(&paths[0]).arg("--files-from").arg(&list).output().unwrap();
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), expected(&[0, 1, 2]));
+
+    let mut child = swhid_cli()
+        .args(["identify", "--files-from", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    write!(child.stdin.take().unwrap(), "{}\n{}\n", paths[2].display(), paths[0].display()).unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), expected(&[2, 0]));
+
+    let output = swhid_cli().args(["identify", "--files-from"]).arg(temp_dir.path().join("missing")).output().unwrap();
+    assert!(!output.status.success());
+}
+
+#[test]
+fn test_cli_identify_stdin() {
+    use std::io::Write;
+    use std::process::Stdio;
+
+    let identify_stdin = |args: &[&str], input: &[u8]| {
+        let mut child = swhid_cli()
+            .arg("identify")
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        child.stdin.take().unwrap().write_all(input).unwrap();
+        child.wait_with_output().unwrap()
+    };
+
+    let output = identify_stdin(&["-"], b"Hello, World!");
+    assert!(output.status.success());
+    assert_eq!(output.stdout, b"swh:1:cnt:b45ef6fec89518d314f546fd6c3025367b721684\n");
+
+    #[cfg(feature = "json")]
+    {
+        let output = identify_stdin(&["--output", "jsonl", "-"], &[0xff; 100_000]);
+        let record: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(record["path"], serde_json::Value::Null);
+        assert_eq!(record["length"], 100_000);
+    }
+
+    for args in [&["-", "-"][..], &["--files-from", "-", "-"]] {
+        let output = identify_stdin(args, b"");
+        assert!(!output.status.success());
+        assert!(String::from_utf8(output.stderr).unwrap().contains("standard input can only be read once"));
+    }
+}
+
+#[test]
+fn test_cli_subcommand_output_formats() {
+    let temp_dir = TempDir::new().unwrap();
+    let (old, new) = (temp_dir.path().join("old"), temp_dir.path().join("new"));
+    for (dir, text) in [(&old, "old\n"), (&new, "new\n")] {
+        fs::create_dir(dir).unwrap();
+        fs::write(dir.join("a.txt"), text).unwrap();
+    }
+    fs::write(new.join("b,c.txt"), "added\n").unwrap();
+
+    // Summaries are left out, and fields line up as columns
+    let output = swhid_cli().args(["diff", "--output", "csv"]).arg(&old).arg(&new).output().unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "path,status\na.txt,modified\n\"b,c.txt\",added\n");
+
+    let computer = swhid::SwhidComputer::new();
+    let tree = computer.compute_directory_swhid(&old).unwrap();
+    let manifest = temp_dir.path().join("manifest.txt");
+    fs::write(&manifest, format!("{} .\n", tree)).unwrap();
+    let output = swhid_cli().args(["check", "--output", "csv", "--manifest"]).arg(&manifest).arg("--root").arg(&new).output().unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        format!(
+            "path,status,expected,actual\n.,mismatch,{},{}\na.txt,extra,,\n\"b,c.txt\",extra,,\n",
+            tree,
+            computer.compute_directory_swhid(&new).unwrap()
+        )
+    );
+
+    #[cfg(feature = "json")]
+    {
+        let jsonl = |args: &[&str]| -> Vec<serde_json::Value> {
+            let output = swhid_cli().args(args).arg("--jsonl").output().unwrap();
+            String::from_utf8(output.stdout)
+                .unwrap()
+                .lines()
+                .map(|line| serde_json::from_str(line).unwrap())
+                .collect()
+        };
+        let old_path = old.to_str().unwrap();
+        let file = computer.compute_content_swhid(b"old\n").unwrap();
+
+        let records = jsonl(&["ls", old_path]);
+        assert_eq!(
+            records,
+            vec![serde_json::json!({
+                "path": old.join("a.txt").to_str().unwrap(),
+                "mode": "100644",
+                "type": "blob",
+                "swhid": file.to_string(),
+            })]
+        );
+        assert_eq!(jsonl(&["tree", old_path])[0]["swhid"], tree.to_string());
+
+        let records = jsonl(&["verify", old_path, &tree.to_string()]);
+        assert_eq!(records[0]["status"], "pass");
+
+        let swhid = "swh:1:cnt:94a9ed024d3859793618152ea559a168bbcbb5e2;lines=9";
+        let records = jsonl(&["parse", swhid]);
+        assert_eq!(
+            records,
+            vec![serde_json::json!({
+                "namespace": "swh",
+                "version": "1",
+                "object_type": "cnt",
+                "hash": "94a9ed024d3859793618152ea559a168bbcbb5e2",
+                "lines": "9",
+            })]
+        );
+        let records = jsonl(&["url", swhid]);
+        assert_eq!(records[0]["swhid"], swhid);
+        assert!(records[0]["url"].as_str().unwrap().starts_with("https://archive.softwareheritage.org/"));
+    }
+}
@@ -0,0 +1,58 @@
+//! Kept in its own test binary so that no other test opens files concurrently
+//! while descriptor usage is observed.
+
+use std::fs;
+use swhid::SwhidComputer;
+use tempfile::TempDir;
+
+#[cfg(target_os = "linux")]
+fn open_fds() -> usize {
+    // Less the descriptor of the listing itself
+    fs::read_dir("/proc/self/fd").unwrap().count() - 1
+}
+
+#[test]
+fn test_max_concurrency_bounds_open_files() {
+    let temp_dir = TempDir::new().unwrap();
+    for d in 0..10 {
+        let sub = temp_dir.path().join(format!("dir_{}", d));
+        fs::create_dir(&sub).unwrap();
+        for f in 0..100 {
+            fs::write(sub.join(format!("file_{}", f)), format!("{} {}", d, f).repeat(1_000)).unwrap();
+        }
+    }
+
+    let expected = SwhidComputer::new().compute_directory_swhid(temp_dir.path()).unwrap();
+
+    let computer = SwhidComputer::new().with_max_concurrency(2);
+    #[cfg(feature = "parallel")]
+    let computer = computer.with_parallelism(8);
+
+    // Sample the open descriptors from another thread while the tree is hashed
+    #[cfg(target_os = "linux")]
+    let baseline = open_fds();
+    let done = std::sync::atomic::AtomicBool::new(false);
+    let (swhid, max_fds) = std::thread::scope(|scope| {
+        let sampler = scope.spawn(|| {
+            let mut max_fds = 0;
+            while !done.load(std::sync::atomic::Ordering::Relaxed) {
+                #[cfg(target_os = "linux")]
+                {
+                    max_fds = max_fds.max(open_fds());
+                }
+                std::thread::yield_now();
+            }
+            max_fds
+        });
+        let swhid = computer.compute_directory_swhid(temp_dir.path());
+        done.store(true, std::sync::atomic::Ordering::Relaxed);
+        (swhid, sampler.join().unwrap())
+    });
+    assert_eq!(swhid.unwrap(), expected);
+
+    // Best effort: at most two directories or files open at once
+    #[cfg(target_os = "linux")]
+    assert!(max_fds <= baseline + 2, "{} fds open, baseline {}", max_fds, baseline);
+    #[cfg(not(target_os = "linux"))]
+    let _ = max_fds;
+}
@@ -1,4 +1,4 @@
-use swhid::{SwhidComputer, Swhid, ObjectType, Content, Directory, QualifiedSwhid};
+use swhid::{SwhidComputer, Swhid, ObjectType, Content, Directory, Origin, QualifiedSwhid};
 use std::fs;
 use tempfile::TempDir;
 
@@ -10,7 +10,7 @@ fn test_content_swhid_conformance() {
     let swhid = content.swhid();
 
     assert_eq!(swhid.object_type(), ObjectType::Content);
-    assert_eq!(swhid.hash().len(), 20);
+    assert_eq!(swhid.digest().len(), 20);
 
     // Verify SWHID format
     let swhid_str = swhid.to_string();
@@ -27,7 +27,7 @@ fn test_directory_swhid_conformance() {
     let swhid = dir.swhid();
 
     assert_eq!(swhid.object_type(), ObjectType::Directory);
-    assert_eq!(swhid.hash().len(), 20);
+    assert_eq!(swhid.digest().len(), 20);
 
     // Verify SWHID format
     let swhid_str = swhid.to_string();
@@ -91,13 +91,13 @@ fn test_qualified_swhid_conformance() {
     // Test that qualified SWHID matches expected format
     let core = Swhid::new(ObjectType::Content, [0u8; 20]);
     let qualified = QualifiedSwhid::new(core)
-        .with_origin("https://github.com/user/repo".to_string())
+        .with_origin(Origin::new("https://github.com/user/repo").unwrap())
         .with_path(b"/src/main.rs".to_vec())
         .with_lines(10, Some(20));
 
     // Verify core SWHID properties
     assert_eq!(qualified.object_type(), ObjectType::Content);
-    assert_eq!(qualified.hash().len(), 20);
+    assert_eq!(qualified.digest().len(), 20);
 
     // Verify qualifiers
     assert_eq!(qualified.origin(), Some("https://github.com/user/repo"));
@@ -157,3 +157,25 @@ fn test_qualified_swhid_validation() {
         .with_anchor(invalid_anchor);
     assert!(result.is_err());
 }
+
+#[test]
+fn test_swhid_clap_value_parser() {
+    use clap::Parser;
+
+    #[derive(Parser)]
+    struct Args {
+        #[arg(value_parser)]
+        swhid: Swhid,
+    }
+
+    let valid = "swh:1:cnt:94a9ed024d3859793618152ea559a168bbcbb5e2";
+    let args = Args::try_parse_from(["prog", valid]).unwrap();
+    assert_eq!(args.swhid.to_string(), valid);
+
+    let err = match Args::try_parse_from(["prog", "swh:1:xyz:94a9ed024d3859793618152ea559a168bbcbb5e2"]) {
+        Err(err) => err,
+        Ok(_) => panic!("malformed SWHID should be rejected"),
+    };
+    assert_eq!(err.kind(), clap::error::ErrorKind::ValueValidation);
+    assert!(err.to_string().contains("Invalid object type: xyz"));
+}
@@ -59,7 +59,7 @@ fn test_content_hash_basic() {
     let swhid = content.swhid();
     
     // Known hash for "Hello, World!" content (matches Python swh identify)
-    assert_eq!(swhid.hash(), &hex::decode("b45ef6fec89518d314f546fd6c3025367b721684").unwrap()[..]);
+    assert_eq!(swhid.digest(), &hex::decode("b45ef6fec89518d314f546fd6c3025367b721684").unwrap()[..]);
     assert_eq!(swhid.object_type(), ObjectType::Content);
 }
 
@@ -72,7 +72,7 @@ fn test_content_hash_empty() {
     let swhid = content.swhid();
     
     // Known hash for empty content
-    assert_eq!(swhid.hash(), &hex::decode("e69de29bb2d1d6434b8b29ae775ad8c2e48c5391").unwrap()[..]);
+    assert_eq!(swhid.digest(), &hex::decode("e69de29bb2d1d6434b8b29ae775ad8c2e48c5391").unwrap()[..]);
 }
 
 #[test]
@@ -85,7 +85,7 @@ fn test_content_hash_large() {
     let swhid = content.swhid();
     
     // Verify it's a valid SHA1 hash
-    assert_eq!(swhid.hash().len(), 20);
+    assert_eq!(swhid.digest().len(), 20);
 }
 
 #[test]
@@ -97,7 +97,7 @@ fn test_directory_hash_single_file() {
     let swhid = dir.swhid();
     
     assert_eq!(swhid.object_type(), ObjectType::Directory);
-    assert_eq!(swhid.hash().len(), 20);
+    assert_eq!(swhid.digest().len(), 20);
 }
 
 #[test]
@@ -110,7 +110,7 @@ fn test_directory_hash_multiple_files() {
     let swhid = dir.swhid();
     
     assert_eq!(swhid.object_type(), ObjectType::Directory);
-    assert_eq!(swhid.hash().len(), 20);
+    assert_eq!(swhid.digest().len(), 20);
 }
 
 #[test]
@@ -125,7 +125,24 @@ fn test_directory_hash_with_subdirectories() {
     let swhid = dir.swhid();
     
     assert_eq!(swhid.object_type(), ObjectType::Directory);
-    assert_eq!(swhid.hash().len(), 20);
+    assert_eq!(swhid.digest().len(), 20);
+}
+
+#[test]
+fn test_directory_hash_known_value() {
+    let test_dir = TestDir::new();
+    // Created out of order: the tree hash must not depend on read_dir order
+    test_dir.create_file("zeta.txt", b"zeta\n");
+    test_dir.create_file("alpha.txt", b"alpha\n");
+    test_dir.create_file("mid.txt", b"mid\n");
+    let subdir = test_dir.create_subdir("sub");
+    fs::write(subdir.join("inner.txt"), b"nested\n").unwrap();
+    
+    let mut dir = Directory::from_disk(test_dir.path(), &[]).unwrap();
+    let swhid = dir.swhid();
+    
+    // Matches `git write-tree` for the same layout
+    assert_eq!(swhid.to_string(), "swh:1:dir:730a08bd69908d3d2d8c346f3718f81602c7f1c6");
 }
 
 #[test]
@@ -138,7 +155,7 @@ fn test_directory_hash_with_executable() {
     let swhid = dir.swhid();
     
     assert_eq!(swhid.object_type(), ObjectType::Directory);
-    assert_eq!(swhid.hash().len(), 20);
+    assert_eq!(swhid.digest().len(), 20);
 }
 
 #[test]
@@ -151,7 +168,7 @@ fn test_directory_hash_with_symlinks() {
     let swhid = dir.swhid();
     
     assert_eq!(swhid.object_type(), ObjectType::Directory);
-    assert_eq!(swhid.hash().len(), 20);
+    assert_eq!(swhid.digest().len(), 20);
 }
 
 #[test]
@@ -162,9 +179,15 @@ fn test_directory_hash_exclude_patterns() {
     
     let mut dir = Directory::from_disk(test_dir.path(), &["*.tmp".to_string()]).unwrap();
     let swhid = dir.swhid();
+    let mut unexcluded = Directory::from_disk(test_dir.path(), &[]).unwrap();
     
     assert_eq!(swhid.object_type(), ObjectType::Directory);
-    assert_eq!(swhid.hash().len(), 20);
+    assert_ne!(swhid, unexcluded.swhid());
+    
+    // Excluding the file is the same as never having it
+    fs::remove_file(test_dir.path().join("exclude.tmp")).unwrap();
+    let mut without = Directory::from_disk(test_dir.path(), &[]).unwrap();
+    assert_eq!(swhid, without.swhid());
 }
 
 #[test]
@@ -175,7 +198,7 @@ fn test_swhid_computer_basic() {
     let swhid = computer.compute_content_swhid(content).unwrap();
     
     assert_eq!(swhid.object_type(), ObjectType::Content);
-    assert_eq!(swhid.hash().len(), 20);
+    assert_eq!(swhid.digest().len(), 20);
 }
 
 #[test]
@@ -187,7 +210,7 @@ fn test_swhid_computer_file() {
     let swhid = computer.compute_file_swhid(test_dir.path().join("test.txt")).unwrap();
     
     assert_eq!(swhid.object_type(), ObjectType::Content);
-    assert_eq!(swhid.hash().len(), 20);
+    assert_eq!(swhid.digest().len(), 20);
 }
 
 #[test]
@@ -199,7 +222,7 @@ fn test_swhid_computer_directory() {
     let swhid = computer.compute_directory_swhid(test_dir.path()).unwrap();
     
     assert_eq!(swhid.object_type(), ObjectType::Directory);
-    assert_eq!(swhid.hash().len(), 20);
+    assert_eq!(swhid.digest().len(), 20);
 }
 
 #[test]
@@ -211,7 +234,7 @@ fn test_swhid_computer_auto_detect_file() {
     let swhid = computer.compute_swhid(test_dir.path().join("test.txt")).unwrap();
     
     assert_eq!(swhid.object_type(), ObjectType::Content);
-    assert_eq!(swhid.hash().len(), 20);
+    assert_eq!(swhid.digest().len(), 20);
 }
 
 #[test]
@@ -223,7 +246,7 @@ fn test_swhid_computer_auto_detect_directory() {
     let swhid = computer.compute_swhid(test_dir.path()).unwrap();
     
     assert_eq!(swhid.object_type(), ObjectType::Directory);
-    assert_eq!(swhid.hash().len(), 20);
+    assert_eq!(swhid.digest().len(), 20);
 }
 
 #[test]
@@ -237,7 +260,7 @@ fn test_swhid_computer_symlink_default() {
     
     // Should hash the symlink target string, not the target file
     assert_eq!(swhid.object_type(), ObjectType::Content);
-    assert_eq!(swhid.hash().len(), 20);
+    assert_eq!(swhid.digest().len(), 20);
 }
 
 #[test]
@@ -251,7 +274,7 @@ fn test_swhid_computer_symlink_dereference() {
     
     // Should hash the target file content
     assert_eq!(swhid.object_type(), ObjectType::Content);
-    assert_eq!(swhid.hash().len(), 20);
+    assert_eq!(swhid.digest().len(), 20);
 }
 
 #[test]
@@ -264,7 +287,15 @@ fn test_swhid_computer_exclude_patterns() {
     let swhid = computer.compute_directory_swhid(test_dir.path()).unwrap();
     
     assert_eq!(swhid.object_type(), ObjectType::Directory);
-    assert_eq!(swhid.hash().len(), 20);
+    assert_eq!(swhid.digest().len(), 20);
+
+    // The excluded file must really be left out of the tree
+    let unfiltered = SwhidComputer::new().compute_directory_swhid(test_dir.path()).unwrap();
+    assert_ne!(swhid, unfiltered);
+    let expected_dir = TestDir::new();
+    expected_dir.create_file("include.txt", b"include content");
+    let expected = SwhidComputer::new().compute_directory_swhid(expected_dir.path()).unwrap();
+    assert_eq!(swhid, expected);
 }
 
 #[test]
@@ -290,3 +321,68 @@ fn test_swhid_verification_mismatch() {
     let is_valid = computer.verify_swhid(test_dir.path().join("test.txt"), wrong_swhid).unwrap();
     assert!(!is_valid);
 }
+
+#[test]
+fn test_directory_hash_git_tree_order() {
+    // A directory sorts as if its name ended with '/', so it comes after
+    // sibling files that extend its name with a byte smaller than '/'
+    let test_dir = TestDir::new();
+    test_dir.create_subdir("lib");
+    test_dir.create_file("lib/mod.rs", b"mod\n");
+    test_dir.create_file("lib.d", b"conf\n");
+    test_dir.create_file("lib-x", b"x\n");
+
+    let mut dir = Directory::from_disk(test_dir.path(), &[]).unwrap();
+    let names: Vec<&[u8]> = dir.entries().iter().map(|e| e.name.as_slice()).collect();
+    assert_eq!(names, vec![&b"lib-x"[..], &b"lib.d"[..], &b"lib"[..]]);
+
+    // As computed by `git write-tree`
+    assert_eq!(
+        dir.swhid().to_string(),
+        "swh:1:dir:26f881944d91dd9b8ab18a3a0cf5964a02ac3420"
+    );
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_directory_hash_reflinked_copies() {
+    use std::process::Command;
+
+    // On copy-on-write file systems (btrfs, XFS, ...) the copies share extents
+    // but not inodes; elsewhere `--reflink=auto` falls back to a plain copy
+    let test_dir = TestDir::new();
+    test_dir.create_file("original.bin", b"shared extents\n");
+    for copy in ["copy1.bin", "copy2.bin"] {
+        let status = Command::new("cp")
+            .arg("--reflink=auto")
+            .arg(test_dir.path().join("original.bin"))
+            .arg(test_dir.path().join(copy))
+            .status()
+            .unwrap();
+        assert!(status.success());
+    }
+    // Diverge one copy; its shared extents are unshared on write
+    test_dir.create_file("copy2.bin", b"diverged\n");
+
+    let computer = SwhidComputer::new();
+    let mut dir = Directory::from_disk(test_dir.path(), &[]).unwrap();
+    let targets: Vec<(&[u8], String)> = dir
+        .entries()
+        .iter()
+        .map(|e| (e.name.as_slice(), hex::encode(e.target)))
+        .collect();
+    let shared = hex::encode(computer.compute_content_swhid(b"shared extents\n").unwrap().digest());
+    let diverged = hex::encode(computer.compute_content_swhid(b"diverged\n").unwrap().digest());
+    assert_eq!(
+        targets,
+        vec![
+            (&b"copy1.bin"[..], shared.clone()),
+            (&b"copy2.bin"[..], diverged),
+            (&b"original.bin"[..], shared),
+        ]
+    );
+
+    // Scanning the same tree again gives the same identifier
+    let mut again = Directory::from_disk(test_dir.path(), &[]).unwrap();
+    assert_eq!(again.swhid(), dir.swhid());
+}